@@ -6,7 +6,11 @@ use mir::{
 };
 use rand::{seq::IteratorRandom, Rng};
 
-use crate::{literal::GenLiteral, mem::BasicMemory, place_select::PlaceSelector};
+use crate::{
+    literal::GenLiteral,
+    mem::{BasicMemory, Layout},
+    place_select::PlaceSelector,
+};
 
 use super::{GenerationCtx, Result, SelectionError};
 
@@ -66,9 +70,7 @@ impl CoreIntrinsic for ArithOffset {
             .except(dest)
             .into_weighted(&ctx.pt)?;
         let ptr = ctx
-            .make_choice_weighted(ptrs.into_iter(), weights, |ppath| {
-                Ok(ppath.to_place(&ctx.pt))
-            })
+            .make_choice_weighted(ptrs.into_iter(), weights, |ppath| ctx.place_of(&ppath))
             .ok()?;
 
         let offset = ctx.pt.get_offset(&ptr);
@@ -86,6 +88,7 @@ impl CoreIntrinsic for ArithOffset {
                 .of_ty(TyCtxt::ISIZE)
                 .into_iter_place(&ctx.pt)
                 .choose(&mut *rng)
+                .inspect(|place| ctx.debug_check_ty(place))
                 .map(Operand::Copy)
                 .unwrap_or_else(|| {
                     Operand::Constant(
@@ -128,14 +131,13 @@ impl CoreIntrinsic for Transmute {
         if ty.contains(tcx, |tcx, ty| match ty.kind(tcx) {
             // Tys with value validity contstraints
             TyKind::Unit | TyKind::Bool | TyKind::Char | TyKind::RawPtr(..) | TyKind::Ref(..) => true, // TODO: pointer transmute
+            TyKind::Adt(adt) if adt.is_enum() => true,
             _ => false,
         }) {
             return false;
         }
-        if BasicMemory::ty_size(ty, tcx).is_none() {
-            return false;
-        }
-        true
+        // Padding bytes are uninit, so they can't be transmuted from or into
+        Layout::of(ty, tcx).is_some_and(|layout| !layout.has_padding)
     }
 
     fn choose_operands(&self, ctx: &GenerationCtx, dest: &Place) -> Option<Vec<Operand>> {
@@ -149,7 +151,7 @@ impl CoreIntrinsic for Transmute {
                 !ty.contains(&ctx.tcx, |tcx, ty| {
                     // Avoid inspecting the bytes in fp as NaN payload is nd
                     ty.is_any_ptr(tcx) || ty == TyCtxt::F32 || ty == TyCtxt::F64
-                })
+                }) && !Layout::of(*ty, &ctx.tcx).is_some_and(|layout| layout.has_padding)
             })
             .collect();
 
@@ -159,9 +161,7 @@ impl CoreIntrinsic for Transmute {
             .except(dest)
             .into_weighted(&ctx.pt)?;
         let src = ctx
-            .make_choice_weighted(srcs.into_iter(), weights, |ppath| {
-                Ok(ppath.to_place(&ctx.pt))
-            })
+            .make_choice_weighted(srcs.into_iter(), weights, |ppath| ctx.place_of(&ppath))
             .ok()?;
         if src.ty(ctx.current_decls(), &ctx.tcx).is_copy(&ctx.tcx) {
             Some(vec![Operand::Copy(src)])
@@ -171,13 +171,66 @@ impl CoreIntrinsic for Transmute {
     }
 }
 
+/// Not an intrinsic: unsizes a reference to an array into a reference to a slice, as custom MIR
+/// can't express unsize coercions
+pub(super) struct Unsize;
+impl Unsize {
+    /// Shared references to arrays of elem, which unsize into &[elem]
+    pub(super) fn array_refs(elem: TyId, tcx: &TyCtxt) -> Vec<TyId> {
+        tcx.iter_enumerated()
+            .filter_map(|(ty, kind)| match kind {
+                TyKind::Ref(array, Mutability::Not) => {
+                    matches!(array.kind(tcx), TyKind::Array(e, _) if *e == elem).then_some(ty)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl CoreIntrinsic for Unsize {
+    fn name(&self) -> &'static str {
+        "core::convert::AsRef::as_ref"
+    }
+
+    fn dest_type(&self, ty: TyId, tcx: &TyCtxt) -> bool {
+        matches!(ty.kind(tcx), TyKind::Ref(slice, Mutability::Not) if slice.is_unsized(tcx))
+    }
+
+    fn choose_operands(&self, ctx: &GenerationCtx, dest: &Place) -> Option<Vec<Operand>> {
+        let dest_ty = dest.ty(ctx.current_decls(), &ctx.tcx);
+        let TyKind::Slice(elem) = dest_ty.pointee_ty(&ctx.tcx)?.kind(&ctx.tcx) else {
+            return None;
+        };
+        let arg = ctx
+            .choose_operand(&Self::array_refs(*elem, &ctx.tcx), dest)
+            .ok()?;
+        Some(vec![arg])
+    }
+
+    fn generate_terminator(
+        &self,
+        ctx: &GenerationCtx,
+        dest: &Place,
+    ) -> Result<(Callee, Vec<Operand>)> {
+        if !self.dest_type(dest.ty(ctx.current_decls(), &ctx.tcx), &ctx.tcx) {
+            return Err(SelectionError::Exhausted);
+        }
+        let args = self
+            .choose_operands(ctx, dest)
+            .ok_or(SelectionError::Exhausted)?;
+        Ok((Callee::Named(self.name()), args))
+    }
+}
+
 impl GenerationCtx {
     pub fn choose_intrinsic(&self, dest: &Place) -> Result<(Callee, Vec<Operand>)> {
-        let choices: [Box<dyn CoreIntrinsic>; 4] = [
+        let choices: [Box<dyn CoreIntrinsic>; 5] = [
             Box::new(Fmaf64),
             Box::new(ArithOffset),
             Box::new(Bswap),
             Box::new(Transmute),
+            Box::new(Unsize),
         ];
 
         let intrinsic = self.make_choice(choices.iter(), Result::Ok)?;
@@ -2,15 +2,16 @@ mod intrinsics;
 
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::{cmp, fmt, vec};
+use std::{cmp, fmt, iter, vec};
 
+use abi::size::Size;
 use index_vec::IndexVec;
 use log::{debug, trace};
 use mir::serialize::Serialize;
 use mir::syntax::{
-    AggregateKind, BasicBlock, BasicBlockData, BinOp, Body, Callee, Function, IntTy, Literal,
-    Local, LocalDecls, Mutability, Operand, Place, Program, ProjectionElem, Rvalue, Statement,
-    SwitchTargets, Terminator, TyId, TyKind, UnOp, VariantIdx,
+    AggregateKind, BasicBlock, BasicBlockData, BinOp, Body, Callee, FieldIdx, Function, IntTy,
+    Literal, Local, LocalDecls, Mutability, Operand, Place, Program, ProjectionElem, Rvalue,
+    Statement, SwitchTargets, Terminator, TyId, TyKind, UintTy, UnOp, VariantIdx,
 };
 use mir::tyctxt::TyCtxt;
 use mir::VarDumper;
@@ -19,11 +20,15 @@ use rand::{seq::IteratorRandom, Rng, RngCore, SeedableRng};
 use rand_distr::{Distribution, WeightedError, WeightedIndex};
 
 use crate::literal::GenLiteral;
+use crate::mem::{Layout, LayoutMismatch, LogTrace, ProbeError};
 use crate::place_select::{PlaceSelector, Weight};
-use crate::ptable::{HasComplexity, PlaceIndex, PlaceOperand, PlaceTable, ToPlaceIndex};
+use crate::ptable::{
+    compare, CallTooDeep, FrameFull, HasComplexity, InitState, PlaceIndex, PlaceOperand, PlacePath,
+    PlaceTable, PtCheckpoint, ToPlaceIndex,
+};
 use crate::ty::{seed_tys, TySelect};
 
-use self::intrinsics::{ArithOffset, Transmute};
+use self::intrinsics::{ArithOffset, Transmute, Unsize};
 use crate::generation::intrinsics::CoreIntrinsic;
 
 /// Max. number of statements & declarations in a bb
@@ -41,6 +46,21 @@ const MAX_ARGS_COUNT: usize = 12;
 /// Expected proportion of variables to be dumped
 const VAR_DUMP_CHANCE: f32 = 0.5;
 
+/// Upper limit of the total size of live locals across all frames
+const STACK_LIMIT: u64 = 1 << 20;
+/// Max. complexity of a place
+const COMPLEXITY_CAP: usize = 100;
+/// Max. number of places in a frame before new variables stop being declared
+const MAX_PLACES_PER_FRAME: usize = 2048;
+/// Max. number of calls on the callstack
+const MAX_CALL_DEPTH: usize = 16;
+/// Max. number of frames of one function on the callstack
+const MAX_RECURSION_DEPTH: usize = 4;
+/// Number of statements after which the complexity of all places decays
+const COMPLEXITY_DECAY_INTERVAL: usize = 64;
+/// Proportion of its complexity a place keeps on each decay
+const COMPLEXITY_DECAY: f32 = 0.75;
+
 #[derive(Debug)]
 pub enum SelectionError {
     Exhausted,
@@ -67,7 +87,7 @@ impl fmt::Debug for Cursor {
 #[derive(Clone)]
 pub struct SavedCtx {
     program: Program,
-    pt: PlaceTable,
+    pt: PtCheckpoint,
     return_stack: Vec<Cursor>,
     cursor: Cursor,
 }
@@ -90,13 +110,14 @@ impl GenerationCtx {
             let (ppath, weights) = PlaceSelector::for_operand(self.tcx.clone())
                 .except(excluded)
                 .of_tys(tys)
+                .in_active_union_fields()
                 .into_weighted(&self.pt)
                 .ok_or(SelectionError::Exhausted)?;
             self.make_choice_weighted(ppath.into_iter(), weights, |ppath| {
                 if self.pt.ty(ppath.target_index()).is_copy(&self.tcx) {
-                    Ok(Operand::Copy(ppath.to_place(&self.pt)))
+                    Ok(Operand::Copy(self.place_of(&ppath)?))
                 } else {
-                    Ok(Operand::Move(ppath.to_place(&self.pt)))
+                    Ok(Operand::Move(self.place_of(&ppath)?))
                 }
             })?
         };
@@ -181,7 +202,7 @@ impl GenerationCtx {
                         .into_weighted(&self.pt)
                         .ok_or(SelectionError::Exhausted)?;
                     let r = self.make_choice_weighted(ppath.into_iter(), weights, |ppath| {
-                        Ok(Operand::Copy(ppath.to_place(&self.pt)))
+                        Ok(Operand::Copy(self.place_of(&ppath)?))
                     });
                     let r = r.unwrap_or_else(|_| {
                         Operand::Constant(
@@ -267,6 +288,45 @@ impl GenerationCtx {
         Ok(rvalue)
     }
 
+    fn generate_ptr_comparison(&self, lhs: &Place) -> Result<Rvalue> {
+        use BinOp::*;
+        if lhs.ty(self.current_decls(), &self.tcx) != TyCtxt::BOOL {
+            return Err(SelectionError::Exhausted);
+        }
+        let ptr_tys: Vec<TyId> = self
+            .tcx
+            .indices()
+            .filter(|ty| ty.is_raw_ptr(&self.tcx))
+            .collect();
+        // Only pointers whose order is known, as the result would otherwise depend on the backend
+        let ptrs: Vec<Place> = PlaceSelector::for_operand(self.tcx.clone())
+            .of_tys(&ptr_tys)
+            .except(lhs)
+            .into_iter_place(&self.pt)
+            .inspect(|ptr| self.debug_check_ty(ptr))
+            .filter(|ptr| self.pt.compare_pointers(ptr, ptr).is_some())
+            .collect();
+        let binop = *[Eq, Ne, Lt, Le, Ge, Gt]
+            .choose(&mut *self.rng.borrow_mut())
+            .unwrap();
+        self.make_choice(ptrs.iter(), |a| {
+            let ty = a.ty(self.current_decls(), &self.tcx);
+            let b = ptrs
+                .iter()
+                .filter(|b| {
+                    b.ty(self.current_decls(), &self.tcx) == ty
+                        && self.pt.compare_pointers(a, *b).is_some()
+                })
+                .choose(&mut *self.rng.borrow_mut())
+                .expect("a pointer compares with itself");
+            Ok(Rvalue::BinaryOp(
+                binop,
+                Operand::Copy(a.clone()),
+                Operand::Copy(b.clone()),
+            ))
+        })
+    }
+
     fn generate_checked_binary_op(&self, lhs: &Place) -> Result<Rvalue> {
         use BinOp::*;
         use TyKind::*;
@@ -360,7 +420,7 @@ impl GenerationCtx {
             .into_weighted(&self.pt)
             .ok_or(SelectionError::Exhausted)?;
         self.make_choice_weighted(candidates.into_iter(), weights, |ppath| {
-            Ok(Rvalue::AddressOf(*mutability, ppath.to_place(&self.pt)))
+            Ok(Rvalue::AddressOf(*mutability, self.place_of(&ppath)?))
         })
     }
 
@@ -376,7 +436,7 @@ impl GenerationCtx {
             .into_weighted(&self.pt)
             .ok_or(SelectionError::Exhausted)?;
         self.make_choice_weighted(candidates.into_iter(), weights, |ppath| {
-            Ok(Rvalue::Ref(*mutability, ppath.to_place(&self.pt)))
+            Ok(Rvalue::Ref(*mutability, self.place_of(&ppath)?))
         })
     }
 
@@ -418,17 +478,37 @@ impl GenerationCtx {
         Ok(agg)
     }
 
-    // fn generate_len(&self, cur_stmt: &mut Statement) -> Result<()> {
-    //     todo!()
-    // }
+    fn generate_len(&self, lhs: &Place) -> Result<Rvalue> {
+        if lhs.ty(self.current_decls(), &self.tcx) != TyCtxt::USIZE {
+            return Err(SelectionError::Exhausted);
+        }
+        let (candidates, weights) = PlaceSelector::for_len(self.tcx.clone())
+            .except(lhs)
+            .into_weighted(&self.pt)
+            .ok_or(SelectionError::Exhausted)?;
+        self.make_choice_weighted(candidates.into_iter(), weights, |ppath| {
+            Ok(Rvalue::PtrMetadata(Operand::Copy(self.place_of(&ppath)?)))
+        })
+    }
 
     // fn generate_retag(&self, cur_stmt: &mut Statement) -> Result<()> {
     //     todo!()
     // }
 
-    // fn generate_discriminant(&self, cur_stmt: &mut Statement) -> Result<()> {
-    //     todo!()
-    // }
+    fn generate_discriminant(&self, lhs: &Place) -> Result<Rvalue> {
+        // The discriminant of an enum without a repr is an isize
+        if lhs.ty(self.current_decls(), &self.tcx) != TyCtxt::ISIZE {
+            return Err(SelectionError::Exhausted);
+        }
+        // Only enums whose variant is known, so that a SwitchInt on the result takes a known edge
+        let (candidates, weights) = PlaceSelector::for_known_discriminant(self.tcx.clone())
+            .except(lhs)
+            .into_weighted(&self.pt)
+            .ok_or(SelectionError::Exhausted)?;
+        self.make_choice_weighted(candidates.into_iter(), weights, |ppath| {
+            Ok(Rvalue::Discriminant(self.place_of(&ppath)?))
+        })
+    }
 
     fn generate_rvalue(&self, lhs: &Place) -> Result<Rvalue> {
         let choices_and_weights: Vec<(fn(&GenerationCtx, &Place) -> Result<Rvalue>, usize)> = vec![
@@ -440,6 +520,9 @@ impl GenerationCtx {
             (Self::generate_address_of, 1),
             (Self::generate_ref, 1),
             (Self::generate_aggregate, 2),
+            (Self::generate_discriminant, 1),
+            (Self::generate_len, 1),
+            (Self::generate_ptr_comparison, 1),
         ];
 
         let (choices, weights): (
@@ -463,7 +546,7 @@ impl GenerationCtx {
             .ok_or(SelectionError::Exhausted)?;
 
         self.make_choice_weighted(lhs_choices.into_iter(), weights, |ppath| {
-            let lhs = ppath.to_place(&self.pt);
+            let lhs = self.place_of(&ppath)?;
             trace!(
                 "generating an assignment statement with lhs {}: {}",
                 lhs.serialize_place(&self.tcx),
@@ -475,6 +558,26 @@ impl GenerationCtx {
         })
     }
 
+    // Finish off a partially init place by assigning to all of it at once
+    fn generate_assign_partial(&self) -> Result<Statement> {
+        let (lhs_choices, weights) = PlaceSelector::for_lhs(self.tcx.clone())
+            .with_init_state(InitState::Partial)
+            .into_weighted(&self.pt)
+            .ok_or(SelectionError::Exhausted)?;
+
+        self.make_choice_weighted(lhs_choices.into_iter(), weights, |ppath| {
+            let lhs = self.place_of(&ppath)?;
+            trace!(
+                "generating an aggregate assignment to partially init {}",
+                lhs.serialize_place(&self.tcx)
+            );
+            Ok(Statement::Assign(
+                lhs.clone(),
+                self.generate_aggregate(&lhs)?,
+            ))
+        })
+    }
+
     // Hack to take &self
     fn generate_new_var(&self) -> Result<Statement> {
         Ok(Statement::Nop)
@@ -491,6 +594,23 @@ impl GenerationCtx {
         local
     }
 
+    /// Declares a new variable, unless the current frame has no room left for it
+    fn try_declare_new_var(&mut self, mutability: Mutability, ty: TyId) -> Option<Local> {
+        let local = self.current_fn().local_decls.next_idx();
+        if let Err(FrameFull { places }) = self.pt.try_allocate_local(local, ty) {
+            trace!("not declaring a new var in a frame of {places} places");
+            return None;
+        }
+        let declared = self.current_fn_mut().declare_new_var(mutability, ty);
+        debug_assert_eq!(local, declared);
+        trace!(
+            "generated new var {}: {}",
+            local.identifier(),
+            ty.serialize(&self.tcx)
+        );
+        Some(local)
+    }
+
     fn generate_storage_live(&self) -> Result<Statement> {
         let local = self
             .current_decls()
@@ -505,17 +625,20 @@ impl GenerationCtx {
         let local = self
             .current_decls()
             .indices()
-            .filter(|local| self.pt.is_place_live(local))
+            .filter(|local| self.pt.is_place_live(local) && self.pt.can_deallocate(*local))
             .choose(&mut *self.rng.borrow_mut())
             .ok_or(SelectionError::Exhausted)?;
         Ok(Statement::StorageDead(local))
     }
 
     fn generate_deinit(&self) -> Result<Statement> {
-        let place = PlaceSelector::for_operand(self.tcx.clone())
+        // Deinit only does something to an init place
+        let place = PlaceSelector::for_lhs(self.tcx.clone())
+            .with_init_state(InitState::Full)
             .into_iter_place(&self.pt)
             .choose(&mut *self.rng.borrow_mut())
             .ok_or(SelectionError::Exhausted)?;
+        self.debug_check_ty(&place);
         Ok(Statement::Deinit(place))
     }
 
@@ -531,7 +654,7 @@ impl GenerationCtx {
             .ok_or(SelectionError::Exhausted)?;
 
         self.make_choice_weighted(choices.into_iter(), weights, |ppath| {
-            let place = ppath.to_place(&self.pt);
+            let place = self.place_of(&ppath)?;
             trace!(
                 "generating a set discriminant statement with place {}",
                 place.serialize_place(&self.tcx),
@@ -554,8 +677,13 @@ impl GenerationCtx {
     fn choose_statement(&mut self) {
         let choices_and_weights: Vec<(fn(&GenerationCtx) -> Result<Statement>, usize)> = vec![
             (Self::generate_assign, 20),
-            (Self::generate_new_var, 4),
+            // Once the frame is full, existing places are reused instead
+            (
+                Self::generate_new_var,
+                if self.pt.frame_has_room() { 4 } else { 0 },
+            ),
             (Self::generate_set_discriminant, 1),
+            (Self::generate_assign_partial, 1),
             // (Self::generate_deinit, 1),
             // (Self::generate_storage_live, 5),
             // (Self::generate_storage_dead, 2),
@@ -577,7 +705,9 @@ impl GenerationCtx {
             let ty = self
                 .ty_weights
                 .choose_ty(&mut *self.rng.borrow_mut(), &self.tcx);
-            self.declare_new_var(Mutability::Mut, ty);
+            if self.pt.can_allocate([ty]) {
+                self.try_declare_new_var(Mutability::Mut, ty);
+            }
         }
 
         if !matches!(statement, Statement::Nop) {
@@ -677,9 +807,9 @@ impl GenerationCtx {
             self.make_choice_weighted(places.into_iter(), weights, |ppath| {
                 let val = self
                     .pt
-                    .known_val(ppath.target_index())
+                    .read_literal(ppath.target_index())
                     .expect("has_value");
-                Ok((ppath.to_place(&self.pt), *val))
+                Ok((self.place_of(&ppath)?, val))
             })?;
 
         let decoy_count = self.rng.get_mut().gen_range(1..=MAX_SWITCH_TARGETS);
@@ -727,14 +857,24 @@ impl GenerationCtx {
 
     fn generate_call(&mut self) -> Result<()> {
         trace!("generating a Call terminator to {:?}", self.cursor);
+        if !self.pt.can_call() {
+            return Err(SelectionError::Exhausted);
+        }
+        // Calls always generate a fresh callee, which can't be on the callstack yet, but
+        // recursion through existing functions must stop at the limit
+        let callee = self.program.functions.next_idx();
+        if self.pt.active_frames(callee) >= MAX_RECURSION_DEPTH {
+            return Err(SelectionError::Exhausted);
+        }
         let (return_places, weights) = PlaceSelector::for_lhs(self.tcx.clone())
             .into_weighted(&self.pt)
             .ok_or(SelectionError::Exhausted)?;
 
-        let return_place =
-            self.make_choice_weighted(return_places.into_iter(), weights, |ppath| {
-                Result::Ok(ppath.to_place(&self.pt))
-            })?;
+        let return_place = self.make_choice_weighted(
+            return_places.clone().into_iter(),
+            weights.clone(),
+            |ppath| self.place_of(&ppath),
+        )?;
 
         let args_count = self.rng.get_mut().gen_range(0..=MAX_ARGS_COUNT);
         let mut selector = PlaceSelector::for_argument(self.tcx.clone())
@@ -746,7 +886,7 @@ impl GenerationCtx {
                 .into_weighted(&self.pt)
                 .ok_or(SelectionError::Exhausted)?;
             let arg = self.make_choice_weighted(places.into_iter(), weights, |ppath| {
-                let place = ppath.to_place(&self.pt);
+                let place = self.place_of(&ppath)?;
                 let pidx = ppath.target_index();
                 let ty = self.pt.ty(pidx);
 
@@ -775,6 +915,30 @@ impl GenerationCtx {
             args.push(arg);
         }
 
+        // The return place is exclusively written by the callee, so it must not be reachable
+        // through the arguments
+        let return_place = if self.pt.call_destination_conflicts(&return_place, &args) {
+            self.make_choice_weighted(return_places.into_iter(), weights, |ppath| {
+                let place = self.place_of(&ppath)?;
+                if self.pt.call_destination_conflicts(&place, &args) {
+                    Err(SelectionError::Exhausted)
+                } else {
+                    Ok(place)
+                }
+            })?
+        } else {
+            return_place
+        };
+
+        // The callee's frame must fit in what's left of the stack
+        let frame_tys = iter::once(return_place.ty(self.current_decls(), &self.tcx)).chain(
+            args.iter()
+                .map(|arg| arg.ty(self.current_decls(), &self.tcx)),
+        );
+        if !self.pt.can_allocate(frame_tys) {
+            return Err(SelectionError::Exhausted);
+        }
+
         // Modification must start after this point, as we may bail during above
         self.save_ctx();
         self.pt.place_written(&return_place);
@@ -801,6 +965,51 @@ impl GenerationCtx {
         Ok(())
     }
 
+    // References to slices are rarely picked as the destination of an arbitrary intrinsic call,
+    // so unsizing gets its own terminator
+    fn generate_unsize_call(&mut self) -> Result<()> {
+        // Only slices of elements that some initialised array reference holds can be produced
+        let slice_refs: Vec<TyId> = self
+            .tcx
+            .indices()
+            .filter(|ty| Unsize.dest_type(*ty, &self.tcx))
+            .filter(|ty| {
+                let TyKind::Slice(elem) = ty.pointee_ty(&self.tcx).unwrap().kind(&self.tcx) else {
+                    return false;
+                };
+                PlaceSelector::for_operand(self.tcx.clone())
+                    .of_tys(&Unsize::array_refs(*elem, &self.tcx))
+                    .into_weighted(&self.pt)
+                    .is_some()
+            })
+            .collect();
+
+        let return_place = match PlaceSelector::for_lhs(self.tcx.clone())
+            .of_tys(&slice_refs)
+            .into_weighted(&self.pt)
+        {
+            Some((return_places, weights)) => {
+                self.make_choice_weighted(return_places.into_iter(), weights, |ppath| {
+                    self.place_of(&ppath)
+                })?
+            }
+            // Nothing else assigns to slice references, so there usually isn't one to reuse
+            None => {
+                let ty = self.make_choice(slice_refs.iter(), |ty| Ok(*ty))?;
+                if !self.pt.can_allocate([ty]) {
+                    return Err(SelectionError::Exhausted);
+                }
+                let local = self
+                    .try_declare_new_var(Mutability::Mut, ty)
+                    .ok_or(SelectionError::Exhausted)?;
+                Place::from_local(local)
+            }
+        };
+
+        let (callee, args) = Unsize.generate_terminator(self, &return_place)?;
+        self.finish_intrinsic_call(return_place, callee, args)
+    }
+
     fn generate_intrinsic_call(&mut self) -> Result<()> {
         let (return_places, weights) = PlaceSelector::for_lhs(self.tcx.clone())
             .into_weighted(&self.pt)
@@ -808,11 +1017,19 @@ impl GenerationCtx {
 
         let return_place =
             self.make_choice_weighted(return_places.into_iter(), weights, |ppath| {
-                Result::Ok(ppath.to_place(&self.pt))
+                self.place_of(&ppath)
             })?;
 
         let (callee, args) = self.choose_intrinsic(&return_place)?;
+        self.finish_intrinsic_call(return_place, callee, args)
+    }
 
+    fn finish_intrinsic_call(
+        &mut self,
+        return_place: Place,
+        callee: Callee,
+        args: Vec<Operand>,
+    ) -> Result<()> {
         // Post generation value manipulation
         let ret = return_place.to_place_index(&self.pt).expect("place exists");
         let arg_places: Vec<PlaceOperand> = args
@@ -822,7 +1039,9 @@ impl GenerationCtx {
 
         self.pt.mark_place_init(ret);
         self.pt.place_written(ret);
-        let Callee::Intrinsic(intrinsic_name) = callee else {
+        // Transmutes keep the bytes of their operand, so the value is forgotten before
+        self.pt.assign_literal(ret, None);
+        let (Callee::Intrinsic(intrinsic_name) | Callee::Named(intrinsic_name)) = callee else {
             panic!("callee is intrinsic");
         };
 
@@ -833,7 +1052,7 @@ impl GenerationCtx {
 
             let lit = match arg_places[1] {
                 PlaceOperand::Copy(p) | PlaceOperand::Move(p) => {
-                    *self.pt.known_val(p).expect("has known value")
+                    self.pt.read_literal(p).expect("has known value")
                 }
                 PlaceOperand::Constant(lit) => lit,
             };
@@ -849,6 +1068,11 @@ impl GenerationCtx {
             if let PlaceOperand::Copy(src) | PlaceOperand::Move(src) = arg_places[0] {
                 self.pt.transmute_place(ret, src)
             }
+        } else if intrinsic_name == Unsize.name() {
+            let (PlaceOperand::Copy(src) | PlaceOperand::Move(src)) = arg_places[0] else {
+                unreachable!("operand is a reference to an array");
+            };
+            self.pt.unsize_ptr(ret, src);
         }
 
         for op in arg_places {
@@ -856,8 +1080,6 @@ impl GenerationCtx {
                 self.pt.mark_place_moved(p);
             }
         }
-
-        self.pt.assign_literal(ret, None);
         // Finish post generation manipulation
 
         let bb = self.add_new_bb();
@@ -871,6 +1093,140 @@ impl GenerationCtx {
         Ok(())
     }
 
+    // Copies a whole array into a disjoint one of the same type, either element by element or
+    // through copy_nonoverlapping, then dumps the destination
+    fn generate_bulk_copy(&mut self) -> Result<()> {
+        let arrays: Vec<TyId> = self
+            .tcx
+            .iter_enumerated()
+            .filter_map(|(ty, kind)| matches!(kind, TyKind::Array(..)).then_some(ty))
+            .collect();
+        let (srcs, weights) = PlaceSelector::for_operand(self.tcx.clone())
+            .of_tys(&arrays)
+            .into_weighted(&self.pt)
+            .ok_or(SelectionError::Exhausted)?;
+        // Both arrays are accessed directly, so neither can be behind a pointer
+        let behind_ptr = |place: &Place| place.projection().iter().any(|proj| proj.is_deref());
+        let (dst, src) = self.make_choice_weighted(srcs.into_iter(), weights, |ppath| {
+            let src = self.place_of(&ppath)?;
+            if behind_ptr(&src) {
+                return Err(SelectionError::Exhausted);
+            }
+            let (dsts, weights) = PlaceSelector::for_lhs(self.tcx.clone())
+                .of_ty(self.pt.ty(ppath.target_index()))
+                .except(&src)
+                .into_weighted(&self.pt)
+                .ok_or(SelectionError::Exhausted)?;
+            let dst = self.make_choice_weighted(dsts.into_iter(), weights, |ppath| {
+                let dst = self.place_of(&ppath)?;
+                if behind_ptr(&dst) {
+                    return Err(SelectionError::Exhausted);
+                }
+                Ok(dst)
+            })?;
+            Ok((dst, src))
+        })?;
+
+        let array = src.ty(self.current_decls(), &self.tcx);
+        let TyKind::Array(_, len) = *array.kind(&self.tcx) else {
+            unreachable!("src is an array");
+        };
+        let [const_ptr, mut_ptr] = [Mutability::Not, Mutability::Mut].map(|mutability| {
+            self.tcx
+                .iter_enumerated()
+                .find_map(|(ty, kind)| (*kind == TyKind::RawPtr(array, mutability)).then_some(ty))
+        });
+        // An empty array has no elements to copy one by one
+        let through_ptrs = len == 0 || self.rng.get_mut().gen_bool(0.5);
+        let tys = if through_ptrs {
+            let (Some(const_ptr), Some(mut_ptr)) = (const_ptr, mut_ptr) else {
+                return Err(SelectionError::Exhausted);
+            };
+            vec![TyCtxt::UNIT, TyCtxt::UNIT, const_ptr, mut_ptr]
+        } else {
+            vec![TyCtxt::UNIT, TyCtxt::UNIT, TyCtxt::USIZE]
+        };
+        if !self.pt.can_allocate(tys.iter().copied()) {
+            return Err(SelectionError::Exhausted);
+        }
+        let locals = tys
+            .into_iter()
+            .map(|ty| self.try_declare_new_var(Mutability::Mut, ty))
+            .collect::<Option<Vec<Local>>>()
+            .ok_or(SelectionError::Exhausted)?;
+        let (unit, unit2) = (locals[0], locals[1]);
+        trace!(
+            "generating a bulk copy from {} to {}",
+            src.serialize_place(&self.tcx),
+            dst.serialize_place(&self.tcx)
+        );
+
+        if through_ptrs {
+            let (src_ptr, dst_ptr) = (locals[2], locals[3]);
+            for (ptr, mutability, place) in [
+                (src_ptr, Mutability::Not, &src),
+                (dst_ptr, Mutability::Mut, &dst),
+            ] {
+                let statement = Statement::Assign(
+                    Place::from_local(ptr),
+                    Rvalue::AddressOf(mutability, place.clone()),
+                );
+                self.post_generation(&statement);
+                self.current_bb_mut().insert_statement(statement);
+            }
+
+            self.pt.place_read_through(src_ptr, &src);
+            let aliases = self.pt.aliases_of_pointee(dst_ptr);
+            self.pt.invalidate_knowledge(&aliases);
+            let copied = self.pt.bulk_copy(&dst, &src);
+            assert!(copied, "src is init and disjoint from dst");
+            self.pt.place_written_through(dst_ptr, &dst);
+            self.pt.mark_place_init(unit);
+
+            let bb = self.add_new_bb();
+            self.current_bb_mut().set_terminator(Terminator::Call {
+                callee: Callee::Intrinsic("copy_nonoverlapping"),
+                destination: Place::from_local(unit),
+                target: bb,
+                args: vec![
+                    Operand::Copy(Place::from_local(src_ptr)),
+                    Operand::Copy(Place::from_local(dst_ptr)),
+                    Operand::Constant(1_usize.try_into().unwrap()),
+                ],
+            });
+            self.enter_bb(bb);
+        } else {
+            // Custom MIR can't index with a constant, so each element is reached through idx
+            let idx = locals[2];
+            let elem = |place: &Place| {
+                let mut place = place.clone();
+                place.project(ProjectionElem::Index(idx));
+                place
+            };
+            for i in 0..len {
+                let statement = Statement::Assign(
+                    Place::from_local(idx),
+                    Rvalue::Use(Operand::Constant(i.try_into().unwrap())),
+                );
+                self.post_generation(&statement);
+                self.current_bb_mut().insert_statement(statement);
+                self.current_bb_mut().insert_statement(Statement::Assign(
+                    elem(&dst),
+                    Rvalue::Use(Operand::Copy(elem(&src))),
+                ));
+            }
+
+            let copied = self.pt.bulk_copy(&dst, &src);
+            assert!(copied, "src is init and disjoint from dst");
+            self.pt.place_written(&dst);
+        }
+
+        if array.hashable(&self.tcx) {
+            self.dump_vals(vec![(dst.local(), Operand::Copy(dst))], unit, unit2);
+        }
+        Ok(())
+    }
+
     // Generate a Return terminator, returns false if it's being
     // generated in fn0
     fn generate_return(&mut self) -> Result<bool> {
@@ -901,6 +1257,8 @@ impl GenerationCtx {
             (Self::generate_goto, 20),
             (Self::generate_switch_int, 20),
             (Self::generate_intrinsic_call, 20),
+            (Self::generate_unsize_call, 5),
+            (Self::generate_bulk_copy, 5),
             (
                 Self::generate_call,
                 MAX_FN_COUNT.saturating_sub(self.program.functions.len()),
@@ -940,42 +1298,54 @@ impl GenerationCtx {
         self.enter_bb(new_bb);
 
         for vars in dumpped.chunks(Program::DUMPER_ARITY) {
-            let new_bb = self.add_new_bb();
+            let vals = vars
+                .iter()
+                .map(|var| (*var, Operand::Move(Place::from_local(*var))))
+                .collect();
+            self.dump_vals(vals, unit, unit2);
+        }
+    }
 
-            let args = if self.program.var_dumper == VarDumper::StdVarDumper || self.program.var_dumper == VarDumper::PrintfVarDumper{
-                let mut args = Vec::with_capacity(1 + Program::DUMPER_ARITY * 2);
-                args.push(Operand::Constant(
-                    self.cursor.function.index().try_into().unwrap(),
-                ));
-                for var in vars {
-                    args.push(Operand::Constant(var.index().try_into().unwrap()));
-                    args.push(Operand::Move(Place::from_local(*var)));
-                }
+    /// Calls the dumper on up to DUMPER_ARITY values, each labelled with the local it comes
+    /// from and padded with unit2, then moves on to a new BB
+    fn dump_vals(&mut self, vals: Vec<(Local, Operand)>, unit: Local, unit2: Local) {
+        let new_bb = self.add_new_bb();
 
-                while args.len() < 1 + Program::DUMPER_ARITY * 2 {
-                    args.push(Operand::Constant(unit2.index().try_into().unwrap()));
-                    args.push(Operand::Copy(Place::from_local(unit2)));
-                }
-                args
-            } else {
-                let mut args = Vec::with_capacity(Program::DUMPER_ARITY);
-                for var in vars {
-                    args.push(Operand::Move(Place::from_local(*var)));
-                }
+        let args = if self.program.var_dumper == VarDumper::StdVarDumper
+            || self.program.var_dumper == VarDumper::PrintfVarDumper
+        {
+            let mut args = Vec::with_capacity(1 + Program::DUMPER_ARITY * 2);
+            args.push(Operand::Constant(
+                self.cursor.function.index().try_into().unwrap(),
+            ));
+            for (var, val) in vals {
+                args.push(Operand::Constant(var.index().try_into().unwrap()));
+                args.push(val);
+            }
 
-                while args.len() < Program::DUMPER_ARITY {
-                    args.push(Operand::Copy(Place::from_local(unit2)));
-                }
-                args
-            };
-            self.current_bb_mut().set_terminator(Terminator::Call {
-                callee: Program::DUMPER_CALL,
-                destination: Place::from_local(unit),
-                target: new_bb,
-                args,
-            });
-            self.enter_bb(new_bb);
-        }
+            while args.len() < 1 + Program::DUMPER_ARITY * 2 {
+                args.push(Operand::Constant(unit2.index().try_into().unwrap()));
+                args.push(Operand::Copy(Place::from_local(unit2)));
+            }
+            args
+        } else {
+            let mut args = Vec::with_capacity(Program::DUMPER_ARITY);
+            for (_, val) in vals {
+                args.push(val);
+            }
+
+            while args.len() < Program::DUMPER_ARITY {
+                args.push(Operand::Copy(Place::from_local(unit2)));
+            }
+            args
+        };
+        self.current_bb_mut().set_terminator(Terminator::Call {
+            callee: Program::DUMPER_CALL,
+            destination: Place::from_local(unit),
+            target: new_bb,
+            args,
+        });
+        self.enter_bb(new_bb);
     }
 }
 
@@ -987,7 +1357,7 @@ impl GenerationCtx {
     fn save_ctx(&mut self) {
         self.saved_ctx.push(SavedCtx {
             program: self.program.clone(),
-            pt: self.pt.clone(),
+            pt: self.pt.checkpoint(),
             return_stack: self.return_stack.clone(),
             cursor: self.cursor,
         });
@@ -1002,7 +1372,7 @@ impl GenerationCtx {
             .pop()
             .expect("has a saved ctx to restore from");
         self.program = saved.program;
-        self.pt = saved.pt;
+        self.pt.rollback(saved.pt);
         self.return_stack = saved.return_stack;
         self.cursor = saved.cursor;
     }
@@ -1031,11 +1401,16 @@ impl GenerationCtx {
             basic_block: starting_bb,
         };
 
-        self.pt.enter_fn(
-            &self.program.functions[self.cursor.function],
-            args,
-            return_dest,
-        );
+        self.pt
+            .try_enter_fn(
+                new_fn,
+                &self.program.functions[self.cursor.function],
+                args,
+                return_dest,
+            )
+            .unwrap_or_else(|CallTooDeep { depth }| {
+                panic!("callstack has room for the call at depth {depth}")
+            });
         new_fn
     }
 
@@ -1081,6 +1456,22 @@ impl GenerationCtx {
 }
 
 impl GenerationCtx {
+    /// Names the place at the end of ppath, picking among the locals that can index each array
+    pub fn place_of(&self, ppath: &PlacePath) -> Result<Place> {
+        let place = ppath
+            .to_place_with(&self.pt, &mut *self.rng.borrow_mut())
+            .ok_or(SelectionError::Exhausted)?;
+        self.debug_check_ty(&place);
+        Ok(place)
+    }
+
+    /// Checks that the place table and the decls of the current function agree on place's type
+    pub fn debug_check_ty(&self, place: &Place) {
+        if cfg!(debug_assertions) {
+            self.pt.assert_ty_consistent(place, self.current_decls());
+        }
+    }
+
     pub fn make_choice_weighted<T, F, R>(
         &self,
         choices: impl Iterator<Item = T> + Clone,
@@ -1187,17 +1578,42 @@ impl GenerationCtx {
         }
     }
 
-    pub fn new(seed: u64, debug_dump: VarDumper) -> Self {
+    /// A program reporting the layouts of the structs generated from seed, whose
+    /// output can be passed back to new
+    pub fn layout_probe(seed: u64) -> String {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+        Layout::probe_program(&seed_tys(&mut rng))
+    }
+
+    pub fn new(seed: u64, debug_dump: VarDumper, probed_layouts: Option<&str>) -> Self {
         let rng = RefCell::new(Box::new(rand::rngs::SmallRng::seed_from_u64(seed)));
-        let tcx = Rc::new(seed_tys(&mut *rng.borrow_mut()));
+        let mut tcx = seed_tys(&mut *rng.borrow_mut());
+        if let Some(output) = probed_layouts
+            && let Err(ProbeError(err)) = Layout::load_probe(output, &mut tcx)
+        {
+            panic!("can't use the layout probe output: {err}");
+        }
+        let tcx = Rc::new(tcx);
+        if let Err(LayoutMismatch(mismatch)) = Layout::self_check(&tcx) {
+            panic!("layouts are inconsistent: {mismatch}");
+        }
         let ty_weights = TySelect::new(&tcx);
+        let mut pt = PlaceTable::new(tcx.clone())
+            .with_stack_limit(Size::from_bytes(STACK_LIMIT))
+            .with_complexity_cap(COMPLEXITY_CAP)
+            .with_max_places_per_frame(MAX_PLACES_PER_FRAME)
+            .with_max_call_depth(MAX_CALL_DEPTH);
+        // Memory events are many, so they are only collected when they would be logged
+        if log::log_enabled!(target: "generate::mem", log::Level::Trace) {
+            pt = pt.with_mem_tracer(Box::new(LogTrace));
+        }
         // TODO: don't zero-initialize current_function and current_bb
         Self {
             rng,
             tcx: tcx.clone(),
             ty_weights,
             program: Program::new(debug_dump),
-            pt: PlaceTable::new(tcx.clone()),
+            pt,
             return_stack: vec![],
             cursor: Cursor {
                 function: Function::new(0),
@@ -1271,12 +1687,18 @@ impl GenerationCtx {
         self.generate_fn0();
 
         // Main loop
+        let mut since_decay = 0;
         loop {
             let statement_count = self.rng.get_mut().gen_range(1..=BB_MAX_LEN);
             trace!("Generating a bb with {statement_count} statements");
             for _ in 0..statement_count {
                 self.choose_statement();
             }
+            since_decay += statement_count;
+            if since_decay >= COMPLEXITY_DECAY_INTERVAL {
+                self.pt.decay_complexity(COMPLEXITY_DECAY);
+                since_decay = 0;
+            }
             if !self.choose_terminator() {
                 break;
             }
@@ -1297,6 +1719,8 @@ impl GenerationCtx {
             }
         }
 
+        debug!("memory at exit: {:?}", self.pt.stats());
+
         // Remove the Rc to self.tcx, so we can own it
         drop(self.pt);
 
@@ -1307,6 +1731,35 @@ impl GenerationCtx {
         // We must evaluate the places first before updating any PlaceTable state,
         // as the updates may affect projections
         let mut actions: Vec<Box<dyn FnOnce(&mut PlaceTable)>> = vec![];
+        // A write through a raw pointer may change what is known about any of its aliases.
+        // This goes first, so the written place still gets its new value below
+        if let Statement::Assign(place, _)
+        | Statement::Deinit(place)
+        | Statement::SetDiscriminant(place, _) = stmt
+            && let Some(i) = place.projection().iter().rposition(|proj| proj.is_deref())
+        {
+            let pointer = Place::from_projected(place.local(), &place.projection()[..i])
+                .to_place_index(&self.pt)
+                .unwrap();
+            if self.pt.ty(pointer).is_raw_ptr(&self.tcx) {
+                let aliases = self.pt.aliases_of_pointee(pointer);
+                actions.push(Box::new(move |pt| pt.invalidate_knowledge(&aliases)));
+            }
+        }
+        // Operands are read before the assignment writes, and reading through a pointer pops
+        // the exclusive borrows above its tag
+        if let Statement::Assign(_, rvalue) = stmt {
+            for place in PlaceTable::read_places(rvalue) {
+                let Some(i) = place.projection().iter().rposition(|proj| proj.is_deref()) else {
+                    continue;
+                };
+                let pointer = Place::from_projected(place.local(), &place.projection()[..i])
+                    .to_place_index(&self.pt)
+                    .unwrap();
+                let read = place.to_place_index(&self.pt).unwrap();
+                actions.push(Box::new(move |pt| pt.place_read_through(pointer, read)));
+            }
+        }
         {
             match stmt {
                 Statement::Assign(lhs, rvalue) => {
@@ -1316,15 +1769,34 @@ impl GenerationCtx {
                     }));
                     match rvalue {
                         Rvalue::AddressOf(_, referent) | Rvalue::Ref(_, referent) => {
+                            // Borrowing through a pointer reborrows from that pointer's tag
+                            let parent = referent
+                                .projection()
+                                .iter()
+                                .rposition(|proj| proj.is_deref())
+                                .map(|i| {
+                                    Place::from_projected(
+                                        referent.local(),
+                                        &referent.projection()[..i],
+                                    )
+                                    .to_place_index(&self.pt)
+                                    .unwrap()
+                                });
                             let referent = referent.to_place_index(&self.pt).unwrap();
                             actions.push(Box::new(move |pt| {
-                                pt.set_ref(lhs, referent, None);
+                                if let Some(parent) = parent {
+                                    pt.set_reborrow(lhs, referent, parent);
+                                } else {
+                                    pt.set_ref(lhs, referent, None);
+                                }
                             }));
                         }
                         _ => {
                             let new_df = rvalue.complexity(&self.pt);
+                            let sources = self.pt.flow_sources(rvalue);
                             actions.push(Box::new(move |pt| {
                                 pt.update_complexity(lhs, new_df);
+                                pt.record_flow(lhs, &sources);
                             }));
                         }
                     }
@@ -1338,7 +1810,13 @@ impl GenerationCtx {
                 }
                 Statement::StorageDead(local) => {
                     let local = *local;
-                    actions.push(Box::new(move |pt| pt.deallocate_local(local)));
+                    actions.push(Box::new(move |pt| {
+                        let deallocated = pt.deallocate_local(local);
+                        debug_assert!(
+                            deallocated.is_ok(),
+                            "storage of a protected local doesn't end"
+                        );
+                    }));
                 }
                 Statement::Deinit(place) => {
                     let place = place.to_place_index(&self.pt).unwrap();
@@ -1371,7 +1849,7 @@ impl GenerationCtx {
                             }));
                         }
                     },
-                    agg @ Rvalue::Aggregate(agg_kind, ..) => {
+                    agg @ Rvalue::Aggregate(agg_kind, ops) => {
                         if self.pt.ty(lhs).kind(&self.tcx).is_enum() {
                             let AggregateKind::Adt(_, vid) = agg_kind else {
                                 panic!("agg kind is not an adt");
@@ -1380,22 +1858,104 @@ impl GenerationCtx {
                                 pt.assign_discriminant(lhs, Some(*vid));
                             }))
                         }
-                        for (target, op) in self.aggregate_places(lhs, agg) {
-                            match op {
-                                Operand::Copy(rhs) | Operand::Move(rhs) => {
-                                    let rhs = rhs.to_place_index(&self.pt).unwrap();
-                                    actions.push(Box::new(move |pt| {
-                                        pt.copy_place(target, rhs);
-                                    }));
-                                }
-                                Operand::Constant(lit) => {
-                                    actions.push(Box::new(move |pt| {
-                                        pt.assign_literal(target, Some(*lit));
-                                    }));
+                        let lits: Option<Vec<Literal>> = ops
+                            .iter()
+                            .map(|op| match op {
+                                Operand::Constant(lit) => Some(*lit),
+                                _ => None,
+                            })
+                            .collect();
+                        if let Some(lits) = lits
+                            && !lits.is_empty()
+                            && matches!(agg_kind, AggregateKind::Tuple | AggregateKind::Array(..))
+                        {
+                            // An aggregate of constants is known as a whole
+                            actions.push(Box::new(move |pt| {
+                                pt.assign_composite_literal(lhs, &lits);
+                            }));
+                        } else {
+                            for (target, op) in self.aggregate_places(lhs, agg) {
+                                match op {
+                                    Operand::Copy(rhs) | Operand::Move(rhs) => {
+                                        let rhs = rhs.to_place_index(&self.pt).unwrap();
+                                        actions.push(Box::new(move |pt| {
+                                            pt.copy_place(target, rhs);
+                                        }));
+                                    }
+                                    Operand::Constant(lit) => {
+                                        actions.push(Box::new(move |pt| {
+                                            pt.assign_literal(target, Some(*lit));
+                                        }));
+                                    }
                                 }
                             }
                         }
                     }
+                    Rvalue::Discriminant(place) => {
+                        let place = place.to_place_index(&self.pt).unwrap();
+                        actions.push(Box::new(move |pt| {
+                            pt.assign_literal(lhs, pt.known_discriminant(place));
+                        }))
+                    }
+                    Rvalue::BinaryOp(binop, Operand::Copy(a), Operand::Copy(b))
+                        if self.pt.ty(a).is_raw_ptr(&self.tcx) =>
+                    {
+                        let val = self
+                            .pt
+                            .compare_pointers(a, b)
+                            .map(|ord| Literal::Bool(compare(*binop, ord)));
+                        actions.push(Box::new(move |pt| {
+                            pt.assign_literal(lhs, val);
+                        }))
+                    }
+                    Rvalue::PtrMetadata(ptr) => {
+                        let ptr = ptr.place().unwrap().to_place_index(&self.pt).unwrap();
+                        actions.push(Box::new(move |pt| {
+                            let len = pt
+                                .pointee_len(ptr)
+                                .map(|len| Literal::Uint(len as u128, UintTy::Usize));
+                            pt.assign_literal(lhs, len);
+                        }))
+                    }
+                    Rvalue::UnaryOp(unop, a) => {
+                        let val = self
+                            .known_operand(a)
+                            .and_then(|a| PlaceTable::fold_unop(*unop, &a));
+                        actions.push(Box::new(move |pt| {
+                            pt.assign_literal(lhs, val);
+                        }))
+                    }
+                    Rvalue::BinaryOp(binop, a, b) => {
+                        let val = self
+                            .known_operand(a)
+                            .zip(self.known_operand(b))
+                            .and_then(|(a, b)| PlaceTable::fold_binop(*binop, &a, &b));
+                        actions.push(Box::new(move |pt| {
+                            pt.assign_literal(lhs, val);
+                        }))
+                    }
+                    Rvalue::CheckedBinaryOp(binop, a, b) => {
+                        let folded = self
+                            .known_operand(a)
+                            .zip(self.known_operand(b))
+                            .and_then(|(a, b)| PlaceTable::fold_checked_binop(*binop, &a, &b));
+                        let field = |i| {
+                            self.pt
+                                .project_from_node(
+                                    lhs,
+                                    ProjectionElem::TupleField(FieldIdx::new(i)),
+                                )
+                                .expect("lhs is a tuple")
+                        };
+                        let (val, overflowed) = (field(0), field(1));
+                        actions.push(Box::new(move |pt| {
+                            pt.assign_literal(lhs, None);
+                            if let Some((folded, overflow)) = folded {
+                                pt.assign_literal(val, Some(folded));
+                                pt.assign_literal(overflowed, Some(Literal::Bool(overflow)));
+                            }
+                        }))
+                    }
                     _ => actions.push(Box::new(move |pt| {
                         pt.assign_literal(lhs, None);
                     })),
@@ -1437,8 +1997,22 @@ impl GenerationCtx {
                 | Statement::Deinit(place)
                 | Statement::SetDiscriminant(place, _) => {
                     let pidx = place.to_place_index(&self.pt).unwrap();
+                    // A write through a pointer uses that pointer's tag
+                    let pointer = place
+                        .projection()
+                        .iter()
+                        .rposition(|proj| proj.is_deref())
+                        .map(|i| {
+                            Place::from_projected(place.local(), &place.projection()[..i])
+                                .to_place_index(&self.pt)
+                                .unwrap()
+                        });
                     actions.push(Box::new(move |pt| {
-                        pt.place_written(pidx);
+                        if let Some(pointer) = pointer {
+                            pt.place_written_through(pointer, pidx);
+                        } else {
+                            pt.place_written(pidx);
+                        }
                     }))
                 }
                 Statement::StorageLive(_) => {}
@@ -1452,6 +2026,17 @@ impl GenerationCtx {
         }
     }
 
+    /// The value of an operand, if it is known
+    fn known_operand(&self, operand: &Operand) -> Option<Literal> {
+        match operand {
+            Operand::Constant(lit) => Some(*lit),
+            Operand::Copy(place) | Operand::Move(place) => self
+                .pt
+                .is_place_init(place)
+                .then(|| self.pt.read_literal(place))?,
+        }
+    }
+
     fn aggregate_places<'a>(
         &self,
         root: PlaceIndex,
@@ -1486,3 +2071,93 @@ impl GenerationCtx {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mir::serialize::Serialize;
+    use mir::syntax::{
+        Callee, Literal, Mutability, Operand, Place, Rvalue, Statement, Terminator, UintTy,
+    };
+    use mir::tyctxt::TyCtxt;
+    use mir::VarDumper;
+
+    use super::GenerationCtx;
+
+    #[test]
+    fn slices_reach_generated_programs() {
+        for seed in 0..4 {
+            let mut ctx = GenerationCtx::new(seed, VarDumper::HashDumper, None);
+            ctx.generate_fn0();
+            // Statements are generated until there is an array reference to unsize
+            let mut statements = 0;
+            while ctx.generate_unsize_call().is_err() {
+                ctx.choose_statement();
+                statements += 1;
+                assert!(statements < 1000, "seed {seed} has nothing to unsize");
+            }
+            let unsize = ctx
+                .current_fn()
+                .basic_blocks
+                .iter()
+                .map(|bb| bb.terminator())
+                .rev()
+                .find(|term| !matches!(term, Terminator::Hole))
+                .unwrap()
+                .serialize(&ctx.tcx);
+            assert!(
+                unsize.contains("= core::convert::AsRef::as_ref("),
+                "{unsize}"
+            );
+
+            let len = Place::from_local(ctx.declare_new_var(Mutability::Mut, TyCtxt::USIZE));
+            let rvalue = ctx.generate_len(&len).expect("a slice has a known length");
+            assert!(matches!(rvalue, Rvalue::PtrMetadata(_)));
+            let stmt = Statement::Assign(len.clone(), rvalue);
+            ctx.post_generation(&stmt);
+            assert!(matches!(
+                ctx.pt.known_val(&len),
+                Some(Literal::Uint(_, UintTy::Usize))
+            ));
+        }
+    }
+
+    #[test]
+    fn bulk_copies_reach_generated_programs() {
+        let (mut unrolled, mut through_ptrs) = (false, false);
+        for seed in 0..4 {
+            let mut ctx = GenerationCtx::new(seed, VarDumper::HashDumper, None);
+            ctx.generate_fn0();
+            let mut statements = 0;
+            for _ in 0..4 {
+                // Statements are generated until there are two arrays of the same type
+                while ctx.generate_bulk_copy().is_err() {
+                    ctx.choose_statement();
+                    statements += 1;
+                    assert!(statements < 1000, "seed {seed} has no arrays to copy");
+                }
+                let terms: Vec<&Terminator> = ctx
+                    .current_fn()
+                    .basic_blocks
+                    .iter()
+                    .map(|bb| bb.terminator())
+                    .filter(|term| !matches!(term, Terminator::Hole))
+                    .collect();
+                let Some((Terminator::Call { args, .. }, before)) = terms.split_last() else {
+                    panic!("the destination is dumped");
+                };
+                let Some(Operand::Copy(dst)) = args.first() else {
+                    panic!("the destination is dumped by copy");
+                };
+                assert!(ctx.pt.is_place_init(dst));
+                match before.last() {
+                    Some(Terminator::Call {
+                        callee: Callee::Intrinsic("copy_nonoverlapping"),
+                        ..
+                    }) => through_ptrs = true,
+                    _ => unrolled = true,
+                }
+            }
+        }
+        assert!(unrolled && through_ptrs);
+    }
+}
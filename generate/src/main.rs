@@ -14,7 +14,7 @@ mod place_select;
 mod ptable;
 mod ty;
 
-use std::time::Instant;
+use std::{fs, path::PathBuf, time::Instant};
 
 use clap::{arg, command, value_parser};
 use log::{debug, info};
@@ -28,6 +28,8 @@ fn main() {
         .args(&[
             arg!(-d --debug "generate a program where values are printed instead of hashed (slow)"),
             arg!(-p --printf_debug "generate a program where values are printed using the C 'printf' function instead of hashed (slow)"),
+            arg!(--layout_probe "print a program which reports the layouts of the generated structs, and exit"),
+            arg!(--layouts <FILE> "use the struct layouts reported by the layout probe program").value_parser(value_parser!(PathBuf)),
             arg!(<seed> "generation seed").value_parser(value_parser!(u64)),
         ])
         .get_matches();
@@ -43,8 +45,15 @@ fn main() {
         (false,true)=>VarDumper::PrintfVarDumper,
         (true,true)=>panic!("You can only choose either the `debug` dumper or `printf_debug` dumper, but both of them have been selected."),
     };
+    if matches.get_flag("layout_probe") {
+        print!("{}", GenerationCtx::layout_probe(seed));
+        return;
+    }
+    let layouts = matches
+        .get_one::<PathBuf>("layouts")
+        .map(|path| fs::read_to_string(path).expect("can read the layout probe output"));
     info!("Generating a program with seed {seed}");
-    let genctxt = GenerationCtx::new(seed, dumper);
+    let genctxt = GenerationCtx::new(seed, dumper, layouts.as_deref());
     let time = Instant::now();
     let (program, tcx) = genctxt.generate();
     println!("{}", program.serialize(&tcx));
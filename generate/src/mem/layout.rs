@@ -0,0 +1,571 @@
+use std::mem;
+
+use abi::{align::Align, size::Size};
+#[cfg(test)]
+use mir::syntax::ProjectionElem;
+use mir::{
+    serialize::Serialize,
+    syntax::{TyId, TyKind},
+    tyctxt::{ProbedLayout, TyCtxt},
+    VarDumper,
+};
+
+use super::BasicMemory;
+
+/// Layout of a type with a deterministic layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub size: Size,
+    pub align: Align,
+    /// Offset of each field, for structs and unions
+    pub field_offsets: Vec<Size>,
+    /// Whether there's padding anywhere in the type, including in its fields
+    pub has_padding: bool,
+}
+
+#[derive(Debug)]
+pub struct ProbeError(pub String);
+
+#[derive(Debug)]
+pub struct LayoutMismatch(pub String);
+
+impl Layout {
+    pub const PTR_SIZE: Size = Size::from_bytes_const(mem::size_of::<*const ()>() as u64);
+
+    fn scalar(size: Size, align: usize) -> Self {
+        Layout {
+            size,
+            align: Align::from_bytes(align as u64).expect("valid alignment"),
+            field_offsets: vec![],
+            has_padding: false,
+        }
+    }
+
+    /// Returns the layout of types with guaranteed layout.
+    /// Composite types under the default layout has no guaranteed layout,
+    /// as the AM is free to insert arbitarily large paddings.
+    pub fn of(ty: TyId, tcx: &TyCtxt) -> Option<Self> {
+        Some(match ty {
+            TyCtxt::UNIT => Self::scalar(Size::ZERO, mem::align_of::<()>()),
+            TyCtxt::BOOL => Self::scalar(Size::from_bytes(1), mem::align_of::<bool>()),
+            TyCtxt::CHAR => Self::scalar(Size::from_bytes(4), mem::align_of::<char>()),
+            TyCtxt::I8 | TyCtxt::U8 => Self::scalar(Size::from_bits(8), mem::align_of::<u8>()),
+            TyCtxt::I16 | TyCtxt::U16 => Self::scalar(Size::from_bits(16), mem::align_of::<u16>()),
+            TyCtxt::I32 | TyCtxt::U32 => Self::scalar(Size::from_bits(32), mem::align_of::<u32>()),
+            TyCtxt::I64 | TyCtxt::U64 => Self::scalar(Size::from_bits(64), mem::align_of::<u64>()),
+            TyCtxt::I128 | TyCtxt::U128 => {
+                Self::scalar(Size::from_bits(128), mem::align_of::<u128>())
+            }
+            TyCtxt::F32 => Self::scalar(Size::from_bits(32), mem::align_of::<f32>()),
+            TyCtxt::F64 => Self::scalar(Size::from_bits(64), mem::align_of::<f64>()),
+            TyCtxt::ISIZE | TyCtxt::USIZE => Self::scalar(Self::PTR_SIZE, mem::align_of::<usize>()),
+            _ => match ty.kind(tcx) {
+                TyKind::RawPtr(pointee, _) | TyKind::Ref(pointee, _) if pointee.is_unsized(tcx) => {
+                    // The address followed by the length
+                    Self::scalar(
+                        Size::from_bytes(Self::PTR_SIZE.bytes() * 2),
+                        mem::align_of::<*const ()>(),
+                    )
+                }
+                TyKind::RawPtr(..) | TyKind::Ref(..) => {
+                    Self::scalar(Self::PTR_SIZE, mem::align_of::<*const ()>())
+                }
+                TyKind::FnPtr(..) => Self::scalar(Self::PTR_SIZE, mem::align_of::<fn()>()),
+                TyKind::Array(ty, len) => {
+                    let elem = Self::of(*ty, tcx)?;
+                    Layout {
+                        size: Size::from_bytes(elem.size.bytes_usize() * len),
+                        align: elem.align,
+                        field_offsets: vec![],
+                        has_padding: elem.has_padding,
+                    }
+                }
+                TyKind::Adt(adt) if adt.is_fieldless() => {
+                    let size = Self::discriminant_size(adt.variants.len());
+                    Self::scalar(size, size.bytes_usize().max(1))
+                }
+                TyKind::Adt(adt) if !adt.is_enum() && tcx.probed_layout(ty).is_some() => {
+                    let fields = &adt.variants.first().expect("adt is a struct").fields;
+                    let probed = tcx.probed_layout(ty).expect("layout is probed");
+                    Self::probed(probed, fields.as_raw_slice(), tcx)?
+                }
+                TyKind::Adt(adt) if !adt.is_enum() && tcx.meta(ty).union => {
+                    let fields = &adt.variants.first().expect("adt is a union").fields;
+                    Self::union(fields.as_raw_slice(), tcx)?
+                }
+                TyKind::Adt(adt) if !adt.is_enum() && tcx.meta(ty).repr_c => {
+                    let fields = &adt.variants.first().expect("adt is a struct").fields;
+                    Self::repr_c(fields.as_raw_slice(), tcx)?
+                }
+                _ => return None,
+            },
+        })
+    }
+
+    /// Returns the projections from a place of type ty to the leaf containing the byte at offset.
+    /// None if the byte is padding or out of bounds
+    #[cfg(test)]
+    pub fn field_path(ty: TyId, offset: Size, tcx: &TyCtxt) -> Option<Vec<ProjectionElem>> {
+        let layout = Self::of(ty, tcx)?;
+        if offset >= layout.size {
+            return None;
+        }
+        match ty.kind(tcx) {
+            TyKind::Array(elem_ty, _) => {
+                let elem_size = Self::of(*elem_ty, tcx)?.size;
+                let index = offset.bytes() / elem_size.bytes();
+                let mut path = vec![ProjectionElem::ConstantIndex { offset: index }];
+                path.extend(Self::field_path(
+                    *elem_ty,
+                    offset - Size::from_bytes(index * elem_size.bytes()),
+                    tcx,
+                )?);
+                Some(path)
+            }
+            TyKind::Adt(adt) if !layout.field_offsets.is_empty() => {
+                let fields = &adt.variants.first().expect("adt is a struct").fields;
+                // Fields aren't necessarily in offset order under the default repr
+                let (fid, field_ty) = fields.iter_enumerated().find(|(fid, field_ty)| {
+                    let start = layout.field_offsets[fid.index()];
+                    start <= offset
+                        && Self::of(**field_ty, tcx)
+                            .is_some_and(|field| offset < start + field.size)
+                })?;
+                let mut path = vec![ProjectionElem::Field(fid)];
+                path.extend(Self::field_path(
+                    *field_ty,
+                    offset - layout.field_offsets[fid.index()],
+                    tcx,
+                )?);
+                Some(path)
+            }
+            _ => Some(vec![]),
+        }
+    }
+
+    /// A program printing the size, alignment and field offsets of every struct,
+    /// in the format read by load_probe. repr(C) structs are included so that
+    /// self_check can compare them with our own computation.
+    /// Enums are left out, as there is no offset_of for their fields.
+    pub fn probe_program(tcx: &TyCtxt) -> String {
+        let mut program = tcx.serialize(VarDumper::StdVarDumper);
+        program += "fn main() {\n";
+        for (ty, kind) in tcx.iter_enumerated() {
+            let TyKind::Adt(adt) = kind else {
+                continue;
+            };
+            if adt.is_enum() {
+                continue;
+            }
+            let name = ty.type_name();
+            let fields = &adt.variants.first().expect("adt is a struct").fields;
+            let args: String = [
+                format!("core::mem::size_of::<{name}>()"),
+                format!("core::mem::align_of::<{name}>()"),
+            ]
+            .into_iter()
+            .chain(
+                fields
+                    .indices()
+                    .map(|fid| format!("core::mem::offset_of!({name}, {})", fid.identifier())),
+            )
+            .map(|arg| format!(", {arg}"))
+            .collect();
+            let placeholders = vec!["{}"; fields.len() + 2].join(" ");
+            program += &format!("println!(\"{} {placeholders}\"{args});\n", ty.index());
+        }
+        program += "}\n";
+        program
+    }
+
+    /// Reads the output of the probe program. Layout::of then uses the reported
+    /// layouts for those structs instead of treating them as unknown.
+    pub fn load_probe(output: &str, tcx: &mut TyCtxt) -> Result<(), ProbeError> {
+        for line in output.lines().filter(|line| !line.trim().is_empty()) {
+            let malformed = || ProbeError(format!("malformed probe output: {line}"));
+            let nums: Vec<u64> = line
+                .split_whitespace()
+                .map(|num| num.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| malformed())?;
+            let [ty, size, align, field_offsets @ ..] = nums.as_slice() else {
+                return Err(malformed());
+            };
+            let ty = TyId::new(*ty as usize);
+            if ty.index() >= tcx.len() {
+                return Err(ProbeError(format!("unknown type in probe output: {line}")));
+            }
+            match ty.kind(tcx) {
+                TyKind::Adt(adt)
+                    if !adt.is_enum()
+                        && adt.variants.first().expect("adt is a struct").fields.len()
+                            == field_offsets.len() => {}
+                _ => return Err(ProbeError(format!("not a struct of this shape: {line}"))),
+            }
+            if !align.is_power_of_two() {
+                return Err(malformed());
+            }
+            tcx.set_probed_layout(
+                ty,
+                ProbedLayout {
+                    size: *size,
+                    align: *align,
+                    field_offsets: field_offsets.to_vec(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that ty_size agrees with the layout of each type, that every layout is well formed,
+    /// and that probed layouts of repr(C) structs match what repr(C) prescribes.
+    /// Memory would be silently corrupted if runs were sized and offset by disagreeing layouts.
+    pub fn self_check(tcx: &TyCtxt) -> Result<(), LayoutMismatch> {
+        for ty in tcx.indices() {
+            let Some(layout) = Self::of(ty, tcx) else {
+                // A probed struct stays unknown if one of its fields is, such as a tuple
+                if tcx.probed_layout(ty).is_some()
+                    && let TyKind::Adt(adt) = ty.kind(tcx)
+                    && adt
+                        .variants
+                        .first()
+                        .expect("adt is a struct")
+                        .fields
+                        .iter()
+                        .all(|field| Self::of(*field, tcx).is_some())
+                {
+                    return Err(LayoutMismatch(format!(
+                        "{}: probed layout has fields out of bounds",
+                        ty.serialize(tcx)
+                    )));
+                }
+                continue;
+            };
+            let mismatch = |what: &str| {
+                Err(LayoutMismatch(format!(
+                    "{}: {what} in {layout:?}",
+                    ty.serialize(tcx)
+                )))
+            };
+            if BasicMemory::ty_size(ty, tcx) != Some(layout.size) {
+                return mismatch("ty_size differs from the layout size");
+            }
+            if layout.size.bytes() % layout.align.bytes() != 0 {
+                return mismatch("size isn't a multiple of the alignment");
+            }
+            match ty.kind(tcx) {
+                TyKind::Array(elem, len) => {
+                    let elem = Self::of(*elem, tcx).expect("element of a sized array is sized");
+                    if elem.size.bytes() * *len as u64 != layout.size.bytes() {
+                        return mismatch("size isn't element size times length");
+                    }
+                }
+                TyKind::Adt(adt) if !adt.is_enum() && !layout.field_offsets.is_empty() => {
+                    let fields = &adt.variants.first().expect("adt is a struct").fields;
+                    for (field, offset) in fields.iter().zip(&layout.field_offsets) {
+                        let Some(field) = Self::of(*field, tcx) else {
+                            return mismatch("a field has no layout");
+                        };
+                        if offset.bytes() % field.align.bytes() != 0 || field.align > layout.align {
+                            return mismatch("a field is misaligned");
+                        }
+                        if *offset + field.size > layout.size {
+                            return mismatch("a field is out of bounds");
+                        }
+                    }
+                    let meta = tcx.meta(ty);
+                    if meta.union {
+                        if Self::union(fields.as_raw_slice(), tcx).as_ref() != Some(&layout) {
+                            return mismatch("probed layout differs from the union layout");
+                        }
+                    } else if meta.repr_c
+                        && Self::repr_c(fields.as_raw_slice(), tcx).as_ref() != Some(&layout)
+                    {
+                        return mismatch("probed layout differs from repr(C)");
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Fields at the offsets reported by the compiler. Any byte not covered by a field is padding
+    fn probed(probed: &ProbedLayout, fields: &[TyId], tcx: &TyCtxt) -> Option<Self> {
+        let mut covered = vec![false; probed.size as usize];
+        let mut has_padding = false;
+        for (&field, &offset) in fields.iter().zip(&probed.field_offsets) {
+            let layout = Self::of(field, tcx)?;
+            has_padding |= layout.has_padding;
+            let start = offset as usize;
+            covered
+                .get_mut(start..start + layout.size.bytes_usize())?
+                .fill(true);
+        }
+        has_padding |= covered.contains(&false);
+        Some(Layout {
+            size: Size::from_bytes(probed.size),
+            align: Align::from_bytes(probed.align).ok()?,
+            field_offsets: probed
+                .field_offsets
+                .iter()
+                .map(|&offset| Size::from_bytes(offset))
+                .collect(),
+            has_padding,
+        })
+    }
+
+    /// Size of the discriminant of a field-less enum, which is the smallest
+    /// integer fitting all variants
+    pub fn discriminant_size(variant_count: usize) -> Size {
+        match variant_count {
+            0..=1 => Size::ZERO,
+            2..=256 => Size::from_bits(8),
+            _ => Size::from_bits(16),
+        }
+    }
+
+    /// Fields laid out in order with natural alignment, as in #[repr(C)]
+    fn repr_c(fields: &[TyId], tcx: &TyCtxt) -> Option<Self> {
+        let mut offset = Size::ZERO;
+        let mut align = Align::ONE;
+        let mut has_padding = false;
+        let mut field_offsets = Vec::with_capacity(fields.len());
+        for &field in fields {
+            let layout = Self::of(field, tcx)?;
+            let field_offset = offset.align_to(layout.align);
+            has_padding |= layout.has_padding || field_offset != offset;
+            field_offsets.push(field_offset);
+            offset = field_offset + layout.size;
+            align = align.max(layout.align);
+        }
+        let size = offset.align_to(align);
+        has_padding |= size != offset;
+        Some(Layout {
+            size,
+            align,
+            field_offsets,
+            has_padding,
+        })
+    }
+
+    /// All fields at offset 0, as in a #[repr(C)] union. Bytes beyond a field are only
+    /// covered by some of them, so such a union is treated as having padding
+    fn union(fields: &[TyId], tcx: &TyCtxt) -> Option<Self> {
+        let layouts: Vec<Self> = fields
+            .iter()
+            .map(|&field| Self::of(field, tcx))
+            .collect::<Option<_>>()?;
+        let align = layouts
+            .iter()
+            .map(|layout| layout.align)
+            .max()
+            .unwrap_or(Align::ONE);
+        let size = layouts
+            .iter()
+            .map(|layout| layout.size)
+            .max()
+            .unwrap_or(Size::ZERO)
+            .align_to(align);
+        Some(Layout {
+            size,
+            align,
+            field_offsets: vec![Size::ZERO; fields.len()],
+            has_padding: layouts
+                .iter()
+                .any(|layout| layout.has_padding || layout.size != size),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abi::size::Size;
+    use index_vec::IndexVec;
+    use mir::{
+        syntax::{Adt, FieldIdx, Mutability, ProjectionElem, TyKind, VariantDef},
+        tyctxt::{AdtMeta, TyCtxt},
+    };
+
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::Layout;
+    use crate::ty::seed_tys;
+
+    #[test]
+    fn field_path() {
+        let mut tcx = TyCtxt::from_primitives();
+        let meta = AdtMeta {
+            copy: true,
+            repr_c: true,
+            union: false,
+        };
+        // struct Inner { u16, u64 }
+        let inner = tcx.push_adt(
+            Adt {
+                variants: IndexVec::from_iter([VariantDef {
+                    fields: IndexVec::from_iter([TyCtxt::U16, TyCtxt::U64]),
+                }]),
+            },
+            meta,
+        );
+        let arr = tcx.push(TyKind::Array(inner, 2));
+        // struct Outer { u8, [Inner; 2] }
+        let outer = tcx.push_adt(
+            Adt {
+                variants: IndexVec::from_iter([VariantDef {
+                    fields: IndexVec::from_iter([TyCtxt::U8, arr]),
+                }]),
+            },
+            meta,
+        );
+
+        assert_eq!(Layout::of(outer, &tcx).unwrap().size, Size::from_bytes(40));
+        assert_eq!(
+            Layout::field_path(outer, Size::ZERO, &tcx).unwrap(),
+            vec![ProjectionElem::Field(FieldIdx::new(0))]
+        );
+        // Padding after the first field
+        assert_eq!(Layout::field_path(outer, Size::from_bytes(3), &tcx), None);
+        // outer.1[1].1 starts at 8 + 16 + 8
+        assert_eq!(
+            Layout::field_path(outer, Size::from_bytes(35), &tcx).unwrap(),
+            vec![
+                ProjectionElem::Field(FieldIdx::new(1)),
+                ProjectionElem::ConstantIndex { offset: 1 },
+                ProjectionElem::Field(FieldIdx::new(1)),
+            ]
+        );
+        // Padding inside outer.1[0]
+        assert_eq!(Layout::field_path(outer, Size::from_bytes(12), &tcx), None);
+        assert_eq!(Layout::field_path(outer, Size::from_bytes(40), &tcx), None);
+    }
+
+    #[test]
+    fn pointer_sizes() {
+        let mut tcx = TyCtxt::from_primitives();
+        let slice = tcx.push(TyKind::Slice(TyCtxt::U8));
+        let str = tcx.push(TyKind::Str);
+        let array = tcx.push(TyKind::Array(TyCtxt::U8, 4));
+        let pointers = [
+            (tcx.push(TyKind::Ref(TyCtxt::U8, Mutability::Not)), 1),
+            (tcx.push(TyKind::RawPtr(array, Mutability::Mut)), 1),
+            (tcx.push(TyKind::Ref(slice, Mutability::Not)), 2),
+            (tcx.push(TyKind::RawPtr(slice, Mutability::Mut)), 2),
+            (tcx.push(TyKind::Ref(str, Mutability::Not)), 2),
+        ];
+        for (ptr, words) in pointers {
+            let layout = Layout::of(ptr, &tcx).unwrap();
+            assert_eq!(layout.size.bytes(), Layout::PTR_SIZE.bytes() * words);
+            assert_eq!(layout.align.bytes(), Layout::PTR_SIZE.bytes());
+        }
+        // Unsized types themselves have no layout
+        assert_eq!(Layout::of(slice, &tcx), None);
+        assert_eq!(Layout::of(str, &tcx), None);
+        assert!(Layout::self_check(&tcx).is_ok());
+    }
+
+    #[test]
+    fn probed_layout() {
+        let mut tcx = TyCtxt::from_primitives();
+        let meta = AdtMeta {
+            copy: true,
+            repr_c: false,
+            union: false,
+        };
+        // struct S { u8, u32, u16 }
+        let s = tcx.push_adt(
+            Adt {
+                variants: IndexVec::from_iter([VariantDef {
+                    fields: IndexVec::from_iter([TyCtxt::U8, TyCtxt::U32, TyCtxt::U16]),
+                }]),
+            },
+            meta,
+        );
+        assert_eq!(Layout::of(s, &tcx), None);
+
+        let program = Layout::probe_program(&tcx);
+        assert!(program.contains(&format!(
+            "println!(\"{} {{}} {{}} {{}} {{}} {{}}\", core::mem::size_of::<Adt{0}>()",
+            s.index()
+        )));
+        assert!(program.contains(&format!("core::mem::offset_of!(Adt{}, fld2)", s.index())));
+
+        // What rustc reports for S, with the fields reordered to 4, 0 and 6
+        Layout::load_probe(&format!("{} 8 4 6 0 4\n", s.index()), &mut tcx).unwrap();
+        let layout = Layout::of(s, &tcx).unwrap();
+        assert_eq!(layout.size, Size::from_bytes(8));
+        assert_eq!(
+            layout.field_offsets,
+            vec![Size::from_bytes(6), Size::ZERO, Size::from_bytes(4)]
+        );
+        assert!(layout.has_padding);
+        assert_eq!(
+            Layout::field_path(s, Size::from_bytes(5), &tcx).unwrap(),
+            vec![ProjectionElem::Field(FieldIdx::new(2))]
+        );
+        assert_eq!(Layout::field_path(s, Size::from_bytes(7), &tcx), None);
+
+        assert!(Layout::load_probe("17 8 four 0 4 6", &mut tcx).is_err());
+        assert!(Layout::load_probe(&format!("{} 8 4 0 4", s.index()), &mut tcx).is_err());
+        assert!(Layout::load_probe(&format!("{} 8 3 0 4 6", s.index()), &mut tcx).is_err());
+        assert!(Layout::load_probe("1 1 1", &mut tcx).is_err());
+        assert!(Layout::load_probe("100 1 1", &mut tcx).is_err());
+    }
+
+    #[test]
+    fn self_check() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let tcx = seed_tys(&mut rng);
+            Layout::self_check(&tcx).unwrap();
+        }
+
+        let mut tcx = TyCtxt::from_primitives();
+        let meta = AdtMeta {
+            copy: true,
+            repr_c: true,
+            union: false,
+        };
+        let s = tcx.push_adt(
+            Adt {
+                variants: IndexVec::from_iter([VariantDef {
+                    fields: IndexVec::from_iter([TyCtxt::U8, TyCtxt::U32]),
+                }]),
+            },
+            meta,
+        );
+        tcx.push(TyKind::Array(s, 3));
+        Layout::self_check(&tcx).unwrap();
+
+        // A compiler agreeing with repr(C)
+        Layout::load_probe(&format!("{} 8 4 0 4", s.index()), &mut tcx).unwrap();
+        Layout::self_check(&tcx).unwrap();
+
+        // A compiler disagreeing with repr(C)
+        Layout::load_probe(&format!("{} 8 4 4 0", s.index()), &mut tcx).unwrap();
+        assert!(Layout::self_check(&tcx).is_err());
+
+        // Field past the end
+        Layout::load_probe(&format!("{} 8 4 0 6", s.index()), &mut tcx).unwrap();
+        assert!(Layout::self_check(&tcx).is_err());
+
+        // A field without a layout leaves the struct unknown
+        let tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::U8, TyCtxt::U32]));
+        let t = tcx.push_adt(
+            Adt {
+                variants: IndexVec::from_iter([VariantDef {
+                    fields: IndexVec::from_iter([tuple, TyCtxt::U8]),
+                }]),
+            },
+            AdtMeta {
+                repr_c: false,
+                ..meta
+            },
+        );
+        Layout::load_probe(&format!("{} 12 4 0 8", t.index()), &mut tcx).unwrap();
+        assert_eq!(Layout::of(t, &tcx), None);
+        Layout::load_probe(&format!("{} 8 4 0 4", s.index()), &mut tcx).unwrap();
+        Layout::self_check(&tcx).unwrap();
+    }
+}
@@ -1,102 +1,391 @@
 use std::{
-    collections::{BTreeSet, HashMap},
-    fmt, mem,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
     ops::Range,
 };
 
 use abi::size::Size;
 use index_vec::{define_index_type, IndexVec};
-use mir::{
-    syntax::{TyId, TyKind},
-    tyctxt::TyCtxt,
-};
+use mir::{syntax::TyId, tyctxt::TyCtxt};
 use rangemap::RangeMap;
 use smallvec::SmallVec;
 
+mod layout;
+mod trace;
+
+pub use layout::{Layout, LayoutMismatch, ProbeError};
+use trace::Tracer;
+#[cfg(test)]
+pub use trace::VecTrace;
+pub use trace::{LogTrace, MemEvent, TraceSink};
+
 define_index_type! {pub struct Tag = u32;}
 
+/// What a pointer may access memory with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provenance {
+    Tag(Tag),
+    /// A pointer cast from an integer may use any tag exposed in the allocation it points into.
+    /// The generator doesn't cast integers to pointers yet
+    #[cfg(test)]
+    Wildcard,
+}
+
+impl From<Tag> for Provenance {
+    fn from(tag: Tag) -> Self {
+        Provenance::Tag(tag)
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AbstractByte {
     /// An uninitialized byte.
     Uninit,
-    /// An initialized byte, optionally with some provenance (if it is encoding a pointer).
-    Init,
+    /// An initialized byte, with its value if it is known.
+    Init(Option<u8>),
 }
 
 impl fmt::Debug for AbstractByte {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Uninit => write!(f, "UU"),
-            Self::Init => write!(f, "II"),
+            Self::Init(None) => write!(f, "II"),
+            Self::Init(Some(val)) => write!(f, "{val:02x}"),
         }
     }
 }
 
 impl AbstractByte {
     pub fn is_init(&self) -> bool {
-        self == &AbstractByte::Init
+        matches!(self, AbstractByte::Init(_))
+    }
+
+    pub fn known_value(&self) -> Option<u8> {
+        match self {
+            AbstractByte::Init(val) => *val,
+            AbstractByte::Uninit => None,
+        }
+    }
+}
+
+/// The lowest size bytes of val, in the host's byte order
+pub fn encode_scalar(val: u128, size: Size) -> Vec<u8> {
+    let size = size.bytes_usize();
+    assert!(size <= 16, "scalars are at most 16 bytes");
+    let bytes = val.to_ne_bytes();
+    if cfg!(target_endian = "little") {
+        bytes[..size].to_vec()
+    } else {
+        bytes[16 - size..].to_vec()
     }
 }
 
+/// The inverse of encode_scalar, zero-extended
+pub fn decode_scalar(bytes: &[u8]) -> u128 {
+    assert!(bytes.len() <= 16, "scalars are at most 16 bytes");
+    let mut buf = [0; 16];
+    if cfg!(target_endian = "little") {
+        buf[..bytes.len()].copy_from_slice(bytes);
+    } else {
+        buf[16 - bytes.len()..].copy_from_slice(bytes);
+    }
+    u128::from_ne_bytes(buf)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BorrowType {
     Raw,
+    /// A read-only shared reference. Writing pops it and everything above
     Shared,
+    /// A shared reference with interior mutability, like &UnsafeCell<T>. It permits
+    /// writes, and isn't popped by them. No generated type has interior mutability yet
+    #[allow(dead_code)]
+    SharedReadWrite,
     Exclusive,
 }
 
+impl BorrowType {
+    /// Whether writes through this leave the borrows of the same kind above it alone
+    fn shares_writes(self) -> bool {
+        !matches!(self, BorrowType::Shared | BorrowType::Exclusive)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Borrow {
     borrow_type: BorrowType,
     tag: Tag,
     protected: bool,
 }
 
+impl fmt::Display for Borrow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} #{}", self.borrow_type, self.tag.index())?;
+        if self.protected {
+            write!(f, " protected")?;
+        }
+        Ok(())
+    }
+}
+
+/// What a tag is allowed to do with a byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Permission {
+    Disabled,
+    Read,
+    Write,
+}
+
 /// A Run represents a contiguous region of memory free of padding
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Run {
     bytes: Box<[AbstractByte]>,
     ref_stack: RangeMap<Vec<Borrow>>,
+
+    // What each tag in ref_stack grants where, kept in sync with it so that
+    // access checks don't have to scan the stacks
+    permissions: BTreeMap<Tag, RangeMap<Permission>>,
+
+    // Borrow stack entries, counting a borrow once for every byte it covers
+    entries: u64,
+
+    // Tag of the pointer each byte is part of, if any
+    provenance: Box<[Option<Tag>]>,
+
+    // Caller supplied origin of the last write to each byte, for diagnostics
+    #[cfg(all(test, debug_assertions))]
+    origins: Box<[Option<u32>]>,
 }
 
 impl Run {
     pub fn new_uninit(size: Size) -> Self {
         let bytes = vec![AbstractByte::Uninit; size.bytes() as usize].into_boxed_slice();
         let ref_stack = RangeMap::new(size, vec![]);
-        Self { bytes, ref_stack }
+        Self {
+            bytes,
+            ref_stack,
+            permissions: BTreeMap::new(),
+            entries: 0,
+            provenance: vec![None; size.bytes() as usize].into_boxed_slice(),
+            #[cfg(all(test, debug_assertions))]
+            origins: vec![None; size.bytes() as usize].into_boxed_slice(),
+        }
     }
 
     pub fn size(&self) -> Size {
         Size::from_bytes(self.bytes.len())
     }
 
+    /// Applies f to the stacks in offset..offset+len, and updates the permissions of the tags
+    /// whose access changed
+    fn modify_stacks(&mut self, offset: Size, len: Size, mut f: impl FnMut(&mut Vec<Borrow>)) {
+        let mut starts = vec![];
+        let mut depths = vec![];
+        let mut changes = vec![];
+        for (start, stack) in self.ref_stack.iter_mut(offset, len) {
+            let before = Self::stack_permissions(stack);
+            let depth = stack.len();
+            f(stack);
+            depths.push((depth, stack.len()));
+            let after = Self::stack_permissions(stack);
+            let granted = |permissions: &[(Tag, Permission)], tag| {
+                permissions
+                    .binary_search_by_key(&tag, |(tag, _)| *tag)
+                    .map_or(Permission::Disabled, |i| permissions[i].1)
+            };
+            for (tag, new) in &after {
+                if granted(&before, *tag) != *new {
+                    changes.push((starts.len(), *tag, *new));
+                }
+            }
+            for (tag, _) in &before {
+                if granted(&after, *tag) == Permission::Disabled {
+                    changes.push((starts.len(), *tag, Permission::Disabled));
+                }
+            }
+            starts.push(start.bytes());
+        }
+        let end = offset.bytes() + len.bytes();
+        for (i, (before, after)) in depths.into_iter().enumerate() {
+            let segment_len = starts.get(i + 1).copied().unwrap_or(end) - starts[i];
+            self.entries = self.entries - before as u64 * segment_len + after as u64 * segment_len;
+        }
+        self.apply_permissions(offset, len, &starts, changes);
+    }
+
+    /// Sets the permission of a tag over the segments starting at starts, where
+    /// changes holds (segment index, tag, permission)
+    fn apply_permissions(
+        &mut self,
+        offset: Size,
+        len: Size,
+        starts: &[u64],
+        changes: Vec<(usize, Tag, Permission)>,
+    ) {
+        let size = self.size();
+        for (segment, tag, new) in changes {
+            // Segments end where the next one starts
+            let start = starts[segment];
+            let end = starts
+                .get(segment + 1)
+                .copied()
+                .unwrap_or(offset.bytes() + len.bytes());
+            let permission = self
+                .permissions
+                .entry(tag)
+                .or_insert_with(|| RangeMap::new(size, Permission::Disabled));
+            for (_, permission) in
+                permission.iter_mut(Size::from_bytes(start), Size::from_bytes(end - start))
+            {
+                *permission = new;
+            }
+            if new == Permission::Disabled
+                && permission
+                    .iter_all()
+                    .all(|(_, permission)| *permission == Permission::Disabled)
+            {
+                self.permissions.remove(&tag);
+            }
+        }
+    }
+
+    /// The access each tag in a stack is granted, ordered by tag. Everything from the first read-only shared
+    /// borrow upwards is popped by writes, which protected borrows mustn't be
+    fn stack_permissions(stack: &[Borrow]) -> SmallVec<[(Tag, Permission); 8]> {
+        let first_shared = stack
+            .iter()
+            .position(|borrow| borrow.borrow_type == BorrowType::Shared);
+        let writable = match first_shared {
+            Some(first_shared) if stack[first_shared..].iter().any(|b| b.protected) => 0,
+            Some(first_shared) => first_shared,
+            None => stack.len(),
+        };
+        let mut permissions: SmallVec<[(Tag, Permission); 8]> = stack
+            .iter()
+            .enumerate()
+            .map(|(i, borrow)| {
+                let granted = if i < writable {
+                    Permission::Write
+                } else {
+                    Permission::Read
+                };
+                (borrow.tag, granted)
+            })
+            .collect();
+        // A tag may be in a stack more than once, and is granted its most permissive access
+        permissions.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        permissions.dedup_by_key(|(tag, _)| *tag);
+        permissions
+    }
+
+    /// Adds what the borrows in each stack grant over its byte range to permissions
+    #[cfg(test)]
+    fn grant(
+        permissions: &mut BTreeMap<Tag, RangeMap<Permission>>,
+        size: Size,
+        stacks: Vec<(Range<u64>, &[Borrow])>,
+    ) {
+        for (range, stack) in stacks {
+            for (tag, granted) in Self::stack_permissions(stack) {
+                let permission = permissions
+                    .entry(tag)
+                    .or_insert_with(|| RangeMap::new(size, Permission::Disabled));
+                for (_, permission) in permission.iter_mut(
+                    Size::from_bytes(range.start),
+                    Size::from_bytes(range.end - range.start),
+                ) {
+                    *permission = granted;
+                }
+            }
+        }
+    }
+
     pub fn add_borrow(&mut self, offset: Size, len: Size, borrow_type: BorrowType, tag: Tag) {
-        for (_, stack) in self.ref_stack.iter_mut(offset, len) {
+        // Pushing onto a stack leaves the access of the tags already in it unchanged
+        let mut starts = vec![];
+        let mut changes = vec![];
+        for (start, stack) in self.ref_stack.iter_mut(offset, len) {
+            let mut granted = if borrow_type == BorrowType::Shared
+                || stack.iter().any(|b| b.borrow_type == BorrowType::Shared)
+            {
+                Permission::Read
+            } else {
+                Permission::Write
+            };
+            if stack.iter().any(|b| b.tag == tag) {
+                let permissions = Self::stack_permissions(stack);
+                let i = permissions
+                    .binary_search_by_key(&tag, |(tag, _)| *tag)
+                    .unwrap();
+                granted = granted.max(permissions[i].1);
+            }
             stack.push(Borrow {
                 borrow_type,
                 tag,
                 protected: false,
             });
+            changes.push((starts.len(), tag, granted));
+            starts.push(start.bytes());
         }
+        self.entries += len.bytes();
+        self.apply_permissions(offset, len, &starts, changes);
     }
 
+    /// Removes the topmost borrow with tag. A tag reborrowed through a copied pointer is in
+    /// the stack more than once, and the borrows below stay in place
     pub fn remove_borrow(&mut self, offset: Size, len: Size, tag: Tag) {
-        for (_, stack) in self.ref_stack.iter_mut(offset, len) {
-            if let Some(i) = stack.iter().position(|b| b.tag == tag) {
+        self.modify_stacks(offset, len, |stack| {
+            if let Some(i) = stack.iter().rposition(|b| b.tag == tag) {
                 let removed = stack.remove(i);
                 assert!(!removed.protected);
             }
-        }
+        });
+    }
+
+    /// Removes every borrow with tag
+    pub fn remove_all_of_tag(&mut self, offset: Size, len: Size, tag: Tag) {
+        self.modify_stacks(offset, len, |stack| {
+            stack.retain(|b| {
+                assert!(b.tag != tag || !b.protected);
+                b.tag != tag
+            });
+        });
     }
 
     pub fn protect(&mut self, offset: Size, len: Size, tag: Tag) {
-        for (_, stack) in self.ref_stack.iter_mut(offset, len) {
+        self.modify_stacks(offset, len, |stack| {
             if let Some(i) = stack.iter().position(|b| b.tag == tag) {
                 stack[i].protected = true;
             }
-        }
+        });
+    }
+
+    pub fn unprotect(&mut self, offset: Size, len: Size, tag: Tag) {
+        self.modify_stacks(offset, len, |stack| {
+            if let Some(i) = stack.iter().position(|b| b.tag == tag) {
+                stack[i].protected = false;
+            }
+        });
+    }
+
+    /// Whether a write, which pops everything from the first shared borrow upwards,
+    /// would pop a protected borrow
+    pub fn write_pops_protected(&self, offset: Size, len: Size) -> bool {
+        self.ref_stack.iter(offset, len).any(|(_, stack)| {
+            stack
+                .iter()
+                .skip_while(|borrow| borrow.borrow_type != BorrowType::Shared)
+                .any(|borrow| borrow.protected)
+        })
     }
 
+    /// Returns tags from the first shared borrow upwards, in ascending order
     pub fn above_first_shared(&self, offset: Size, len: Size) -> Vec<Tag> {
         let mut edges = BTreeSet::new();
         for (_, stack) in self.ref_stack.iter(offset, len) {
@@ -110,6 +399,7 @@ impl Run {
         edges.iter().copied().collect()
     }
 
+    /// Returns tags below the first shared borrow, in ascending order
     pub fn below_first_shared(&self, offset: Size, len: Size) -> Vec<Tag> {
         let mut edges = BTreeSet::new();
         for (_, stack) in self.ref_stack.iter(offset, len) {
@@ -123,28 +413,191 @@ impl Run {
         edges.iter().copied().collect()
     }
 
+    /// Removes tag and everything above it. Returns the removed tags in ascending order
     pub fn remove_all_above(&mut self, offset: Size, len: Size, tag: Tag) -> Vec<Tag> {
-        let mut edges = vec![];
-        for (_, stack) in self.ref_stack.iter_mut(offset, len) {
-            let index = stack.iter().position(|borrow| borrow.tag == tag);
-            if let Some(index) = index {
+        let mut edges = BTreeSet::new();
+        self.modify_stacks(offset, len, |stack| {
+            if let Some(index) = stack.iter().position(|borrow| borrow.tag == tag) {
+                edges.extend(stack[index..].iter().map(|borrow| borrow.tag));
+                stack.truncate(index);
+            }
+        });
+        edges.into_iter().collect()
+    }
+
+    /// Writes with tag, which pops everything above it, except for the raw borrows right above
+    /// a raw tag. Returns the popped tags in ascending order
+    pub fn write_with(&mut self, offset: Size, len: Size, tag: Tag) -> Vec<Tag> {
+        assert!(
+            !self.write_with_pops_protected(offset, len, tag),
+            "write pops a protected borrow"
+        );
+        let mut edges = BTreeSet::new();
+        self.modify_stacks(offset, len, |stack| {
+            if let Some(index) = Self::write_cutoff(stack, tag) {
                 edges.extend(stack[index..].iter().map(|borrow| borrow.tag));
                 stack.truncate(index);
             }
+        });
+        edges.into_iter().collect()
+    }
+
+    /// Whether a write with tag would pop a protected borrow
+    pub fn write_with_pops_protected(&self, offset: Size, len: Size, tag: Tag) -> bool {
+        self.ref_stack.iter(offset, len).any(|(_, stack)| {
+            Self::write_cutoff(stack, tag)
+                .is_some_and(|index| stack[index..].iter().any(|borrow| borrow.protected))
+        })
+    }
+
+    /// Where a write with tag starts popping. Raw borrows share writes with the raw borrows
+    /// right above them, so a raw tag keeps those. None if the stack doesn't have tag
+    fn write_cutoff(stack: &[Borrow], tag: Tag) -> Option<usize> {
+        let granting = stack.iter().rposition(|borrow| borrow.tag == tag)?;
+        let kept = if stack[granting].borrow_type.shares_writes() {
+            stack[granting + 1..]
+                .iter()
+                .take_while(|borrow| borrow.borrow_type.shares_writes())
+                .count()
+        } else {
+            0
+        };
+        Some(granting + 1 + kept)
+    }
+
+    /// Whether a read with tag would pop a protected exclusive borrow
+    pub fn read_with_pops_protected(&self, offset: Size, len: Size, tag: Tag) -> bool {
+        self.ref_stack.iter(offset, len).any(|(_, stack)| {
+            stack
+                .iter()
+                .skip_while(|borrow| borrow.tag != tag)
+                .skip(1)
+                .any(|borrow| borrow.borrow_type == BorrowType::Exclusive && borrow.protected)
+        })
+    }
+
+    /// Reads with tag, which pops all exclusive borrows above it. Returns the popped tags
+    /// in ascending order
+    pub fn read_with(&mut self, offset: Size, len: Size, tag: Tag) -> Vec<Tag> {
+        let mut edges = BTreeSet::new();
+        self.modify_stacks(offset, len, |stack| {
+            let Some(granting) = stack.iter().position(|borrow| borrow.tag == tag) else {
+                return;
+            };
+            let mut i = granting + 1;
+            while i < stack.len() {
+                if stack[i].borrow_type == BorrowType::Exclusive {
+                    let removed = stack.remove(i);
+                    assert!(!removed.protected, "read pops a protected borrow");
+                    edges.insert(removed.tag);
+                } else {
+                    i += 1;
+                }
+            }
+        });
+        edges.into_iter().collect()
+    }
+
+    /// The borrow stack of each byte range, clipped to offset..offset+len
+    fn stacks(&self, offset: Size, len: Size) -> Vec<(Range<u64>, &[Borrow])> {
+        Self::clipped_stacks(&self.ref_stack, offset, len)
+    }
+
+    fn clipped_stacks(
+        ref_stack: &RangeMap<Vec<Borrow>>,
+        offset: Size,
+        len: Size,
+    ) -> Vec<(Range<u64>, &[Borrow])> {
+        let end = offset.bytes() + len.bytes();
+        let mut stacks: Vec<(Range<u64>, &[Borrow])> = ref_stack
+            .iter(offset, len)
+            .map(|(start, stack)| (start.bytes().max(offset.bytes())..end, stack.as_slice()))
+            .collect();
+        for i in 1..stacks.len() {
+            stacks[i - 1].0.end = stacks[i].0.start;
+        }
+        stacks
+    }
+
+    /// Checks that the permissions and entry count agree with the stacks.
+    /// Returns the offset and tag of the first violation
+    fn check_stacks(&self) -> Result<(), (Size, Option<Tag>, &'static str)> {
+        let mut entries = 0;
+        for (range, stack) in self.ref_stack.iter_all() {
+            let offset = Size::from_bytes(range.start);
+            let len = Size::from_bytes(range.end - range.start);
+            entries += len.bytes() * stack.len() as u64;
+            for (tag, granted) in Self::stack_permissions(stack) {
+                let permission = self.permissions.get(&tag);
+                if !permission.is_some_and(|permission| {
+                    permission
+                        .iter(offset, len)
+                        .all(|(_, permission)| *permission == granted)
+                }) {
+                    return Err((offset, Some(tag), "has outdated permissions"));
+                }
+            }
+        }
+        for (tag, permission) in &self.permissions {
+            let permitted = permission
+                .iter_all()
+                .filter(|(_, permission)| **permission != Permission::Disabled);
+            for (range, _) in permitted {
+                let ungranted = Self::clipped_stacks(
+                    &self.ref_stack,
+                    Size::from_bytes(range.start),
+                    Size::from_bytes(range.end - range.start),
+                )
+                .into_iter()
+                .find(|(_, stack)| !stack.iter().any(|borrow| borrow.tag == *tag));
+                if let Some((range, _)) = ungranted {
+                    return Err((
+                        Size::from_bytes(range.start),
+                        Some(*tag),
+                        "is permitted outside of its borrows",
+                    ));
+                }
+            }
+        }
+        if entries != self.entries {
+            return Err((Size::ZERO, None, "miscounts the stack entries"));
+        }
+        Ok(())
+    }
+
+    fn permitted(&self, offset: Size, len: Size, tag: Tag, needed: Permission) -> bool {
+        if len == Size::ZERO {
+            return true;
         }
-        edges
+        self.permissions.get(&tag).is_some_and(|permission| {
+            permission
+                .iter(offset, len)
+                .all(|(_, permission)| *permission >= needed)
+        })
     }
 
+    /// Zero-sized accesses touch no stack, so they are always allowed
     pub fn can_read_with(&self, offset: Size, len: Size, tag: Tag) -> bool {
-        //FIXME: performance
+        self.permitted(offset, len, tag, Permission::Read)
+    }
+
+    pub fn can_write_with(&self, offset: Size, len: Size, tag: Tag) -> bool {
+        self.permitted(offset, len, tag, Permission::Write)
+    }
+
+    /// can_read_with by scanning the stacks
+    #[cfg(test)]
+    fn can_read_with_slow(&self, offset: Size, len: Size, tag: Tag) -> bool {
         self.ref_stack
             .iter(offset, len)
             .all(|(_, stack)| stack.iter().any(|borrow| borrow.tag == tag))
     }
 
-    pub fn can_write_with(&self, offset: Size, len: Size, tag: Tag) -> bool {
-        //FIXME: performance
+    /// can_write_with by scanning the stacks
+    #[cfg(test)]
+    fn can_write_with_slow(&self, offset: Size, len: Size, tag: Tag) -> bool {
         for (_, stack) in self.ref_stack.iter(offset, len) {
+            // SharedReadWrite borrows grant writes, so they aren't a cutoff
             let first_shared = stack
                 .iter()
                 .position(|borrow| borrow.borrow_type == BorrowType::Shared);
@@ -164,11 +617,71 @@ impl Run {
         }
         true
     }
+
+    /// A byte is init only if it's init in both, and its value is known only if it's the same in
+    /// both. A borrow is kept only if it's in both
+    #[cfg(test)]
+    fn join(&self, other: &Self) -> Self {
+        assert_eq!(
+            self.size(),
+            other.size(),
+            "joined runs must have the same size"
+        );
+        let bytes = self
+            .bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .map(|(a, b)| match (a, b) {
+                (AbstractByte::Init(a), AbstractByte::Init(b)) => {
+                    AbstractByte::Init(if a == b { *a } else { None })
+                }
+                _ => AbstractByte::Uninit,
+            })
+            .collect();
+        let mut ref_stack = self.ref_stack.clone();
+        for (range, other_stack) in other.ref_stack.iter_all() {
+            let offset = Size::from_bytes(range.start);
+            let len = Size::from_bytes(range.end - range.start);
+            for (_, stack) in ref_stack.iter_mut(offset, len) {
+                stack.retain(|borrow| other_stack.contains(borrow));
+            }
+        }
+        let mut permissions = BTreeMap::new();
+        Self::grant(
+            &mut permissions,
+            self.size(),
+            Self::clipped_stacks(&ref_stack, Size::ZERO, self.size()),
+        );
+        let entries = ref_stack
+            .iter_all()
+            .map(|(range, stack)| (range.end - range.start) * stack.len() as u64)
+            .sum();
+        Self {
+            bytes,
+            ref_stack,
+            permissions,
+            entries,
+            provenance: self
+                .provenance
+                .iter()
+                .zip(other.provenance.iter())
+                .map(|(a, b)| if a == b { *a } else { None })
+                .collect(),
+            #[cfg(all(test, debug_assertions))]
+            origins: self
+                .origins
+                .iter()
+                .zip(other.origins.iter())
+                .map(|(a, b)| if a == b { *a } else { None })
+                .collect(),
+        }
+    }
 }
 
 define_index_type! {pub struct RunId = u32;}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RunAndOffset(RunId, Size);
 
 impl RunAndOffset {
@@ -179,9 +692,21 @@ impl RunAndOffset {
     pub fn offset(&self, offset: isize) -> Self {
         Self(self.0, Size::from_bytes(self.1.bytes() as isize + offset))
     }
+
+    /// Like offset, unless the result would be before the start of the run
+    pub fn checked_offset(&self, offset: isize) -> Option<Self> {
+        let bytes = (self.1.bytes() as isize).checked_add(offset)?;
+        (bytes >= 0).then(|| Self(self.0, Size::from_bytes(bytes)))
+    }
+
+    /// The order of two positions, if they are in the same run
+    pub fn cmp_in_run(&self, other: &Self) -> Option<Ordering> {
+        self.same_run(other).then(|| self.1.cmp(&other.1))
+    }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Allocation {
     /// The data stored in this allocation.
     runs: IndexVec<RunId, Run>,
@@ -189,9 +714,34 @@ struct Allocation {
     // align: Align,
     /// Whether this allocation is still live.
     live: bool,
+    /// Tags of pointers which have been cast to integers.
+    exposed: BTreeSet<Tag>,
+    /// Where this allocation lives, which decides who may deallocate it.
+    kind: AllocKind,
+}
+
+/// Where an allocation lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AllocKind {
+    /// Deallocated when its frame returns, and counted towards the stack limit
+    Stack,
+    /// Lives for the whole program
+    Static,
+    /// Lives until it is explicitly freed, regardless of frames. Program has no heap
+    /// allocations yet, so only tests create them
+    #[cfg(test)]
+    Heap,
 }
 
 impl Allocation {
+    fn size(&self) -> Size {
+        self.runs
+            .iter()
+            .map(|run| run.size())
+            .fold(Size::ZERO, |total, size| total + size)
+    }
+
     fn runs_and_sizes(&self) -> impl Iterator<Item = (RunId, Size)> + '_ {
         self.runs
             .iter_enumerated()
@@ -215,6 +765,21 @@ impl AllocationBuilder {
         RunAndOffset(run_id, Size::ZERO)
     }
 
+    /// Points to size bytes at run_and_offset within a run created by this builder.
+    /// Several pointers may cover the same bytes, such as the fields of a union
+    pub fn run_ptr(&self, run_and_offset: RunAndOffset, size: Size) -> RunPointer {
+        let RunAndOffset(run, offset) = run_and_offset;
+        assert!(
+            offset + size <= self.runs[run].size(),
+            "{size:?} bytes at {run_and_offset:?} are outside of the run"
+        );
+        RunPointer {
+            alloc_id: self.alloc_id,
+            run_and_offset,
+            size,
+        }
+    }
+
     pub fn alloc_id(&self) -> AllocId {
         self.alloc_id
     }
@@ -223,6 +788,8 @@ impl AllocationBuilder {
         Allocation {
             runs: self.runs,
             live: true,
+            exposed: BTreeSet::new(),
+            kind: AllocKind::Stack,
         }
     }
 }
@@ -234,7 +801,7 @@ trait RangeExt: Sized {
 
 impl RangeExt for Range<usize> {
     fn overlap(&self, other: &Self) -> bool {
-        self.start <= other.end && other.start <= self.end
+        self.start < other.end && other.start < self.end
     }
     fn subtract(&self, other: &Self) -> [Option<Self>; 2] {
         assert!(self.overlap(other));
@@ -255,6 +822,7 @@ impl RangeExt for Range<usize> {
 define_index_type! {pub struct AllocId = u32;}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RunPointer {
     pub alloc_id: AllocId,
     pub run_and_offset: RunAndOffset,
@@ -296,136 +864,909 @@ impl RunPointer {
         }
         self.bytes_range().overlap(&other.bytes_range())
     }
+
+    /// Whether other lies entirely within self
+    pub fn contains(&self, other: &Self) -> bool {
+        self.alloc_id == other.alloc_id
+            && self.run_and_offset.same_run(&other.run_and_offset)
+            && self.bytes_range().start <= other.bytes_range().start
+            && other.bytes_range().end <= self.bytes_range().end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunPtrError {
+    /// The allocation doesn't exist
+    UnknownAllocation(AllocId),
+    /// The allocation has been deallocated, so its bytes can't be accessed
+    DeadAllocation(AllocId),
+    /// The allocation has no such run
+    UnknownRun(AllocId, RunId),
+    /// The range goes past the end of the run
+    OutOfBounds { run_ptr: RunPointer, run_size: Size },
+}
+
+impl fmt::Display for RunPtrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownAllocation(alloc_id) => {
+                write!(f, "alloc {} doesn't exist", alloc_id.index())
+            }
+            Self::DeadAllocation(alloc_id) => {
+                write!(f, "alloc {} has been deallocated", alloc_id.index())
+            }
+            Self::UnknownRun(alloc_id, run) => {
+                write!(f, "alloc {} has no run {}", alloc_id.index(), run.index())
+            }
+            Self::OutOfBounds { run_ptr, run_size } => write!(
+                f,
+                "alloc {} run {}: offset {} + size {} is past the end of the run of size {}",
+                run_ptr.alloc_id.index(),
+                run_ptr.run().index(),
+                run_ptr.offset().bytes(),
+                run_ptr.size.bytes(),
+                run_size.bytes()
+            ),
+        }
+    }
+}
+
+/// Why a heap allocation couldn't be freed
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FreeError {
+    /// The allocation was already freed
+    DoubleFree,
+    /// The allocation isn't on the heap
+    NotHeap(AllocKind),
+    /// Protected borrows into the allocation still exist
+    Protected(Vec<Tag>),
+}
+
+/// The borrow stack of a byte range, as seen by an access
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackExplanation {
+    /// Offsets within the run
+    pub range: Range<u64>,
+    /// From the bottom of the stack to the top
+    pub stack: Vec<Borrow>,
+    /// Position of the accessing tag in the stack
+    pub tag_position: Option<usize>,
+    /// Position of the first read-only shared borrow, which writes pop along with everything above
+    pub first_shared: Option<usize>,
+}
+
+/// Why memory allows or rejects accesses through a tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessExplanation {
+    pub run_ptr: RunPointer,
+    pub tag: Tag,
+    pub can_read: bool,
+    pub can_write: bool,
+    pub stacks: Vec<StackExplanation>,
+}
+
+impl fmt::Display for AccessExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let allowed = |allowed| if allowed { "allowed" } else { "rejected" };
+        writeln!(
+            f,
+            "tag #{} in {:?} run {} bytes {:?}: read {}, write {}",
+            self.tag.index(),
+            self.run_ptr.alloc_id,
+            self.run_ptr.run().index(),
+            self.run_ptr.bytes_range(),
+            allowed(self.can_read),
+            allowed(self.can_write),
+        )?;
+        for stack in &self.stacks {
+            write!(f, "  {:?}: [", stack.range)?;
+            for (i, borrow) in stack.stack.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{borrow}")?;
+            }
+            write!(f, "]")?;
+            match stack.tag_position {
+                Some(i) => write!(f, ", tag at {i}")?,
+                None => write!(f, ", tag not in stack")?,
+            }
+            if let Some(i) = stack.first_shared {
+                write!(f, ", first shared at {i}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The borrows removed by remove_tags_above
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemovedTags {
+    /// The parts of each removed tag's pointers which were popped
+    pub popped: BTreeMap<Tag, SmallVec<[RunPointer; 4]>>,
+    /// Tags with no valid borrows left in any run, in ascending order
+    pub all_gone: Vec<Tag>,
+}
+
+impl RemovedTags {
+    /// Tags which lost some, but not all of their borrows, in ascending order
+    #[allow(dead_code)]
+    pub fn partially_invalidated(&self) -> Vec<Tag> {
+        self.popped
+            .keys()
+            .filter(|tag| !self.all_gone.contains(tag))
+            .copied()
+            .collect()
+    }
 }
 
+/// A saved state of BasicMemory
+#[derive(Clone)]
+pub struct MemorySnapshot(BasicMemory);
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicMemory {
     allocations: IndexVec<AllocId, Allocation>,
 
     // a lookup table to aid removal from borrow stacks
     // a tag may cover multiple runs, e.g. &(u32, u32),
-    pointers: HashMap<Tag, SmallVec<[RunPointer; 4]>>,
+    // ordered by tag so that iterating over it is deterministic
+    pointers: BTreeMap<Tag, SmallVec<[RunPointer; 4]>>,
+
+    // The smallest tag that hasn't been handed out or used
+    next_tag: Tag,
+
+    // Tag of the pointer each reborrow was derived from
+    parents: BTreeMap<Tag, Tag>,
+
+    // Tags which may have a protected borrow somewhere
+    protected: BTreeSet<Tag>,
+
+    // Total size of live allocations, and how large it may grow
+    usage: Size,
+    stack_limit: Option<Size>,
+
+    // Counters behind stats, updated along with what they count
+    live_allocations: usize,
+    live_bytes: Size,
+    runs: usize,
+    stack_entries: u64,
+
+    // Where events are reported isn't part of the state
+    #[cfg_attr(feature = "serde", serde(skip))]
+    tracer: Tracer,
 }
 
-impl BasicMemory {
-    const PTR_SIZE: Size = Size::from_bytes_const(mem::size_of::<*const ()>() as u64);
+/// How much abstract memory is in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemStats {
+    pub live_allocations: usize,
+    pub dead_allocations: usize,
+    /// Bytes of live allocations, statics and the heap included
+    pub live_bytes: Size,
+    /// Runs retained across all allocations, dead or alive
+    pub runs: usize,
+    /// Bytes of those runs
+    pub bytes_retained: usize,
+    /// Borrow stack entries, counting a borrow once for every byte it covers
+    pub stack_entries: u64,
+    /// Tags with a borrow somewhere
+    pub tags: usize,
+}
+
+/// A desync in the borrow stacks, or between them and the structures derived from them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantViolation {
+    pub alloc_id: AllocId,
+    pub run: RunId,
+    pub offset: Size,
+    pub tag: Option<Tag>,
+    pub problem: &'static str,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "alloc {} run {} offset {}: ",
+            self.alloc_id.index(),
+            self.run.index(),
+            self.offset.bytes()
+        )?;
+        match self.tag {
+            Some(tag) => write!(f, "tag #{} {}", tag.index(), self.problem),
+            None => write!(f, "run {}", self.problem),
+        }
+    }
+}
 
+/// An allocation was refused because it would exceed the stack limit
+#[derive(Debug)]
+pub struct StackOverflow {
+    pub requested: Size,
+    pub available: Size,
+}
+
+impl BasicMemory {
     pub fn new() -> Self {
         Self {
             allocations: IndexVec::new(),
-            pointers: HashMap::new(),
+            pointers: BTreeMap::new(),
+            next_tag: Tag::new(0),
+            parents: BTreeMap::new(),
+            protected: BTreeSet::new(),
+            usage: Size::ZERO,
+            stack_limit: None,
+            live_allocations: 0,
+            live_bytes: Size::ZERO,
+            runs: 0,
+            stack_entries: 0,
+            tracer: Tracer::default(),
         }
     }
 
-    pub fn allocate_with_builder<F>(&mut self, build: F) -> AllocId
-    where
-        F: FnOnce(&mut AllocationBuilder),
-    {
-        let alloc_id = self.allocations.len_idx();
-        let mut builder = AllocationBuilder {
-            alloc_id,
-            runs: IndexVec::new(),
-        };
-        build(&mut builder);
-        self.allocations.push(builder.build())
-    }
-
-    pub fn deallocate(&mut self, alloc_id: AllocId) {
-        self.allocations[alloc_id].live = false;
+    pub fn stats(&self) -> MemStats {
+        MemStats {
+            live_allocations: self.live_allocations,
+            dead_allocations: self.allocations.len() - self.live_allocations,
+            live_bytes: self.live_bytes,
+            runs: self.runs,
+            bytes_retained: self.bytes_retained(),
+            stack_entries: self.stack_entries,
+            tags: self.pointers.len(),
+        }
     }
 
-    pub fn is_live(&self, alloc_id: AllocId) -> bool {
-        self.allocations[alloc_id].live
+    /// Reports every change to memory to sink from now on
+    pub fn set_tracer(&mut self, sink: Box<dyn TraceSink>) {
+        self.tracer = Tracer::new(sink);
     }
 
-    pub fn bytes(&self, run_ptr: RunPointer) -> &[AbstractByte] {
-        assert!(
-            self.allocations[run_ptr.alloc_id].live,
-            "can't access dead bytes"
-        );
-        &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].bytes[run_ptr.bytes_range()]
+    /// Caps the total size of live allocations, so that generated programs don't overflow
+    /// the real stack
+    pub fn with_stack_limit(mut self, limit: Size) -> Self {
+        self.stack_limit = Some(limit);
+        self
     }
 
-    pub fn fill(&mut self, run_ptr: RunPointer, val: AbstractByte) {
-        self.bytes_mut(run_ptr).fill(val);
+    /// Total size of live allocations
+    #[allow(dead_code)]
+    pub fn current_usage(&self) -> Size {
+        self.usage
     }
 
-    pub fn bytes_mut(&mut self, run_ptr: RunPointer) -> &mut [AbstractByte] {
-        assert!(
-            self.allocations[run_ptr.alloc_id].live,
-            "can't access dead bytes"
-        );
-        &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].bytes[run_ptr.bytes_range()]
+    /// Whether an allocation of size fits in what's left of the stack limit
+    pub fn fits(&self, size: Size) -> bool {
+        self.stack_limit
+            .is_none_or(|limit| self.usage + size <= limit)
     }
 
-    pub fn copy(&mut self, dst: RunPointer, src: RunPointer) {
-        assert_eq!(dst.size, src.size);
-        let tmp = self.bytes(src).to_vec();
-        self.bytes_mut(dst).copy_from_slice(&tmp)
+    /// Saves all allocations, bytes, borrow stacks and the tag lookup table
+    pub fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot(self.clone())
     }
 
-    /// Returns Size for types with guaranteed size.
-    /// Composite types under the default layout has no guaranteed size,
-    /// as the AM is free to insert arbitarily large paddings.
-    pub fn ty_size(ty: TyId, tcx: &TyCtxt) -> Option<Size> {
-        Some(match ty {
-            TyCtxt::UNIT => Size::ZERO,
-            TyCtxt::BOOL => Size::from_bytes(1),
-            TyCtxt::CHAR => Size::from_bytes(4),
-            TyCtxt::I8 | TyCtxt::U8 => Size::from_bits(8),
-            TyCtxt::I16 | TyCtxt::U16 => Size::from_bits(16),
-            TyCtxt::I32 | TyCtxt::U32 => Size::from_bits(32),
-            TyCtxt::I64 | TyCtxt::U64 => Size::from_bits(64),
-            TyCtxt::I128 | TyCtxt::U128 => Size::from_bits(128),
-            TyCtxt::F32 => Size::from_bits(32),
-            TyCtxt::F64 => Size::from_bits(64),
-            TyCtxt::ISIZE | TyCtxt::USIZE => Self::PTR_SIZE,
-            _ => match ty.kind(tcx) {
-                TyKind::RawPtr(..) => Self::PTR_SIZE,
-                TyKind::Ref(..) => Self::PTR_SIZE,
-                TyKind::Array(ty, len) => {
-                    return Self::ty_size(*ty, tcx)
-                        .map(|elem| Size::from_bytes(elem.bytes_usize() * len))
-                }
-                _ => return None,
-            },
-        })
+    /// Puts memory back exactly as it was when the snapshot was taken
+    pub fn restore(&mut self, snapshot: MemorySnapshot) {
+        let tracer = std::mem::take(&mut self.tracer);
+        *self = snapshot.0;
+        self.tracer = tracer;
+        self.tracer.record(MemEvent::Restore);
     }
 
-    pub fn add_ref(&mut self, run_ptr: RunPointer, borrow_type: BorrowType, tag: Tag) {
-        self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].add_borrow(
-            run_ptr.offset(),
-            run_ptr.size,
-            borrow_type,
-            tag,
+    /// The conservative state where control flow from self and other meets.
+    /// Both must have grown from a common predecessor with the same allocations and runs.
+    #[cfg(test)]
+    pub fn join(&self, other: &Self) -> Self {
+        assert_eq!(
+            self.allocations.len(),
+            other.allocations.len(),
+            "joined memories must have the same allocations"
         );
-        self.pointers
-            .entry(tag)
-            .and_modify(|ptrs| ptrs.push(run_ptr))
-            .or_insert(SmallVec::from([run_ptr].as_slice()));
+        let allocations = self
+            .allocations
+            .iter()
+            .zip(other.allocations.iter())
+            .map(|(a, b)| {
+                assert_eq!(a.runs.len(), b.runs.len());
+                assert_eq!(a.kind, b.kind);
+                Allocation {
+                    runs: a
+                        .runs
+                        .iter()
+                        .zip(b.runs.iter())
+                        .map(|(a, b)| a.join(b))
+                        .collect(),
+                    live: a.live && b.live,
+                    exposed: a.exposed.intersection(&b.exposed).copied().collect(),
+                    kind: a.kind,
+                }
+            })
+            .collect();
+        let usage = Self::live_size(&allocations);
+        let live_allocations = allocations.iter().filter(|alloc| alloc.live).count();
+        let live_bytes = allocations
+            .iter()
+            .filter(|alloc| alloc.live)
+            .map(Allocation::size)
+            .fold(Size::ZERO, |total, size| total + size);
+        let runs = allocations.iter().map(|alloc| alloc.runs.len()).sum();
+        let stack_entries = allocations
+            .iter()
+            .flat_map(|alloc| alloc.runs.iter())
+            .map(|run| run.entries)
+            .sum();
+        let pointers = self
+            .pointers
+            .iter()
+            .filter_map(|(tag, run_ptrs)| {
+                let other_ptrs = other.pointers.get(tag)?;
+                let run_ptrs: SmallVec<[RunPointer; 4]> = run_ptrs
+                    .iter()
+                    .filter(|run_ptr| other_ptrs.contains(run_ptr))
+                    .copied()
+                    .collect();
+                (!run_ptrs.is_empty()).then_some((*tag, run_ptrs))
+            })
+            .collect();
+        Self {
+            allocations,
+            pointers,
+            next_tag: self.next_tag.max(other.next_tag),
+            parents: self
+                .parents
+                .iter()
+                .filter(|(child, parent)| other.parents.get(child) == Some(parent))
+                .map(|(child, parent)| (*child, *parent))
+                .collect(),
+            protected: self.protected.union(&other.protected).copied().collect(),
+            usage,
+            stack_limit: self.stack_limit,
+            live_allocations,
+            live_bytes,
+            runs,
+            stack_entries,
+            tracer: self.tracer.clone(),
+        }
     }
 
-    /// Remove tag for all runs
-    pub fn remove_tag(&mut self, tag: Tag) {
-        let run_ptrs = self.pointers.remove(&tag);
-        if let Some(run_ptrs) = run_ptrs {
-            for run_ptr in run_ptrs {
-                self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].remove_borrow(
-                    run_ptr.offset(),
-                    run_ptr.size,
-                    tag,
-                );
-            }
-        }
+    #[cfg(test)]
+    fn live_size(allocations: &IndexVec<AllocId, Allocation>) -> Size {
+        allocations
+            .iter()
+            .filter(|alloc| alloc.live && alloc.kind == AllocKind::Stack)
+            .map(Allocation::size)
+            .fold(Size::ZERO, |total, size| total + size)
     }
 
-    /// Remove a range (run_ptr) from the lookup table.
-    fn derange(&mut self, tag: Tag, run_ptr: RunPointer) {
+    /// Allocates the runs added by build, unless that would exceed the stack limit
+    pub fn allocate_with_builder<F>(&mut self, build: F) -> Result<AllocId, StackOverflow>
+    where
+        F: FnOnce(&mut AllocationBuilder),
+    {
+        let alloc_id = self.allocations.len_idx();
+        let mut builder = AllocationBuilder {
+            alloc_id,
+            runs: IndexVec::new(),
+        };
+        build(&mut builder);
+        let alloc = builder.build();
+        let size = alloc.size();
+        if !self.fits(size) {
+            return Err(StackOverflow {
+                requested: size,
+                available: self.stack_limit.expect("has a limit") - self.usage,
+            });
+        }
+        self.usage += size;
+        Ok(self.push_allocation(alloc))
+    }
+
+    /// Allocates a static. Statics don't count towards the stack limit and are never deallocated
+    #[cfg(test)]
+    pub fn allocate_with_builder_static<F>(&mut self, build: F) -> AllocId
+    where
+        F: FnOnce(&mut AllocationBuilder),
+    {
+        self.allocate_unlimited(AllocKind::Static, build)
+    }
+
+    /// Allocates on the heap. Heap allocations don't count towards the stack limit and
+    /// are only deallocated by free
+    #[cfg(test)]
+    pub fn allocate_with_builder_heap<F>(&mut self, build: F) -> AllocId
+    where
+        F: FnOnce(&mut AllocationBuilder),
+    {
+        self.allocate_unlimited(AllocKind::Heap, build)
+    }
+
+    /// Allocates a single uninit run of size bytes on the heap
+    #[cfg(test)]
+    pub fn allocate_heap(&mut self, size: Size) -> AllocId {
+        self.allocate_with_builder_heap(|builder| {
+            builder.new_run(size);
+        })
+    }
+
+    #[cfg(test)]
+    fn allocate_unlimited<F>(&mut self, kind: AllocKind, build: F) -> AllocId
+    where
+        F: FnOnce(&mut AllocationBuilder),
+    {
+        let mut builder = AllocationBuilder {
+            alloc_id: self.allocations.len_idx(),
+            runs: IndexVec::new(),
+        };
+        build(&mut builder);
+        let mut alloc = builder.build();
+        alloc.kind = kind;
+        self.push_allocation(alloc)
+    }
+
+    fn push_allocation(&mut self, alloc: Allocation) -> AllocId {
+        let size = alloc.size();
+        self.live_allocations += 1;
+        self.live_bytes += size;
+        self.runs += alloc.runs.len();
+        let alloc_id = self.allocations.push(alloc);
+        self.tracer.record(MemEvent::Allocate { alloc_id, size });
+        alloc_id
+    }
+
+    /// Deallocating while a protected borrow into the allocation exists is UB, so that is
+    /// refused with the protected tags
+    pub fn deallocate(&mut self, alloc_id: AllocId) -> Result<(), Vec<Tag>> {
+        assert_eq!(
+            self.allocations[alloc_id].kind,
+            AllocKind::Stack,
+            "only stack allocations are deallocated with their frame"
+        );
+        self.deallocate_inner(alloc_id)
+    }
+
+    /// Frees a heap allocation. Unlike deallocate, freeing twice is reported rather than
+    /// ignored, since the generated program would be UB
+    #[cfg(test)]
+    pub fn free(&mut self, alloc_id: AllocId) -> Result<(), FreeError> {
+        let alloc = &self.allocations[alloc_id];
+        if alloc.kind != AllocKind::Heap {
+            return Err(FreeError::NotHeap(alloc.kind));
+        }
+        if !alloc.live {
+            return Err(FreeError::DoubleFree);
+        }
+        self.deallocate_inner(alloc_id)
+            .map_err(FreeError::Protected)
+    }
+
+    fn deallocate_inner(&mut self, alloc_id: AllocId) -> Result<(), Vec<Tag>> {
+        let protected = self.protected_tags(alloc_id);
+        if !protected.is_empty() {
+            return Err(protected);
+        }
+        let alloc = &mut self.allocations[alloc_id];
+        if alloc.live {
+            if alloc.kind == AllocKind::Stack {
+                self.usage = self.usage - alloc.size();
+            }
+            self.live_allocations -= 1;
+            self.live_bytes = self.live_bytes - alloc.size();
+        }
+        alloc.live = false;
+        self.tracer.record(MemEvent::Deallocate(alloc_id));
+        Ok(())
+    }
+
+    pub fn is_live(&self, alloc_id: AllocId) -> bool {
+        self.allocations[alloc_id].live
+    }
+
+    pub fn is_static(&self, alloc_id: AllocId) -> bool {
+        self.allocations[alloc_id].kind == AllocKind::Static
+    }
+
+    #[cfg(test)]
+    pub fn alloc_kind(&self, alloc_id: AllocId) -> AllocKind {
+        self.allocations[alloc_id].kind
+    }
+
+    /// Drops the runs of dead allocations and all pointers into them.
+    /// The dead AllocIds stay reserved, so they are never reused.
+    pub fn compact(&mut self) {
+        for alloc in self.allocations.iter_mut().filter(|alloc| !alloc.live) {
+            self.runs -= alloc.runs.len();
+            self.stack_entries -= alloc.runs.iter().map(|run| run.entries).sum::<u64>();
+            alloc.runs = IndexVec::new();
+            alloc.exposed.clear();
+        }
+        self.pointers.retain(|_, run_ptrs| {
+            run_ptrs.retain(|run_ptr| self.allocations[run_ptr.alloc_id].live);
+            !run_ptrs.is_empty()
+        });
+        self.tracer.record(MemEvent::Compact);
+    }
+
+    /// Number of bytes stored across all allocations, dead or alive
+    pub fn bytes_retained(&self) -> usize {
+        self.allocations
+            .iter()
+            .flat_map(|alloc| alloc.runs.iter())
+            .map(|run| run.bytes.len())
+            .sum()
+    }
+
+    /// Returns all tags which still have a borrow into an allocation, in ascending order.
+    /// After the allocation is deallocated, these are dangling.
+    pub fn dangling_tags(&self, alloc_id: AllocId) -> Vec<Tag> {
+        self.pointers
+            .iter()
+            .filter(|(_, run_ptrs)| run_ptrs.iter().any(|run_ptr| run_ptr.alloc_id == alloc_id))
+            .map(|(tag, _)| *tag)
+            .collect()
+    }
+
+    pub fn bytes(&self, run_ptr: RunPointer) -> &[AbstractByte] {
+        self.try_bytes(run_ptr)
+            .unwrap_or_else(|err| panic!("can't access bytes: {err}"))
+    }
+
+    /// The init byte ranges in run_ptr, sorted and coalesced. Offsets are from the start of the run
+    #[cfg(test)]
+    pub fn init_ranges(&self, run_ptr: RunPointer) -> Vec<Range<Size>> {
+        let start = run_ptr.offset();
+        let mut ranges: Vec<Range<Size>> = vec![];
+        for (i, byte) in self.bytes(run_ptr).iter().enumerate() {
+            if !byte.is_init() {
+                continue;
+            }
+            let offset = start + Size::from_bytes(i);
+            match ranges.last_mut() {
+                Some(last) if last.end == offset => last.end = offset + Size::from_bytes(1),
+                _ => ranges.push(offset..offset + Size::from_bytes(1)),
+            }
+        }
+        ranges
+    }
+
+    /// How many of run_ptr's bytes are init, out of how many
+    #[cfg(test)]
+    pub fn init_fraction(&self, run_ptr: RunPointer) -> (u64, u64) {
+        let init = self
+            .bytes(run_ptr)
+            .iter()
+            .filter(|byte| byte.is_init())
+            .count();
+        (init as u64, run_ptr.size.bytes())
+    }
+
+    /// Checks that run_ptr points into an existing run and doesn't go past its end.
+    /// Pointers into dead allocations are still valid until they are compacted.
+    pub fn check_run_ptr(&self, run_ptr: RunPointer) -> Result<(), RunPtrError> {
+        let alloc = self
+            .allocations
+            .get(run_ptr.alloc_id)
+            .ok_or(RunPtrError::UnknownAllocation(run_ptr.alloc_id))?;
+        let run = alloc
+            .runs
+            .get(run_ptr.run())
+            .ok_or(RunPtrError::UnknownRun(run_ptr.alloc_id, run_ptr.run()))?;
+        if run_ptr.bytes_range().end > run.bytes.len() {
+            return Err(RunPtrError::OutOfBounds {
+                run_ptr,
+                run_size: run.size(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Creates a RunPointer, checking that it is in bounds
+    pub fn run_ptr(
+        &self,
+        alloc_id: AllocId,
+        run_and_offset: RunAndOffset,
+        size: Size,
+    ) -> Result<RunPointer, RunPtrError> {
+        let run_ptr = RunPointer {
+            alloc_id,
+            run_and_offset,
+            size,
+        };
+        self.check_run_ptr(run_ptr).map(|()| run_ptr)
+    }
+
+    fn debug_check_run_ptr(&self, run_ptr: RunPointer) {
+        if cfg!(debug_assertions)
+            && let Err(err) = self.check_run_ptr(run_ptr)
+        {
+            panic!("invalid run pointer: {err}");
+        }
+    }
+
+    /// Finds the run accessed by run_ptr, checking that the access is valid
+    fn accessed_run(&self, run_ptr: RunPointer) -> Result<&Run, RunPtrError> {
+        if self
+            .allocations
+            .get(run_ptr.alloc_id)
+            .is_some_and(|alloc| !alloc.live)
+        {
+            return Err(RunPtrError::DeadAllocation(run_ptr.alloc_id));
+        }
+        self.check_run_ptr(run_ptr)?;
+        Ok(&self.allocations[run_ptr.alloc_id].runs[run_ptr.run()])
+    }
+
+    pub fn try_bytes(&self, run_ptr: RunPointer) -> Result<&[AbstractByte], RunPtrError> {
+        Ok(&self.accessed_run(run_ptr)?.bytes[run_ptr.bytes_range()])
+    }
+
+    #[allow(dead_code)]
+    pub fn try_bytes_mut(
+        &mut self,
+        run_ptr: RunPointer,
+    ) -> Result<&mut [AbstractByte], RunPtrError> {
+        self.accessed_run(run_ptr)?;
+        Ok(self.bytes_mut(run_ptr))
+    }
+
+    #[allow(dead_code)]
+    pub fn try_copy(&mut self, dst: RunPointer, src: RunPointer) -> Result<(), RunPtrError> {
+        self.accessed_run(dst)?;
+        self.accessed_run(src)?;
+        self.copy(dst, src);
+        Ok(())
+    }
+
+    pub fn fill(&mut self, run_ptr: RunPointer, val: AbstractByte) {
+        self.fill_bytes(run_ptr, val);
+        self.tracer.record(MemEvent::Fill { run_ptr, val });
+    }
+
+    /// Forgets the values of the init bytes, which stay init and keep their provenance.
+    /// The bytes of dead allocations are left alone
+    pub fn forget_values(&mut self, run_ptr: RunPointer) {
+        self.debug_check_run_ptr(run_ptr);
+        if !self.allocations[run_ptr.alloc_id].live {
+            return;
+        }
+        for byte in self.live_bytes_mut(run_ptr) {
+            if byte.is_init() {
+                *byte = AbstractByte::Init(None);
+            }
+        }
+        self.tracer.record(MemEvent::ForgetValues(run_ptr));
+    }
+
+    fn fill_bytes(&mut self, run_ptr: RunPointer, val: AbstractByte) {
+        self.debug_check_run_ptr(run_ptr);
+        self.live_bytes_mut(run_ptr).fill(val);
+        let run = &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        run.provenance[run_ptr.bytes_range()].fill(None);
+    }
+
+    /// Marks the bytes as encoding a pointer with the tag
+    pub fn set_provenance(&mut self, run_ptr: RunPointer, tag: Tag) {
+        self.debug_check_run_ptr(run_ptr);
+        let run = &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        run.provenance[run_ptr.bytes_range()].fill(Some(tag));
+        self.tracer.record(MemEvent::SetProvenance { run_ptr, tag });
+    }
+
+    /// The tag of the pointer encoded in the bytes, if all of them carry the same one
+    pub fn provenance(&self, run_ptr: RunPointer) -> Option<Tag> {
+        let run = &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        let (first, rest) = run.provenance[run_ptr.bytes_range()].split_first()?;
+        rest.iter().all(|tag| tag == first).then_some(*first)?
+    }
+
+    pub fn bytes_mut(&mut self, run_ptr: RunPointer) -> &mut [AbstractByte] {
+        self.tracer.record(MemEvent::BytesMut(run_ptr));
+        self.live_bytes_mut(run_ptr)
+    }
+
+    fn live_bytes_mut(&mut self, run_ptr: RunPointer) -> &mut [AbstractByte] {
+        if let Err(err) = self.accessed_run(run_ptr) {
+            panic!("can't access bytes: {err}");
+        }
+        &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].bytes[run_ptr.bytes_range()]
+    }
+
+    /// Initializes bytes, and remembers origin as their last writer in debug builds.
+    /// The generator doesn't number its statements yet, so only tests supply origins
+    #[cfg(test)]
+    pub fn write_bytes(&mut self, run_ptr: RunPointer, origin: u32) {
+        self.fill_bytes(run_ptr, AbstractByte::Init(None));
+        #[cfg(debug_assertions)]
+        {
+            let run = &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+            run.origins[run_ptr.bytes_range()].fill(Some(origin));
+        }
+        self.tracer.record(MemEvent::WriteBytes { run_ptr, origin });
+    }
+
+    /// Writes the lowest run_ptr.size bytes of val, which become known
+    pub fn write_scalar(&mut self, run_ptr: RunPointer, val: u128) {
+        self.debug_check_run_ptr(run_ptr);
+        let bytes = self.live_bytes_mut(run_ptr);
+        for (byte, val) in bytes.iter_mut().zip(encode_scalar(val, run_ptr.size)) {
+            *byte = AbstractByte::Init(Some(val));
+        }
+        let run = &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        run.provenance[run_ptr.bytes_range()].fill(None);
+        self.tracer.record(MemEvent::WriteScalar { run_ptr, val });
+    }
+
+    /// The zero-extended scalar in the bytes, if all of them are known
+    pub fn read_scalar(&self, run_ptr: RunPointer) -> Option<u128> {
+        if run_ptr.size.bytes() > 16 {
+            return None;
+        }
+        let bytes: Option<Vec<u8>> = self
+            .bytes(run_ptr)
+            .iter()
+            .map(AbstractByte::known_value)
+            .collect();
+        Some(decode_scalar(&bytes?))
+    }
+
+    /// The origin of the last write_bytes to each byte
+    #[cfg(all(test, debug_assertions))]
+    pub fn last_writer(&self, run_ptr: RunPointer) -> Vec<Option<u32>> {
+        let run = &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        run.origins[run_ptr.bytes_range()].to_vec()
+    }
+
+    /// Origins aren't recorded in release builds
+    #[cfg(all(test, not(debug_assertions)))]
+    pub fn last_writer(&self, run_ptr: RunPointer) -> Vec<Option<u32>> {
+        vec![None; run_ptr.size.bytes_usize()]
+    }
+
+    pub fn copy(&mut self, dst: RunPointer, src: RunPointer) {
+        assert_eq!(dst.size, src.size);
+        self.debug_check_run_ptr(dst);
+        self.debug_check_run_ptr(src);
+        let tmp = self.bytes(src).to_vec();
+        self.live_bytes_mut(dst).copy_from_slice(&tmp);
+
+        let src_run = &self.allocations[src.alloc_id].runs[src.run()];
+        let tmp = src_run.provenance[src.bytes_range()].to_vec();
+        let dst_run = &mut self.allocations[dst.alloc_id].runs[dst.run()];
+        dst_run.provenance[dst.bytes_range()].copy_from_slice(&tmp);
+        self.tracer.record(MemEvent::Copy { dst, src });
+    }
+
+    /// Returns Size for types with guaranteed size.
+    pub fn ty_size(ty: TyId, tcx: &TyCtxt) -> Option<Size> {
+        Layout::of(ty, tcx).map(|layout| layout.size)
+    }
+
+    /// Splits the bytes of a pointer into its address and, for a fat pointer, its metadata
+    pub fn ptr_parts(run_ptr: RunPointer) -> (RunPointer, Option<RunPointer>) {
+        let half = |offset: isize| RunPointer {
+            alloc_id: run_ptr.alloc_id,
+            run_and_offset: run_ptr.run_and_offset.offset(offset),
+            size: Layout::PTR_SIZE,
+        };
+        let ptr_size = Layout::PTR_SIZE.bytes();
+        match run_ptr.size.bytes() {
+            size if size == ptr_size => (run_ptr, None),
+            size if size == ptr_size * 2 => (half(0), Some(half(ptr_size as isize))),
+            _ => panic!("{run_ptr:?} isn't a pointer"),
+        }
+    }
+
+    /// Returns a tag which has never been used in this memory
+    pub fn fresh_tag(&mut self) -> Tag {
+        let tag = self.next_unused_tag();
+        self.tracer.record(MemEvent::FreshTag(tag));
+        tag
+    }
+
+    fn next_unused_tag(&mut self) -> Tag {
+        let tag = self.next_tag;
+        self.next_tag = tag + 1;
+        tag
+    }
+
+    /// Records that child was created by reborrowing through a pointer with the parent tag
+    pub fn set_parent_tag(&mut self, child: Tag, parent: Tag) {
+        assert_ne!(child, parent, "a tag can't be derived from itself");
+        self.parents.insert(child, parent);
+        self.tracer.record(MemEvent::SetParentTag { child, parent });
+    }
+
+    /// The tag a reborrow was derived from
+    #[cfg(test)]
+    pub fn parent_tag(&self, tag: Tag) -> Option<Tag> {
+        self.parents.get(&tag).copied()
+    }
+
+    /// add_ref, but refuses run pointers outside of their run instead of panicking
+    #[allow(dead_code)]
+    pub fn try_add_ref(
+        &mut self,
+        run_ptr: RunPointer,
+        borrow_type: BorrowType,
+        tag: Option<Tag>,
+    ) -> Result<Tag, RunPtrError> {
+        self.check_run_ptr(run_ptr)?;
+        Ok(self.add_ref(run_ptr, borrow_type, tag))
+    }
+
+    /// Adds a borrow to the run's stacks. If tag is None, a fresh one is used.
+    /// Returns the tag of the borrow. A zero-sized borrow leaves the stacks alone,
+    /// but its tag is still tracked so it can be removed later.
+    pub fn add_ref(
+        &mut self,
+        run_ptr: RunPointer,
+        borrow_type: BorrowType,
+        tag: Option<Tag>,
+    ) -> Tag {
+        let tag = match tag {
+            Some(tag) => {
+                // Never hand out the tag later on
+                self.next_tag = self.next_tag.max(tag + 1);
+                tag
+            }
+            None => self.next_unused_tag(),
+        };
+        self.debug_check_run_ptr(run_ptr);
+        self.modify_run(run_ptr, |run| {
+            run.add_borrow(run_ptr.offset(), run_ptr.size, borrow_type, tag)
+        });
+        self.pointers
+            .entry(tag)
+            .and_modify(|ptrs| ptrs.push(run_ptr))
+            .or_insert(SmallVec::from([run_ptr].as_slice()));
+        self.debug_check_allocation(run_ptr.alloc_id);
+        self.tracer.record(MemEvent::AddRef {
+            run_ptr,
+            borrow_type,
+            tag,
+        });
+        tag
+    }
+
+    /// Remove tag for all runs
+    pub fn remove_tag(&mut self, tag: Tag) {
+        let run_ptrs = self.pointers.remove(&tag).unwrap_or_default();
+        for run_ptr in &run_ptrs {
+            self.modify_run(*run_ptr, |run| {
+                run.remove_all_of_tag(run_ptr.offset(), run_ptr.size, tag)
+            });
+        }
+        self.debug_check_allocations(&run_ptrs);
+        self.tracer.record(MemEvent::RemoveTag(tag));
+    }
+
+    /// Applies f to the borrow stacks of the run run_ptr points into, keeping count of their entries
+    fn modify_run<R>(&mut self, run_ptr: RunPointer, f: impl FnOnce(&mut Run) -> R) -> R {
+        let run = &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        let before = run.entries;
+        let result = f(run);
+        self.stack_entries = self.stack_entries - before + run.entries;
+        result
+    }
+
+    /// Remove a range (run_ptr) from the lookup table.
+    /// Returns the parts of tag's pointers which were removed
+    fn derange(&mut self, tag: Tag, run_ptr: RunPointer) -> SmallVec<[RunPointer; 4]> {
+        let mut removed = SmallVec::new();
         if let Some(all_run_ptrs) = self.pointers.get(&tag) {
             // Check if the run_ptr we removed overlaps with ones cached, then remove/split them as necessary
             let mut updated = SmallVec::new();
             for stored in all_run_ptrs {
                 if stored.overlap(&run_ptr) {
+                    let (stored_range, range) = (stored.bytes_range(), run_ptr.bytes_range());
+                    removed.push(RunPointer::from_bytes_range(
+                        stored_range.start.max(range.start)..stored_range.end.min(range.end),
+                        stored.alloc_id,
+                        stored.run(),
+                    ));
                     let left_and_right = stored.bytes_range().subtract(&run_ptr.bytes_range());
                     for range in left_and_right {
                         if let Some(range) = range {
@@ -448,46 +1789,106 @@ impl BasicMemory {
                 self.pointers.insert(tag, updated);
             }
         }
+        removed
     }
 
-    /// Remove all tags including and above from a run. Returns a list of edges with no valid borrows
-    /// left in any run after removal
-    pub fn remove_tags_above(&mut self, tag: Tag, run_ptr: RunPointer) -> Vec<Tag> {
-        let removed = self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].remove_all_above(
-            run_ptr.offset(),
-            run_ptr.size,
-            tag,
-        );
+    /// Removes the parts of run_ptr where tag is no longer in the stack from the lookup
+    /// table, leaving those where it wasn't popped. Returns the removed parts
+    fn derange_popped(&mut self, tag: Tag, run_ptr: RunPointer) -> SmallVec<[RunPointer; 4]> {
+        let run = &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        let mut popped: Vec<Range<u64>> = vec![];
+        for (range, stack) in run.stacks(run_ptr.offset(), run_ptr.size) {
+            if stack.iter().any(|borrow| borrow.tag == tag) {
+                continue;
+            }
+            match popped.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => popped.push(range),
+            }
+        }
+        let mut removed = SmallVec::new();
+        for range in popped {
+            let range = range.start as usize..range.end as usize;
+            removed.extend(self.derange(
+                tag,
+                RunPointer::from_bytes_range(range, run_ptr.alloc_id, run_ptr.run()),
+            ));
+        }
+        removed
+    }
 
-        let mut all_gone = vec![];
+    /// Remove all tags including and above from a run
+    #[allow(dead_code)]
+    pub fn remove_tags_above(&mut self, tag: Tag, run_ptr: RunPointer) -> RemovedTags {
+        self.debug_check_run_ptr(run_ptr);
+        let removed = self.modify_run(run_ptr, |run| {
+            run.remove_all_above(run_ptr.offset(), run_ptr.size, tag)
+        });
+
+        let mut result = RemovedTags::default();
         for edge in removed {
-            self.derange(edge, run_ptr);
+            result
+                .popped
+                .insert(edge, self.derange_popped(edge, run_ptr));
             if !self.pointers.contains_key(&edge) {
-                all_gone.push(edge);
+                result.all_gone.push(edge);
             }
         }
-        all_gone
+        self.debug_check_allocation(run_ptr.alloc_id);
+        self.tracer
+            .record(MemEvent::RemoveTagsAbove { tag, run_ptr });
+        result
     }
 
-    /// Remove tag for a run ptr. Returns true if the ref is no longer present in any
-    /// borrow stack
+    /// Remove the topmost borrow of tag for a run ptr. Returns true if the ref is no longer
+    /// present in any borrow stack
     pub fn remove_tag_run_ptr(&mut self, tag: Tag, run_ptr: RunPointer) -> bool {
-        self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].remove_borrow(
-            run_ptr.offset(),
-            run_ptr.size,
-            tag,
-        );
+        self.debug_check_run_ptr(run_ptr);
+        self.modify_run(run_ptr, |run| {
+            run.remove_borrow(run_ptr.offset(), run_ptr.size, tag)
+        });
 
-        self.derange(tag, run_ptr);
+        self.derange_popped(tag, run_ptr);
+        self.debug_check_allocation(run_ptr.alloc_id);
+        self.tracer
+            .record(MemEvent::RemoveTagRunPtr { tag, run_ptr });
         !self.pointers.contains_key(&tag)
     }
 
+    /// remove_tag_run_ptr, but refuses run pointers outside of their run instead of panicking
+    #[allow(dead_code)]
+    pub fn try_remove_tag_run_ptr(
+        &mut self,
+        tag: Tag,
+        run_ptr: RunPointer,
+    ) -> Result<bool, RunPtrError> {
+        self.check_run_ptr(run_ptr)?;
+        Ok(self.remove_tag_run_ptr(tag, run_ptr))
+    }
+
+    /// Returns tags from the first shared borrow upwards, in ascending order
     pub fn above_first_shared(&self, run_ptr: RunPointer) -> Vec<Tag> {
         self.allocations[run_ptr.alloc_id].runs[run_ptr.run()]
             .above_first_shared(run_ptr.offset(), run_ptr.size)
     }
 
     pub fn mark_protected(&mut self, run_ptr: RunPointer, tag: Tag) {
+        self.debug_check_run_ptr(run_ptr);
+        self.protect_run(run_ptr, tag);
+        self.debug_check_allocation(run_ptr.alloc_id);
+        self.tracer.record(MemEvent::Protect { run_ptr, tag });
+    }
+
+    /// mark_protected, but refuses run pointers outside of their run instead of panicking
+    #[allow(dead_code)]
+    pub fn try_mark_protected(&mut self, run_ptr: RunPointer, tag: Tag) -> Result<(), RunPtrError> {
+        self.check_run_ptr(run_ptr)?;
+        self.mark_protected(run_ptr, tag);
+        Ok(())
+    }
+
+    fn protect_run(&mut self, run_ptr: RunPointer, tag: Tag) {
+        self.protected.insert(tag);
         self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].protect(
             run_ptr.offset(),
             run_ptr.size,
@@ -495,19 +1896,1459 @@ impl BasicMemory {
         )
     }
 
-    pub fn can_read_with(&self, run_ptr: RunPointer, tag: Tag) -> bool {
-        self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].can_read_with(
+    #[cfg(test)]
+    pub fn mark_unprotected(&mut self, run_ptr: RunPointer, tag: Tag) {
+        self.debug_check_run_ptr(run_ptr);
+        self.unprotect_run(run_ptr, tag);
+        self.debug_check_allocation(run_ptr.alloc_id);
+        self.tracer.record(MemEvent::Unprotect { run_ptr, tag });
+    }
+
+    fn unprotect_run(&mut self, run_ptr: RunPointer, tag: Tag) {
+        self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].unprotect(
             run_ptr.offset(),
             run_ptr.size,
             tag,
         )
     }
 
-    pub fn can_write_with(&self, run_ptr: RunPointer, tag: Tag) -> bool {
-        self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].can_write_with(
-            run_ptr.offset(),
-            run_ptr.size,
+    /// Protects tag's borrows everywhere it points to
+    pub fn protect_tag(&mut self, tag: Tag) {
+        let run_ptrs = self.pointers.get(&tag).cloned().unwrap_or_default();
+        for run_ptr in &run_ptrs {
+            self.protect_run(*run_ptr, tag);
+        }
+        self.debug_check_allocations(&run_ptrs);
+        self.tracer.record(MemEvent::ProtectTag(tag));
+    }
+
+    /// Whether tag may have a protected borrow somewhere
+    pub fn is_protected(&self, tag: Tag) -> bool {
+        self.protected.contains(&tag)
+    }
+
+    /// Lifts the protector of tag's borrows everywhere it points to
+    pub fn unprotect_tag(&mut self, tag: Tag) {
+        let run_ptrs = self.pointers.get(&tag).cloned().unwrap_or_default();
+        for run_ptr in &run_ptrs {
+            self.unprotect_run(*run_ptr, tag);
+        }
+        self.protected.remove(&tag);
+        self.debug_check_allocations(&run_ptrs);
+        self.tracer.record(MemEvent::UnprotectTag(tag));
+    }
+
+    /// Checks the stacks of every live allocation, and that the lookup table and
+    /// protected tags mirror them
+    #[cfg(test)]
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        self.allocations
+            .iter_enumerated()
+            .filter(|(_, alloc)| alloc.live)
+            .try_for_each(|(alloc_id, _)| self.check_allocation(alloc_id))
+    }
+
+    fn check_allocation(&self, alloc_id: AllocId) -> Result<(), InvariantViolation> {
+        let violation = |run, offset, tag, problem| InvariantViolation {
+            alloc_id,
+            run,
+            offset,
             tag,
-        )
+            problem,
+        };
+        // Bytes each tag covers in each run, according to the stacks and the lookup table
+        let mut in_stacks: BTreeMap<(RunId, Tag), u64> = BTreeMap::new();
+        for (run_id, run) in self.allocations[alloc_id].runs.iter_enumerated() {
+            run.check_stacks()
+                .map_err(|(offset, tag, problem)| violation(run_id, offset, tag, problem))?;
+            for (range, stack) in run.ref_stack.iter_all() {
+                for borrow in stack {
+                    if borrow.protected && !self.protected.contains(&borrow.tag) {
+                        return Err(violation(
+                            run_id,
+                            Size::from_bytes(range.start),
+                            Some(borrow.tag),
+                            "is protected without being tracked as such",
+                        ));
+                    }
+                    *in_stacks.entry((run_id, borrow.tag)).or_default() += range.end - range.start;
+                }
+            }
+        }
+        // Every table entry into the allocation is looked up, so one left behind by a tag
+        // with no borrow here is caught too
+        let mut in_table: BTreeMap<(RunId, Tag), u64> = BTreeMap::new();
+        for (tag, run_ptrs) in &self.pointers {
+            let run_ptrs = run_ptrs
+                .iter()
+                .filter(|run_ptr| run_ptr.alloc_id == alloc_id);
+            for run_ptr in run_ptrs {
+                let run = &self.allocations[alloc_id].runs[run_ptr.run()];
+                if let Some((offset, _)) = run
+                    .ref_stack
+                    .iter(run_ptr.offset(), run_ptr.size)
+                    .find(|(_, stack)| !stack.iter().any(|borrow| borrow.tag == *tag))
+                {
+                    return Err(violation(
+                        run_ptr.run(),
+                        offset.max(run_ptr.offset()),
+                        Some(*tag),
+                        "is in the lookup table but not in the stack",
+                    ));
+                }
+                *in_table.entry((run_ptr.run(), *tag)).or_default() += run_ptr.size.bytes();
+            }
+        }
+        // Every byte in the lookup table is in the stacks, so any difference is a borrow
+        // the table is missing
+        if let Some(((run, tag), _)) = in_stacks
+            .iter()
+            .find(|(key, bytes)| in_table.get(key) != Some(bytes))
+        {
+            return Err(violation(
+                *run,
+                Size::ZERO,
+                Some(*tag),
+                "is in the stack but not in the lookup table",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The stacks of run_ptr's run, without any bookkeeping, for breaking invariants on purpose
+    #[cfg(test)]
+    fn run_mut(&mut self, run_ptr: RunPointer) -> &mut Run {
+        &mut self.allocations[run_ptr.alloc_id].runs[run_ptr.run()]
+    }
+
+    /// Panics in debug builds if a live allocation breaks an invariant
+    fn debug_check_allocation(&self, alloc_id: AllocId) {
+        if cfg!(debug_assertions)
+            && self.allocations[alloc_id].live
+            && let Err(violation) = self.check_allocation(alloc_id)
+        {
+            panic!("broken borrow stack invariant: {violation}");
+        }
+    }
+
+    fn debug_check_allocations(&self, run_ptrs: &[RunPointer]) {
+        let alloc_ids: BTreeSet<AllocId> =
+            run_ptrs.iter().map(|run_ptr| run_ptr.alloc_id).collect();
+        for alloc_id in alloc_ids {
+            self.debug_check_allocation(alloc_id);
+        }
+    }
+
+    /// Tags with a protected borrow into an allocation, in ascending order
+    pub fn protected_tags(&self, alloc_id: AllocId) -> Vec<Tag> {
+        if !self.has_protectors(alloc_id) {
+            return vec![];
+        }
+        let tags: BTreeSet<Tag> = self.allocations[alloc_id]
+            .runs
+            .iter()
+            .flat_map(|run| run.ref_stack.iter_all())
+            .flat_map(|(_, stack)| stack.iter())
+            .filter(|borrow| borrow.protected)
+            .map(|borrow| borrow.tag)
+            .collect();
+        tags.into_iter().collect()
+    }
+
+    /// Whether any borrow into an allocation may be protected
+    pub fn has_protectors(&self, alloc_id: AllocId) -> bool {
+        self.protected.iter().any(|tag| {
+            self.pointers
+                .get(tag)
+                .is_some_and(|run_ptrs| run_ptrs.iter().any(|run_ptr| run_ptr.alloc_id == alloc_id))
+        })
+    }
+
+    pub fn write_pops_protected(&self, run_ptr: RunPointer) -> bool {
+        self.has_protectors(run_ptr.alloc_id)
+            && self.allocations[run_ptr.alloc_id].runs[run_ptr.run()]
+                .write_pops_protected(run_ptr.offset(), run_ptr.size)
+    }
+
+    /// Writes run_ptr with tag, which pops the borrows above the tag as Run::write_with does.
+    /// Returns the popped tags which no longer grant access anywhere, in ascending order
+    pub fn write_with(&mut self, run_ptr: RunPointer, tag: Tag) -> Vec<Tag> {
+        let popped = self.modify_run(run_ptr, |run| {
+            run.write_with(run_ptr.offset(), run_ptr.size, tag)
+        });
+        let mut all_gone = vec![];
+        for edge in popped {
+            self.derange_popped(edge, run_ptr);
+            if !self.pointers.contains_key(&edge) {
+                all_gone.push(edge);
+            }
+        }
+        self.debug_check_allocation(run_ptr.alloc_id);
+        self.tracer.record(MemEvent::WriteWith { run_ptr, tag });
+        all_gone
+    }
+
+    pub fn write_with_pops_protected(&self, run_ptr: RunPointer, tag: Tag) -> bool {
+        self.has_protectors(run_ptr.alloc_id)
+            && self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].write_with_pops_protected(
+                run_ptr.offset(),
+                run_ptr.size,
+                tag,
+            )
+    }
+
+    pub fn read_with_pops_protected(&self, run_ptr: RunPointer, tag: Tag) -> bool {
+        self.has_protectors(run_ptr.alloc_id)
+            && self.allocations[run_ptr.alloc_id].runs[run_ptr.run()].read_with_pops_protected(
+                run_ptr.offset(),
+                run_ptr.size,
+                tag,
+            )
+    }
+
+    /// Reads run_ptr with tag, which invalidates exclusive borrows above the tag.
+    /// Returns the invalidated tags in ascending order
+    pub fn read_with(&mut self, run_ptr: RunPointer, tag: Tag) -> Vec<Tag> {
+        let invalidated = self.modify_run(run_ptr, |run| {
+            run.read_with(run_ptr.offset(), run_ptr.size, tag)
+        });
+        for edge in &invalidated {
+            self.derange_popped(*edge, run_ptr);
+        }
+        self.debug_check_allocation(run_ptr.alloc_id);
+        self.tracer.record(MemEvent::ReadWith { run_ptr, tag });
+        invalidated
+    }
+
+    /// Renders the borrow stack of each byte range covered by run_ptr
+    #[cfg(test)]
+    pub fn dump_stacks(&self, run_ptr: RunPointer) -> String {
+        let run = &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        run.stacks(run_ptr.offset(), run_ptr.size)
+            .into_iter()
+            .map(|(range, stack)| {
+                let borrows: Vec<String> = stack.iter().map(|borrow| borrow.to_string()).collect();
+                format!("{range:?}: [{}]\n", borrows.join(", "))
+            })
+            .collect()
+    }
+
+    /// Collects everything that decides whether tag may read or write the bytes
+    pub fn explain(&self, run_ptr: RunPointer, tag: Tag) -> AccessExplanation {
+        let run = &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        let stacks = run
+            .stacks(run_ptr.offset(), run_ptr.size)
+            .into_iter()
+            .map(|(range, stack)| StackExplanation {
+                range,
+                stack: stack.to_vec(),
+                tag_position: stack.iter().position(|borrow| borrow.tag == tag),
+                first_shared: stack
+                    .iter()
+                    .position(|borrow| borrow.borrow_type == BorrowType::Shared),
+            })
+            .collect();
+        AccessExplanation {
+            run_ptr,
+            tag,
+            can_read: self.can_read_with(run_ptr, tag),
+            can_write: self.can_write_with(run_ptr, tag),
+            stacks,
+        }
+    }
+
+    /// Marks tag as exposed to pointers cast from integers into run_ptr's allocation
+    #[cfg(test)]
+    pub fn expose(&mut self, run_ptr: RunPointer, tag: Tag) {
+        self.debug_check_run_ptr(run_ptr);
+        self.allocations[run_ptr.alloc_id].exposed.insert(tag);
+        self.tracer.record(MemEvent::Expose { run_ptr, tag });
+    }
+
+    /// Tags exposed in an allocation, in ascending order
+    pub fn exposed_tags(&self, alloc_id: AllocId) -> impl Iterator<Item = Tag> + '_ {
+        self.allocations[alloc_id].exposed.iter().copied()
+    }
+
+    /// The tags an access with provenance may use, any one of which has to grant it.
+    /// Only wildcards look at run_ptr
+    #[cfg_attr(not(test), allow(unused_variables))]
+    fn candidate_tags(&self, run_ptr: RunPointer, provenance: Provenance) -> SmallVec<[Tag; 1]> {
+        match provenance {
+            Provenance::Tag(tag) => SmallVec::from([tag]),
+            #[cfg(test)]
+            Provenance::Wildcard => self.exposed_tags(run_ptr.alloc_id).collect(),
+        }
+    }
+
+    pub fn can_read_with(&self, run_ptr: RunPointer, provenance: impl Into<Provenance>) -> bool {
+        let run = &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        self.candidate_tags(run_ptr, provenance.into())
+            .into_iter()
+            .any(|tag| run.can_read_with(run_ptr.offset(), run_ptr.size, tag))
+    }
+
+    pub fn can_write_with(&self, run_ptr: RunPointer, provenance: impl Into<Provenance>) -> bool {
+        let run = &self.allocations[run_ptr.alloc_id].runs[run_ptr.run()];
+        self.candidate_tags(run_ptr, provenance.into())
+            .into_iter()
+            .any(|tag| run.can_write_with(run_ptr.offset(), run_ptr.size, tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+    use std::collections::BTreeSet;
+
+    use abi::size::Size;
+    use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+    use smallvec::SmallVec;
+    use test::Bencher;
+
+    use super::{
+        AbstractByte, AllocId, AllocKind, BasicMemory, Borrow, BorrowType, FreeError,
+        InvariantViolation, Layout, MemEvent, MemStats, Provenance, Run, RunAndOffset, RunId,
+        RunPointer, RunPtrError, Tag, VecTrace,
+    };
+
+    /// Allocates a single run of `size` bytes and points at all of it
+    fn single_run(mem: &mut BasicMemory, size: u64) -> RunPointer {
+        let mut run = None;
+        let alloc_id = mem
+            .allocate_with_builder(|builder| {
+                run = Some(builder.new_run(Size::from_bytes(size)));
+            })
+            .unwrap();
+        RunPointer {
+            alloc_id,
+            run_and_offset: run.unwrap(),
+            size: Size::from_bytes(size),
+        }
+    }
+
+    /// `len` bytes of `run_ptr` from `offset`, without a bounds check
+    fn slice(run_ptr: RunPointer, offset: usize, len: usize) -> RunPointer {
+        RunPointer {
+            run_and_offset: run_ptr.run_and_offset.offset(offset as isize),
+            size: Size::from_bytes(len),
+            ..run_ptr
+        }
+    }
+
+    fn random_range(rng: &mut impl Rng, size: u64, max_len: u64) -> (Size, Size) {
+        let offset = rng.gen_range(0..size);
+        let len = rng.gen_range(0..=(size - offset).min(max_len));
+        (Size::from_bytes(offset), Size::from_bytes(len))
+    }
+
+    fn random_borrows(rng: &mut impl Rng, size: u64, count: usize, max_len: u64) -> Run {
+        let mut run = Run::new_uninit(Size::from_bytes(size));
+        for tag in 0..count {
+            let (offset, len) = random_range(rng, size, max_len);
+            let borrow_type = *[
+                BorrowType::Raw,
+                BorrowType::Shared,
+                BorrowType::SharedReadWrite,
+                BorrowType::Exclusive,
+            ]
+            .choose(rng)
+            .unwrap();
+            run.add_borrow(offset, len, borrow_type, Tag::new(tag));
+        }
+        run
+    }
+
+    #[test]
+    fn permissions_match_stacks() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut run = random_borrows(&mut rng, 128, 50, 64);
+            for _ in 0..200 {
+                let (offset, len) = random_range(&mut rng, 128, 64);
+                let tag = Tag::new(rng.gen_range(0..60));
+                let protected = run
+                    .ref_stack
+                    .iter_all()
+                    .any(|(_, stack)| stack.iter().any(|borrow| borrow.protected));
+                match rng.gen_range(0..6) {
+                    0 => run.protect(offset, len, tag),
+                    1 => run.unprotect(offset, len, tag),
+                    2 => {
+                        run.remove_all_above(offset, len, tag);
+                    }
+                    // These assert that nothing protected is popped
+                    3 if !protected => {
+                        run.read_with(offset, len, tag);
+                    }
+                    4 if !protected => run.remove_borrow(offset, len, tag),
+                    _ => run.add_borrow(offset, len, BorrowType::Exclusive, tag),
+                }
+                for tag in (0..60).map(Tag::new) {
+                    let (offset, len) = random_range(&mut rng, 128, 64);
+                    assert_eq!(
+                        run.can_read_with(offset, len, tag),
+                        run.can_read_with_slow(offset, len, tag)
+                    );
+                    assert_eq!(
+                        run.can_write_with(offset, len, tag),
+                        run.can_write_with_slow(offset, len, tag)
+                    );
+                }
+                // So is the number of stack entries
+                let entries: u64 = run
+                    .ref_stack
+                    .iter_all()
+                    .map(|(range, stack)| (range.end - range.start) * stack.len() as u64)
+                    .sum();
+                assert_eq!(run.entries, entries);
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_tag_removal() {
+        let (offset, len) = (Size::ZERO, Size::from_bytes(8));
+        let (ptr, shared) = (Tag::new(0), Tag::new(1));
+        let mut run = Run::new_uninit(len);
+        run.add_borrow(offset, len, BorrowType::Raw, ptr);
+        run.add_borrow(offset, len, BorrowType::Shared, shared);
+        // Reborrowed through a copy of the raw pointer
+        run.add_borrow(offset, len, BorrowType::Raw, ptr);
+        assert_eq!(run.above_first_shared(offset, len), vec![ptr, shared]);
+
+        // Only the reborrow is gone, so the original borrow still grants writes
+        run.remove_borrow(offset, len, ptr);
+        let tags = |run: &Run| {
+            run.stacks(offset, len)[0]
+                .1
+                .iter()
+                .map(|borrow| borrow.tag)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tags(&run), vec![ptr, shared]);
+        assert!(run.can_write_with(offset, len, ptr));
+        assert_eq!(run.above_first_shared(offset, len), vec![shared]);
+        assert_eq!(run.check_stacks(), Ok(()));
+
+        run.add_borrow(offset, len, BorrowType::Raw, ptr);
+        run.remove_all_of_tag(offset, len, ptr);
+        assert_eq!(tags(&run), vec![shared]);
+        assert!(!run.can_read_with(offset, len, ptr));
+        assert_eq!(run.above_first_shared(offset, len), vec![shared]);
+        assert_eq!(run.check_stacks(), Ok(()));
+    }
+
+    fn bench_access(b: &mut Bencher, access: fn(&Run, Size, Size, Tag) -> bool) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        // References to whole places make for deep stacks
+        let run = random_borrows(&mut rng, 4096, 1000, 4096);
+        b.iter(|| {
+            let (offset, len) = random_range(&mut rng, 4096, 64);
+            access(&run, offset, len, Tag::new(rng.gen_range(0..1000)))
+        })
+    }
+
+    #[bench]
+    fn bench_can_read_with(b: &mut Bencher) {
+        bench_access(b, Run::can_read_with);
+    }
+
+    #[bench]
+    fn bench_can_read_with_slow(b: &mut Bencher) {
+        bench_access(b, Run::can_read_with_slow);
+    }
+
+    #[bench]
+    fn bench_can_write_with(b: &mut Bencher) {
+        bench_access(b, Run::can_write_with);
+    }
+
+    #[bench]
+    fn bench_can_write_with_slow(b: &mut Bencher) {
+        bench_access(b, Run::can_write_with_slow);
+    }
+
+    // Performs a fixed sequence of borrow stack operations and records all returned tags
+    fn scripted(mem: &mut BasicMemory) -> Vec<Vec<Tag>> {
+        let mut runs = vec![];
+        let alloc_id = mem
+            .allocate_with_builder(|builder| {
+                runs.push(builder.new_run(Size::from_bytes(16)));
+                runs.push(builder.new_run(Size::from_bytes(8)));
+            })
+            .unwrap();
+        let run_ptr = |run: usize, offset: usize, len: usize| RunPointer {
+            alloc_id,
+            run_and_offset: runs[run].offset(offset as isize),
+            size: Size::from_bytes(len),
+        };
+
+        let mut outputs = vec![];
+        for i in 0..64 {
+            let borrow_type = match i % 3 {
+                0 => BorrowType::Exclusive,
+                1 => BorrowType::Raw,
+                _ => BorrowType::Shared,
+            };
+            // Tags are handed out in descending order so they don't follow insertion order
+            let tag = Tag::new(100 - i);
+            mem.add_ref(run_ptr(i % 2, i % 4, 4), borrow_type, Some(tag));
+            if i % 2 == 0 {
+                mem.add_ref(run_ptr(1, 0, 8), borrow_type, Some(tag));
+            }
+            if i % 7 == 6 {
+                outputs.push(
+                    mem.remove_tags_above(Tag::new(100 - i + 3), run_ptr(0, 0, 16))
+                        .all_gone,
+                );
+            }
+            outputs.push(mem.above_first_shared(run_ptr(0, 0, 16)));
+            outputs.push(mem.dangling_tags(alloc_id));
+        }
+        outputs
+    }
+
+    #[test]
+    fn deterministic_tags() {
+        let mut fresh = BasicMemory::new();
+
+        // Give the second memory a different history
+        let mut used = BasicMemory::new();
+        let mut scratch = None;
+        let scratch_alloc = used
+            .allocate_with_builder(|builder| {
+                scratch = Some(builder.new_run(Size::from_bytes(1)));
+            })
+            .unwrap();
+        for i in 200..300 {
+            let run_ptr = RunPointer {
+                alloc_id: scratch_alloc,
+                run_and_offset: scratch.unwrap(),
+                size: Size::from_bytes(1),
+            };
+            used.add_ref(run_ptr, BorrowType::Raw, Some(Tag::new(i)));
+            if i % 2 == 0 {
+                used.remove_tag(Tag::new(i));
+            }
+        }
+        used.deallocate(scratch_alloc).unwrap();
+
+        let from_fresh = scripted(&mut fresh);
+        let from_used = scripted(&mut used);
+        assert_eq!(from_fresh, from_used);
+        for tags in from_fresh {
+            assert!(tags.is_sorted());
+        }
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut mem = BasicMemory::new();
+        let mut runs = vec![];
+        let alloc_id = mem
+            .allocate_with_builder(|builder| {
+                runs.push(builder.new_run(Size::ZERO));
+                runs.push(builder.new_run(Size::from_bytes(4)));
+            })
+            .unwrap();
+        let zst = RunPointer {
+            alloc_id,
+            run_and_offset: runs[0],
+            size: Size::ZERO,
+        };
+        // Zero-length access at the end of a non-empty run
+        let end = RunPointer {
+            alloc_id,
+            run_and_offset: runs[1].offset(4),
+            size: Size::ZERO,
+        };
+
+        for run_ptr in [zst, end] {
+            assert!(mem.try_bytes(run_ptr).unwrap().is_empty());
+            assert!(mem.can_read_with(run_ptr, Tag::new(0)));
+            assert!(mem.can_write_with(run_ptr, Tag::new(0)));
+            mem.fill(run_ptr, AbstractByte::Init(None));
+        }
+
+        let tag = mem.add_ref(zst, BorrowType::Exclusive, None);
+        assert_eq!(mem.dump_stacks(zst), "");
+        assert!(mem.can_write_with(zst, tag));
+        assert!(mem.pointers.contains_key(&tag));
+        mem.remove_tag(tag);
+        assert!(!mem.pointers.contains_key(&tag));
+    }
+
+    #[test]
+    fn stack_limit() {
+        let mut mem = BasicMemory::new().with_stack_limit(Size::from_bytes(16));
+        let first = mem
+            .allocate_with_builder(|builder| {
+                builder.new_run(Size::from_bytes(4));
+                builder.new_run(Size::from_bytes(8));
+            })
+            .unwrap();
+        assert_eq!(mem.current_usage(), Size::from_bytes(12));
+
+        let refused = mem
+            .allocate_with_builder(|builder| {
+                builder.new_run(Size::from_bytes(8));
+            })
+            .unwrap_err();
+        assert_eq!(refused.requested, Size::from_bytes(8));
+        assert_eq!(refused.available, Size::from_bytes(4));
+        assert_eq!(mem.current_usage(), Size::from_bytes(12));
+
+        mem.deallocate(first).unwrap();
+        mem.deallocate(first).unwrap();
+        assert_eq!(mem.current_usage(), Size::ZERO);
+        mem.allocate_with_builder(|builder| {
+            builder.new_run(Size::from_bytes(16));
+        })
+        .unwrap();
+        assert!(!mem.fits(Size::from_bytes(1)));
+
+        // Statics live outside of the stack
+        let s = mem.allocate_with_builder_static(|builder| {
+            builder.new_run(Size::from_bytes(8));
+        });
+        assert!(mem.is_static(s) && mem.is_live(s));
+        assert_eq!(mem.current_usage(), Size::from_bytes(16));
+        mem.compact();
+        assert!(mem.is_live(s));
+    }
+
+    #[test]
+    fn heap_free() {
+        let mut mem = BasicMemory::new().with_stack_limit(Size::from_bytes(4));
+        let heap = mem.allocate_heap(Size::from_bytes(8));
+        assert_eq!(mem.alloc_kind(heap), AllocKind::Heap);
+        assert_eq!(mem.current_usage(), Size::ZERO);
+
+        let frame = mem
+            .allocate_with_builder(|builder| {
+                builder.new_run(Size::from_bytes(4));
+            })
+            .unwrap();
+        assert_eq!(mem.free(frame), Err(FreeError::NotHeap(AllocKind::Stack)));
+        mem.deallocate(frame).unwrap();
+        assert!(mem.is_live(heap));
+
+        let run_ptr = RunPointer::from_bytes_range(0..8, heap, RunId::new(0));
+        let tag = mem.add_ref(run_ptr, BorrowType::Raw, None);
+        mem.mark_protected(run_ptr, tag);
+        assert_eq!(mem.free(heap), Err(FreeError::Protected(vec![tag])));
+        mem.mark_unprotected(run_ptr, tag);
+
+        mem.free(heap).unwrap();
+        assert!(!mem.is_live(heap));
+        assert_eq!(mem.free(heap), Err(FreeError::DoubleFree));
+        mem.compact();
+        assert_eq!(mem.free(heap), Err(FreeError::DoubleFree));
+    }
+
+    #[test]
+    fn exposed_provenance() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 4);
+        let alloc_id = run_ptr.alloc_id;
+        let exclusive = mem.add_ref(run_ptr, BorrowType::Exclusive, None);
+        let shared = mem.add_ref(run_ptr, BorrowType::Shared, None);
+
+        // Nothing exposed, so an integer can't be used as a pointer
+        assert!(!mem.can_read_with(run_ptr, Provenance::Wildcard));
+        assert!(!mem.can_write_with(run_ptr, Provenance::Wildcard));
+
+        // A shared reference exposed only grants reads
+        mem.expose(run_ptr, shared);
+        assert!(mem.can_read_with(run_ptr, Provenance::Wildcard));
+        assert!(!mem.can_write_with(run_ptr, Provenance::Wildcard));
+
+        mem.expose(run_ptr, exclusive);
+        assert_eq!(
+            mem.exposed_tags(alloc_id).collect::<Vec<_>>(),
+            vec![exclusive, shared]
+        );
+        assert!(mem.can_write_with(run_ptr, Provenance::Wildcard));
+
+        // Exposed, but popped
+        mem.remove_tag(exclusive);
+        assert!(!mem.can_write_with(run_ptr, Provenance::Wildcard));
+    }
+
+    #[test]
+    fn shared_read_write() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 4);
+        mem.add_ref(run_ptr, BorrowType::Exclusive, Some(Tag::new(0)));
+        mem.add_ref(run_ptr, BorrowType::SharedReadWrite, Some(Tag::new(1)));
+        mem.add_ref(run_ptr, BorrowType::SharedReadWrite, Some(Tag::new(2)));
+
+        // Writing through either SharedReadWrite doesn't invalidate the other
+        assert!(mem.above_first_shared(run_ptr).is_empty());
+        assert!(mem.can_write_with(run_ptr, Tag::new(1)));
+        assert!(mem.can_write_with(run_ptr, Tag::new(2)));
+
+        // A read-only shared reference on top is the cutoff
+        mem.add_ref(run_ptr, BorrowType::Shared, Some(Tag::new(3)));
+        assert_eq!(mem.above_first_shared(run_ptr), [Tag::new(3)]);
+        assert!(mem.can_write_with(run_ptr, Tag::new(2)));
+        assert!(!mem.can_write_with(run_ptr, Tag::new(3)));
+        assert!(mem.can_read_with(run_ptr, Tag::new(3)));
+    }
+
+    #[test]
+    fn read_invalidates_child() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 4);
+        let alloc_id = run_ptr.alloc_id;
+        // parent = &mut x
+        let parent = Tag::new(0);
+        mem.add_ref(run_ptr, BorrowType::Exclusive, Some(parent));
+        // child = &mut *parent
+        let child = Tag::new(1);
+        mem.add_ref(run_ptr, BorrowType::Exclusive, Some(child));
+        // raw = &raw const *child
+        let raw = Tag::new(2);
+        mem.add_ref(run_ptr, BorrowType::Raw, Some(raw));
+        assert!(mem.can_write_with(run_ptr, child));
+
+        // Reading through child keeps it alive
+        assert!(mem.read_with(run_ptr, child).is_empty());
+
+        // Reading through parent invalidates child, but not the raw pointer
+        assert_eq!(mem.read_with(run_ptr, parent), [child]);
+        assert!(!mem.can_read_with(run_ptr, child));
+        assert!(mem.can_read_with(run_ptr, parent));
+        assert!(mem.can_read_with(run_ptr, raw));
+        assert!(mem.dangling_tags(alloc_id).iter().all(|tag| *tag != child));
+    }
+
+    #[test]
+    fn write_invalidates_child() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 8);
+        let run_ptr = |offset, len| slice(whole, offset, len);
+        // parent = &mut x, child = &mut (*parent).1
+        let parent = mem.add_ref(run_ptr(0, 8), BorrowType::Exclusive, None);
+        let child = mem.add_ref(run_ptr(4, 4), BorrowType::Exclusive, None);
+        let raw = mem.add_ref(run_ptr(0, 4), BorrowType::Raw, None);
+        assert!(mem.can_write_with(run_ptr(4, 4), child));
+
+        // Writing through parent elsewhere leaves child alone
+        assert_eq!(mem.write_with(run_ptr(0, 4), parent), [raw]);
+        assert!(mem.can_write_with(run_ptr(4, 4), child));
+
+        assert_eq!(mem.write_with(run_ptr(0, 8), parent), [child]);
+        assert!(!mem.can_write_with(run_ptr(4, 4), child));
+        assert!(mem.can_write_with(run_ptr(0, 8), parent));
+
+        // A write can't pop a protected borrow
+        let arg = mem.add_ref(run_ptr(0, 8), BorrowType::Exclusive, None);
+        mem.protect_tag(arg);
+        assert!(mem.write_with_pops_protected(run_ptr(0, 1), parent));
+        assert!(!mem.write_with_pops_protected(run_ptr(0, 1), arg));
+        assert!(mem.write_with(run_ptr(0, 8), arg).is_empty());
+    }
+
+    #[test]
+    fn write_keeps_raw_siblings() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 4);
+        // parent = &mut x, raw1 = &raw mut *parent, raw2 = &raw mut *parent
+        let parent = mem.add_ref(run_ptr, BorrowType::Exclusive, None);
+        let raw1 = mem.add_ref(run_ptr, BorrowType::Raw, None);
+        let raw2 = mem.add_ref(run_ptr, BorrowType::Raw, None);
+        let shared = mem.add_ref(run_ptr, BorrowType::Shared, None);
+
+        // Writing through raw1 leaves raw2 usable
+        assert_eq!(mem.write_with(run_ptr, raw1), [shared]);
+        assert!(mem.can_write_with(run_ptr, raw2));
+
+        // A raw pointer on top of an exclusive one is popped by writing through it
+        let child = mem.add_ref(run_ptr, BorrowType::Exclusive, None);
+        let raw3 = mem.add_ref(run_ptr, BorrowType::Raw, None);
+        assert_eq!(mem.write_with(run_ptr, raw2), [child, raw3]);
+        assert!(mem.can_write_with(run_ptr, raw1));
+
+        // Writing through parent pops all of them
+        assert_eq!(mem.write_with(run_ptr, parent), [raw1, raw2]);
+    }
+
+    #[test]
+    fn init_ranges() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 8);
+        let run_ptr = |offset, len| slice(whole, offset, len);
+        assert!(mem.init_ranges(run_ptr(0, 8)).is_empty());
+        assert_eq!(mem.init_fraction(run_ptr(0, 8)), (0, 8));
+
+        mem.fill(run_ptr(2, 2), AbstractByte::Init(None));
+        mem.write_scalar(run_ptr(4, 2), 0xffff);
+        assert_eq!(
+            mem.init_ranges(run_ptr(0, 8)),
+            [Size::from_bytes(2)..Size::from_bytes(6)]
+        );
+        assert_eq!(mem.init_fraction(run_ptr(0, 8)), (4, 8));
+
+        // Only the part of the run pointed to is reported
+        mem.fill(run_ptr(7, 1), AbstractByte::Init(None));
+        assert_eq!(
+            mem.init_ranges(run_ptr(3, 5)),
+            [
+                Size::from_bytes(3)..Size::from_bytes(6),
+                Size::from_bytes(7)..Size::from_bytes(8)
+            ]
+        );
+        assert_eq!(mem.init_fraction(run_ptr(3, 5)), (4, 5));
+    }
+
+    #[test]
+    fn ptr_parts() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, Layout::PTR_SIZE.bytes() * 2);
+        let ptr = |size| RunPointer { size, ..whole };
+        let thin = ptr(Layout::PTR_SIZE);
+        assert_eq!(BasicMemory::ptr_parts(thin), (thin, None));
+
+        let fat = ptr(Size::from_bytes(Layout::PTR_SIZE.bytes() * 2));
+        let (address, metadata) = BasicMemory::ptr_parts(fat);
+        let metadata = metadata.unwrap();
+        assert_eq!(address, thin);
+        assert_eq!(metadata.offset(), Layout::PTR_SIZE);
+        assert_eq!(metadata.len(), Layout::PTR_SIZE);
+
+        // The halves are written and read separately
+        let tag = mem.fresh_tag();
+        mem.fill(fat, AbstractByte::Uninit);
+        mem.set_provenance(address, tag);
+        mem.fill(metadata, AbstractByte::Init(None));
+        assert_eq!(mem.provenance(address), Some(tag));
+        assert_eq!(mem.provenance(metadata), None);
+        assert_eq!(mem.provenance(fat), None);
+        assert!(mem.bytes(metadata).iter().all(|b| b.is_init()));
+    }
+
+    #[test]
+    fn trace() {
+        let mut mem = BasicMemory::new();
+        let trace = VecTrace::default();
+        mem.set_tracer(Box::new(trace.clone()));
+        let run_ptr = single_run(&mut mem, 4);
+        let alloc_id = run_ptr.alloc_id;
+        mem.write_bytes(run_ptr, 3);
+        let tag = mem.add_ref(run_ptr, BorrowType::Exclusive, None);
+        let snapshot = mem.snapshot();
+        mem.protect_tag(tag);
+        mem.unprotect_tag(tag);
+        mem.mark_protected(run_ptr, tag);
+        mem.mark_unprotected(run_ptr, tag);
+        mem.read_with(run_ptr, tag);
+        mem.expose(run_ptr, tag);
+        mem.remove_tags_above(tag, run_ptr);
+        mem.remove_tag(tag);
+        mem.restore(snapshot);
+        // Joins and clones keep reporting to the sink
+        let mut mem = mem.join(&mem.clone());
+        mem.deallocate(alloc_id).unwrap();
+
+        assert_eq!(
+            trace.events(),
+            [
+                MemEvent::Allocate {
+                    alloc_id,
+                    size: Size::from_bytes(4)
+                },
+                MemEvent::WriteBytes { run_ptr, origin: 3 },
+                MemEvent::AddRef {
+                    run_ptr,
+                    borrow_type: BorrowType::Exclusive,
+                    tag
+                },
+                MemEvent::ProtectTag(tag),
+                MemEvent::UnprotectTag(tag),
+                MemEvent::Protect { run_ptr, tag },
+                MemEvent::Unprotect { run_ptr, tag },
+                MemEvent::ReadWith { run_ptr, tag },
+                MemEvent::Expose { run_ptr, tag },
+                MemEvent::RemoveTagsAbove { tag, run_ptr },
+                MemEvent::RemoveTag(tag),
+                MemEvent::Restore,
+                MemEvent::Deallocate(alloc_id),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 4);
+        let alloc_id = run_ptr.alloc_id;
+        mem.fill(run_ptr, AbstractByte::Init(None));
+        mem.add_ref(run_ptr, BorrowType::Shared, Some(Tag::new(0)));
+
+        let snapshot = mem.snapshot();
+
+        mem.add_ref(run_ptr, BorrowType::Exclusive, Some(Tag::new(1)));
+        mem.remove_tag(Tag::new(0));
+        mem.fill(run_ptr, AbstractByte::Uninit);
+        let new_alloc = mem
+            .allocate_with_builder(|builder| {
+                builder.new_run(Size::from_bytes(1));
+            })
+            .unwrap();
+        mem.deallocate(alloc_id).unwrap();
+
+        mem.restore(snapshot);
+        assert!(mem.is_live(alloc_id));
+        assert!(mem.bytes(run_ptr).iter().all(|b| b.is_init()));
+        assert!(mem.can_read_with(run_ptr, Tag::new(0)));
+        assert!(!mem.can_read_with(run_ptr, Tag::new(1)));
+        assert_eq!(mem.dangling_tags(alloc_id), [Tag::new(0)]);
+
+        // Allocation ids are handed out again
+        let realloc = mem
+            .allocate_with_builder(|builder| {
+                builder.new_run(Size::from_bytes(1));
+            })
+            .unwrap();
+        assert_eq!(realloc, new_alloc);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn last_writer() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 4);
+        let run_ptr = |offset, len| slice(whole, offset, len);
+        assert_eq!(mem.last_writer(run_ptr(0, 4)), [None; 4]);
+
+        mem.write_bytes(run_ptr(0, 4), 1);
+        mem.write_bytes(run_ptr(2, 1), 7);
+        assert_eq!(
+            mem.last_writer(run_ptr(0, 4)),
+            [Some(1), Some(1), Some(7), Some(1)]
+        );
+        assert!(mem.bytes(run_ptr(0, 4)).iter().all(|b| b.is_init()));
+    }
+
+    #[test]
+    fn fallible_access() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 4);
+        let alloc_id = run_ptr.alloc_id;
+        let other = mem
+            .allocate_with_builder(|builder| {
+                builder.new_run(Size::from_bytes(4));
+            })
+            .unwrap();
+
+        mem.try_bytes_mut(run_ptr)
+            .unwrap()
+            .fill(AbstractByte::Init(None));
+        assert!(mem.try_bytes(run_ptr).unwrap().iter().all(|b| b.is_init()));
+
+        let past_end = RunPointer {
+            size: Size::from_bytes(5),
+            ..run_ptr
+        };
+        assert_eq!(
+            mem.try_bytes(past_end),
+            Err(RunPtrError::OutOfBounds {
+                run_ptr: past_end,
+                run_size: Size::from_bytes(4)
+            })
+        );
+
+        let unknown_run = RunPointer {
+            run_and_offset: RunAndOffset(RunId::new(1), Size::ZERO),
+            ..run_ptr
+        };
+        assert_eq!(
+            mem.try_bytes(unknown_run),
+            Err(RunPtrError::UnknownRun(alloc_id, RunId::new(1)))
+        );
+
+        let unknown_alloc = RunPointer {
+            alloc_id: AllocId::new(2),
+            ..run_ptr
+        };
+        assert_eq!(
+            mem.try_bytes(unknown_alloc),
+            Err(RunPtrError::UnknownAllocation(AllocId::new(2)))
+        );
+
+        mem.deallocate(other).unwrap();
+        let dead = RunPointer {
+            alloc_id: other,
+            ..run_ptr
+        };
+        assert_eq!(
+            mem.try_copy(dead, run_ptr),
+            Err(RunPtrError::DeadAllocation(other))
+        );
+        assert_eq!(
+            mem.try_copy(run_ptr, dead),
+            Err(RunPtrError::DeadAllocation(other))
+        );
+    }
+
+    #[test]
+    fn run_ptr_bounds() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 8);
+        let (alloc_id, run) = (whole.alloc_id, whole.run_and_offset);
+
+        // Ends exactly at the end of the run
+        assert!(mem
+            .run_ptr(alloc_id, run.offset(4), Size::from_bytes(4))
+            .is_ok());
+        assert!(mem.run_ptr(alloc_id, run, Size::from_bytes(8)).is_ok());
+        assert!(mem.run_ptr(alloc_id, run.offset(8), Size::ZERO).is_ok());
+
+        // One byte past the end
+        let past_end = RunPointer {
+            alloc_id,
+            run_and_offset: run.offset(5),
+            size: Size::from_bytes(4),
+        };
+        assert_eq!(
+            mem.check_run_ptr(past_end),
+            Err(RunPtrError::OutOfBounds {
+                run_ptr: past_end,
+                run_size: Size::from_bytes(8)
+            })
+        );
+        assert!(mem.run_ptr(alloc_id, run, Size::from_bytes(9)).is_err());
+
+        // Pointers into dead allocations are still in bounds
+        mem.deallocate(alloc_id).unwrap();
+        assert!(mem
+            .run_ptr(alloc_id, run.offset(4), Size::from_bytes(4))
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid run pointer")]
+    fn fill_out_of_bounds() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 8);
+        mem.fill(slice(whole, 1, 8), AbstractByte::Init(None));
+    }
+
+    #[test]
+    fn join() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 8);
+        let alloc_id = whole.alloc_id;
+        let run_ptr = |offset| slice(whole, offset, 4);
+        mem.fill(run_ptr(0), AbstractByte::Init(None));
+        mem.add_ref(run_ptr(0), BorrowType::Raw, Some(Tag::new(0)));
+
+        let mut left = mem.clone();
+        let mut right = mem.clone();
+        left.fill(run_ptr(4), AbstractByte::Init(None));
+        left.add_ref(run_ptr(4), BorrowType::Exclusive, Some(Tag::new(1)));
+        right.add_ref(run_ptr(4), BorrowType::Shared, Some(Tag::new(2)));
+
+        let joined = left.join(&right);
+        assert!(joined.bytes(run_ptr(0)).iter().all(|b| b.is_init()));
+        assert!(joined.bytes(run_ptr(4)).iter().all(|b| !b.is_init()));
+        assert!(joined.can_read_with(run_ptr(0), Tag::new(0)));
+        assert!(!joined.can_read_with(run_ptr(4), Tag::new(1)));
+        assert!(!joined.can_read_with(run_ptr(4), Tag::new(2)));
+        assert_eq!(joined.dangling_tags(alloc_id), vec![Tag::new(0)]);
+
+        right.deallocate(alloc_id).unwrap();
+        assert!(!left.join(&right).is_live(alloc_id));
+    }
+
+    #[test]
+    fn compact() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 8);
+        let dead = run_ptr.alloc_id;
+        let live = single_run(&mut mem, 4).alloc_id;
+        mem.add_ref(run_ptr, BorrowType::Raw, Some(Tag::new(0)));
+        assert_eq!(mem.bytes_retained(), 12);
+
+        mem.deallocate(dead).unwrap();
+        mem.compact();
+        assert_eq!(mem.bytes_retained(), 4);
+        assert!(mem.dangling_tags(dead).is_empty());
+        assert!(mem.is_live(live));
+        assert_eq!(
+            mem.try_bytes(run_ptr),
+            Err(RunPtrError::DeadAllocation(dead))
+        );
+
+        let new = mem.allocate_with_builder(|_| {}).unwrap();
+        assert_ne!(new, dead);
+        assert_ne!(new, live);
+    }
+
+    #[test]
+    fn stats() {
+        let mut mem = BasicMemory::new();
+        let mut runs = vec![];
+        let a = mem
+            .allocate_with_builder(|builder| {
+                runs.push(builder.new_run(Size::from_bytes(8)));
+                builder.new_run(Size::from_bytes(4));
+            })
+            .unwrap();
+        let b = mem
+            .allocate_with_builder(|builder| {
+                runs.push(builder.new_run(Size::from_bytes(2)));
+            })
+            .unwrap();
+        // Statics are live bytes too, though they don't count towards the stack limit
+        mem.allocate_with_builder_static(|builder| {
+            builder.new_run(Size::from_bytes(4));
+        });
+        assert_eq!(mem.current_usage(), Size::from_bytes(14));
+        let run_ptr = |alloc_id, run: RunAndOffset, offset, len| RunPointer {
+            alloc_id,
+            run_and_offset: run.offset(offset),
+            size: Size::from_bytes(len),
+        };
+        let t0 = mem.add_ref(run_ptr(a, runs[0], 0, 8), BorrowType::Raw, None);
+        let t1 = mem.add_ref(run_ptr(a, runs[0], 2, 4), BorrowType::Shared, None);
+        mem.add_ref(run_ptr(b, runs[1], 0, 2), BorrowType::Exclusive, None);
+        assert_eq!(
+            mem.stats(),
+            MemStats {
+                live_allocations: 3,
+                dead_allocations: 0,
+                live_bytes: Size::from_bytes(18),
+                runs: 4,
+                bytes_retained: 18,
+                stack_entries: 14,
+                tags: 3,
+            }
+        );
+
+        mem.remove_tag(t1);
+        mem.remove_tag_run_ptr(t0, run_ptr(a, runs[0], 0, 4));
+        assert_eq!(mem.stats().stack_entries, 6);
+        assert_eq!(mem.stats().tags, 2);
+
+        mem.deallocate(a).unwrap();
+        assert_eq!(
+            mem.stats(),
+            MemStats {
+                live_allocations: 2,
+                dead_allocations: 1,
+                live_bytes: Size::from_bytes(6),
+                runs: 4,
+                bytes_retained: 18,
+                stack_entries: 6,
+                tags: 2,
+            }
+        );
+
+        // Compaction drops the runs of dead allocations along with their borrows
+        mem.compact();
+        assert_eq!(
+            mem.stats(),
+            MemStats {
+                live_allocations: 2,
+                dead_allocations: 1,
+                live_bytes: Size::from_bytes(6),
+                runs: 2,
+                bytes_retained: 6,
+                stack_entries: 2,
+                tags: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn copy_provenance() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 16);
+        let run_ptr = |offset| slice(whole, offset, 8);
+        mem.fill(run_ptr(0), AbstractByte::Init(None));
+        mem.set_provenance(run_ptr(0), Tag::new(3));
+        assert_eq!(mem.provenance(run_ptr(0)), Some(Tag::new(3)));
+        assert_eq!(mem.provenance(run_ptr(8)), None);
+        // Half of a pointer
+        assert_eq!(mem.provenance(run_ptr(4)), None);
+
+        mem.copy(run_ptr(8), run_ptr(0));
+        assert_eq!(mem.provenance(run_ptr(8)), Some(Tag::new(3)));
+
+        mem.fill(run_ptr(0), AbstractByte::Init(None));
+        assert_eq!(mem.provenance(run_ptr(0)), None);
+    }
+
+    #[test]
+    fn fresh_tags() {
+        let mut mem = BasicMemory::new();
+        let mut seen = BTreeSet::new();
+        let new_run_ptr = |mem: &mut BasicMemory| single_run(mem, 4);
+
+        // An explicitly chosen tag is never handed out afterwards
+        let run_ptr = new_run_ptr(&mut mem);
+        seen.insert(mem.add_ref(run_ptr, BorrowType::Raw, Some(Tag::new(50))));
+
+        for i in 0..100 {
+            let run_ptr = new_run_ptr(&mut mem);
+            let tag = mem.add_ref(run_ptr, BorrowType::Raw, None);
+            assert!(seen.insert(tag));
+            assert!(seen.insert(mem.fresh_tag()));
+            if i % 3 == 0 {
+                mem.remove_tag(tag);
+                mem.deallocate(run_ptr.alloc_id).unwrap();
+                mem.compact();
+            }
+        }
+        assert_eq!(seen.len(), 201);
+    }
+
+    #[test]
+    fn explain() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 8);
+        let alloc_id = whole.alloc_id;
+        let run_ptr = |offset, len| slice(whole, offset, len);
+        mem.add_ref(run_ptr(0, 8), BorrowType::Exclusive, Some(Tag::new(0)));
+        mem.add_ref(run_ptr(0, 4), BorrowType::Shared, Some(Tag::new(1)));
+        mem.add_ref(run_ptr(4, 4), BorrowType::Raw, Some(Tag::new(2)));
+        mem.mark_protected(run_ptr(4, 4), Tag::new(2));
+
+        assert_eq!(
+            mem.dump_stacks(run_ptr(2, 6)),
+            "2..4: [Exclusive #0, Shared #1]\n\
+             4..8: [Exclusive #0, Raw #2 protected]\n"
+        );
+
+        let explanation = mem.explain(run_ptr(0, 8), Tag::new(2));
+        assert!(!explanation.can_read);
+        assert!(!explanation.can_write);
+        assert_eq!(explanation.stacks[0].tag_position, None);
+        assert_eq!(explanation.stacks[0].first_shared, Some(1));
+        assert_eq!(explanation.stacks[1].tag_position, Some(1));
+        assert_eq!(
+            explanation.to_string(),
+            format!(
+                "tag #2 in {alloc_id:?} run 0 bytes 0..8: read rejected, write rejected\n\
+                 \x20 0..4: [Exclusive #0, Shared #1], tag not in stack, first shared at 1\n\
+                 \x20 4..8: [Exclusive #0, Raw #2 protected], tag at 1\n"
+            )
+        );
+    }
+
+    #[test]
+    fn partial_removal() {
+        let mut mem = BasicMemory::new();
+        let mut runs = vec![];
+        let alloc_id = mem
+            .allocate_with_builder(|builder| {
+                runs.push(builder.new_run(Size::from_bytes(4)));
+                runs.push(builder.new_run(Size::from_bytes(8)));
+            })
+            .unwrap();
+        let run_ptr = |run: usize, offset: usize, len: usize| RunPointer {
+            alloc_id,
+            run_and_offset: runs[run].offset(offset as isize),
+            size: Size::from_bytes(len),
+        };
+        // A reference to a whole (u32, u64)
+        let tuple_ref = Tag::new(0);
+        mem.add_ref(run_ptr(0, 0, 4), BorrowType::Exclusive, Some(tuple_ref));
+        mem.add_ref(run_ptr(1, 0, 8), BorrowType::Exclusive, Some(tuple_ref));
+        let field_ref = Tag::new(1);
+        mem.add_ref(run_ptr(0, 0, 4), BorrowType::Exclusive, Some(field_ref));
+
+        // Writing the first field through another pointer below both
+        let removed = mem.remove_tags_above(tuple_ref, run_ptr(0, 0, 4));
+        assert_eq!(removed.all_gone, vec![field_ref]);
+        assert_eq!(removed.partially_invalidated(), vec![tuple_ref]);
+        assert_eq!(removed.popped[&tuple_ref].as_slice(), &[run_ptr(0, 0, 4)]);
+        assert_eq!(removed.popped[&field_ref].as_slice(), &[run_ptr(0, 0, 4)]);
+        assert!(mem.can_read_with(run_ptr(1, 0, 8), tuple_ref));
+
+        // Only part of a run
+        let removed = mem.remove_tags_above(tuple_ref, run_ptr(1, 2, 4));
+        assert_eq!(removed.popped[&tuple_ref].as_slice(), &[run_ptr(1, 2, 4)]);
+        assert!(removed.all_gone.is_empty());
+        assert!(mem.can_read_with(run_ptr(1, 0, 2), tuple_ref));
+        assert!(!mem.can_read_with(run_ptr(1, 2, 1), tuple_ref));
+    }
+
+    #[test]
+    fn shared_cutoff_sorted() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 4);
+        let run_ptr = |offset| slice(whole, offset, 2);
+        mem.add_ref(run_ptr(0), BorrowType::Exclusive, Some(Tag::new(5)));
+        mem.add_ref(run_ptr(2), BorrowType::Exclusive, Some(Tag::new(1)));
+        mem.add_ref(run_ptr(0), BorrowType::Shared, Some(Tag::new(4)));
+        mem.add_ref(run_ptr(2), BorrowType::Shared, Some(Tag::new(2)));
+        mem.add_ref(run_ptr(0), BorrowType::Raw, Some(Tag::new(0)));
+
+        assert_eq!(
+            mem.above_first_shared(whole),
+            [Tag::new(0), Tag::new(2), Tag::new(4)]
+        );
+        assert_eq!(
+            mem.allocations[whole.alloc_id].runs[whole.run()]
+                .below_first_shared(whole.offset(), whole.len()),
+            [Tag::new(1), Tag::new(5)]
+        );
+    }
+
+    #[test]
+    fn invariant_violations() {
+        let mut mem = BasicMemory::new();
+        let whole = single_run(&mut mem, 8);
+        let alloc_id = whole.alloc_id;
+        let run_ptr = |offset, len| slice(whole, offset, len);
+        let parent = mem.add_ref(run_ptr(0, 8), BorrowType::Exclusive, None);
+        let child = mem.add_ref(run_ptr(4, 4), BorrowType::Shared, None);
+        mem.mark_protected(run_ptr(0, 8), parent);
+        mem.read_with(run_ptr(0, 4), parent);
+        assert_eq!(mem.check_invariants(), Ok(()));
+
+        // The lookup table loses track of a borrow
+        let mut broken = mem.clone();
+        broken.pointers.remove(&child);
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantViolation {
+                alloc_id,
+                run: RunId::new(0),
+                offset: Size::ZERO,
+                tag: Some(child),
+                problem: "is in the stack but not in the lookup table",
+            })
+        );
+
+        // The lookup table claims more bytes than the borrow covers
+        let mut broken = mem.clone();
+        broken
+            .pointers
+            .insert(child, SmallVec::from_slice(&[run_ptr(0, 8)]));
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantViolation {
+                alloc_id,
+                run: RunId::new(0),
+                offset: Size::ZERO,
+                tag: Some(child),
+                problem: "is in the lookup table but not in the stack",
+            })
+        );
+
+        // The lookup table keeps an entry for a tag with no borrow left in the allocation
+        let mut broken = mem.clone();
+        let stale = Tag::new(child.index() + 1);
+        broken
+            .pointers
+            .insert(stale, SmallVec::from_slice(&[run_ptr(2, 2)]));
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantViolation {
+                alloc_id,
+                run: RunId::new(0),
+                offset: Size::from_bytes(2),
+                tag: Some(stale),
+                problem: "is in the lookup table but not in the stack",
+            })
+        );
+
+        // A protected borrow is dropped without going through the memory
+        let mut broken = mem.clone();
+        broken
+            .run_mut(run_ptr(0, 8))
+            .ref_stack
+            .iter_mut(Size::from_bytes(2), Size::from_bytes(1))
+            .for_each(|(_, stack)| stack.retain(|borrow| borrow.tag != parent));
+        let violation = broken.check_invariants().unwrap_err();
+        assert_eq!(
+            violation.to_string(),
+            format!(
+                "alloc 0 run 0 offset 2: tag #{} is permitted outside of its borrows",
+                parent.index()
+            )
+        );
+
+        // A borrow is pushed without counting its entries
+        let mut broken = mem.clone();
+        broken
+            .run_mut(run_ptr(0, 8))
+            .ref_stack
+            .iter_mut(Size::from_bytes(6), Size::from_bytes(2))
+            .for_each(|(_, stack)| {
+                stack.push(Borrow {
+                    borrow_type: BorrowType::Shared,
+                    tag: child,
+                    protected: false,
+                })
+            });
+        let violation = broken.check_invariants().unwrap_err();
+        assert_eq!(
+            (violation.offset, violation.tag, violation.problem),
+            (Size::ZERO, None, "miscounts the stack entries")
+        );
+    }
+
+    #[test]
+    fn borrow_out_of_bounds() {
+        let mut mem = BasicMemory::new();
+        let mut run = None;
+        let alloc_id = mem
+            .allocate_with_builder(|builder| {
+                builder.new_run(Size::from_bytes(2));
+                run = Some(builder.new_run(Size::from_bytes(8)));
+            })
+            .unwrap();
+        let in_bounds = RunPointer {
+            alloc_id,
+            run_and_offset: run.unwrap(),
+            size: Size::from_bytes(8),
+        };
+        let past_end = RunPointer {
+            run_and_offset: run.unwrap().offset(6),
+            size: Size::from_bytes(4),
+            ..in_bounds
+        };
+        let message = "alloc 0 run 1: offset 6 + size 4 is past the end of the run of size 8";
+
+        let err = mem
+            .try_add_ref(past_end, BorrowType::Raw, None)
+            .unwrap_err();
+        assert_eq!(err.to_string(), message);
+        assert!(mem.pointers.is_empty());
+
+        let tag = mem.try_add_ref(in_bounds, BorrowType::Raw, None).unwrap();
+        let err = mem.try_mark_protected(past_end, tag).unwrap_err();
+        assert_eq!(err.to_string(), message);
+        assert!(mem.protected_tags(alloc_id).is_empty());
+        let err = mem.try_remove_tag_run_ptr(tag, past_end).unwrap_err();
+        assert_eq!(err.to_string(), message);
+        assert_eq!(mem.try_remove_tag_run_ptr(tag, in_bounds), Ok(true));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid run pointer: alloc 0 run 0: offset 2 + size 4")]
+    fn borrow_out_of_bounds_panics() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = slice(single_run(&mut mem, 4), 2, 4);
+        mem.add_ref(run_ptr, BorrowType::Shared, None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "broken borrow stack invariant")]
+    fn mutation_checks_invariants() {
+        let mut mem = BasicMemory::new();
+        let run_ptr = single_run(&mut mem, 4);
+        let tag = mem.add_ref(run_ptr, BorrowType::Exclusive, None);
+        mem.pointers.remove(&tag);
+        mem.add_ref(run_ptr, BorrowType::Shared, None);
     }
 }
@@ -0,0 +1,146 @@
+use std::{cell::RefCell, rc::Rc};
+
+use abi::size::Size;
+
+use super::{AbstractByte, AllocId, BorrowType, RunPointer, Tag};
+
+/// A logical change made to a BasicMemory, one for each call to a public mutating method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemEvent {
+    Allocate {
+        alloc_id: AllocId,
+        size: Size,
+    },
+    Deallocate(AllocId),
+    Compact,
+    Restore,
+    /// Mutable access to the bytes, which may have been changed in any way
+    BytesMut(RunPointer),
+    Fill {
+        run_ptr: RunPointer,
+        val: AbstractByte,
+    },
+    ForgetValues(RunPointer),
+    #[cfg(test)]
+    WriteBytes {
+        run_ptr: RunPointer,
+        origin: u32,
+    },
+    WriteScalar {
+        run_ptr: RunPointer,
+        val: u128,
+    },
+    SetProvenance {
+        run_ptr: RunPointer,
+        tag: Tag,
+    },
+    Copy {
+        dst: RunPointer,
+        src: RunPointer,
+    },
+    FreshTag(Tag),
+    SetParentTag {
+        child: Tag,
+        parent: Tag,
+    },
+    AddRef {
+        run_ptr: RunPointer,
+        borrow_type: BorrowType,
+        tag: Tag,
+    },
+    RemoveTag(Tag),
+    RemoveTagsAbove {
+        tag: Tag,
+        run_ptr: RunPointer,
+    },
+    RemoveTagRunPtr {
+        tag: Tag,
+        run_ptr: RunPointer,
+    },
+    Protect {
+        run_ptr: RunPointer,
+        tag: Tag,
+    },
+    #[cfg(test)]
+    Unprotect {
+        run_ptr: RunPointer,
+        tag: Tag,
+    },
+    ProtectTag(Tag),
+    UnprotectTag(Tag),
+    WriteWith {
+        run_ptr: RunPointer,
+        tag: Tag,
+    },
+    ReadWith {
+        run_ptr: RunPointer,
+        tag: Tag,
+    },
+    #[cfg(test)]
+    Expose {
+        run_ptr: RunPointer,
+        tag: Tag,
+    },
+}
+
+/// Receives the events of a memory in the order they happen
+pub trait TraceSink {
+    fn record(&mut self, event: MemEvent);
+}
+
+/// Discards all events
+struct NoTrace;
+
+impl TraceSink for NoTrace {
+    fn record(&mut self, _: MemEvent) {}
+}
+
+/// Logs every event at trace level
+pub struct LogTrace;
+
+impl TraceSink for LogTrace {
+    fn record(&mut self, event: MemEvent) {
+        log::trace!(target: "generate::mem", "{event:?}");
+    }
+}
+
+/// Collects events into a list shared by all its clones, so it can still be read
+/// after being handed to a memory
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct VecTrace(Rc<RefCell<Vec<MemEvent>>>);
+
+#[cfg(test)]
+impl VecTrace {
+    pub fn events(&self) -> Vec<MemEvent> {
+        self.0.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl TraceSink for VecTrace {
+    fn record(&mut self, event: MemEvent) {
+        self.0.borrow_mut().push(event);
+    }
+}
+
+/// The sink a memory reports to. Clones and joins of the memory, such as snapshots,
+/// report to the same sink
+#[derive(Clone)]
+pub(super) struct Tracer(Rc<RefCell<Box<dyn TraceSink>>>);
+
+impl Tracer {
+    pub(super) fn new(sink: Box<dyn TraceSink>) -> Self {
+        Self(Rc::new(RefCell::new(sink)))
+    }
+
+    pub(super) fn record(&mut self, event: MemEvent) {
+        self.0.borrow_mut().record(event);
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new(Box::new(NoTrace))
+    }
+}
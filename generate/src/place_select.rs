@@ -9,7 +9,7 @@ use rand_distr::WeightedIndex;
 
 use crate::{
     mem::BasicMemory,
-    ptable::{PlaceIndex, PlacePath, PlaceTable, ToPlaceIndex},
+    ptable::{InitState, PlaceIndex, PlacePath, PlaceTable, ToPlaceIndex},
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -20,8 +20,10 @@ enum PlaceUsage {
     Pointee,
     Argument,
     KnownVal,
+    KnownDiscriminant,
     NonZero,
     Offsetee,
+    Len,
 }
 
 #[derive(Clone)]
@@ -32,6 +34,8 @@ pub struct PlaceSelector {
     refed: Vec<PlaceIndex>,
     size: Option<Size>,
     allow_uninit: bool,
+    init_state: Option<InitState>,
+    active_union_fields: bool,
     usage: PlaceUsage,
     tcx: Rc<TyCtxt>,
 }
@@ -39,6 +43,7 @@ pub struct PlaceSelector {
 pub type Weight = usize;
 
 const RET_LHS_WEIGH_FACTOR: Weight = 2;
+const SINK_LHS_WEIGHT_FACTOR: Weight = 2;
 const UNINIT_WEIGHT_FACTOR: Weight = 2;
 const DEREF_WEIGHT_FACTOR: Weight = 2;
 const LIT_ARG_WEIGHT_FACTOR: Weight = 2;
@@ -46,6 +51,8 @@ const PTR_ARG_WEIGHT_FACTOR: Weight = 2;
 const REF_ARG_WEIGHT_FACTOR: Weight = 9999;
 const OFFSETTED_PTR_WEIGHT_FACTOR: Weight = 10;
 const ROUNDTRIPPED_PTR_WEIGHT_FACTOR: Weight = 100;
+const LOCAL_WEIGHT_FACTOR: Weight = 2;
+const SHALLOW_DEREF_WEIGHT_FACTOR: Weight = 4;
 
 impl PlaceSelector {
     pub fn for_pointee(tcx: Rc<TyCtxt>) -> Self {
@@ -63,6 +70,8 @@ impl PlaceSelector {
             usage: PlaceUsage::Operand,
             exclusions: vec![],
             allow_uninit: false,
+            init_state: None,
+            active_union_fields: false,
             tcx,
             moved: vec![],
             refed: vec![],
@@ -98,6 +107,13 @@ impl PlaceSelector {
         }
     }
 
+    pub fn for_known_discriminant(tcx: Rc<TyCtxt>) -> Self {
+        Self {
+            usage: PlaceUsage::KnownDiscriminant,
+            ..Self::for_operand(tcx)
+        }
+    }
+
     pub fn for_non_zero(tcx: Rc<TyCtxt>) -> Self {
         Self {
             usage: PlaceUsage::NonZero,
@@ -112,6 +128,13 @@ impl PlaceSelector {
         }
     }
 
+    pub fn for_len(tcx: Rc<TyCtxt>) -> Self {
+        Self {
+            usage: PlaceUsage::Len,
+            ..Self::for_operand(tcx)
+        }
+    }
+
     pub fn of_ty(self, ty: TyId) -> Self {
         let tys = Some(vec![ty]);
         Self { tys, ..self }
@@ -129,6 +152,24 @@ impl PlaceSelector {
         }
     }
 
+    /// Only places exactly this much init, whether or not the usage would allow uninit places
+    pub fn with_init_state(self, state: InitState) -> Self {
+        Self {
+            init_state: Some(state),
+            allow_uninit: true,
+            ..self
+        }
+    }
+
+    /// Only places reached through the field last written of every union on the way. The bytes
+    /// of other fields may have uninit tails or hold invalid values for their types
+    pub fn in_active_union_fields(self) -> Self {
+        Self {
+            active_union_fields: true,
+            ..self
+        }
+    }
+
     pub fn except(self, exclude: &Place) -> Self {
         let mut exclusions = self.exclusions;
         // TODO: More granular place discrimination
@@ -154,7 +195,8 @@ impl PlaceSelector {
         let exclusion_indicies: Vec<PlaceIndex> = self
             .exclusions
             .iter()
-            .map(|place| place.to_place_index(pt).expect("excluded place exists"))
+            // A place which no longer resolves names nothing that could be selected
+            .filter_map(|place| place.to_place_index(pt))
             .chain(pt.return_dest_stack()) // Don't touch anything that overlaps with any RET in the stack
             .chain(pt.moved_in_args_stack()) // Don't touch anything that overlaps with moved in args in the stack
             .collect();
@@ -168,7 +210,10 @@ impl PlaceSelector {
             .iter()
             .map(|place| place.to_place_index(pt).expect("place exists"))
             .collect();
-        pt.reachable_nodes().filter(move |ppath| {
+        let writes_through = matches!(self.usage, PlaceUsage::LHS | PlaceUsage::SetDiscriminant);
+        // Only the places on the way to those of the right type are walked
+        let reachable = pt.reachable_nodes_leading_to(self.tys.as_deref(), writes_through);
+        reachable.filter(move |ppath| {
             let index = ppath.target_index();
 
             // Well-typedness
@@ -178,6 +223,16 @@ impl PlaceSelector {
                 return false;
             }
 
+            // Unsized places can only be borrowed
+            if pt.ty(index).is_unsized(&self.tcx) && self.usage != PlaceUsage::Pointee {
+                return false;
+            }
+
+            // The length is read from the metadata of a pointer to a slice
+            if self.usage == PlaceUsage::Len && pt.pointee_len(index).is_none() {
+                return false;
+            }
+
             // Liveness
             if !pt.is_place_live(index) {
                 return false;
@@ -187,20 +242,38 @@ impl PlaceSelector {
             if !self.allow_uninit && !pt.is_place_init(index) {
                 return false;
             };
+            if self
+                .init_state
+                .is_some_and(|state| pt.init_state(index) != state)
+            {
+                return false;
+            }
+
+            if self.active_union_fields && ppath.through_inactive_union_field(pt) {
+                return false;
+            }
 
             // Known val
-            if self.usage == PlaceUsage::KnownVal && pt.known_val(index).is_none() {
+            if self.usage == PlaceUsage::KnownVal
+                && pt.read_literal(index).is_none()
+                && pt.composite_known_val(index).is_none()
+            {
+                return false;
+            }
+
+            if self.usage == PlaceUsage::KnownDiscriminant && pt.known_discriminant(index).is_none()
+            {
                 return false;
             }
 
             if self.usage == PlaceUsage::NonZero {
-                let Some(known_val) = pt.known_val(index) else {
+                let Some(known_val) = pt.read_literal(index) else {
                     return false;
                 };
                 match known_val {
-                    Literal::Uint(v, _) if *v != 0 => {}
-                    Literal::Int(v, _) if *v != 0 => {}
-                    Literal::Float(v, _) if *v != 0. => {}
+                    Literal::Uint(v, _) if v != 0 => {}
+                    Literal::Int(v, _) if v != 0 => {}
+                    Literal::Float(v, _) if v != 0. => {}
                     _ => return false,
                 }
             }
@@ -218,18 +291,28 @@ impl PlaceSelector {
                 return false;
             }
 
-            // We assume that if path has a deref, then the source is the pointer
+            // Writes and moves must not pop the borrow of a protected argument
+            let writes = match self.usage {
+                PlaceUsage::LHS | PlaceUsage::SetDiscriminant => true,
+                PlaceUsage::Operand | PlaceUsage::Argument => !pt.ty(index).is_copy(&self.tcx),
+                _ => false,
+            };
+            if writes && pt.write_pops_protected(index) {
+                return false;
+            }
+
             if ppath.projections(pt).any(|proj| proj.is_deref()) {
+                let pointer = ppath.access_source(pt);
                 match self.usage {
                     // writes
                     PlaceUsage::LHS | PlaceUsage::SetDiscriminant => {
-                        if !pt.can_write_through(ppath.source(), index) {
+                        if !pt.can_write_through(pointer, index) {
                             return false;
                         }
                     }
                     // reads
                     _ => {
-                        if !pt.can_read_through(ppath.source(), index) {
+                        if !pt.can_read_through(pointer, index) {
                             return false;
                         }
                     }
@@ -250,7 +333,7 @@ impl PlaceSelector {
                 if !pt.ty(index).is_copy(&self.tcx) {
                     // If this is a type that must be moved, then we must be able to write through
                     // the chosen projection,
-                    if !pt.can_write_through(ppath.source(), index) {
+                    if !pt.can_write_through(ppath.access_source(pt), index) {
                         return false;
                     }
                     // and it must not be referenced by an already-picked reference
@@ -284,7 +367,9 @@ impl PlaceSelector {
                             if ty.contains(&tcx, |tcx, ty| ty.is_raw_ptr(tcx)) {
                                 weight *= PTR_ARG_WEIGHT_FACTOR;
                             }
-                            if pt.known_val(index).is_some() {
+                            if pt.read_literal(index).is_some()
+                                || pt.composite_known_val(index).is_some()
+                            {
                                 weight *= LIT_ARG_WEIGHT_FACTOR;
                             }
                             // Encourage isize for pointer offset
@@ -306,6 +391,10 @@ impl PlaceSelector {
                                 weight *= RET_LHS_WEIGH_FACTOR;
                             }
                             let target = ppath.target_index();
+                            // Sinks themselves are return places, weighted above
+                            if pt.distance_to_sink(target).is_some_and(|d| d > 0) {
+                                weight *= SINK_LHS_WEIGHT_FACTOR;
+                            }
                             if pt.ty(target).is_raw_ptr(&tcx) && pt.get_offset(target).is_some() {
                                 weight = 0;
                             }
@@ -313,20 +402,32 @@ impl PlaceSelector {
                         }
                         PlaceUsage::Operand => pt.get_complexity(place),
                         PlaceUsage::Pointee => 1,
-                        PlaceUsage::KnownVal | PlaceUsage::NonZero => pt.get_complexity(place),
-                        PlaceUsage::Offsetee => 1,
+                        PlaceUsage::KnownVal
+                        | PlaceUsage::KnownDiscriminant
+                        | PlaceUsage::NonZero => pt.get_complexity(place),
+                        PlaceUsage::Offsetee | PlaceUsage::Len => 1,
                     };
 
                     if ppath.projections(pt).any(|proj| proj.is_deref()) {
                         weight *= DEREF_WEIGHT_FACTOR;
                     }
 
+                    // A pointer nested in a place adds a path for each place behind it
+                    if !ppath.has_nested_deref(pt) {
+                        weight *= SHALLOW_DEREF_WEIGHT_FACTOR;
+                    }
+
                     if ppath.nodes(pt).any(|place| {
                         pt.ty(place).is_raw_ptr(&tcx) && pt.has_offset_roundtripped(place)
                     }) {
                         weight *= ROUNDTRIPPED_PTR_WEIGHT_FACTOR;
                     }
 
+                    // Statics are reachable from every function, so favour the frame's own locals
+                    if !pt.is_static(ppath.source()) {
+                        weight *= LOCAL_WEIGHT_FACTOR;
+                    }
+
                     (ppath, weight)
                 })
                 .unzip();
@@ -348,7 +449,9 @@ mod tests {
     use std::rc::Rc;
 
     use mir::{
-        syntax::{Local, Place},
+        syntax::{
+            Body, FieldIdx, IntTy, Literal, Local, Mutability, Place, ProjectionElem, TyKind,
+        },
         tyctxt::TyCtxt,
     };
     use rand::{
@@ -359,17 +462,17 @@ mod tests {
     use test::Bencher;
 
     use crate::{
-        ptable::PlaceTable,
+        ptable::{InitState, PlaceTable, ToPlaceIndex},
         ty::{seed_tys, TySelect},
     };
 
     use super::PlaceSelector;
 
-    fn build_pt(rng: &mut impl Rng) -> (PlaceTable, Rc<TyCtxt>) {
+    fn build_pt(rng: &mut impl Rng, locals: usize) -> (PlaceTable, Rc<TyCtxt>) {
         let tcx = Rc::new(seed_tys(rng));
         let mut pt = PlaceTable::new(tcx.clone());
         let ty_weights = TySelect::new(&tcx);
-        for i in 0..=32 {
+        for i in 0..=locals {
             let pidx = pt.allocate_local(Local::new(i), ty_weights.choose_ty(rng, &tcx));
             if i % 2 == 0 {
                 pt.mark_place_init(pidx);
@@ -381,7 +484,7 @@ mod tests {
     #[bench]
     fn bench_select(b: &mut Bencher) {
         let mut rng = SmallRng::seed_from_u64(0);
-        let (pt, tcx) = build_pt(&mut rng);
+        let (pt, tcx) = build_pt(&mut rng, 32);
 
         b.iter(|| {
             PlaceSelector::for_lhs(tcx.clone())
@@ -395,7 +498,7 @@ mod tests {
     #[bench]
     fn bench_materialise_into_vec(b: &mut Bencher) {
         let mut rng = SmallRng::seed_from_u64(0);
-        let (pt, tcx) = build_pt(&mut rng);
+        let (pt, tcx) = build_pt(&mut rng, 32);
 
         b.iter(|| {
             let places: Vec<Place> = PlaceSelector::for_lhs(tcx.clone())
@@ -409,4 +512,200 @@ mod tests {
                 .expect("places not empty");
         })
     }
+
+    // The selection of places of a type out of a few thousand places, through nodes_of_ty
+    #[bench]
+    fn bench_select_of_ty(b: &mut Bencher) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (pt, tcx) = build_pt(&mut rng, 512);
+
+        b.iter(|| {
+            PlaceSelector::for_operand(tcx.clone())
+                .of_ty(TyCtxt::I32)
+                .into_iter_place(&pt)
+                .count()
+        })
+    }
+
+    // The same selection, by checking the type of every reachable place
+    #[bench]
+    fn bench_select_of_ty_by_scan(b: &mut Bencher) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (pt, tcx) = build_pt(&mut rng, 512);
+
+        b.iter(|| {
+            PlaceSelector::for_operand(tcx.clone())
+                .into_iter_place(&pt)
+                .filter(|place| pt.ty(place.to_place_index(&pt).unwrap()) == TyCtxt::I32)
+                .count()
+        })
+    }
+
+    #[test]
+    fn of_ty_selects_as_scan() {
+        for seed in 0..8 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            let (pt, tcx) = build_pt(&mut rng, 64);
+            for ty in [TyCtxt::I32, TyCtxt::U8, TyCtxt::BOOL, TyCtxt::F64] {
+                for selector in [
+                    PlaceSelector::for_operand(tcx.clone()),
+                    PlaceSelector::for_lhs(tcx.clone()),
+                ] {
+                    let selected: Vec<Place> =
+                        selector.clone().of_ty(ty).into_iter_place(&pt).collect();
+                    let scanned: Vec<Place> = selector
+                        .into_iter_place(&pt)
+                        .filter(|place| pt.ty(place.to_place_index(&pt).unwrap()) == ty)
+                        .collect();
+                    assert_eq!(selected, scanned);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_writes_through_shared_ref() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_shared = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let t_mut = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
+        let tcx = Rc::new(tcx);
+        let mut pt = PlaceTable::new(tcx.clone());
+
+        // _3 = &_1, _4 = &mut _2
+        for (i, ty) in [
+            (1, TyCtxt::I32),
+            (2, TyCtxt::I32),
+            (3, t_shared),
+            (4, t_mut),
+        ] {
+            pt.allocate_local(Local::new(i), ty);
+            pt.mark_place_init(Local::new(i));
+        }
+        pt.set_ref(Local::new(3), Local::new(1), None);
+        pt.set_ref(Local::new(4), Local::new(2), None);
+        let shared = Place::from_projected(Local::new(3), &[ProjectionElem::Deref]);
+        let exclusive = Place::from_projected(Local::new(4), &[ProjectionElem::Deref]);
+
+        let lhs: Vec<Place> = PlaceSelector::for_lhs(tcx.clone())
+            .into_iter_place(&pt)
+            .collect();
+        assert!(!lhs.contains(&shared));
+        assert!(lhs.contains(&exclusive));
+        assert!(lhs.contains(&Place::from_local(Local::new(1))));
+
+        let operands: Vec<Place> = PlaceSelector::for_operand(tcx.clone())
+            .into_iter_place(&pt)
+            .collect();
+        assert!(operands.contains(&shared));
+        assert!(operands.contains(&exclusive));
+    }
+
+    #[test]
+    fn nested_deref_selectable() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_shared = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let t_mut = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
+        let t_pair = tcx.push(TyKind::Tuple(vec![t_shared, t_mut]));
+        let tcx = Rc::new(tcx);
+        let mut pt = PlaceTable::new(tcx.clone()).with_nested_derefs();
+
+        // _3 = (&_1, &mut _2)
+        for (i, ty) in [(1, TyCtxt::I32), (2, TyCtxt::I32), (3, t_pair)] {
+            pt.allocate_local(Local::new(i), ty);
+            pt.mark_place_init(Local::new(i));
+        }
+        let field = |i| {
+            Place::from_projected(
+                Local::new(3),
+                &[ProjectionElem::TupleField(FieldIdx::new(i))],
+            )
+        };
+        pt.set_ref(field(0), Local::new(1), None);
+        pt.set_ref(field(1), Local::new(2), None);
+        let deref = |i| {
+            let mut place = field(i);
+            place.project(ProjectionElem::Deref);
+            place
+        };
+
+        let lhs: Vec<Place> = PlaceSelector::for_lhs(tcx.clone())
+            .into_iter_place(&pt)
+            .collect();
+        assert!(!lhs.contains(&deref(0)));
+        assert!(lhs.contains(&deref(1)));
+
+        let operands: Vec<Place> = PlaceSelector::for_operand(tcx.clone())
+            .into_iter_place(&pt)
+            .collect();
+        assert!(operands.contains(&deref(0)));
+        assert!(operands.contains(&deref(1)));
+
+        // Accesses go through the tag of the field holding the reference
+        let ppath = pt
+            .reachable_nodes()
+            .find(|ppath| ppath.to_place(&pt) == deref(1))
+            .unwrap();
+        assert!(ppath.has_nested_deref(&pt));
+        assert_eq!(
+            ppath.access_source(&pt),
+            field(1).to_place_index(&pt).unwrap()
+        );
+    }
+
+    #[test]
+    fn select_by_init_state() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_pair = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I64]));
+        let tcx = Rc::new(tcx);
+        let mut pt = PlaceTable::new(tcx.clone());
+
+        // _1.0 is init, _1.1 is not
+        pt.allocate_local(Local::new(1), t_pair);
+        let first = Place::from_projected(
+            Local::new(1),
+            &[ProjectionElem::TupleField(FieldIdx::new(0))],
+        );
+        pt.mark_place_init(&first);
+
+        let select = |state| -> Vec<Place> {
+            PlaceSelector::for_operand(tcx.clone())
+                .with_init_state(state)
+                .into_iter_place(&pt)
+                .collect()
+        };
+        assert_eq!(
+            select(InitState::Partial),
+            [Place::from_local(Local::new(1))]
+        );
+        assert_eq!(select(InitState::Full), [first]);
+        assert_eq!(select(InitState::Uninit).len(), 1);
+    }
+
+    #[test]
+    fn static_selectable_through_pointers() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let tcx = Rc::new(tcx);
+        let mut pt = PlaceTable::new(tcx.clone());
+
+        let s = pt.allocate_static(TyCtxt::I32);
+        pt.mark_place_init(s);
+        pt.assign_literal(s, Some(Literal::Int(7, IntTy::I32)));
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let r = pt.allocate_local(Local::new(1), t_ref);
+        let selected = |pt: &PlaceTable| -> Vec<Place> {
+            PlaceSelector::for_operand(tcx.clone())
+                .of_ty(TyCtxt::I32)
+                .into_iter_place(pt)
+                .collect()
+        };
+        // Statics can't be named, so they aren't places of their own
+        assert!(selected(&pt).is_empty());
+
+        pt.mark_place_init(r);
+        pt.set_ref(r, s, None);
+        let mut deref = Place::from_local(Local::new(1));
+        deref.project(ProjectionElem::Deref);
+        assert_eq!(selected(&pt), [deref]);
+    }
 }
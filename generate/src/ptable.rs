@@ -1,26 +1,34 @@
 use std::{
-    collections::{BTreeSet, BinaryHeap, HashMap, HashSet},
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
     iter,
     rc::Rc,
     vec,
 };
 
+use abi::size::Size;
 use bimap::BiHashMap;
 use index_vec::IndexVec;
 use mir::{
+    serialize::Serialize,
     syntax::{
-        Body, FieldIdx, Literal, Local, Mutability, Operand, Place, ProjectionElem, Rvalue, TyId,
-        TyKind, UintTy, VariantIdx,
+        BinOp, Body, FieldIdx, FloatTy, Function, IntTy, Literal, Local, LocalDecls, Mutability,
+        Operand, Place, ProjectionElem, Rvalue, TyId, TyKind, UintTy, UnOp, VariantIdx,
     },
     tyctxt::TyCtxt,
 };
 use petgraph::{
     prelude::EdgeIndex, prelude::NodeIndex, stable_graph::StableGraph, visit::EdgeRef, Direction,
 };
+use rand::{seq::SliceRandom, Rng};
 use smallvec::{smallvec, SmallVec};
 
+#[cfg(test)]
+use crate::mem::FreeError;
 use crate::mem::{
-    AbstractByte, AllocId, AllocationBuilder, BasicMemory, BorrowType, RunPointer, Tag,
+    AbstractByte, AllocId, AllocationBuilder, BasicMemory, BorrowType, Layout, MemStats,
+    MemorySnapshot, RunPointer, StackOverflow, Tag, TraceSink,
 };
 
 type PlaceGraph = StableGraph<PlaceNode, ProjectionElem>;
@@ -29,6 +37,7 @@ pub type ProjectionIndex = EdgeIndex;
 pub type Path = SmallVec<[ProjectionIndex; 4]>;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Frame {
     locals: BiHashMap<Local, PlaceIndex>,
 
@@ -39,6 +48,27 @@ struct Frame {
     // while the frame is on stack
     return_destination: PlaceIndex,
     moved_in: SmallVec<[PlaceIndex; 4]>,
+
+    // tags of reference arguments, protected until the frame is popped
+    protected: Vec<Tag>,
+
+    // borrow of the return destination, protected so that nothing can write to it
+    // through a pointer during the call
+    return_tag: Option<Tag>,
+
+    // locals of this frame holding an integer literal, by type and then by its bits. Those
+    // holding a usize index arrays
+    int_candidates: HashMap<TyId, HashMap<u128, SmallVec<[Local; 1]>>>,
+
+    // places of the locals of this frame by type, in increasing order. The statics pseudo-frame
+    // holds those of heap allocations too, which outlive frames as well
+    nodes_by_ty: HashMap<TyId, Vec<PlaceIndex>>,
+
+    // the function called, if the frame was pushed by try_enter_fn
+    function: Option<Function>,
+
+    // arguments of the function, in order, with the mutability of their bindings
+    args: SmallVec<[(Local, Mutability); 4]>,
 }
 
 impl Frame {
@@ -48,6 +78,12 @@ impl Frame {
             locals_ordered: BinaryHeap::new(),
             return_destination: dest,
             moved_in: SmallVec::from_iter(moved_in),
+            protected: vec![],
+            return_tag: None,
+            int_candidates: HashMap::new(),
+            nodes_by_ty: HashMap::new(),
+            function: None,
+            args: SmallVec::new(),
         }
     }
 
@@ -58,6 +94,29 @@ impl Frame {
         self.locals_ordered.push(pidx);
     }
 
+    fn add_nodes(&mut self, nodes: impl IntoIterator<Item = (TyId, PlaceIndex)>) {
+        for (ty, pidx) in nodes {
+            self.nodes_by_ty.entry(ty).or_default().push(pidx);
+        }
+    }
+
+    /// Removes local, whose places are the indices from its own up to last_subfield
+    fn remove_local(&mut self, local: Local, last_subfield: PlaceIndex) -> Option<PlaceIndex> {
+        let (_, pidx) = self.locals.remove_by_left(&local)?;
+        self.locals_ordered.retain(|&p| p != pidx);
+        for nodes in self.nodes_by_ty.values_mut() {
+            nodes.retain(|p| !(pidx..=last_subfield).contains(p));
+        }
+        for locals in self
+            .int_candidates
+            .values_mut()
+            .flat_map(|bucket| bucket.values_mut())
+        {
+            locals.retain(|l| *l != local);
+        }
+        Some(pidx)
+    }
+
     fn get_by_index(&self, pidx: PlaceIndex) -> Option<Local> {
         self.locals.get_by_right(&pidx).copied()
     }
@@ -89,20 +148,149 @@ impl PlaceOperand {
     }
 }
 
+/// A local was refused because its frame already holds max_places_per_frame places
+#[derive(Debug)]
+pub struct FrameFull {
+    pub places: usize,
+}
+
+/// A call was refused because the callstack already holds max_call_depth calls
+#[derive(Debug)]
+pub struct CallTooDeep {
+    pub depth: usize,
+}
+
+/// A saved state of a PlaceTable, see PlaceTable::checkpoint
+#[derive(Clone)]
+pub struct PtCheckpoint {
+    frames: Vec<Frame>,
+    statics: Frame,
+    pointer_tags: IndexVec<Tag, BTreeSet<PlaceIndex>>,
+    places: PlaceGraph,
+    referencers: HashMap<PlaceIndex, SmallVec<[PlaceIndex; 2]>>,
+    flows: BTreeMap<PlaceIndex, BTreeSet<PlaceIndex>>,
+    memory: MemorySnapshot,
+}
+
+/// How much of a place is init
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitState {
+    Uninit,
+    Partial,
+    Full,
+}
+
+/// What a Deref edge dereferences, read off the type of its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerefKind {
+    SharedRef,
+    MutRef,
+    ConstPtr,
+    MutPtr,
+}
+
+impl DerefKind {
+    /// Whether the pointee can never be written through this deref. Raw pointers of either
+    /// mutability are left to the borrow stacks
+    pub fn is_read_only(self) -> bool {
+        self == DerefKind::SharedRef
+    }
+}
+
+/// How a raw pointer was cast from the pointer it was created from. Its pointee keeps the
+/// type of the origin's, which the casts may have changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Derivation {
+    pub origin: PlaceIndex,
+    /// The pointer types cast to, in order
+    pub casts: SmallVec<[TyId; 2]>,
+}
+
+/// How far a raw pointer was offset from its pointee: in elements of its pointee type, which
+/// were elem_size bytes each when it was offset. Pointers to unsized types have no elem_size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PtrOffset {
+    elems: isize,
+    elem_size: Option<Size>,
+}
+
+impl PtrOffset {
+    fn is_zero(self) -> bool {
+        self.elems == 0
+    }
+
+    fn bytes(self) -> Option<isize> {
+        self.elems
+            .checked_mul(isize::try_from(self.elem_size?.bytes()).ok()?)
+    }
+
+    /// This offset followed by offset more elements of elem_size
+    fn offset_by(self, elems: isize, elem_size: Option<Size>) -> Self {
+        if self.elem_size == elem_size {
+            return PtrOffset {
+                elems: self.elems.wrapping_add(elems),
+                elem_size,
+            };
+        }
+        // The pointer was offset as another type, so the sum only has a unit in bytes
+        let bytes = self
+            .bytes()
+            .zip(PtrOffset { elems, elem_size }.bytes())
+            .and_then(|(a, b)| a.checked_add(b));
+        match bytes {
+            Some(bytes) => PtrOffset {
+                elems: bytes,
+                elem_size: Some(Size::from_bytes(1)),
+            },
+            None => PtrOffset {
+                elems: self.elems.wrapping_add(elems),
+                elem_size: None,
+            },
+        }
+    }
+}
+
 /// A data structure keeping track of all _syntactically expressible places_ in the program.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PlaceTable {
     /// The callstack
     frames: Vec<Frame>,
-    index_candidates: HashMap<usize, SmallVec<[Local; 1]>>,
+    /// Pseudo-frame below the callstack holding the statics, which every function can reach
+    statics: Frame,
     pointer_tags: IndexVec<Tag, BTreeSet<PlaceIndex>>,
 
+    /// Nodes are never removed, so indices keep increasing and the subfields of a place are
+    /// the indices right after it, up to its last_subfield
     places: PlaceGraph,
+    /// The pointers with a Deref edge into each place, in the order their edges were added
+    referencers: HashMap<PlaceIndex, SmallVec<[PlaceIndex; 2]>>,
+    /// The places each place was assigned from
+    flows: BTreeMap<PlaceIndex, BTreeSet<PlaceIndex>>,
+    /// How many assignments away from a sink the places flowing into one are. Computed on
+    /// demand, and dropped whenever flows or sinks change
+    #[cfg_attr(feature = "serde", serde(skip))]
+    sink_distances: RefCell<Option<HashMap<PlaceIndex, usize>>>,
     memory: BasicMemory,
     tcx: Rc<TyCtxt>,
+
+    /// The most complexity a place can have
+    complexity_cap: usize,
+    /// The most places try_allocate_local lets a frame hold
+    max_places_per_frame: Option<usize>,
+    /// The most calls try_enter_fn lets the callstack hold on top of fn0
+    max_call_depth: Option<usize>,
+    /// The most Derefs a reachable path may go through
+    max_derefs_per_path: usize,
+    /// Whether reachable paths may dereference pointers other than their source. Runtime MIR
+    /// only allows a Deref as the first projection
+    nested_derefs: bool,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PlaceNode {
     pub ty: TyId,
     alloc_id: AllocId,
@@ -114,14 +302,73 @@ pub struct PlaceNode {
     // Remember the value of simple literals
     val: Option<Literal>,
 
+    // For function pointers, the function pointed to
+    fn_target: Option<Function>,
+
     // Offsetted raw pointer value
-    offset: Option<isize>,
+    offset: Option<PtrOffset>,
+
+    // For raw pointers cast from another pointer, where they were cast from
+    derivation: Option<Derivation>,
 
     // For enum types, the currently active variant
     active_variant: Option<VariantIdx>,
 
+    // For enums with fields, the run holding the discriminant. The run of a field-less enum is
+    // its discriminant
+    discriminant: Option<RunPointer>,
+
+    // For unions, the field last written, whose value the bytes hold
+    active_field: Option<FieldIdx>,
+
     // Tags of raw pointer or references
     tag: Option<Tag>,
+
+    // For pointers, whether the allocation they pointed into has died since
+    dangling: bool,
+
+    // Whether the value of this place is observable at the end of the program
+    sink: bool,
+
+    // Subfields are added right after their parent, so those of this node are the indices up
+    // to this one
+    last_subfield: PlaceIndex,
+
+    // For slices, the array they were unsized from, which holds their elements. The slice of
+    // an array is added right after its elements, as its last subfield
+    slice_of: Option<PlaceIndex>,
+}
+
+impl PlaceNode {
+    /// A place with nothing known about it, to be added to the graph at index
+    fn new(ty: TyId, alloc_id: AllocId, run_ptr: Option<RunPointer>, index: PlaceIndex) -> Self {
+        PlaceNode {
+            ty,
+            alloc_id,
+            complexity: 0,
+            run_ptr,
+            val: None,
+            fn_target: None,
+            offset: None,
+            derivation: None,
+            active_variant: None,
+            discriminant: None,
+            active_field: None,
+            tag: None,
+            dangling: false,
+            sink: false,
+            last_subfield: index,
+            slice_of: None,
+        }
+    }
+}
+
+/// Size of the place graph and the memory behind it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaceStats {
+    pub memory: MemStats,
+    pub nodes: usize,
+    pub edges: usize,
 }
 
 pub trait ToPlaceIndex {
@@ -156,9 +403,9 @@ where
 }
 
 /// VisitAction specifies what to do next after visiting
-/// each node in {update, visit}_transitive_{sub, super}fields
+/// each node in walk_subtree and update_transitive_{sub, super}fields
 #[derive(PartialEq, Eq, Clone, Copy)]
-enum VisitAction {
+pub enum VisitAction {
     // Keep going in the current branch
     Continue,
     // Stop going in the current branch, but keep going in others
@@ -174,12 +421,181 @@ impl PlaceTable {
                 /* fn0 dummy */ PlaceIndex::new(usize::MAX),
                 iter::empty(),
             )],
-            index_candidates: HashMap::new(),
+            statics: Frame::new(PlaceIndex::new(usize::MAX), iter::empty()),
             pointer_tags: IndexVec::new(),
             places: StableGraph::default(),
+            referencers: HashMap::new(),
+            flows: BTreeMap::new(),
+            sink_distances: RefCell::new(None),
             memory: BasicMemory::new(),
             tcx,
+            complexity_cap: 100,
+            max_places_per_frame: None,
+            max_call_depth: None,
+            max_derefs_per_path: 1,
+            nested_derefs: false,
+        }
+    }
+
+    pub fn with_stack_limit(mut self, limit: Size) -> Self {
+        self.memory = self.memory.with_stack_limit(limit);
+        self
+    }
+
+    pub fn with_complexity_cap(mut self, cap: usize) -> Self {
+        self.complexity_cap = cap;
+        self
+    }
+
+    pub fn with_max_places_per_frame(mut self, max: usize) -> Self {
+        self.max_places_per_frame = Some(max);
+        self
+    }
+
+    pub fn with_max_call_depth(mut self, max: usize) -> Self {
+        self.max_call_depth = Some(max);
+        self
+    }
+
+    /// Reports every change to the memory to sink
+    pub fn with_mem_tracer(mut self, sink: Box<dyn TraceSink>) -> Self {
+        self.memory.set_tracer(sink);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_max_derefs_per_path(mut self, max: usize) -> Self {
+        self.max_derefs_per_path = max;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_nested_derefs(mut self) -> Self {
+        self.nested_derefs = true;
+        self
+    }
+
+    /// Whether locals of tys can be allocated without exceeding the stack limit.
+    /// Must be checked before allocate_local and enter_fn when a limit is set
+    pub fn can_allocate(&self, tys: impl IntoIterator<Item = TyId>) -> bool {
+        let size = tys
+            .into_iter()
+            .map(|ty| Self::runs_size(ty, &self.tcx))
+            .fold(Size::ZERO, |total, size| total + size);
+        self.memory.fits(size)
+    }
+
+    /// Total size of the runs add_place creates for ty
+    fn runs_size(ty: TyId, tcx: &TyCtxt) -> Size {
+        if let Some(size) = BasicMemory::ty_size(ty, tcx) {
+            return size;
+        }
+        let sum = |tys: &mut dyn Iterator<Item = &TyId>| {
+            tys.map(|ty| Self::runs_size(*ty, tcx))
+                .fold(Size::ZERO, |total, size| total + size)
+        };
+        match ty.kind(tcx) {
+            TyKind::Tuple(elems) => sum(&mut elems.iter()),
+            TyKind::Array(elem, len) => Self::runs_size(*elem, tcx) * *len as u64,
+            // Every variant has runs of its own
+            TyKind::Adt(adt) => sum(&mut adt.variants.iter().flat_map(|var| var.fields.iter())),
+            _ => Size::ZERO,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn stack_usage(&self) -> Size {
+        self.memory.current_usage()
+    }
+
+    pub fn stats(&self) -> PlaceStats {
+        PlaceStats {
+            memory: self.memory.stats(),
+            nodes: self.places.node_count(),
+            edges: self.places.edge_count(),
+        }
+    }
+
+    /// Renders the place graph in Graphviz's DOT format, for debugging. Locals of the current
+    /// frame are bold, places in dead allocations are gray, and Deref edges are dashed. Moved
+    /// places show as uninit
+    #[allow(dead_code)]
+    pub fn to_dot(&self) -> String {
+        use std::fmt::Write;
+
+        use petgraph::visit::IntoEdgeReferences;
+
+        let mut dot = String::from("digraph {\n");
+        for pidx in self.places.node_indices() {
+            let node = &self.places[pidx];
+            let mut label = String::new();
+            let mut attrs = String::new();
+            if let Some(local) = self.current_frame().get_by_index(pidx) {
+                write!(label, "{}: ", local.identifier()).unwrap();
+                attrs.push_str(" style=bold");
+            } else if let Some((depth, local)) = self
+                .frames
+                .iter()
+                .enumerate()
+                .find_map(|(depth, frame)| Some((depth, frame.get_by_index(pidx)?)))
+            {
+                write!(label, "frame {depth} {}: ", local.identifier()).unwrap();
+            } else if let Some(index) = self.statics.get_by_index(pidx) {
+                write!(label, "static {}: ", index.index()).unwrap();
+            }
+            write!(
+                label,
+                "{}\\n{}, complexity {}",
+                node.ty.serialize(&self.tcx),
+                if self.is_place_init(pidx) {
+                    "init"
+                } else {
+                    "uninit"
+                },
+                node.complexity
+            )
+            .unwrap();
+            if let Some(val) = node.val {
+                write!(label, ", val {}", val.serialize(&self.tcx)).unwrap();
+            }
+            if !self.memory.is_live(node.alloc_id) {
+                attrs.push_str(" color=gray fontcolor=gray");
+            }
+            writeln!(
+                dot,
+                "    {} [label=\"{}\"{attrs}]",
+                pidx.index(),
+                label.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        for edge in self.places.edge_references() {
+            let label = match *edge.weight() {
+                ProjectionElem::Deref => "*".to_owned(),
+                ProjectionElem::TupleField(fid) => format!(".{}", fid.index()),
+                ProjectionElem::Field(fid) => format!(".{}", fid.identifier()),
+                ProjectionElem::DowncastField(vid, fid, _) => {
+                    format!(" as {}.{}", vid.identifier(), fid.identifier())
+                }
+                ProjectionElem::Index(local) => format!("[{}]", local.identifier()),
+                ProjectionElem::ConstantIndex { offset } => format!("[{offset}]"),
+                ProjectionElem::Subslice { from, to } => format!("[{from}..{to}]"),
+            };
+            writeln!(
+                dot,
+                "    {} -> {} [label=\"{label}\"{}]",
+                edge.source().index(),
+                edge.target().index(),
+                if edge.weight().is_deref() {
+                    " style=dashed"
+                } else {
+                    ""
+                }
+            )
+            .unwrap();
         }
+        dot.push_str("}\n");
+        dot
     }
 
     fn current_frame_mut(&mut self) -> &mut Frame {
@@ -191,16 +607,37 @@ impl PlaceTable {
     }
 
     pub fn enter_fn0(&mut self, body: &Body) {
-        // Declare return place
-        self.allocate_local(Local::RET, body.return_ty());
+        // Declare return place, which the program outputs
+        let ret = self.allocate_local(Local::RET, body.return_ty());
+        self.mark_sink(ret);
         // Declare args
         body.args_decl_iter().for_each(|(local, decl)| {
             let pidx = self.allocate_local(local, decl.ty);
+            self.current_frame_mut().args.push((local, decl.mutability));
             // encourage use of args
             self.update_complexity(pidx, 5);
         });
     }
 
+    /// The arguments of the current function whose storage is live, in order, with their places
+    #[allow(dead_code)]
+    pub fn current_args(&self) -> impl Iterator<Item = (Local, PlaceIndex)> + '_ {
+        let frame = self.current_frame();
+        frame
+            .args
+            .iter()
+            .filter_map(|&(local, _)| Some((local, frame.get_by_local(local)?)))
+    }
+
+    /// The mutability of the binding of local, if it is an argument of the current function
+    #[allow(dead_code)]
+    pub fn arg_mutability(&self, local: Local) -> Option<Mutability> {
+        self.current_frame()
+            .args
+            .iter()
+            .find_map(|&(arg, mutability)| (arg == local).then_some(mutability))
+    }
+
     /// Returns whether the selection of function call arguments will not cause
     /// UB. Must be called while we are still in the Caller
     pub fn arguments_ok(&self, args: &[Operand], return_dest: &Place) -> bool {
@@ -220,37 +657,31 @@ impl PlaceTable {
             })
             .chain([return_dest])
         {
-            self.visit_transitive_subfields(
-                arg.to_place_index(self).expect("place exists"),
-                |pid| {
-                    let node = &self.places[pid];
-                    if let Some(run_ptr) = node.run_ptr {
-                        invalidated.extend(self.memory.above_first_shared(run_ptr));
-                        VisitAction::Stop
-                    } else {
-                        VisitAction::Continue
-                    }
-                },
-            );
+            self.walk_subtree(arg.to_place_index(self).expect("place exists"), |pid| {
+                let node = &self.places[pid];
+                if let Some(run_ptr) = node.run_ptr {
+                    invalidated.extend(self.memory.above_first_shared(run_ptr));
+                    VisitAction::Stop
+                } else {
+                    VisitAction::Continue
+                }
+            });
         }
         // Find if any references uses an invalidated tag
         for arg in args {
             match arg {
                 Operand::Copy(p) | Operand::Move(p) => {
-                    self.visit_transitive_subfields(
-                        p.to_place_index(self).expect("place exists"),
-                        |pid| {
-                            let node = &self.places[pid];
-                            if node.ty.is_ref(&self.tcx) {
-                                let tag = node.tag.expect("has tag");
-                                if invalidated.contains(&tag) {
-                                    ok = false;
-                                    return VisitAction::ShortCircuit;
-                                }
+                    self.walk_subtree(p.to_place_index(self).expect("place exists"), |pid| {
+                        let node = &self.places[pid];
+                        if node.ty.is_ref(&self.tcx) {
+                            let tag = node.tag.expect("has tag");
+                            if invalidated.contains(&tag) {
+                                ok = false;
+                                return VisitAction::ShortCircuit;
                             }
-                            VisitAction::Continue
-                        },
-                    );
+                        }
+                        VisitAction::Continue
+                    });
                 }
                 _ => {}
             }
@@ -258,6 +689,56 @@ impl PlaceTable {
         ok
     }
 
+    /// Whether the return destination of a call overlaps with an argument place, or with
+    /// anything reachable through the pointers in them. Must be called while we are still in
+    /// the Caller
+    pub fn call_destination_conflicts(&self, dest: &Place, args: &[Operand]) -> bool {
+        let dest = dest.to_place_index(self).expect("place exists");
+        let mut to_visit: Vec<PlaceIndex> = args
+            .iter()
+            .filter_map(Operand::place)
+            .map(|p| p.to_place_index(self).expect("place exists"))
+            .collect();
+        let mut visited = HashSet::new();
+        while let Some(place) = to_visit.pop() {
+            if !visited.insert(place) {
+                continue;
+            }
+            if self.overlap(place, dest) {
+                return true;
+            }
+            // Pointees may hold pointers of their own
+            self.walk_subtree(place, |pid| {
+                if self.ty(pid).is_any_ptr(&self.tcx)
+                    && let Some(pointee) = self.pointee(pid)
+                {
+                    to_visit.push(pointee);
+                }
+                VisitAction::Continue
+            });
+        }
+        false
+    }
+
+    /// enter_fn into callee, unless the callstack already holds max_call_depth calls. The
+    /// frame is counted towards the active frames of callee
+    pub fn try_enter_fn(
+        &mut self,
+        callee: Function,
+        body: &Body,
+        args: &[Operand],
+        return_dest: &Place,
+    ) -> Result<(), CallTooDeep> {
+        if !self.can_call() {
+            return Err(CallTooDeep {
+                depth: self.call_depth(),
+            });
+        }
+        self.enter_fn(body, args, return_dest);
+        self.current_frame_mut().function = Some(callee);
+        Ok(())
+    }
+
     pub fn enter_fn(&mut self, body: &Body, args: &[Operand], return_dest: &Place) {
         // Get the PlaceIndices before frame switch
 
@@ -278,13 +759,15 @@ impl PlaceTable {
 
         // Frame switch
         self.frames.push(Frame::new(return_dest, moved_in));
-        self.index_candidates.clear();
 
-        self.allocate_local(Local::RET, body.return_ty());
+        let ret = self.allocate_local(Local::RET, body.return_ty());
+        self.record_flow(return_dest, &[ret]);
+        let mut ref_args = vec![];
         body.args_decl_iter()
             .zip(args)
             .for_each(|((local, decl), arg)| {
                 let pidx = self.allocate_local(local, decl.ty);
+                self.current_frame_mut().args.push((local, decl.mutability));
 
                 match &arg {
                     PlaceOperand::Copy(source_pidx) | PlaceOperand::Move(source_pidx) => {
@@ -293,19 +776,56 @@ impl PlaceTable {
                             "function arguments must be init: arg {local:?} source {source_pidx:?}"
                         );
                         self.copy_place(pidx, source_pidx);
-                        self.update_transitive_subfields(pidx, |this, node| {
-                            if this.ty(node).is_ref(&this.tcx) {
-                                this.mark_ref_protected(node);
+                        self.record_flow(pidx, &[*source_pidx]);
+                        self.walk_subtree(pidx, |node| {
+                            if self.ty(node).is_ref(&self.tcx) {
+                                ref_args.push(node);
                             }
                             VisitAction::Continue
                         })
                     }
                     PlaceOperand::Constant(lit) => self.assign_literal(pidx, Some(*lit)),
                 }
+                // The source may be a projection, in which case only its subtree is moved out
+                // and the containing local stays live, partially init
                 if let PlaceOperand::Move(source_pidx) = arg {
                     self.mark_place_moved(source_pidx);
                 }
             });
+        // Reference arguments are retagged with a child of the caller's tag, and the children are
+        // protected once all arguments have been moved in
+        for node in ref_args {
+            if let Some(pointee) = self.pointee(node) {
+                // Retagging keeps the pointer's value, only its provenance changes
+                let run_ptr = self.places[node].run_ptr.expect("pointer is a scalar");
+                let bytes = self.memory.bytes(run_ptr).to_vec();
+                self.set_reborrow(node, pointee, node);
+                self.memory.bytes_mut(run_ptr).copy_from_slice(&bytes);
+                let tag = self.places[node].tag.expect("has tag");
+                self.memory
+                    .set_provenance(BasicMemory::ptr_parts(run_ptr).0, tag);
+            }
+            self.mark_ref_protected(node);
+        }
+        self.protect_return_destination(return_dest);
+    }
+
+    /// Pushes a protected borrow onto the return destination, which no pointer holds. Writes
+    /// through any pointer would pop it until the current call returns
+    fn protect_return_destination(&mut self, dest: PlaceIndex) {
+        let tag = self.memory.fresh_tag();
+        let pushed = self.pointer_tags.push(BTreeSet::new());
+        debug_assert_eq!(tag, pushed, "all tags come from memory");
+        self.update_transitive_subfields(dest, |this, place| {
+            if let Some(run) = this.places[place].run_ptr {
+                this.memory.add_ref(run, BorrowType::Exclusive, Some(tag));
+                VisitAction::Stop
+            } else {
+                VisitAction::Continue
+            }
+        });
+        self.memory.protect_tag(tag);
+        self.current_frame_mut().return_tag = Some(tag);
     }
 
     /// Checks if the value in RET would be valid upon return
@@ -322,7 +842,7 @@ impl PlaceTable {
             .collect();
         let mut has_stack_ref = false;
         // Check if it contains any references that will be invalidated upon return
-        self.visit_transitive_subfields(
+        self.walk_subtree(
             Local::RET.to_place_index(self).expect("ret exists"),
             |node| {
                 if self.ty(node).is_ref(&self.tcx) {
@@ -341,49 +861,231 @@ impl PlaceTable {
     }
 
     pub fn exit_fn(&mut self) {
-        // FIXME: this is quite flimsy wrt. statement order
-        let callee_ret = Place::RETURN_SLOT
-            .to_place_index(self)
-            .expect("place exists");
-        // Frame switch
+        // Frame switch. Both ends of the return copy are taken from the callee frame itself,
+        // so nothing below depends on which frame is current. The callee's places stay in
+        // the graph, holding the returned value, until they are deallocated at the end
         let old_frame = self.frames.pop().expect("call stack isn't empty");
-        self.index_candidates.clear(); // Invalidate cache
+        let callee_ret = old_frame
+            .get_by_local(Local::RET)
+            .expect("callee has a return place");
+        let return_dest = old_frame.return_destination;
+
+        // Protectors end with the call, unless an outer call protects the same tag
+        for tag in &old_frame.protected {
+            if !self
+                .frames
+                .iter()
+                .any(|frame| frame.protected.contains(tag))
+            {
+                self.memory.unprotect_tag(*tag);
+            }
+        }
+
+        if let Some(tag) = old_frame.return_tag {
+            self.memory.unprotect_tag(tag);
+            self.memory.remove_tag(tag);
+        }
 
-        // Copy ret
-        self.copy_place(old_frame.return_destination, callee_ret);
+        // Copy ret. Pointers in the returned value keep pointing to the same places, which
+        // are caller-visible unless they belong to the callee
+        self.copy_place_inner(return_dest, callee_ret, false);
 
         // TODO: the following to loops can probably be merged
-        // Remove ref edges into about to be deallocated places (necessary to prevent dangling references)
+        // Remove ref edges into about to be deallocated places (necessary to prevent dangling references).
+        // This includes the edges of pointers returned into the callee's locals
         let mut ref_edges = vec![];
+        let mut dangling = vec![];
+        // as well as edges out of them, so no pointer in a dead place is ever followed
         for pidx in old_frame.locals.right_values() {
-            self.visit_transitive_subfields(*pidx, |node| {
-                ref_edges.extend(self.pointers_to(node).iter().map(|(_, edge)| edge));
+            self.walk_subtree(*pidx, |node| {
+                for (pointer, edge) in self.pointers_to(node) {
+                    ref_edges.push(edge);
+                    dangling.push(pointer);
+                }
+                if self.ty(node).is_any_ptr(&self.tcx)
+                    && let Some(edge) = self.ref_edge(node)
+                {
+                    ref_edges.push(edge);
+                }
                 VisitAction::Continue
             });
         }
+        ref_edges.sort();
+        ref_edges.dedup();
         for edge in ref_edges {
             self.remove_edge(edge);
         }
+        for pointer in dangling {
+            self.places[pointer].dangling = true;
+        }
 
-        // Deallocate places
+        // Deallocate places. Only calls made from this frame could have protected its locals,
+        // and they have all returned
         for pidx in old_frame.locals.right_values() {
-            self.memory.deallocate(self.places[*pidx].alloc_id);
+            let deallocated = self.memory.deallocate(self.places[*pidx].alloc_id);
+            debug_assert!(
+                deallocated.is_ok(),
+                "locals of a returning function aren't protected"
+            );
+        }
+        self.memory.compact();
+    }
+
+    /// Allocates local in the current frame, unless the frame already holds
+    /// max_places_per_frame places
+    pub fn try_allocate_local(&mut self, local: Local, ty: TyId) -> Result<PlaceIndex, FrameFull> {
+        if !self.frame_has_room() {
+            return Err(FrameFull {
+                places: self.frame_place_count(),
+            });
         }
+        Ok(self.allocate_local(local, ty))
     }
 
+    /// Allocates local in the current frame regardless of max_places_per_frame, as the
+    /// return place and arguments of a call must exist
     pub fn allocate_local(&mut self, local: Local, ty: TyId) -> PlaceIndex {
         let mut pidx = Default::default();
-        self.memory.allocate_with_builder(|builder| {
+        self.memory
+            .allocate_with_builder(|builder| {
+                pidx = Self::add_place(&mut self.places, ty, &self.tcx, builder, None);
+            })
+            .unwrap_or_else(|overflow: StackOverflow| {
+                panic!(
+                    "a local of {} bytes doesn't fit in the {} bytes left on the stack",
+                    overflow.requested.bytes(),
+                    overflow.available.bytes()
+                )
+            });
+        let nodes = self.nodes_in(pidx);
+        let frame = self.current_frame_mut();
+        frame.add_local(local, pidx);
+        frame.add_nodes(nodes);
+        pidx
+    }
+
+    /// Allocates a static, which outlives every frame. Statics are numbered in the order
+    /// they are allocated. Program can't name statics yet, so generation doesn't allocate any
+    #[cfg(test)]
+    pub fn allocate_static(&mut self, ty: TyId) -> PlaceIndex {
+        let mut pidx = Default::default();
+        self.memory.allocate_with_builder_static(|builder| {
             pidx = Self::add_place(&mut self.places, ty, &self.tcx, builder, None);
         });
-        self.current_frame_mut().add_local(local, pidx);
+        let index = Local::new(self.statics.locals.len());
+        let nodes = self.nodes_in(pidx);
+        self.statics.add_local(index, pidx);
+        self.statics.add_nodes(nodes);
+        pidx
+    }
+
+    /// The types and indices of the places added for the allocation rooted at pidx
+    fn nodes_in(&self, pidx: PlaceIndex) -> Vec<(TyId, PlaceIndex)> {
+        (pidx.index()..=self.places[pidx].last_subfield.index())
+            .map(|i| {
+                let node = PlaceIndex::new(i);
+                (self.places[node].ty, node)
+            })
+            .collect()
+    }
+
+    /// The places of type ty in the current frame, the statics and the heap, in increasing
+    /// order within each. The places of ty reachable without a Deref are among them, but
+    /// those behind projections which can't be expressed aren't filtered out
+    pub fn nodes_of_ty(&self, ty: TyId) -> impl Iterator<Item = PlaceIndex> + '_ {
+        [self.current_frame(), &self.statics]
+            .into_iter()
+            .filter_map(move |frame| frame.nodes_by_ty.get(&ty))
+            .flatten()
+            .copied()
+    }
+
+    /// Allocates local as a raw pointer of type ty, pointing to a fresh place on the heap.
+    /// The heap place isn't owned by any frame, and lives until free_heap
+    #[cfg(test)]
+    pub fn allocate_heap_local(&mut self, local: Local, ty: TyId) -> PlaceIndex {
+        let TyKind::RawPtr(pointee_ty, _) = *ty.kind(&self.tcx) else {
+            panic!("heap allocations are pointed to by raw pointers");
+        };
+        let pointer = self.allocate_local(local, ty);
+        let mut pidx = Default::default();
+        self.memory.allocate_with_builder_heap(|builder| {
+            pidx = Self::add_place(&mut self.places, pointee_ty, &self.tcx, builder, None);
+        });
+        let nodes = self.nodes_in(pidx);
+        self.statics.add_nodes(nodes);
+        self.mark_place_init(pointer);
+        self.set_ref(pointer, pidx, None);
         pidx
     }
 
-    pub fn deallocate_local(&mut self, local: Local) {
-        // FIXME: should we need to remove local from the frame?
+    /// Frees the heap allocation of p. Pointers into it are dangling afterwards
+    #[cfg(test)]
+    pub fn free_heap(&mut self, p: impl ToPlaceIndex) -> Result<(), FreeError> {
+        let pidx = p.to_place_index(self).expect("place exists");
+        let alloc_id = self.places[pidx].alloc_id;
+        self.memory.free(alloc_id)?;
+        self.remove_dangling_edges(alloc_id);
+        Ok(())
+    }
+
+    pub fn is_static(&self, p: impl ToPlaceIndex) -> bool {
+        let pidx = p.to_place_index(self).expect("place exists");
+        self.memory.is_static(self.places[pidx].alloc_id)
+    }
+
+    /// Whether the storage of local can end, which it can't while a call protects it
+    pub fn can_deallocate(&self, local: Local) -> bool {
+        let pidx = local.to_place_index(self).expect("place exists");
+        self.memory
+            .protected_tags(self.places[pidx].alloc_id)
+            .is_empty()
+    }
+
+    /// Ends the storage of local. Fails with the protected tags if a call protects it
+    /// Once it has ended, local is removed from the frame, so places rooted at it no longer
+    /// resolve and aren't reachable until its storage starts again
+    pub fn deallocate_local(&mut self, local: Local) -> Result<(), Vec<Tag>> {
         let pidx = local.to_place_index(self).expect("place exists");
-        self.memory.deallocate(self.places[pidx].alloc_id);
+        let alloc_id = self.places[pidx].alloc_id;
+        if !self.can_deallocate(local) {
+            return Err(self.memory.protected_tags(alloc_id));
+        }
+
+        // Nothing can follow the pointers in the dead local anymore. Dropping a reference's
+        // edge uninits its bytes, so this is done while they are still live
+        let mut out_edges = vec![];
+        self.walk_subtree(pidx, |node| {
+            if self.ty(node).is_any_ptr(&self.tcx)
+                && let Some(edge) = self.ref_edge(node)
+            {
+                out_edges.push(edge);
+            }
+            VisitAction::Continue
+        });
+        for edge in out_edges {
+            self.remove_edge(edge);
+        }
+        self.memory.deallocate(alloc_id)?;
+        self.remove_dangling_edges(alloc_id);
+
+        let last_subfield = self.places[pidx].last_subfield;
+        self.current_frame_mut().remove_local(local, last_subfield);
+        Ok(())
+    }
+
+    /// Pointers into the dead allocation must not be dereferenced anymore
+    fn remove_dangling_edges(&mut self, alloc_id: AllocId) {
+        for tag in self.memory.dangling_tags(alloc_id) {
+            for pointer in self.pointer_tags[tag].clone() {
+                if self.is_place_live(pointer)
+                    && let Some(edge) = self.ref_edge(pointer)
+                {
+                    self.remove_edge(edge);
+                    self.places[pointer].dangling = true;
+                }
+            }
+        }
     }
 
     fn add_place(
@@ -394,46 +1096,25 @@ impl PlaceTable {
         run_ptr: Option<RunPointer>,
     ) -> PlaceIndex {
         let alloc_id = alloc_builder.alloc_id();
-        let pidx = if run_ptr.is_some() {
-            // If this is called recursively, and our parent (array) already allocated a run
-            places.add_node(PlaceNode {
-                ty,
-                alloc_id,
-                complexity: 0,
-                run_ptr,
-                val: None,
-                offset: None,
-                active_variant: None,
-                tag: None,
-            })
-        } else if let Some(size) = BasicMemory::ty_size(ty, tcx) {
-            let run_and_offset = alloc_builder.new_run(size);
-            places.add_node(PlaceNode {
-                ty,
-                alloc_id,
-                complexity: 0,
-                run_ptr: Some(RunPointer {
-                    alloc_id,
-                    run_and_offset,
-                    size,
-                }),
-                val: None,
-                offset: None,
-                active_variant: None,
-                tag: None,
-            })
-        } else {
-            places.add_node(PlaceNode {
-                ty,
+        if let Some(run_ptr) = run_ptr {
+            debug_assert_eq!(
+                Some(run_ptr.size),
+                Layout::of(ty, tcx).map(|layout| layout.size),
+                "run given for {ty:?} doesn't match its layout"
+            );
+        }
+        // If this is called recursively, and our parent (array) already allocated a run
+        let run_ptr = run_ptr.or_else(|| {
+            BasicMemory::ty_size(ty, tcx).map(|size| RunPointer {
                 alloc_id,
-                complexity: 0,
-                run_ptr: None,
-                val: None,
-                offset: None,
-                active_variant: None,
-                tag: None,
+                run_and_offset: alloc_builder.new_run(size),
+                size,
             })
-        };
+        });
+        let pidx = Self::push_node(
+            places,
+            PlaceNode::new(ty, alloc_id, run_ptr, PlaceIndex::new(places.node_count())),
+        );
         match ty.kind(tcx) {
             TyKind::Tuple(elems) => elems.iter().enumerate().for_each(|(idx, elem)| {
                 let sub_pidx = Self::add_place(places, *elem, tcx, alloc_builder, None);
@@ -444,6 +1125,10 @@ impl PlaceTable {
                 );
             }),
             TyKind::Array(elem_ty, len) => {
+                // Elements are added eagerly rather than on first projection: subtrees must take
+                // up contiguous indices (see last_subfield), and projections only borrow the
+                // table. ARRAY_MAX_LEN keeps the number of element nodes small
+                let mut elems = vec![];
                 for i in 0..*len {
                     let child_run_ptr = if let Some(run_ptr) = places[pidx].run_ptr {
                         let child_size =
@@ -458,6 +1143,14 @@ impl PlaceTable {
                     } else {
                         None
                     };
+                    if let Some(run_ptr) = places[pidx].run_ptr
+                        && let Some(child_run_ptr) = child_run_ptr
+                    {
+                        debug_assert!(
+                            run_ptr.contains(&child_run_ptr),
+                            "element {i} at {child_run_ptr:?} is outside of the array at {run_ptr:?}"
+                        );
+                    }
                     let elem_pidx =
                         Self::add_place(places, *elem_ty, tcx, alloc_builder, child_run_ptr);
                     places.add_edge(
@@ -465,9 +1158,80 @@ impl PlaceTable {
                         elem_pidx,
                         ProjectionElem::ConstantIndex { offset: i as u64 },
                     );
+                    elems.push(elem_pidx);
+                }
+                // Subslices share the array's bytes and elements. There is one for each range
+                // whose array type exists
+                for sub_len in 1..*len {
+                    let Some(sub_ty) = tcx.iter_enumerated().find_map(|(ty, kind)| {
+                        (*kind == TyKind::Array(*elem_ty, sub_len)).then_some(ty)
+                    }) else {
+                        continue;
+                    };
+                    for from in 0..=*len - sub_len {
+                        let sub_run_ptr = places[pidx].run_ptr.map(|run_ptr| {
+                            let elem_size =
+                                BasicMemory::ty_size(*elem_ty, tcx).expect("ty has fixed size");
+                            RunPointer {
+                                alloc_id,
+                                run_and_offset: run_ptr
+                                    .run_and_offset
+                                    .offset((from * elem_size.bytes_usize()) as isize),
+                                size: Size::from_bytes(sub_len * elem_size.bytes_usize()),
+                            }
+                        });
+                        let sub = Self::push_node(
+                            places,
+                            PlaceNode::new(
+                                sub_ty,
+                                alloc_id,
+                                sub_run_ptr,
+                                PlaceIndex::new(places.node_count()),
+                            ),
+                        );
+                        places.add_edge(
+                            pidx,
+                            sub,
+                            ProjectionElem::Subslice {
+                                from: from as u64,
+                                to: (from + sub_len) as u64,
+                            },
+                        );
+                        for (i, &elem) in elems[from..from + sub_len].iter().enumerate() {
+                            places.add_edge(
+                                sub,
+                                elem,
+                                ProjectionElem::ConstantIndex { offset: i as u64 },
+                            );
+                        }
+                    }
+                }
+                // The slice shares the array's bytes and elements, so it has no subfields of its own
+                if let Some(slice_ty) = tcx
+                    .iter_enumerated()
+                    .find_map(|(ty, kind)| (*kind == TyKind::Slice(*elem_ty)).then_some(ty))
+                {
+                    let slice = Self::push_node(
+                        places,
+                        PlaceNode {
+                            slice_of: Some(pidx),
+                            ..PlaceNode::new(
+                                slice_ty,
+                                alloc_id,
+                                places[pidx].run_ptr,
+                                PlaceIndex::new(places.node_count()),
+                            )
+                        },
+                    );
+                    debug_assert_eq!(places[slice].last_subfield, slice);
                 }
             }
             TyKind::Adt(adt) if adt.is_enum() => {
+                if places[pidx].run_ptr.is_none() {
+                    let size = Layout::discriminant_size(adt.variants.len());
+                    let run_and_offset = alloc_builder.new_run(size);
+                    places[pidx].discriminant = Some(alloc_builder.run_ptr(run_and_offset, size));
+                }
                 for (vid, var) in adt.variants.iter_enumerated() {
                     for (fid, ty) in var.fields.iter_enumerated() {
                         let field_pidx = Self::add_place(places, *ty, tcx, alloc_builder, None);
@@ -481,14 +1245,44 @@ impl PlaceTable {
             }
             TyKind::Adt(adt) => {
                 let fields = &adt.variants.first().expect("adt is a struct").fields;
+                // A struct with a run has a fixed layout, fields live inside of their parent's run.
+                // The fields of a union all start at offset 0 and share its bytes
+                let run_ptr = places[pidx].run_ptr;
+                assert!(
+                    run_ptr.is_some() || !tcx.meta(ty).union,
+                    "union {ty:?} has no fixed layout"
+                );
+                let offsets = run_ptr.map(|_| {
+                    Layout::of(ty, tcx)
+                        .expect("ty has fixed layout")
+                        .field_offsets
+                });
                 for (fid, ty) in fields.iter_enumerated() {
-                    let field_pidx = Self::add_place(places, *ty, tcx, alloc_builder, None);
+                    let field_run_ptr = run_ptr.zip(offsets.as_ref()).map(|(run_ptr, offsets)| {
+                        alloc_builder.run_ptr(
+                            run_ptr
+                                .run_and_offset
+                                .offset(offsets[fid.index()].bytes() as isize),
+                            BasicMemory::ty_size(*ty, tcx).expect("ty has fixed size"),
+                        )
+                    });
+                    let field_pidx =
+                        Self::add_place(places, *ty, tcx, alloc_builder, field_run_ptr);
                     places.add_edge(pidx, field_pidx, ProjectionElem::Field(fid));
                 }
             }
             TyKind::RawPtr(..) => { /* pointer has no subfields  */ }
             _ => { /* primitives, no projection */ }
         }
+        places[pidx].last_subfield = PlaceIndex::new(places.node_count() - 1);
+        pidx
+    }
+
+    /// Adds node at the next index, which PlaceNode::new was given as its last_subfield
+    fn push_node(places: &mut PlaceGraph, node: PlaceNode) -> PlaceIndex {
+        let next = PlaceIndex::new(places.node_count());
+        let pidx = places.add_node(node);
+        debug_assert_eq!(pidx, next, "nodes are never removed from the place graph");
         pidx
     }
 
@@ -500,15 +1294,49 @@ impl PlaceTable {
         }
         self.update_complexity(dst, self.places[src].complexity);
         self.assign_literal(dst, None);
+        let dst_is_ptr = self.ty(dst).is_any_ptr(&self.tcx);
+        if dst_is_ptr && let Some(old) = self.ref_edge(dst) {
+            self.remove_overwritten_edge(old);
+        }
         let (dst_node, src_node) = self.places.index_twice_mut(dst, src);
         self.memory.copy(
             dst_node.run_ptr.expect("dst is packed"),
             src_node.run_ptr.expect("src is packed"),
         );
 
-        if dst_node.ty.is_any_ptr(&self.tcx) {
-            todo!()
-        }
+        if dst_is_ptr {
+            self.places[dst].tag = None;
+            self.places[dst].offset = None;
+            self.places[dst].derivation = None;
+            self.places[dst].dangling = false;
+            // The pointer can only be recovered from the tag carried by the bytes
+            let run_ptr = self.places[dst].run_ptr.expect("pointer is a scalar");
+            let (address, _) = BasicMemory::ptr_parts(run_ptr);
+            if let Some(tag) = self.memory.provenance(address)
+                && let Some((pointer, pointee)) = self.provenance_source(tag, dst)
+            {
+                self.set_ref(dst, pointee, Some(pointer));
+                self.places[dst].offset = self.places[pointer].offset;
+            }
+        }
+    }
+
+    /// Finds a pointer with the tag that dst can be reconstructed from, and its pointee.
+    /// The bytes may have come from any pointer with the tag, so all of them must agree
+    /// on the pointee and offset
+    fn provenance_source(&self, tag: Tag, dst: PlaceIndex) -> Option<(PlaceIndex, PlaceIndex)> {
+        let mut pointers = self.pointer_tags.get(tag)?.iter().copied();
+        let pointer = pointers.next()?;
+        let pointee = self.pointee(pointer)?;
+        if self.ty(dst).pointee_ty(&self.tcx) != Some(self.ty(pointee)) {
+            return None;
+        }
+        pointers
+            .all(|other| {
+                self.pointee(other) == Some(pointee)
+                    && self.places[other].offset == self.places[pointer].offset
+            })
+            .then_some((pointer, pointee))
     }
 
     /// Assigns a discriminant to a enum-typed place, and invalidates all variant projections
@@ -519,6 +1347,12 @@ impl PlaceTable {
         let p = p.to_place_index(self).expect("place exists");
         assert!(self.ty(p).kind(&self.tcx).is_enum());
         self.places[p].active_variant = discriminant;
+        if let Some(run_ptr) = self.places[p].discriminant {
+            match discriminant {
+                Some(vid) => self.memory.write_scalar(run_ptr, vid.index() as u128),
+                None => self.memory.fill(run_ptr, AbstractByte::Uninit),
+            }
+        }
 
         // All inactive variant fields are uninit
         let invalidated: Vec<NodeIndex> = self
@@ -532,12 +1366,22 @@ impl PlaceTable {
         }
     }
 
+    /// Clears the active variant of an enum along with its discriminant
+    fn forget_variant(&mut self, pidx: PlaceIndex) {
+        self.places[pidx].active_variant = None;
+        if let Some(run_ptr) = self.places[pidx].discriminant {
+            self.memory.fill(run_ptr, AbstractByte::Uninit);
+        }
+    }
+
     /// Invalidate place marks the place as uninit, it additionally removes any deref edges *into* the place + transitive subplaces
     fn invalidate_place(&mut self, p: impl ToPlaceIndex) {
         let pidx = p.to_place_index(self).unwrap();
 
         self.update_transitive_subfields(pidx, |this, place| {
-            this.places[place].active_variant = None;
+            this.forget_variant(place);
+            this.places[place].val = None;
+            this.places[place].fn_target = None;
             let node = &this.places[place];
             if let Some(run_ptr) = node.run_ptr {
                 this.memory.fill(run_ptr, AbstractByte::Uninit);
@@ -558,6 +1402,125 @@ impl PlaceTable {
     pub fn copy_place(&mut self, dst: impl ToPlaceIndex, src: impl ToPlaceIndex) {
         let dst = dst.to_place_index(self).expect("place exists");
         let src = src.to_place_index(self).expect("place exists");
+        self.copy_place_inner(dst, src, false);
+        debug_assert!(
+            self.init_mirrors(dst, src),
+            "copy of {src:?} to {dst:?} left them with different init"
+        );
+    }
+
+    /// Makes dst init exactly where src is, without copying any values. Whatever becomes init
+    /// in dst holds an unknown value, and whatever becomes uninit is invalidated
+    #[cfg(test)]
+    pub fn copy_init_only(&mut self, dst: impl ToPlaceIndex, src: impl ToPlaceIndex) {
+        let dst = dst.to_place_index(self).expect("place exists");
+        let src = src.to_place_index(self).expect("place exists");
+        assert_eq!(self.ty(dst), self.ty(src));
+        self.copy_init_only_inner(dst, src);
+        debug_assert!(self.init_mirrors(dst, src));
+    }
+
+    #[cfg(test)]
+    fn copy_init_only_inner(&mut self, dst: PlaceIndex, src: PlaceIndex) {
+        if dst == src {
+            return;
+        }
+        // Which variant is active is part of an enum's init
+        if self.ty(dst).kind(&self.tcx).is_enum()
+            && self.places[dst].active_variant != self.places[src].active_variant
+        {
+            self.assign_discriminant(dst, self.places[src].active_variant);
+        }
+        if self.is_place_init(src) {
+            if !self.is_place_init(dst) {
+                self.mark_place_init(dst);
+            }
+            return;
+        }
+        let projs = self.init_projections(src);
+        if projs.is_empty() || self.is_union(dst) {
+            // A leaf or union is mirrored byte by byte
+            self.remove_pointer_edges(dst);
+            self.mark_place_uninit(dst);
+            let (Some(dst_run), Some(src_run)) =
+                (self.places[dst].run_ptr, self.places[src].run_ptr)
+            else {
+                return;
+            };
+            let init: Vec<bool> = self
+                .memory
+                .bytes(src_run)
+                .iter()
+                .map(|b| b.is_init())
+                .collect();
+            for (byte, init) in self.memory.bytes_mut(dst_run).iter_mut().zip(init) {
+                if init {
+                    *byte = AbstractByte::Init(None);
+                }
+            }
+            return;
+        }
+        for proj in projs {
+            let new_dst = self
+                .project_from_node(dst, proj)
+                .expect("projection exists");
+            let new_src = self
+                .project_from_node(src, proj)
+                .expect("projection exists");
+            self.copy_init_only_inner(new_dst, new_src);
+        }
+    }
+
+    /// Whether dst is init wherever src is and nowhere else
+    fn init_mirrors(&self, dst: PlaceIndex, src: PlaceIndex) -> bool {
+        if self.is_place_init(dst) != self.is_place_init(src) {
+            return false;
+        }
+        self.init_projections(src).into_iter().all(|proj| {
+            match (
+                self.project_from_node(dst, proj),
+                self.project_from_node(src, proj),
+            ) {
+                (Some(new_dst), Some(new_src)) => self.init_mirrors(new_dst, new_src),
+                _ => true,
+            }
+        })
+    }
+
+    /// The projections to the fields of p which count towards its init, like in is_place_init
+    fn init_projections(&self, p: PlaceIndex) -> Vec<ProjectionElem> {
+        self.places
+            .edges_directed(p, Direction::Outgoing)
+            .filter(|e| match e.weight() {
+                ProjectionElem::Deref => false,
+                ProjectionElem::DowncastField(vid, ..) => {
+                    self.places[p].active_variant == Some(*vid)
+                }
+                _ => true,
+            })
+            .map(|e| *e.weight())
+            .collect()
+    }
+
+    /// Copies an array into another array of the same type, like `dst.copy_from_slice(&src)`.
+    /// Returns false if the arrays overlap or src isn't fully init, in which case nothing is copied.
+    pub fn bulk_copy(&mut self, dst: impl ToPlaceIndex, src: impl ToPlaceIndex) -> bool {
+        let dst = dst.to_place_index(self).expect("place exists");
+        let src = src.to_place_index(self).expect("place exists");
+        if self.ty(dst) != self.ty(src)
+            || !matches!(self.ty(dst).kind(&self.tcx), TyKind::Array(..))
+        {
+            return false;
+        }
+        if self.overlap(dst, src) || !self.is_place_init(src) {
+            return false;
+        }
+        self.copy_place_inner(dst, src, false);
+        true
+    }
+
+    // If bytes_copied is set, the parent run containing dst has already been copied
+    fn copy_place_inner(&mut self, dst: PlaceIndex, src: PlaceIndex, bytes_copied: bool) {
         if dst == src {
             return;
         }
@@ -568,38 +1531,113 @@ impl PlaceTable {
             self.assign_discriminant(dst, self.places[src].active_variant);
         }
 
-        let (dst_node, src_node) = self.places.index_twice_mut(dst, src);
-        assert_eq!(dst_node.ty, src_node.ty);
+        if self.is_union(dst) {
+            self.copy_union(dst, src);
+            return;
+        }
+
+        assert_eq!(self.places[dst].ty, self.places[src].ty);
 
-        if let Some(run_ptr) = src_node.run_ptr {
+        let mut bytes_copied = bytes_copied;
+        if !bytes_copied && let Some(run_ptr) = self.places[src].run_ptr {
+            // Dropping a reference's edge uninits it, so the old edges of all pointers in
+            // dst go before their bytes are overwritten
+            self.remove_pointer_edges(dst);
             self.memory
-                .copy(dst_node.run_ptr.expect("dst is packed"), run_ptr);
+                .copy(self.places[dst].run_ptr.expect("dst is packed"), run_ptr);
+            bytes_copied = true;
         }
 
-        if dst_node.ty.is_any_ptr(&self.tcx) {
-            if let Some(pointee) = self.pointee(src) {
-                self.set_ref(dst, pointee, Some(src));
-            }
-            if let Some(old) = self.ref_edge(dst) {
-                self.remove_edge(old);
-            }
-            self.places[dst].offset = self.places[src].offset;
+        if self.ty(dst).is_any_ptr(&self.tcx) {
+            self.copy_pointer(dst, src);
+        }
+        if self.ty(dst).is_fn_ptr(&self.tcx) {
+            self.places[dst].fn_target = self.places[src].fn_target;
         }
 
-        let projs: Vec<_> = self
-            .places
-            .edges_directed(dst, Direction::Outgoing)
-            .filter_map(|e| (!e.weight().is_deref()).then_some(e.weight()))
-            .copied()
+        // The elements of subslices are the array's own, which are copied as they are
+        let fields: Vec<_> = self
+            .subfields(dst)
+            .filter(|(proj, _)| !matches!(proj, ProjectionElem::Subslice { .. }))
             .collect();
-        for proj in projs {
-            let new_dst = self
-                .project_from_node(dst, proj)
-                .expect("projection exists");
+        for (proj, new_dst) in fields {
             let new_src = self
                 .project_from_node(src, proj)
                 .expect("projection exists");
-            self.copy_place(new_dst, new_src);
+            self.copy_place_inner(new_dst, new_src, bytes_copied);
+        }
+    }
+
+    /// Removes the Deref edges of all pointers in p
+    fn remove_pointer_edges(&mut self, p: PlaceIndex) {
+        let mut edges = vec![];
+        self.walk_subtree(p, |node| {
+            if self.ty(node).is_any_ptr(&self.tcx)
+                && let Some(edge) = self.ref_edge(node)
+            {
+                edges.push(edge);
+            }
+            VisitAction::Continue
+        });
+        for edge in edges {
+            self.remove_overwritten_edge(edge);
+        }
+    }
+
+    /// Copies what is known about the pointer in src to dst, whose bytes already hold src's:
+    /// its pointee, through the same tag, and its offset
+    fn copy_pointer(&mut self, dst: PlaceIndex, src: PlaceIndex) {
+        if let Some(pointee) = self.pointee(src) {
+            self.set_ref(dst, pointee, Some(src));
+        }
+        let (dst_node, src_node) = self.places.index_twice_mut(dst, src);
+        dst_node.offset = src_node.offset;
+        dst_node.derivation = src_node.derivation.clone();
+        // A dangling pointer has no pointee to copy, but keeps its tag
+        dst_node.dangling = src_node.dangling;
+        if src_node.dangling {
+            dst_node.tag = src_node.tag;
+        }
+    }
+
+    /// Copies a union as raw bytes. Its fields share the bytes, so copying them one by one
+    /// would let a field clobber what was copied through another
+    fn copy_union(&mut self, dst: PlaceIndex, src: PlaceIndex) {
+        // Both have the same type, so their subfields are visited in the same order
+        let pairs: Vec<(PlaceIndex, PlaceIndex)> = self
+            .transitive_subfields(dst)
+            .into_iter()
+            .zip(self.transitive_subfields(src))
+            .collect();
+        // Old edges are dropped before the copy, since dropping a reference's edge uninits it
+        debug_assert!(pairs
+            .iter()
+            .all(|&(dst_field, src_field)| self.ty(dst_field) == self.ty(src_field)));
+        self.remove_pointer_edges(dst);
+        let dst_run = self.places[dst].run_ptr.expect("union has a fixed layout");
+        let src_run = self.places[src].run_ptr.expect("union has a fixed layout");
+        self.memory.copy(dst_run, src_run);
+
+        for (dst_field, src_field) in pairs {
+            let (dst_node, src_node) = self.places.index_twice_mut(dst_field, src_field);
+            dst_node.val = src_node.val;
+            dst_node.fn_target = src_node.fn_target;
+            dst_node.active_variant = src_node.active_variant;
+            dst_node.active_field = src_node.active_field;
+            dst_node.offset = src_node.offset;
+            dst_node.derivation = src_node.derivation.clone();
+            dst_node.dangling = src_node.dangling;
+            if self.ty(dst_field).is_any_ptr(&self.tcx) {
+                // Only a pointer whose bytes weren't overwritten through a sibling still points
+                let run_ptr = self.places[dst_field].run_ptr.expect("pointer is a scalar");
+                let (address, _) = BasicMemory::ptr_parts(run_ptr);
+                if let Some(pointee) = self.pointee(src_field)
+                    && self.places[src_field].tag.is_some()
+                    && self.memory.provenance(address) == self.places[src_field].tag
+                {
+                    self.set_ref(dst_field, pointee, Some(src_field));
+                }
+            }
         }
     }
 
@@ -609,17 +1647,38 @@ impl PlaceTable {
         mut proj: ProjectionElem,
     ) -> Option<PlaceIndex> {
         if let ProjectionElem::Index(local) = proj {
-            let Some(Literal::Uint(i, UintTy::Usize)) = self.known_val(local) else {
-                panic!("projection has a usize knownval");
+            // A place built earlier may index with a local that has since been reassigned,
+            // moved, or isn't in the current frame
+            let index = self.current_frame().get_by_local(local)?;
+            if !self.is_place_init(index) {
+                return None;
+            }
+            let Some(Literal::Uint(i, UintTy::Usize)) = self.places[index].val else {
+                return None;
             };
-            proj = ProjectionElem::ConstantIndex { offset: *i as u64 };
+            proj = ProjectionElem::ConstantIndex { offset: i as u64 };
         }
+        if proj.is_deref() && self.reinterprets(pidx) {
+            return None;
+        }
+        // Indices past the length of a slice have no edge from its array, so they don't resolve
         self.places
-            .edges_directed(pidx, Direction::Outgoing)
+            .edges_directed(self.projection_source(pidx), Direction::Outgoing)
             .find(|edge| edge.weight() == &proj)
             .map(|e| e.target())
     }
 
+    /// The node the projections of pidx start from. A slice is projected through its array
+    fn projection_source(&self, pidx: PlaceIndex) -> PlaceIndex {
+        self.places[pidx].slice_of.unwrap_or(pidx)
+    }
+
+    /// The slice that array unsizes to, if its type exists
+    fn slice_view(&self, array: PlaceIndex) -> Option<PlaceIndex> {
+        let last = self.places[array].last_subfield;
+        (self.places[last].slice_of == Some(array)).then_some(last)
+    }
+
     /// Get PlaceIndex from a Place
     fn get_node(&self, place: &Place) -> Option<PlaceIndex> {
         let mut node = self.current_frame().get_by_local(place.local())?;
@@ -635,57 +1694,134 @@ impl PlaceTable {
         Some(node)
     }
 
-    /// Call update on all transitive superfields of start, *excluding* start
+    /// Call update on all transitive superfields of start, *excluding* start, each once and
+    /// only after all of its subfields on the way
     fn update_transitive_superfields<F>(&mut self, start: PlaceIndex, mut visit: F)
     where
         F: FnMut(&mut Self, PlaceIndex) -> VisitAction,
     {
-        let mut to_visit: Vec<NodeIndex> = self.immediate_superfields(start).collect();
-        while let Some(node) = to_visit.pop() {
-            let todo = visit(self, node);
-            match todo {
-                VisitAction::Continue => to_visit.extend(self.immediate_superfields(node)),
-                // These two should be the same as there is only one branch
-                VisitAction::Stop => {}
-                VisitAction::ShortCircuit => break,
+        if self.superfields_are_chain(start) {
+            // There is only one order
+            let mut node = self.immediate_superfields(start).next();
+            while let Some(current) = node {
+                match visit(self, current) {
+                    VisitAction::Continue => node = self.immediate_superfields(current).next(),
+                    VisitAction::Stop | VisitAction::ShortCircuit => break,
+                }
+            }
+            return;
+        }
+
+        // How many subfields of each superfield are superfields of start as well
+        let mut ready: Vec<NodeIndex> = self.immediate_superfields(start).collect();
+        ready.sort();
+        ready.dedup();
+        let mut collected: HashSet<NodeIndex> = ready.iter().copied().collect();
+        let mut pending: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut to_collect = ready.clone();
+        while let Some(node) = to_collect.pop() {
+            for sup in self.immediate_superfields(node) {
+                *pending.entry(sup).or_default() += 1;
+                if collected.insert(sup) {
+                    to_collect.push(sup);
+                }
+            }
+        }
+        ready.retain(|node| !pending.contains_key(node));
+        let mut reached: HashSet<NodeIndex> = self.immediate_superfields(start).collect();
+        while let Some(node) = ready.pop() {
+            let todo = if reached.contains(&node) {
+                visit(self, node)
+            } else {
+                // Only reachable through places that stopped
+                VisitAction::Stop
+            };
+            if let VisitAction::ShortCircuit = todo {
+                break;
+            }
+            let supers: Vec<NodeIndex> = self.immediate_superfields(node).collect();
+            for sup in supers {
+                if let VisitAction::Continue = todo {
+                    reached.insert(sup);
+                }
+                let count = pending.get_mut(&sup).expect("superfield is collected");
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(sup);
+                }
+            }
+        }
+    }
+
+    /// Whether every transitive superfield of start has at most one superfield itself
+    fn superfields_are_chain(&self, start: PlaceIndex) -> bool {
+        let mut node = start;
+        loop {
+            let mut supers = self.immediate_superfields(node);
+            match (supers.next(), supers.next()) {
+                (None, _) => return true,
+                (Some(sup), None) => node = sup,
+                (Some(_), Some(_)) => return false,
             }
         }
     }
 
-    /// Call visit on all transitive subfields of start, *including* start
+    /// Call visit on all transitive subfields of start, *including* start, each once
     fn update_transitive_subfields<F>(&mut self, start: PlaceIndex, mut visit: F)
     where
         F: FnMut(&mut Self, PlaceIndex) -> VisitAction,
     {
         let mut to_visit = vec![start];
+        let mut visited = HashSet::new();
         while let Some(node) = to_visit.pop() {
             let todo = visit(self, node);
             match todo {
-                VisitAction::Continue => to_visit.extend(self.immediate_subfields(node)),
+                VisitAction::Continue => {
+                    let subs: Vec<NodeIndex> = self.immediate_subfields(node).collect();
+                    to_visit.extend(
+                        subs.into_iter()
+                            .filter(|sub| self.first_visit(*sub, &mut visited)),
+                    );
+                }
                 VisitAction::Stop => {}
                 VisitAction::ShortCircuit => break,
             }
         }
     }
 
-    fn visit_transitive_subfields<F>(&self, start: PlaceIndex, mut visit: F)
+    /// Call visit on all transitive subfields of start, *including* start, each once. Places
+    /// are visited depth-first, each before its subfields, and siblings in the order of
+    /// subfields. A place with more than one superfield is visited under the first of them
+    pub fn walk_subtree<F>(&self, start: impl ToPlaceIndex, mut visit: F)
     where
         F: FnMut(PlaceIndex) -> VisitAction,
     {
+        let start = start.to_place_index(self).expect("place exists");
+        // Subfields come out newest first, so they are popped in the order they were added
         let mut to_visit = vec![start];
+        let mut visited = HashSet::new();
         while let Some(node) = to_visit.pop() {
             let todo = visit(node);
             match todo {
-                VisitAction::Continue => to_visit.extend(self.immediate_subfields(node)),
+                VisitAction::Continue => to_visit.extend(
+                    self.immediate_subfields(node)
+                        .filter(|sub| self.first_visit(*sub, &mut visited)),
+                ),
                 VisitAction::Stop => {}
                 VisitAction::ShortCircuit => break,
             }
         }
     }
 
+    /// Whether a walk down the subfields reaches sub for the first time. Only places with
+    /// more than one superfield can be reached twice, so only those are remembered
+    fn first_visit(&self, sub: PlaceIndex, visited: &mut HashSet<PlaceIndex>) -> bool {
+        self.immediate_superfields(sub).nth(1).is_none() || visited.insert(sub)
+    }
+
     pub fn update_complexity(&mut self, target: impl ToPlaceIndex, new_flow: usize) {
         let target = target.to_place_index(self).expect("place exists");
-        let new_flow = new_flow.min(100);
+        let new_flow = new_flow.min(self.complexity_cap);
 
         // Subplaces' complexity is overwritten as target's new complexity
         self.update_transitive_subfields(target, |this, place| {
@@ -706,29 +1842,186 @@ impl PlaceTable {
         })
     }
 
+    /// Scales the complexity of every place by factor, so that places which haven't been
+    /// written in a while weigh less than recently written ones. Rounds up, so that no
+    /// place's complexity decays to zero
+    pub fn decay_complexity(&mut self, factor: f32) {
+        assert!(
+            (0.0..=1.0).contains(&factor),
+            "decay factor must be in [0, 1]"
+        );
+        // Scaling keeps every place's complexity the max of its subfields'
+        for node in self.places.node_weights_mut() {
+            node.complexity = (node.complexity as f32 * factor).ceil() as usize;
+        }
+    }
+
     pub fn get_complexity(&self, p: impl ToPlaceIndex) -> usize {
         let pidx = p.to_place_index(self).unwrap();
-        let node = &self.places[pidx];
-        if node.ty.is_any_ptr(&self.tcx) {
-            if let Some(pointee) = self.project_from_node(pidx, ProjectionElem::Deref) {
-                self.get_complexity(pointee)
+        if let Some((pointee, _)) = self.resolve_pointee_chain(pidx) {
+            self.places[pointee].complexity
+        } else {
+            // Use the initial complexity
+            self.places[pidx].complexity
+        }
+    }
+
+    /// Marks p and its subfields as observable, like the return place of fn0
+    pub fn mark_sink(&mut self, p: impl ToPlaceIndex) {
+        let p = p.to_place_index(self).expect("place exists");
+        for i in p.index()..=self.places[p].last_subfield.index() {
+            self.places[PlaceIndex::new(i)].sink = true;
+        }
+        *self.sink_distances.get_mut() = None;
+    }
+
+    /// Records that the values of srcs were used to compute the new value of dst
+    pub fn record_flow(&mut self, dst: impl ToPlaceIndex, srcs: &[PlaceIndex]) {
+        let dst = dst.to_place_index(self).expect("place exists");
+        if srcs.is_empty() {
+            return;
+        }
+        self.flows.entry(dst).or_default().extend(srcs);
+        // A new flow only brings its sources closer to the sinks, so the distances are kept
+        // up to date rather than recomputed
+        let Some(mut distances) = self.sink_distances.get_mut().take() else {
+            return;
+        };
+        if let Some(distance) = self.sink_distance_in(&distances, dst) {
+            let mut queue = VecDeque::new();
+            for &src in srcs {
+                if distances.get(&src).is_none_or(|&d| d > distance + 1) {
+                    distances.insert(src, distance + 1);
+                    queue.push_back(src);
+                }
+            }
+            self.relax_sink_distances(&mut distances, queue);
+        }
+        *self.sink_distances.get_mut() = Some(distances);
+    }
+
+    /// The places whose values an assignment of rvalue reads. Borrowing a place doesn't
+    pub fn flow_sources(&self, rvalue: &Rvalue) -> Vec<PlaceIndex> {
+        Self::read_places(rvalue)
+            .into_iter()
+            .map(|place| place.to_place_index(self).expect("place exists"))
+            .collect()
+    }
+
+    /// The places an rvalue reads, as flow_sources
+    pub fn read_places(rvalue: &Rvalue) -> Vec<&Place> {
+        match rvalue {
+            Rvalue::Use(op)
+            | Rvalue::Cast(op, _)
+            | Rvalue::UnaryOp(_, op)
+            | Rvalue::PtrMetadata(op) => op.place().into_iter().collect(),
+            Rvalue::BinaryOp(_, l, r) | Rvalue::CheckedBinaryOp(_, l, r) => {
+                l.place().into_iter().chain(r.place()).collect()
+            }
+            Rvalue::Aggregate(_, ops) => ops.iter().filter_map(Operand::place).collect(),
+            Rvalue::Len(place) | Rvalue::Discriminant(place) => vec![place],
+            Rvalue::AddressOf(..) | Rvalue::Ref(..) => vec![],
+        }
+    }
+
+    /// How many assignments the value of p is from reaching a sink, or None if it never flows
+    /// into one. Writing to p changes the places it is a field of, so the nearest of those counts
+    pub fn distance_to_sink(&self, p: impl ToPlaceIndex) -> Option<usize> {
+        let p = p.to_place_index(self).expect("place exists");
+        let mut distances = self.sink_distances.borrow_mut();
+        let distances = distances.get_or_insert_with(|| self.compute_sink_distances());
+        self.sink_distance_in(distances, p)
+    }
+
+    /// distance_to_sink, with the distances of the places flowing into a sink given
+    fn sink_distance_in(
+        &self,
+        distances: &HashMap<PlaceIndex, usize>,
+        mut node: PlaceIndex,
+    ) -> Option<usize> {
+        let mut nearest = None;
+        loop {
+            let distance = if self.places[node].sink {
+                Some(0)
             } else {
-                // Use the initial complexity
-                node.complexity
+                distances.get(&node).copied()
+            };
+            nearest = nearest.into_iter().chain(distance).min();
+            match self.parent(node) {
+                Some((_, parent)) => node = parent,
+                None => return nearest,
+            }
+        }
+    }
+
+    /// Breadth first from the sinks, against the direction of the flows
+    fn compute_sink_distances(&self) -> HashMap<PlaceIndex, usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        for &dst in self.flows.keys() {
+            if self.places[dst].sink {
+                distances.insert(dst, 0);
+                queue.push_back(dst);
+            }
+        }
+        self.relax_sink_distances(&mut distances, queue);
+        distances
+    }
+
+    /// Brings the sources of the flows into the queued places, whose distances went down, as
+    /// close to the sinks as those allow. A flow into any field of a place is a flow into it
+    fn relax_sink_distances(
+        &self,
+        distances: &mut HashMap<PlaceIndex, usize>,
+        mut queue: VecDeque<PlaceIndex>,
+    ) {
+        while let Some(dst) = queue.pop_front() {
+            let distance = distances[&dst] + 1;
+            let last_subfield = self.places[dst].last_subfield;
+            for srcs in self.flows.range(dst..=last_subfield).map(|(_, srcs)| srcs) {
+                for &src in srcs {
+                    if distances.get(&src).is_none_or(|&d| d > distance) {
+                        distances.insert(src, distance);
+                        queue.push_back(src);
+                    }
+                }
             }
-        } else {
-            node.complexity
         }
     }
 
+    /// Protects the borrow of a reference argument for the duration of the current call
     fn mark_ref_protected(&mut self, p: impl ToPlaceIndex) {
         let p = p.to_place_index(&self).expect("place exists");
         assert!(self.ty(p).is_ref(&self.tcx));
-        let run = self.places[p].run_ptr.expect("has run");
-        self.memory
-            .mark_protected(run, self.places[p].tag.expect("has tag"));
+        let tag = self.places[p].tag.expect("has tag");
+        self.memory.protect_tag(tag);
+        self.current_frame_mut().protected.push(tag);
+    }
+
+    /// Whether writing to p would pop a protected borrow
+    pub fn write_pops_protected(&self, p: impl ToPlaceIndex) -> bool {
+        let p = p.to_place_index(self).expect("place exists");
+        if !self.memory.has_protectors(self.places[p].alloc_id) {
+            return false;
+        }
+        let mut pops = false;
+        self.walk_subtree(p, |node| {
+            if let Some(run) = self.places[node].run_ptr {
+                if self.memory.write_pops_protected(run) {
+                    pops = true;
+                    return VisitAction::ShortCircuit;
+                }
+                VisitAction::Stop
+            } else {
+                VisitAction::Continue
+            }
+        });
+        pops
     }
 
+    /// A moved-from place is left uninit, so assigning to it again makes it usable like any
+    /// other place. Arguments moved into a call stay off limits until it returns, through
+    /// moved_in_args_stack
     pub fn mark_place_moved(&mut self, p: impl ToPlaceIndex) {
         let p = p.to_place_index(&self).expect("place exists");
         self.mark_place_uninit(p);
@@ -738,12 +2031,15 @@ impl PlaceTable {
     pub fn mark_place_uninit(&mut self, p: impl ToPlaceIndex) {
         let pidx = p.to_place_index(self).unwrap();
 
-        // If this is a pointer, we have to remove the Deref edge, but not for other projections
+        // If this is a pointer, we have to remove the Deref edge and its tag, but not for other
+        // projections
         // FIXME: this should be transitive
-        if self.places[pidx].ty.is_any_ptr(&self.tcx)
-            && let Some(old) = self.ref_edge(pidx)
-        {
-            self.remove_edge(old);
+        if self.places[pidx].ty.is_any_ptr(&self.tcx) {
+            if let Some(old) = self.ref_edge(pidx) {
+                self.remove_overwritten_edge(old);
+            }
+            self.places[pidx].tag = None;
+            self.places[pidx].derivation = None;
         }
 
         // If this place is pointed to by a reference, we must remove the Deref edge
@@ -754,34 +2050,55 @@ impl PlaceTable {
             }
         }
 
-        self.update_transitive_subfields(pidx, |this, place| {
-            this.places[place].active_variant = None;
-            let node = &this.places[place];
-            if let Some(run_ptr) = node.run_ptr {
-                this.memory.fill(run_ptr, AbstractByte::Uninit);
-                VisitAction::Stop
-            } else {
-                VisitAction::Continue
-            }
-        });
+        let (fields, runs) = self.subtree_runs(pidx, false);
+        for place in fields {
+            self.forget_variant(place);
+            self.places[place].active_field = None;
+        }
+        for run_ptr in runs {
+            self.memory.fill(run_ptr, AbstractByte::Uninit);
+        }
     }
 
     pub fn mark_place_init(&mut self, p: impl ToPlaceIndex) {
         let pidx = p.to_place_index(self).unwrap();
-        self.update_transitive_subfields(pidx, |this, place| {
-            let node = &this.places[place];
-            if let Some(run_ptr) = node.run_ptr {
-                this.memory.fill(run_ptr, AbstractByte::Init);
-                VisitAction::Stop
-            } else {
-                VisitAction::Continue
+        self.mark_union_fields_written(pidx);
+        // Padding bytes stay uninit, so only the fields are filled
+        let (fields, runs) = self.subtree_runs(pidx, true);
+        for place in fields {
+            // Which field of a union written as a whole holds a valid value is unknown
+            self.places[place].active_field = None;
+        }
+        for run_ptr in runs {
+            self.memory.fill(run_ptr, AbstractByte::Init(None));
+        }
+    }
+
+    /// The places in p's subtree down to the first ones with a run, and those runs. With
+    /// skip_padding, places with padding are walked through to their fields
+    fn subtree_runs(
+        &self,
+        p: PlaceIndex,
+        skip_padding: bool,
+    ) -> (Vec<PlaceIndex>, Vec<RunPointer>) {
+        let mut fields = vec![];
+        let mut runs = vec![];
+        self.walk_subtree(p, |place| {
+            fields.push(place);
+            match self.places[place].run_ptr {
+                Some(run_ptr) if !(skip_padding && self.has_padding(place)) => {
+                    runs.push(run_ptr);
+                    VisitAction::Stop
+                }
+                _ => VisitAction::Continue,
             }
         });
+        (fields, runs)
     }
 
     /// Returns all subfields that are reference type in a Place
     pub fn refs_in(&self, p: impl ToPlaceIndex) -> Vec<PlaceIndex> {
-        self.subfields(p)
+        self.transitive_subfields(p)
             .iter()
             .filter(|pidx| self.ty(pidx).is_ref(&self.tcx))
             .copied()
@@ -792,7 +2109,7 @@ impl PlaceTable {
     pub fn contains_ref_to(&self, r: impl ToPlaceIndex, dest: PlaceIndex) -> bool {
         let r = r.to_place_index(self).expect("place exists");
         let mut contains = false;
-        self.visit_transitive_subfields(r.to_place_index(self).expect("place exists"), |pid| {
+        self.walk_subtree(r, |pid| {
             if self.ty(pid).is_ref(&self.tcx) {
                 if let Some(pointee) = self.pointee(pid)
                     && self.overlap(pointee, dest)
@@ -806,18 +2123,27 @@ impl PlaceTable {
         contains
     }
 
-    /// Returns all pointers to a pointee, and the ref edge
+    /// Returns all pointers to a pointee, and the ref edge. Pointers to the slice of an array
+    /// point to the array too
     fn pointers_to(&self, pointee: PlaceIndex) -> Vec<(NodeIndex, ProjectionIndex)> {
-        self.places
-            .edges_directed(pointee, Direction::Incoming)
-            .filter_map(|edge| {
-                edge.weight()
-                    .is_deref()
-                    .then_some((edge.source(), edge.id()))
+        iter::once(pointee)
+            .chain(self.slice_view(pointee))
+            .flat_map(|pointee| self.referencers_of(pointee))
+            .map(|&pointer| {
+                let edge = self.ref_edge(pointer).expect("referencer has a ref edge");
+                (pointer, edge)
             })
             .collect()
     }
 
+    /// The pointers pointing to exactly p, in the order they were made to point to it
+    pub fn referencers_of(&self, p: impl ToPlaceIndex) -> &[PlaceIndex] {
+        let p = p.to_place_index(self).expect("place exists");
+        self.referencers
+            .get(&p)
+            .map_or(&[], |pointers| pointers.as_slice())
+    }
+
     /// Returns the pointee in pointer -[Deref]-> pointee, if one exists
     fn pointee(&self, pointer: PlaceIndex) -> Option<PlaceIndex> {
         assert!(self.places[pointer].ty.is_any_ptr(&self.tcx));
@@ -827,6 +2153,29 @@ impl PlaceTable {
             .map(|deref| deref.target())
     }
 
+    /// The tag carried by the value of pointer p, with which its borrows are pushed
+    #[allow(dead_code)]
+    pub fn tag_of(&self, p: impl ToPlaceIndex) -> Option<Tag> {
+        let p = p.to_place_index(self).expect("place exists");
+        assert!(self.ty(p).is_any_ptr(&self.tcx));
+        self.places[p].tag
+    }
+
+    /// Follows the Deref edges from p to the first place that isn't a pointer, and returns it
+    /// with the number of edges followed. None if a pointer on the way has no edge or is offset
+    pub fn resolve_pointee_chain(&self, p: impl ToPlaceIndex) -> Option<(PlaceIndex, usize)> {
+        let mut node = p.to_place_index(self).expect("place exists");
+        let mut hops = 0;
+        while self.ty(node).is_any_ptr(&self.tcx) {
+            if self.places[node].offset.is_some_and(|o| !o.is_zero()) || self.reinterprets(node) {
+                return None;
+            }
+            node = self.pointee(node)?;
+            hops += 1;
+        }
+        Some((node, hops))
+    }
+
     /// Returns the edge in pointer -[edge: Deref]-> pointee, if one exists
     fn ref_edge(&self, pointer: PlaceIndex) -> Option<ProjectionIndex> {
         assert!(self.places[pointer].ty.is_any_ptr(&self.tcx));
@@ -836,19 +2185,36 @@ impl PlaceTable {
             .map(|deref| deref.id())
     }
 
-    /// Creates an edge pointer -[Deref]-> pointee
+    /// Creates an edge pointer -[Deref]-> pointee. A copied pointer keeps the derivation of
+    /// the pointer it was copied from
     pub fn set_ref(
         &mut self,
         pointer: impl ToPlaceIndex,
         pointee: impl ToPlaceIndex,
         copied_from: Option<PlaceIndex>,
+    ) {
+        let derivation = copied_from.and_then(|p| self.places[p].derivation.clone());
+        self.set_derived_ref(pointer, pointee, copied_from, derivation);
+    }
+
+    /// Like set_ref, with the derivation of the pointer given. A cast pointer may point to a
+    /// place of another type, as long as its own pointee type fits in the place's layout
+    fn set_derived_ref(
+        &mut self,
+        pointer: impl ToPlaceIndex,
+        pointee: impl ToPlaceIndex,
+        copied_from: Option<PlaceIndex>,
+        derivation: Option<Derivation>,
     ) {
         let pointer = pointer.to_place_index(self).expect("place exists");
         let pointee = pointee.to_place_index(self).expect("place exists");
 
-        assert_eq!(
-            self.places[pointer].ty.pointee_ty(&self.tcx).unwrap(),
-            self.places[pointee].ty
+        self.places[pointer].derivation = derivation;
+        assert!(
+            self.can_point_to(pointer, pointee),
+            "{:?} can't point to a place of type {:?}",
+            self.ty(pointer),
+            self.ty(pointee)
         );
 
         let ref_type = match self.ty(pointer).kind(&self.tcx) {
@@ -859,26 +2225,42 @@ impl PlaceTable {
         };
 
         if let Some(old) = self.ref_edge(pointer) {
-            self.remove_edge(old);
+            // Reborrowing through the pointer itself keeps its tag
+            if copied_from.is_some_and(|p| self.places[p].tag == self.places[pointer].tag) {
+                self.remove_edge(old);
+            } else {
+                self.remove_overwritten_edge(old);
+            }
         }
 
         self.places[pointer].offset = None;
+        self.places[pointer].dangling = false;
         self.update_complexity(pointer, self.places[pointee].complexity);
 
         // Add new ref edge
-        self.places
-            .add_edge(pointer, pointee, ProjectionElem::Deref);
-
+        self.add_ref_edge(pointer, pointee);
+
+        let run_ptr = self.places[pointer].run_ptr.expect("pointer is a scalar");
+        // Only the address carries provenance, not the metadata of a fat pointer
+        let (address, metadata) = BasicMemory::ptr_parts(run_ptr);
+        if let Some(len) = self.slice_len(pointee) {
+            let metadata = metadata.expect("pointer to a slice is fat");
+            self.memory.write_scalar(metadata, len as u128);
+        }
         if let Some(copied_from) = copied_from {
             let tag = self.places[copied_from].tag.expect("has tag");
             self.places[pointer].tag = Some(tag);
             self.pointer_tags[tag].insert(pointer);
+            self.memory.set_provenance(address, tag);
         } else {
-            let tag = self.pointer_tags.push(BTreeSet::from([pointer]));
+            let tag = self.memory.fresh_tag();
+            let pushed = self.pointer_tags.push(BTreeSet::from([pointer]));
+            debug_assert_eq!(tag, pushed, "all tags come from memory");
             self.places[pointer].tag = Some(tag);
+            self.memory.set_provenance(address, tag);
             self.update_transitive_subfields(pointee, |this, place| {
                 if let Some(run) = this.places[place].run_ptr {
-                    this.memory.add_ref(run, ref_type, tag);
+                    this.memory.add_ref(run, ref_type, Some(tag));
                     VisitAction::Stop
                 } else {
                     VisitAction::Continue
@@ -894,6 +2276,120 @@ impl PlaceTable {
         );
     }
 
+    /// Whether pointer can point to pointee: if its pointee type is pointee's, or for a cast
+    /// pointer, if the type is no larger or more aligned than pointee's
+    fn can_point_to(&self, pointer: PlaceIndex, pointee: PlaceIndex) -> bool {
+        let pointee_ty = self
+            .ty(pointer)
+            .pointee_ty(&self.tcx)
+            .expect("pointer has a pointee type");
+        if pointee_ty == self.ty(pointee) {
+            return true;
+        }
+        if self.places[pointer].derivation.is_none() {
+            return false;
+        }
+        match (
+            Layout::of(pointee_ty, &self.tcx),
+            Layout::of(self.ty(pointee), &self.tcx),
+        ) {
+            (Some(cast), Some(place)) => cast.size <= place.size && cast.align <= place.align,
+            _ => false,
+        }
+    }
+
+    /// Whether pointer points to a place of another type than its pointee type. Such a place
+    /// can't be projected to through the pointer, only read as bytes with deref_bytes
+    pub fn reinterprets(&self, pointer: impl ToPlaceIndex) -> bool {
+        let pointer = pointer.to_place_index(self).expect("place exists");
+        self.pointee(pointer)
+            .is_some_and(|pointee| self.ty(pointer).pointee_ty(&self.tcx) != Some(self.ty(pointee)))
+    }
+
+    /// Casts the raw pointer in src to the raw pointer type of dst, like `src as *const U`.
+    /// dst points to src's pointee through src's tag, if the cast type fits in it
+    #[cfg(test)]
+    pub fn cast_ptr(&mut self, dst: impl ToPlaceIndex, src: impl ToPlaceIndex) {
+        let dst = dst.to_place_index(self).expect("place exists");
+        let src = src.to_place_index(self).expect("place exists");
+        assert!(self.ty(src).is_raw_ptr(&self.tcx) && self.ty(dst).is_raw_ptr(&self.tcx));
+        assert_eq!(
+            BasicMemory::ty_size(self.ty(dst), &self.tcx),
+            BasicMemory::ty_size(self.ty(src), &self.tcx),
+            "only pointers of the same width are cast"
+        );
+        self.update_complexity(dst, self.places[src].complexity);
+        self.assign_literal(dst, None);
+        if let Some(old) = self.ref_edge(dst) {
+            self.remove_overwritten_edge(old);
+        }
+        self.memory.copy(
+            self.places[dst].run_ptr.expect("pointer is a scalar"),
+            self.places[src].run_ptr.expect("pointer is a scalar"),
+        );
+
+        let mut derivation = self.places[src].derivation.clone().unwrap_or(Derivation {
+            origin: src,
+            casts: smallvec![],
+        });
+        derivation.casts.push(self.ty(dst));
+        self.places[dst].derivation = Some(derivation.clone());
+        self.places[dst].dangling = self.places[src].dangling;
+        // An offset counts elements of the pointee type, which the cast changes
+        if self.offseted(src) {
+            return;
+        }
+        if let Some(pointee) = self.pointee(src)
+            && self.can_point_to(dst, pointee)
+        {
+            self.set_derived_ref(dst, pointee, Some(src), Some(derivation));
+            self.places[dst].offset = self.places[src].offset;
+        }
+    }
+
+    /// Coerces the pointer to an array in src into a pointer to its slice in dst, like
+    /// `src as &[T]`. The slice is borrowed through src's tag
+    pub fn unsize_ptr(&mut self, dst: impl ToPlaceIndex, src: impl ToPlaceIndex) {
+        let dst = dst.to_place_index(self).expect("place exists");
+        let src = src.to_place_index(self).expect("place exists");
+        self.update_complexity(dst, self.places[src].complexity);
+        self.assign_literal(dst, None);
+
+        let dst_run = self.places[dst].run_ptr.expect("pointer is a scalar");
+        let (address, _) = BasicMemory::ptr_parts(dst_run);
+        self.memory.copy(
+            address,
+            self.places[src].run_ptr.expect("pointer is a scalar"),
+        );
+        if let Some(pointee) = self.pointee(src)
+            && let Some(slice) = self.slice_view(pointee)
+        {
+            self.set_ref(dst, slice, Some(src));
+        } else if let Some(old) = self.ref_edge(dst) {
+            self.remove_overwritten_edge(old);
+        }
+        self.places[dst].offset = self.places[src].offset;
+    }
+
+    /// Like set_ref, for a pointee borrowed through parent, e.g. `&(*parent)`.
+    /// The new tag is recorded as derived from parent's.
+    pub fn set_reborrow(
+        &mut self,
+        pointer: impl ToPlaceIndex,
+        pointee: impl ToPlaceIndex,
+        parent: impl ToPlaceIndex,
+    ) {
+        let pointer = pointer.to_place_index(self).expect("place exists");
+        let parent = parent.to_place_index(self).expect("place exists");
+        // pointer may be parent itself
+        let parent_tag = self.places[parent].tag;
+        self.set_ref(pointer, pointee, None);
+        if let Some(parent_tag) = parent_tag {
+            let tag = self.places[pointer].tag.expect("has tag");
+            self.memory.set_parent_tag(tag, parent_tag);
+        }
+    }
+
     pub fn is_place_live(&self, p: impl ToPlaceIndex) -> bool {
         let Some(pidx) = p.to_place_index(self) else {
             return false;
@@ -903,19 +2399,36 @@ impl PlaceTable {
     }
 
     pub fn is_place_init(&self, p: impl ToPlaceIndex) -> bool {
+        self.init_state(p) == InitState::Full
+    }
+
+    /// How much of a place is init, going by the bytes of its run or else its fields
+    pub fn init_state(&self, p: impl ToPlaceIndex) -> InitState {
         if !self.is_place_live(&p) {
-            return false;
+            return InitState::Uninit;
         }
-        let pidx = p.to_place_index(self).unwrap();
+        // A slice is init as far as its array is
+        let pidx = self.projection_source(p.to_place_index(self).unwrap());
         let node = &self.places[pidx];
-        if let Some(run_ptr) = node.run_ptr {
-            // Leaf
-            self.memory.bytes(run_ptr).iter().all(|b| b.is_init())
+        if node
+            .run_ptr
+            .is_some_and(|run_ptr| run_ptr.size == Size::ZERO)
+        {
+            // A ZST holds no bytes that could be uninit
+            InitState::Full
+        } else if let Some(run_ptr) = node.run_ptr
+            && !self.has_padding(pidx)
+        {
+            // Leaf, or composite without padding
+            bytes_init_state(self.memory.bytes(run_ptr))
         } else if node.ty.kind(&self.tcx).is_enum() && node.active_variant.is_none() {
             // Uninit enum
-            false
+            InitState::Uninit
         } else {
-            self.places
+            // The discriminant of an enum with an active variant is init
+            let mut state = node.active_variant.map(|_| InitState::Full);
+            for sub in self
+                .places
                 .edges_directed(pidx, Direction::Outgoing)
                 .filter_map(|e| {
                     if e.weight().is_deref() {
@@ -929,13 +2442,25 @@ impl PlaceTable {
                         Some(e.target())
                     }
                 })
-                .all(|sub| self.is_place_init(sub))
+            {
+                let sub_state = self.init_state(sub);
+                if sub_state == InitState::Partial || state.is_some_and(|s| s != sub_state) {
+                    return InitState::Partial;
+                }
+                state = Some(sub_state);
+            }
+            state.unwrap_or(InitState::Full)
         }
     }
 
+    // Whether the run of a place contains padding bytes, which are not part of any field
+    fn has_padding(&self, pidx: PlaceIndex) -> bool {
+        Layout::of(self.places[pidx].ty, &self.tcx).is_some_and(|layout| layout.has_padding)
+    }
+
     fn immediate_subfields(&self, pidx: PlaceIndex) -> impl Iterator<Item = PlaceIndex> + '_ {
         self.places
-            .edges_directed(pidx, Direction::Outgoing)
+            .edges_directed(self.projection_source(pidx), Direction::Outgoing)
             .filter_map(|e| (!e.weight().is_deref()).then_some(e.target()))
     }
 
@@ -945,20 +2470,140 @@ impl PlaceTable {
             .filter_map(|e| (!e.weight().is_deref()).then_some(e.source()))
     }
 
+    /// The immediate subfields of p and the projections to them, in the order they were added:
+    /// fields by index, then the subslices of an array. The subfields of a slice are its array's
+    pub fn subfields(
+        &self,
+        p: impl ToPlaceIndex,
+    ) -> impl Iterator<Item = (ProjectionElem, PlaceIndex)> + '_ {
+        let p = p.to_place_index(self).expect("place exists");
+        let subs: SmallVec<[(ProjectionElem, PlaceIndex); 8]> = self
+            .places
+            .edges_directed(self.projection_source(p), Direction::Outgoing)
+            .filter(|e| !e.weight().is_deref())
+            .map(|e| (*e.weight(), e.target()))
+            .collect();
+        subs.into_iter().rev()
+    }
+
+    /// The place p is a field of, and the projection to it. The elements of a subslice are the
+    /// array's
+    pub fn parent(&self, p: impl ToPlaceIndex) -> Option<(ProjectionElem, PlaceIndex)> {
+        let p = p.to_place_index(self).expect("place exists");
+        self.places
+            .edges_directed(p, Direction::Incoming)
+            .filter(|e| !e.weight().is_deref() && !self.is_subslice(e.source()))
+            .map(|e| (*e.weight(), e.source()))
+            .next()
+    }
+
+    pub fn deref_kind(&self, edge: ProjectionIndex) -> DerefKind {
+        assert!(self.places[edge].is_deref(), "edge must be a deref");
+        let (source, _) = self.places.edge_endpoints(edge).expect("edge exists");
+        match self.ty(source).kind(&self.tcx) {
+            TyKind::Ref(_, Mutability::Not) => DerefKind::SharedRef,
+            TyKind::Ref(_, Mutability::Mut) => DerefKind::MutRef,
+            TyKind::RawPtr(_, Mutability::Not) => DerefKind::ConstPtr,
+            TyKind::RawPtr(_, Mutability::Mut) => DerefKind::MutPtr,
+            _ => panic!("source must be of pointer type"),
+        }
+    }
+
     // Returns an iterator over all places reachable from node through projections
-    fn reachable_from_node(&self, pidx: PlaceIndex) -> ProjectionIter<'_> {
-        ProjectionIter::new(self, pidx)
+    #[cfg(test)]
+    fn reachable_from_node(&self, pidx: PlaceIndex, writes: bool) -> ProjectionIter<'_> {
+        ProjectionIter::new(self, pidx, writes, None)
     }
 
     pub fn reachable_nodes(&self) -> impl Iterator<Item = PlacePath> + Clone + '_ {
-        let local_iter = self.current_frame().locals_ordered.iter();
-        local_iter.flat_map(|&pidx| self.reachable_from_node(pidx))
+        self.reachable_nodes_for(false)
+    }
+
+    /// Reachable places, leaving out those behind a read-only deref if they are to be written
+    pub fn reachable_nodes_for(
+        &self,
+        writes: bool,
+    ) -> impl Iterator<Item = PlacePath> + Clone + '_ {
+        self.reachable_nodes_leading_to(None, writes)
+    }
+
+    /// Like reachable_nodes_for, but if tys is given, the places before the first Deref are
+    /// only those with a place of one of tys among their subfields, found through nodes_of_ty.
+    /// Those of tys are reached through the same paths, in the same order
+    pub fn reachable_nodes_leading_to<'pt>(
+        &'pt self,
+        tys: Option<&[TyId]>,
+        writes: bool,
+    ) -> impl Iterator<Item = PlacePath> + Clone + use<'pt> {
+        let leading_to: Option<Rc<[PlaceIndex]>> = tys.map(|tys| {
+            // A pointer in a field may lead to a place of tys anywhere
+            let ptr_tys: Vec<TyId> = if self.nested_derefs {
+                self.current_frame()
+                    .nodes_by_ty
+                    .keys()
+                    .chain(self.statics.nodes_by_ty.keys())
+                    .copied()
+                    .filter(|ty| ty.is_any_ptr(&self.tcx))
+                    .collect()
+            } else {
+                vec![]
+            };
+            let mut nodes: Vec<PlaceIndex> = tys
+                .iter()
+                .chain(&ptr_tys)
+                .flat_map(|ty| self.nodes_of_ty(*ty))
+                .collect();
+            nodes.sort_unstable();
+            nodes.dedup();
+            nodes.into()
+        });
+        // Program can't name statics yet, so they are only reached through pointers
+        self.current_frame()
+            .locals_ordered
+            .iter()
+            .filter({
+                let leading_to = leading_to.clone();
+                move |&&pidx| {
+                    self.ty(pidx).is_any_ptr(&self.tcx)
+                        || self.leads_to(pidx, leading_to.as_deref())
+                }
+            })
+            .flat_map(move |&pidx| ProjectionIter::new(self, pidx, writes, leading_to.clone()))
+    }
+
+    /// Whether p or one of its subfields is among nodes, which are sorted. A slice leads to
+    /// whatever its array does
+    fn leads_to(&self, p: PlaceIndex, nodes: Option<&[PlaceIndex]>) -> bool {
+        let Some(nodes) = nodes else {
+            return true;
+        };
+        let p = self.projection_source(p);
+        let first = nodes.partition_point(|node| *node < p);
+        nodes
+            .get(first)
+            .is_some_and(|node| *node <= self.places[p].last_subfield)
+    }
+
+    /// Reachable raw pointers into allocations that have died. They still hold their address,
+    /// so they can be copied, compared or cast, but never dereferenced
+    #[allow(dead_code)]
+    pub fn dangling_pointers(&self) -> impl Iterator<Item = PlacePath> + '_ {
+        self.reachable_nodes().filter(|ppath| {
+            let pointer = ppath.target_index();
+            let node = &self.places[pointer];
+            if !node.dangling || !node.ty.is_raw_ptr(&self.tcx) || !self.is_place_init(pointer) {
+                return false;
+            }
+            // The bytes must not have been overwritten since
+            let (address, _) = BasicMemory::ptr_parts(node.run_ptr.expect("pointer is a scalar"));
+            node.tag.is_some() && self.memory.provenance(address) == node.tag
+        })
     }
 
     /// Returns all transitive subfields of a place
-    fn subfields(&self, p: impl ToPlaceIndex) -> Vec<PlaceIndex> {
+    fn transitive_subfields(&self, p: impl ToPlaceIndex) -> Vec<PlaceIndex> {
         let mut subs: Vec<PlaceIndex> = vec![];
-        self.visit_transitive_subfields(p.to_place_index(self).expect("place exists"), |sub| {
+        self.walk_subtree(p, |sub| {
             subs.push(sub);
             VisitAction::Continue
         });
@@ -967,8 +2612,9 @@ impl PlaceTable {
 
     /// Whether two places overlap or alias
     pub fn overlap(&self, a: impl ToPlaceIndex, b: impl ToPlaceIndex) -> bool {
-        let a = a.to_place_index(self).expect("place exists");
-        let b = b.to_place_index(self).expect("place exists");
+        // A slice overlaps with exactly what its array overlaps with
+        let a = self.projection_source(a.to_place_index(self).expect("place exists"));
+        let b = self.projection_source(b.to_place_index(self).expect("place exists"));
 
         if a == b {
             return true;
@@ -978,45 +2624,158 @@ impl PlaceTable {
             return false;
         }
 
-        let a_sub: Vec<PlaceIndex> = self.subfields(a);
+        if self.is_subfield(a, b) || self.is_subfield(b, a) {
+            return true;
+        }
+
+        // A subslice overlaps with what its elements overlap with
+        for (sub, other) in [(a, b), (b, a)] {
+            if self.is_subslice(sub) {
+                return self
+                    .immediate_subfields(sub)
+                    .any(|elem| self.overlap(elem, other));
+            }
+        }
+
+        // Fields of a union share bytes but no subfields
+        self.places[a]
+            .run_ptr
+            .zip(self.places[b].run_ptr)
+            .is_some_and(|(a, b)| a.overlap(&b))
+    }
+
+    /// Whether p is a subslice of an array, whose elements are the array's own
+    fn is_subslice(&self, p: PlaceIndex) -> bool {
+        self.places
+            .edges_directed(p, Direction::Incoming)
+            .any(|edge| matches!(edge.weight(), ProjectionElem::Subslice { .. }))
+    }
 
-        let b_sub: Vec<PlaceIndex> = self.subfields(b);
+    /// Whether sub is p or one of its transitive subfields
+    fn is_subfield(&self, sub: PlaceIndex, p: PlaceIndex) -> bool {
+        (p..=self.places[p].last_subfield).contains(&sub)
+    }
 
-        // TODO: should I use a hashmap here?
-        for a_node in a_sub {
-            for b_node in &b_sub {
-                if a_node == *b_node {
-                    return true;
-                }
+    fn is_union(&self, pidx: PlaceIndex) -> bool {
+        let ty = self.places[pidx].ty;
+        matches!(ty.kind(&self.tcx), TyKind::Adt(adt) if !adt.is_enum() && self.tcx.meta(ty).union)
+    }
+
+    /// Makes the fields leading to p active in every enclosing union
+    fn mark_union_fields_written(&mut self, p: PlaceIndex) {
+        let mut node = p;
+        while let Some(edge) = self
+            .places
+            .edges_directed(node, Direction::Incoming)
+            .find(|e| !e.weight().is_deref())
+        {
+            let parent = edge.source();
+            if let ProjectionElem::Field(fid) = *edge.weight()
+                && self.is_union(parent)
+            {
+                self.places[parent].active_field = Some(fid);
             }
+            node = parent;
         }
-        false
     }
 
-    pub fn assign_literal(&mut self, p: impl ToPlaceIndex, val: Option<Literal>) {
-        let p = p.to_place_index(self).expect("place exists");
-        if let Some(local) = self.current_frame().get_by_index(p) {
-            // If place is a local
-            if let Some(&Literal::Uint(i, UintTy::Usize)) = self.known_val(p)
-                && let Some(old) = self.index_candidates.get_mut(&(i as usize))
+    /// The field of union p last written, if it is known
+    #[cfg(test)]
+    pub fn active_union_field(&self, p: impl ToPlaceIndex) -> Option<PlaceIndex> {
+        let pidx = p.to_place_index(self).expect("place exists");
+        assert!(self.is_union(pidx));
+        let fid = self.places[pidx].active_field?;
+        self.project_from_node(pidx, ProjectionElem::Field(fid))
+    }
+
+    /// Forgets the known values of places sharing bytes with p through an enclosing union
+    fn forget_union_siblings(&mut self, p: PlaceIndex) {
+        let Some(run_ptr) = self.places[p].run_ptr else {
+            return;
+        };
+        let mut outermost_union = None;
+        let mut node = p;
+        while let Some((_, parent)) = self.parent(node) {
+            if self.is_union(parent) {
+                outermost_union = Some(parent);
+            }
+            node = parent;
+        }
+        let Some(union) = outermost_union else {
+            return;
+        };
+        let written = p..=self.places[p].last_subfield;
+        for sibling in self.transitive_subfields(union) {
+            if !written.contains(&sibling)
+                && self.places[sibling]
+                    .run_ptr
+                    .is_some_and(|sibling| sibling.overlap(&run_ptr))
+            {
+                self.places[sibling].val = None;
+                self.places[sibling].fn_target = None;
+            }
+        }
+    }
+
+    /// Moves a local from the candidates of its old integer value to those of its new one
+    fn update_int_candidates(
+        &mut self,
+        p: PlaceIndex,
+        old_val: Option<Literal>,
+        val: Option<Literal>,
+    ) {
+        let ty = self.places[p].ty;
+        // If place is a local, of the current frame or of a caller written through a pointer
+        if let Some((frame, local)) = self
+            .frames
+            .iter_mut()
+            .rev()
+            .find_map(|frame| frame.get_by_index(p).map(|local| (frame, local)))
+        {
+            if let Some(bits) = old_val.and_then(int_bits)
+                && let Some(old) = frame
+                    .int_candidates
+                    .get_mut(&ty)
+                    .and_then(|bucket| bucket.get_mut(&bits))
                 && let Some(to_remove) = old.iter().position(|&l| l == local)
             {
                 // unconditionally remove the old entry if it exists
                 old.remove(to_remove);
             }
-            if let Some(Literal::Uint(i, UintTy::Usize)) = val {
-                self.index_candidates
-                    .entry(i as usize)
+            if let Some(bits) = val.and_then(int_bits) {
+                frame
+                    .int_candidates
+                    .entry(ty)
+                    .or_default()
+                    .entry(bits)
                     .or_default()
                     .push(local)
             }
         }
+    }
+
+    pub fn assign_literal(&mut self, p: impl ToPlaceIndex, val: Option<Literal>) {
+        let p = p.to_place_index(self).expect("place exists");
+        let old_val = self.places[p].val;
+        self.update_int_candidates(p, old_val, val);
 
         if let Some(val) = val {
             self.places[p].val = Some(val);
+            // Initializing is left to mark_place_init, only the value of init bytes is recorded
+            if let Some(bits) = scalar_bits(val)
+                && let Some(run_ptr) = self.places[p].run_ptr
+                && self.memory.bytes(run_ptr).iter().all(|b| b.is_init())
+            {
+                self.memory.write_scalar(run_ptr, bits);
+            }
         } else {
+            // The bytes would otherwise still give the old value away through read_literal
             self.update_transitive_subfields(p, |this, node| {
                 this.places[node].val = None;
+                this.places[node].fn_target = None;
+                if let Some(run_ptr) = this.places[node].run_ptr {
+                    this.memory.forget_values(run_ptr);
+                }
                 VisitAction::Continue
             });
             self.update_transitive_superfields(p, |this, node| {
@@ -1024,6 +2783,57 @@ impl PlaceTable {
                 VisitAction::Continue
             });
         }
+        self.forget_union_siblings(p);
+    }
+
+    /// Assigns the literals of an aggregate of constants to the fields of a tuple, array or
+    /// struct, in order. A field which is one of those itself takes as many literals as it has
+    /// scalar fields, recursively
+    pub fn assign_composite_literal(&mut self, p: impl ToPlaceIndex, lits: &[Literal]) {
+        let p = p.to_place_index(self).expect("place exists");
+        let rest = self.assign_composite_literal_inner(p, lits);
+        assert!(rest.is_empty(), "{} literals left over", rest.len());
+    }
+
+    /// Returns the literals left after those assigned to p
+    fn assign_composite_literal_inner<'l>(
+        &mut self,
+        p: PlaceIndex,
+        lits: &'l [Literal],
+    ) -> &'l [Literal] {
+        let Some(fields) = self.composite_fields(p) else {
+            let (lit, rest) = lits.split_first().expect("a literal for each scalar field");
+            self.assign_literal(p, Some(*lit));
+            return rest;
+        };
+        fields.into_iter().fold(lits, |lits, proj| {
+            let field = self.project_from_node(p, proj).expect("projection exists");
+            self.assign_composite_literal_inner(field, lits)
+        })
+    }
+
+    /// Records that the function pointer p points to target, or that it isn't known where.
+    /// Like assign_literal, this leaves initializing to mark_place_init
+    #[cfg(test)]
+    pub fn assign_fn_target(&mut self, p: impl ToPlaceIndex, target: Option<Function>) {
+        let p = p.to_place_index(self).expect("place exists");
+        assert!(
+            self.ty(p).is_fn_ptr(&self.tcx),
+            "{p:?} isn't a function pointer"
+        );
+        self.assign_literal(p, None);
+        self.places[p].fn_target = target;
+    }
+
+    /// The function an init function pointer is known to point to, so that calling through it
+    /// can enter the right function
+    #[cfg(test)]
+    pub fn known_fn_target(&self, p: impl ToPlaceIndex) -> Option<Function> {
+        let p = p.to_place_index(self).expect("place exists");
+        if !self.is_place_init(p) {
+            return None;
+        }
+        self.places[p].fn_target
     }
 
     /// Return destinations of all currently active frames
@@ -1045,74 +2855,691 @@ impl PlaceTable {
         self.places[p.to_place_index(self).expect("place exists")].ty
     }
 
+    /// Panics if place has a different type in the table than local_decls give it, which would
+    /// make the emitted program ill-typed
+    pub fn assert_ty_consistent(&self, place: &Place, local_decls: &LocalDecls) {
+        let ty = self.ty(place);
+        let decl_ty = place.ty(local_decls, &self.tcx);
+        assert_eq!(
+            ty,
+            decl_ty,
+            "{} is {} in the place table but {} in the local decls",
+            place.serialize_place(&self.tcx),
+            ty.serialize(&self.tcx),
+            decl_ty.serialize(&self.tcx)
+        );
+    }
+
+    /// The result of the MIR BinaryOp op on a and b, if it is defined and deterministic.
+    /// Integer arithmetic wraps and shift amounts are masked to the width of a, but division
+    /// by zero and signed division overflow are UB, and NaN payloads aren't known
+    pub fn fold_binop(op: BinOp, a: &Literal, b: &Literal) -> Option<Literal> {
+        use BinOp::*;
+        match (*a, *b) {
+            (Literal::Uint(..) | Literal::Int(..), Literal::Uint(..) | Literal::Int(..))
+                if matches!(op, Shl | Shr) =>
+            {
+                Self::fold_shift(op, a, b).map(|(val, _)| val)
+            }
+            (Literal::Uint(..) | Literal::Int(..), _) | (Literal::Bool(_), _)
+                if a.ty() != b.ty() =>
+            {
+                None
+            }
+            (Literal::Uint(..) | Literal::Int(..), _) => {
+                let (bits, signed) = int_width(a)?;
+                let (x, y) = (scalar_bits(*a)?, scalar_bits(*b)?);
+                let (sx, sy) = (sign_extend(x, bits), sign_extend(y, bits));
+                let val = match op {
+                    Add => x.wrapping_add(y),
+                    Sub => x.wrapping_sub(y),
+                    Mul => x.wrapping_mul(y),
+                    Div | Rem if y == 0 => return None,
+                    Div | Rem if signed && sx == min_signed(bits) && sy == -1 => return None,
+                    Div if signed => (sx / sy) as u128,
+                    Rem if signed => (sx % sy) as u128,
+                    Div => x / y,
+                    Rem => x % y,
+                    BitXor => x ^ y,
+                    BitAnd => x & y,
+                    BitOr => x | y,
+                    Eq | Lt | Le | Ne | Ge | Gt => {
+                        let ord = if signed { sx.cmp(&sy) } else { x.cmp(&y) };
+                        return Some(Literal::Bool(compare(op, ord)));
+                    }
+                    Shl | Shr | Offset => return None,
+                };
+                Some(int_literal(a, val))
+            }
+            (Literal::Bool(x), Literal::Bool(y)) => Some(Literal::Bool(match op {
+                BitXor => x ^ y,
+                BitAnd => x & y,
+                BitOr => x | y,
+                Eq | Lt | Le | Ne | Ge | Gt => compare(op, x.cmp(&y)),
+                _ => return None,
+            })),
+            (Literal::Char(x), Literal::Char(y)) => match op {
+                Eq | Lt | Le | Ne | Ge | Gt => Some(Literal::Bool(compare(op, x.cmp(&y)))),
+                _ => None,
+            },
+            (Literal::Float(x, x_ty), Literal::Float(y, y_ty)) if x_ty == y_ty => {
+                let val = match op {
+                    Eq => return Some(Literal::Bool(x == y)),
+                    Ne => return Some(Literal::Bool(x != y)),
+                    Lt => return Some(Literal::Bool(x < y)),
+                    Le => return Some(Literal::Bool(x <= y)),
+                    Ge => return Some(Literal::Bool(x >= y)),
+                    Gt => return Some(Literal::Bool(x > y)),
+                    // Every f32 is exact as an f64, but the operation must round like f32's
+                    Add | Sub | Mul | Div | Rem if x_ty == FloatTy::F32 => {
+                        let (x, y) = (x as f32, y as f32);
+                        (match op {
+                            Add => x + y,
+                            Sub => x - y,
+                            Mul => x * y,
+                            Div => x / y,
+                            _ => x % y,
+                        }) as f64
+                    }
+                    Add => x + y,
+                    Sub => x - y,
+                    Mul => x * y,
+                    Div => x / y,
+                    Rem => x % y,
+                    _ => return None,
+                };
+                (!val.is_nan()).then_some(Literal::Float(val, x_ty))
+            }
+            _ => None,
+        }
+    }
+
+    /// The result of the MIR CheckedBinaryOp op on a and b, and whether it overflowed.
+    /// The result is the wrapped one, as with fold_binop
+    pub fn fold_checked_binop(op: BinOp, a: &Literal, b: &Literal) -> Option<(Literal, bool)> {
+        use BinOp::*;
+        if matches!(op, Shl | Shr) {
+            return Self::fold_shift(op, a, b);
+        }
+        if !matches!(op, Add | Sub | Mul) || a.ty() != b.ty() {
+            return None;
+        }
+        let val = Self::fold_binop(op, a, b)?;
+        let (bits, signed) = int_width(a)?;
+        let overflowed = if signed {
+            let (x, y) = (
+                sign_extend(scalar_bits(*a)?, bits),
+                sign_extend(scalar_bits(*b)?, bits),
+            );
+            let exact = match op {
+                Add => x.checked_add(y),
+                Sub => x.checked_sub(y),
+                _ => x.checked_mul(y),
+            };
+            exact.is_none_or(|exact| exact < min_signed(bits) || exact > max_signed(bits))
+        } else {
+            let (x, y) = (scalar_bits(*a)?, scalar_bits(*b)?);
+            let exact = match op {
+                Add => x.checked_add(y),
+                Sub => x.checked_sub(y),
+                _ => x.checked_mul(y),
+            };
+            exact.is_none_or(|exact| exact > truncate(u128::MAX, bits))
+        };
+        Some((val, overflowed))
+    }
+
+    /// Shifts a by the amount b, masked to the width of a, and whether b had to be masked
+    fn fold_shift(op: BinOp, a: &Literal, b: &Literal) -> Option<(Literal, bool)> {
+        let (bits, signed) = int_width(a)?;
+        let x = scalar_bits(*a)?;
+        // CheckedBinaryOp reports whether the amount had to be masked
+        let (masked, overflowed) = match *b {
+            Literal::Uint(y, _) => ((y % bits as u128) as u32, y >= bits as u128),
+            Literal::Int(y, _) => (
+                y.rem_euclid(bits as i128) as u32,
+                y < 0 || y >= bits as i128,
+            ),
+            _ => return None,
+        };
+        let val = match op {
+            BinOp::Shl => x << masked,
+            BinOp::Shr if signed => (sign_extend(x, bits) >> masked) as u128,
+            BinOp::Shr => x >> masked,
+            _ => return None,
+        };
+        Some((int_literal(a, val), overflowed))
+    }
+
+    /// The result of the MIR UnaryOp op on a. Negation wraps
+    pub fn fold_unop(op: UnOp, a: &Literal) -> Option<Literal> {
+        match (op, *a) {
+            (UnOp::Not, Literal::Bool(x)) => Some(Literal::Bool(!x)),
+            (UnOp::Not, Literal::Uint(..) | Literal::Int(..)) => {
+                Some(int_literal(a, !scalar_bits(*a)?))
+            }
+            (UnOp::Neg, Literal::Int(..)) => Some(int_literal(a, scalar_bits(*a)?.wrapping_neg())),
+            (UnOp::Neg, Literal::Float(x, ty)) => Some(Literal::Float(-x, ty)),
+            _ => None,
+        }
+    }
+
     pub fn known_val(&self, p: impl ToPlaceIndex) -> Option<&Literal> {
         self.places[p.to_place_index(self).expect("place exists")]
             .val
             .as_ref()
     }
 
+    /// The known values of the fields of a tuple, array or struct, in order, if each field is a
+    /// scalar with a known value. It is read from the fields, so it follows their assignments
+    pub fn composite_known_val(&self, p: impl ToPlaceIndex) -> Option<Vec<Literal>> {
+        let p = p.to_place_index(self).expect("place exists");
+        self.composite_fields(p)?
+            .into_iter()
+            .map(|proj| self.known_val(self.project_from_node(p, proj)?).copied())
+            .collect()
+    }
+
+    /// The projections to the fields of a tuple, array or struct, in order
+    fn composite_fields(&self, p: PlaceIndex) -> Option<Vec<ProjectionElem>> {
+        let fields: Vec<ProjectionElem> = match self.ty(p).kind(&self.tcx) {
+            TyKind::Tuple(elems) => (0..elems.len())
+                .map(|i| ProjectionElem::TupleField(FieldIdx::new(i)))
+                .collect(),
+            TyKind::Array(_, len) => (0..*len)
+                .map(|i| ProjectionElem::ConstantIndex { offset: i as u64 })
+                .collect(),
+            TyKind::Adt(adt) if !adt.is_enum() && !self.is_union(p) => adt.variants
+                [VariantIdx::new(0)]
+            .fields
+            .indices()
+            .map(ProjectionElem::Field)
+            .collect(),
+            _ => return None,
+        };
+        Some(fields)
+    }
+
+    /// The known value of a scalar place, falling back to its bytes if they are all known,
+    /// such as after being copied through another type
+    pub fn read_literal(&self, p: impl ToPlaceIndex) -> Option<Literal> {
+        let p = p.to_place_index(self).expect("place exists");
+        if let Some(val) = self.places[p].val {
+            return Some(val);
+        }
+        self.decode_literal(self.ty(p), self.places[p].run_ptr?)
+    }
+
+    /// The scalar of type ty in the bytes of run_ptr, if they are all known
+    fn decode_literal(&self, ty: TyId, run_ptr: RunPointer) -> Option<Literal> {
+        let bits = self.memory.read_scalar(run_ptr)?;
+        match *ty.kind(&self.tcx) {
+            TyKind::Bool => (bits <= 1).then_some(Literal::Bool(bits == 1)),
+            TyKind::Char => char::from_u32(bits as u32).map(Literal::Char),
+            TyKind::Uint(ty) => Some(Literal::Uint(bits, ty)),
+            TyKind::Int(ty) => {
+                // Sign-extend from the place's size
+                let unused = 128 - 8 * run_ptr.size.bytes() as u32;
+                Some(Literal::Int((bits << unused) as i128 >> unused, ty))
+            }
+            _ => None,
+        }
+    }
+
+    /// The run a read through pointer accesses: the leading bytes of its pointee, as many as
+    /// its own pointee type has. For a cast pointer, the pointee may be larger
+    #[cfg(test)]
+    fn deref_run(&self, pointer: PlaceIndex) -> Option<RunPointer> {
+        if self.places[pointer].offset.is_some_and(|o| !o.is_zero()) {
+            return None;
+        }
+        let pointee = self.pointee(pointer)?;
+        let run_ptr = self.places[pointee].run_ptr?;
+        let size = BasicMemory::ty_size(self.ty(pointer).pointee_ty(&self.tcx)?, &self.tcx)?;
+        (size <= run_ptr.size).then_some(RunPointer { size, ..run_ptr })
+    }
+
+    /// The bytes a read through pointer sees, if its pointee is packed
+    #[cfg(test)]
+    pub fn deref_bytes(&self, pointer: impl ToPlaceIndex) -> Option<&[AbstractByte]> {
+        let pointer = pointer.to_place_index(self).expect("place exists");
+        Some(self.memory.bytes(self.deref_run(pointer)?))
+    }
+
+    /// How much of what a read through pointer sees is init, going by the bytes
+    #[cfg(test)]
+    pub fn deref_init_state(&self, pointer: impl ToPlaceIndex) -> Option<InitState> {
+        self.deref_bytes(pointer).map(bytes_init_state)
+    }
+
+    /// The scalar a read through pointer sees, decoded as its pointee type from the bytes
+    #[cfg(test)]
+    pub fn read_through(&self, pointer: impl ToPlaceIndex) -> Option<Literal> {
+        let pointer = pointer.to_place_index(self).expect("place exists");
+        let pointee_ty = self.ty(pointer).pointee_ty(&self.tcx)?;
+        self.decode_literal(pointee_ty, self.deref_run(pointer)?)
+    }
+
+    /// The number of elements of a slice, carried from the array it was unsized from
+    pub fn slice_len(&self, p: impl ToPlaceIndex) -> Option<usize> {
+        let array = self.places[p.to_place_index(self).expect("place exists")].slice_of?;
+        let TyKind::Array(_, len) = self.ty(array).kind(&self.tcx) else {
+            panic!("slices are unsized from arrays");
+        };
+        Some(*len)
+    }
+
+    /// The length of the slice the pointer points to, which is its metadata
+    pub fn pointee_len(&self, ptr: impl ToPlaceIndex) -> Option<usize> {
+        let ptr = ptr.to_place_index(self).expect("place exists");
+        if !self.ty(ptr).is_any_ptr(&self.tcx)
+            || self.places[ptr].offset.is_some_and(|o| !o.is_zero())
+            || self.reinterprets(ptr)
+        {
+            return None;
+        }
+        self.slice_len(self.pointee(ptr)?)
+    }
+
     pub fn known_variant(&self, p: impl ToPlaceIndex) -> Option<VariantIdx> {
         self.places[p.to_place_index(self).expect("place exists")].active_variant
     }
 
-    // Whether the pointer has been offsetted (and therefore unusable)
+    /// What Discriminant(p) evaluates to, if the active variant is known. Generated enums have
+    /// no explicit discriminants, so it is the variant index as an isize
+    pub fn known_discriminant(&self, p: impl ToPlaceIndex) -> Option<Literal> {
+        self.known_variant(p)
+            .map(|vid| Literal::Int(vid.index() as i128, IntTy::Isize))
+    }
+
+    // Whether the pointer has been offsetted out of its pointee (and therefore unusable)
     pub fn offseted(&self, p: impl ToPlaceIndex) -> bool {
         let p = p.to_place_index(self).expect("place exists");
         assert!(self.places[p].ty.is_raw_ptr(&self.tcx));
 
-        !matches!(self.places[p].offset, None | Some(0))
+        self.places[p].offset.is_some_and(|o| !o.is_zero())
     }
 
+    /// The offset of the pointer in elements of its pointee type, like the Offset binop
     pub fn get_offset(&self, p: impl ToPlaceIndex) -> Option<isize> {
         let p = p.to_place_index(self).expect("place exists");
         assert!(self.places[p].ty.is_raw_ptr(&self.tcx));
 
-        self.places[p].offset
+        self.places[p].offset.map(|o| o.elems)
     }
 
-    pub fn offset_ptr(&mut self, p: impl ToPlaceIndex, offset: isize) {
+    /// The offset of the pointer in bytes, if the size of what it was offset by is known
+    #[cfg(test)]
+    pub fn get_offset_bytes(&self, p: impl ToPlaceIndex) -> Option<isize> {
         let p = p.to_place_index(self).expect("place exists");
         assert!(self.places[p].ty.is_raw_ptr(&self.tcx));
 
-        self.places[p].offset = match self.places[p].offset {
-            None => Some(offset),
-            Some(o) => Some(offset.wrapping_add(o)),
-        };
+        self.places[p].offset?.bytes()
     }
 
-    /// Whether a pointer has had multiple offsets summing up to zero (therefore usable)
-    pub fn has_offset_roundtripped(&self, p: impl ToPlaceIndex) -> bool {
+    /// Offsets the pointer by offset elements of its pointee type
+    pub fn offset_ptr(&mut self, p: impl ToPlaceIndex, offset: isize) {
         let p = p.to_place_index(self).expect("place exists");
         assert!(self.places[p].ty.is_raw_ptr(&self.tcx));
 
-        self.places[p].offset == Some(0)
+        let elem_size = self
+            .ty(p)
+            .pointee_ty(&self.tcx)
+            .and_then(|ty| BasicMemory::ty_size(ty, &self.tcx));
+        self.places[p].offset = Some(match self.places[p].offset {
+            None => PtrOffset {
+                elems: offset,
+                elem_size,
+            },
+            Some(o) => o.offset_by(offset, elem_size),
+        });
+        self.retarget_within_array(p);
     }
 
-    fn locals_with_val(&self, val: usize) -> Vec<Local> {
-        if let Some(locals) = self.index_candidates.get(&val) {
-            locals
-                .iter()
-                .copied()
-                .filter(|local| {
-                    if self.is_place_init(local)
-                        && let Some(Literal::Uint(v, UintTy::Usize)) = self.known_val(local)
-                        && *v as usize == val
-                    {
-                        true
-                    } else {
-                        false
-                    }
-                })
-                .collect()
+    /// If the offset pointer p still points into the array its pointee is an element of, moves
+    /// its Deref edge to the element it now points to. A pointer cast from a pointer to the
+    /// whole array points to its first element. The pointer must already have provenance over
+    /// the element, like one derived from the whole array, as `&raw const arr[i]` only has it
+    /// over arr[i]
+    fn retarget_within_array(&mut self, p: PlaceIndex) {
+        let Some(offset) = self.places[p].offset.filter(|o| !o.is_zero()) else {
+            return;
+        };
+        let Some(tag) = self.places[p].tag else {
+            return;
+        };
+        let (Some(deref), Some(pointee)) = (self.ref_edge(p), self.pointee(p)) else {
+            return;
+        };
+        let elem = if !self.reinterprets(p) {
+            pointee
+        } else if let TyKind::Array(elem_ty, _) = *self.ty(pointee).kind(&self.tcx)
+            && self.ty(p).pointee_ty(&self.tcx) == Some(elem_ty)
+            && let Some(first) =
+                self.project_from_node(pointee, ProjectionElem::ConstantIndex { offset: 0 })
+        {
+            first
         } else {
-            vec![]
-        }
-    }
+            return;
+        };
+        let Some(target) = self.element_at_offset(elem, offset) else {
+            return;
+        };
+
+        let mut runs = vec![];
+        self.walk_subtree(target, |node| {
+            if let Some(run) = self.places[node].run_ptr {
+                runs.push(run);
+                VisitAction::Stop
+            } else {
+                VisitAction::Continue
+            }
+        });
+        if !runs.iter().all(|&run| self.memory.can_read_with(run, tag)) {
+            return;
+        }
+
+        // The pointer keeps its tag, so pointer_tags stays as it is
+        self.unlink_ref_edge(deref);
+        self.add_ref_edge(p, target);
+        self.places[p].offset = None;
+    }
+
+    /// The element offset away from elem in the same array, if elem is an element and the
+    /// offset is a whole number of its elements
+    fn element_at_offset(&self, elem: PlaceIndex, offset: PtrOffset) -> Option<PlaceIndex> {
+        let (array, index) = self
+            .places
+            .edges_directed(elem, Direction::Incoming)
+            .filter(|edge| !self.is_subslice(edge.source()))
+            .find_map(|edge| match *edge.weight() {
+                ProjectionElem::ConstantIndex { offset } => Some((edge.source(), offset)),
+                _ => None,
+            })?;
+        let elem_size = isize::try_from(BasicMemory::ty_size(self.ty(elem), &self.tcx)?.bytes())
+            .ok()
+            .filter(|&size| size > 0)?;
+        let bytes = offset.bytes()?;
+        if bytes % elem_size != 0 {
+            return None;
+        }
+        let index = u64::try_from((index as isize).checked_add(bytes / elem_size)?).ok()?;
+        self.project_from_node(array, ProjectionElem::ConstantIndex { offset: index })
+    }
+
+    /// Every place a write through the raw pointer ptr may change the value of: the place it
+    /// points to after its offset, that place's transitive super- and subfields, and the
+    /// pointers into any of them
+    pub fn aliases_of_pointee(&self, ptr: impl ToPlaceIndex) -> Vec<PlaceIndex> {
+        let ptr = ptr.to_place_index(self).expect("place exists");
+        assert!(self.ty(ptr).is_raw_ptr(&self.tcx));
+        let Some(pointee) = self.pointee(ptr) else {
+            return vec![];
+        };
+        let target = match self.places[ptr].offset {
+            Some(offset) if !offset.is_zero() => self.element_at_offset(pointee, offset),
+            _ => Some(pointee),
+        };
+        let Some(target) = target else {
+            return vec![];
+        };
+
+        let mut aliases = BTreeSet::new();
+        self.walk_subtree(target, |node| {
+            aliases.insert(node);
+            VisitAction::Continue
+        });
+        let mut to_visit = vec![target];
+        while let Some(node) = to_visit.pop() {
+            to_visit.extend(
+                self.immediate_superfields(node)
+                    .filter(|sup| aliases.insert(*sup)),
+            );
+        }
+        let pointers: Vec<PlaceIndex> = aliases
+            .iter()
+            .flat_map(|&node| self.pointers_to(node))
+            .map(|(pointer, _)| pointer)
+            .collect();
+        aliases.extend(pointers);
+        aliases.into_iter().collect()
+    }
+
+    /// Forgets the values of places written to in ways that aren't tracked, like the places
+    /// from aliases_of_pointee. Pointers are known by their pointees, so they stay
+    pub fn invalidate_knowledge(&mut self, places: &[PlaceIndex]) {
+        for &place in places {
+            if !self.ty(place).is_any_ptr(&self.tcx) {
+                let old_val = self.places[place].val.take();
+                self.places[place].fn_target = None;
+                if let Some(run_ptr) = self.places[place].run_ptr {
+                    self.memory.forget_values(run_ptr);
+                }
+                self.update_int_candidates(place, old_val, None);
+                self.forget_union_siblings(place);
+            }
+        }
+    }
+
+    /// The symbolic identity of a pointer: its pointee, and how many elements it is offset by
+    #[cfg(test)]
+    pub fn pointer_identity(&self, p: impl ToPlaceIndex) -> Option<(PlaceIndex, isize)> {
+        let p = p.to_place_index(self).expect("place exists");
+        Some((
+            self.pointee(p)?,
+            self.places[p].offset.map_or(0, |o| o.elems),
+        ))
+    }
+
+    /// The address a pointer holds, if it is within the run of its pointee
+    fn pointer_address(&self, p: PlaceIndex) -> Option<RunPointer> {
+        let pointee = self.pointee(p)?;
+        let run_ptr = self.places[pointee].run_ptr?;
+        let offset = match self.places[p].offset {
+            Some(offset) => offset.bytes()?,
+            None => 0,
+        };
+        let run_and_offset = run_ptr.run_and_offset.checked_offset(offset)?;
+        self.memory
+            .run_ptr(run_ptr.alloc_id, run_and_offset, Size::ZERO)
+            .ok()
+    }
+
+    /// The order of the addresses held by two pointers. It is only known if they point into
+    /// the same run of the same allocation, whose layout doesn't depend on the backend
+    pub fn compare_pointers(&self, a: impl ToPlaceIndex, b: impl ToPlaceIndex) -> Option<Ordering> {
+        let a = self.pointer_address(a.to_place_index(self).expect("place exists"))?;
+        let b = self.pointer_address(b.to_place_index(self).expect("place exists"))?;
+        if a.alloc_id != b.alloc_id {
+            return None;
+        }
+        a.run_and_offset.cmp_in_run(&b.run_and_offset)
+    }
+
+    /// Whether a pointer has had multiple offsets summing up to zero (therefore usable)
+    pub fn has_offset_roundtripped(&self, p: impl ToPlaceIndex) -> bool {
+        let p = p.to_place_index(self).expect("place exists");
+        assert!(self.places[p].ty.is_raw_ptr(&self.tcx));
+
+        self.places[p].offset.is_some_and(|o| o.is_zero())
+    }
+
+    /// Locals of the current frame holding a usize of val, to index with
+    fn locals_with_val(&self, val: usize) -> Vec<Local> {
+        self.locals_with_int_val(val as u128, TyCtxt::USIZE)
+    }
+
+    /// Init locals of the current frame of integer type ty holding val, in two's complement
+    pub fn locals_with_int_val(&self, val: u128, ty: TyId) -> Vec<Local> {
+        let Some(locals) = self
+            .current_frame()
+            .int_candidates
+            .get(&ty)
+            .and_then(|bucket| bucket.get(&val))
+        else {
+            return vec![];
+        };
+        locals
+            .iter()
+            .copied()
+            .filter(|local| {
+                self.is_place_init(local)
+                    && self.ty(local) == ty
+                    && self.known_val(local).copied().and_then(int_bits) == Some(val)
+            })
+            .collect()
+    }
+
+    /// Saves the places, frames and memory, so that changes made while trying something out can
+    /// be undone
+    pub fn checkpoint(&self) -> PtCheckpoint {
+        PtCheckpoint {
+            frames: self.frames.clone(),
+            statics: self.statics.clone(),
+            pointer_tags: self.pointer_tags.clone(),
+            places: self.places.clone(),
+            referencers: self.referencers.clone(),
+            flows: self.flows.clone(),
+            memory: self.memory.snapshot(),
+        }
+    }
+
+    /// Puts the place table back as it was when checkpoint was taken, so that every query
+    /// answers as it did then
+    pub fn rollback(&mut self, checkpoint: PtCheckpoint) {
+        self.frames = checkpoint.frames;
+        self.statics = checkpoint.statics;
+        self.pointer_tags = checkpoint.pointer_tags;
+        self.places = checkpoint.places;
+        self.referencers = checkpoint.referencers;
+        self.flows = checkpoint.flows;
+        *self.sink_distances.get_mut() = None;
+        self.memory.restore(checkpoint.memory);
+    }
+
+    /// The conservative state where control flow from self and other meets. Places are init,
+    /// pointers point to their pointee, and values are known only if they are in both.
+    /// Both must have grown from a common predecessor with the same frames and places.
+    #[cfg(test)]
+    pub fn join(&self, other: &Self) -> Self {
+        assert_eq!(self.frames.len(), other.frames.len());
+        assert!(
+            self.places.node_indices().eq(other.places.node_indices()),
+            "joined place tables must have the same places"
+        );
+        let mut joined = self.clone();
+        joined.memory = self.memory.join(&other.memory);
+        // The joined memory hands out tags after those minted on either path, so pointer_tags
+        // needs a slot for each of them. The pointers of other's tags aren't in the joined graph
+        let tag_count = self.pointer_tags.len().max(other.pointer_tags.len());
+        joined.pointer_tags.resize(tag_count, BTreeSet::new());
+        for pidx in self.places.node_indices() {
+            let (a, b) = (&self.places[pidx], &other.places[pidx]);
+            let node = &mut joined.places[pidx];
+            node.complexity = a.complexity.max(b.complexity);
+            node.val = a
+                .val
+                .filter(|a| b.val.is_some_and(|b| Self::same_literal(a, &b)));
+            node.fn_target = a.fn_target.filter(|_| a.fn_target == b.fn_target);
+            node.offset = a.offset.filter(|_| a.offset == b.offset);
+            node.derivation = a
+                .derivation
+                .clone()
+                .filter(|_| a.derivation == b.derivation);
+            node.dangling = a.dangling && b.dangling;
+            node.active_variant = a
+                .active_variant
+                .filter(|_| a.active_variant == b.active_variant);
+            node.active_field = a.active_field.filter(|_| a.active_field == b.active_field);
+        }
+        for (frame, other_frame) in joined.frames.iter_mut().zip(&other.frames) {
+            for (ty, bucket) in frame.int_candidates.iter_mut() {
+                for (bits, locals) in bucket.iter_mut() {
+                    let other_locals = other_frame
+                        .int_candidates
+                        .get(ty)
+                        .and_then(|other| other.get(bits));
+                    locals.retain(|local| other_locals.is_some_and(|other| other.contains(local)));
+                }
+            }
+        }
+        // Data flowing along either path flows
+        for (dst, srcs) in &other.flows {
+            joined.flows.entry(*dst).or_default().extend(srcs);
+        }
+        *joined.sink_distances.get_mut() = None;
+        // Pointers must have the same pointee through the same tag
+        for pointer in self.places.node_indices() {
+            if self.places[pointer].ty.is_any_ptr(&self.tcx)
+                && let Some(edge) = self.ref_edge(pointer)
+                && (self.places[pointer].tag != other.places[pointer].tag
+                    || self.pointee(pointer) != other.pointee(pointer))
+            {
+                joined.remove_edge(edge);
+                joined.places[pointer].tag = None;
+            }
+        }
+        joined
+    }
+
+    /// Literals are the same if they have the same bits, so that 0.0 and -0.0 differ
+    #[cfg(test)]
+    fn same_literal(a: &Literal, b: &Literal) -> bool {
+        match (a, b) {
+            (Literal::Uint(a, a_ty), Literal::Uint(b, b_ty)) => a == b && a_ty == b_ty,
+            (Literal::Int(a, a_ty), Literal::Int(b, b_ty)) => a == b && a_ty == b_ty,
+            (Literal::Bool(a), Literal::Bool(b)) => a == b,
+            (Literal::Char(a), Literal::Char(b)) => a == b,
+            (Literal::Float(a, a_ty), Literal::Float(b, b_ty)) => {
+                a.to_bits() == b.to_bits() && a_ty == b_ty
+            }
+            _ => false,
+        }
+    }
 
     pub fn place_count(&self) -> usize {
         self.places.node_count()
     }
 
+    /// Number of places rooted at the locals of the current frame
+    pub fn frame_place_count(&self) -> usize {
+        self.current_frame()
+            .locals
+            .right_values()
+            .map(|&pidx| self.places[pidx].last_subfield.index() - pidx.index() + 1)
+            .sum()
+    }
+
+    #[allow(dead_code)]
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Number of calls on the callstack, on top of fn0
+    pub fn call_depth(&self) -> usize {
+        self.frames.len() - 1
+    }
+
+    /// Whether try_enter_fn can push another frame
+    pub fn can_call(&self) -> bool {
+        self.max_call_depth
+            .is_none_or(|max| self.call_depth() < max)
+    }
+
+    /// Number of frames of function on the callstack, which is more than one if it recurses
+    pub fn active_frames(&self, function: Function) -> usize {
+        self.frames
+            .iter()
+            .filter(|frame| frame.function == Some(function))
+            .count()
+    }
+
+    /// Whether try_allocate_local can add another local to the current frame
+    pub fn frame_has_room(&self) -> bool {
+        self.max_places_per_frame
+            .is_none_or(|max| self.frame_place_count() < max)
+    }
+
     /// Whether writing to a place will invalidate a tag
     fn will_write_invalidate(&self, dest: RunPointer, tag: Tag) -> bool {
         let invalidated = self.memory.above_first_shared(dest);
@@ -1123,6 +3550,7 @@ impl PlaceTable {
     /// shared reference on the stack, and mark references uninit
     pub fn place_written(&mut self, p: impl ToPlaceIndex) {
         let p = p.to_place_index(self).expect("place exists");
+        self.forget_union_siblings(p);
         self.update_transitive_subfields(p, |this, place| {
             if let Some(run) = this.places[place].run_ptr {
                 let invalidated = this.memory.above_first_shared(run);
@@ -1143,6 +3571,55 @@ impl PlaceTable {
         });
     }
 
+    /// To be called when p is read through ptr. Pops the exclusive borrows above ptr's tag,
+    /// and removes the Deref edges of the pointers they belonged to
+    pub fn place_read_through(&mut self, ptr: impl ToPlaceIndex, p: impl ToPlaceIndex) {
+        let ptr = ptr.to_place_index(self).expect("place exists");
+        let p = p.to_place_index(self).expect("place exists");
+        let Some(tag) = self.places[ptr].tag else {
+            return;
+        };
+        self.update_transitive_subfields(p, |this, place| {
+            if let Some(run) = this.places[place].run_ptr {
+                for popped in this.memory.read_with(run, tag) {
+                    for pointer in this.pointer_tags[popped].clone() {
+                        if let Some(edge) = this.ref_edge(pointer) {
+                            this.remove_edge(edge);
+                        }
+                    }
+                }
+                VisitAction::Stop
+            } else {
+                VisitAction::Continue
+            }
+        });
+    }
+
+    /// To be called when p is written to through ptr. Pops the borrows above ptr's tag, and
+    /// removes the Deref edges of pointers which lost all of their borrows
+    pub fn place_written_through(&mut self, ptr: impl ToPlaceIndex, p: impl ToPlaceIndex) {
+        let ptr = ptr.to_place_index(self).expect("place exists");
+        let p = p.to_place_index(self).expect("place exists");
+        let Some(tag) = self.places[ptr].tag else {
+            return self.place_written(p);
+        };
+        self.forget_union_siblings(p);
+        self.update_transitive_subfields(p, |this, place| {
+            if let Some(run) = this.places[place].run_ptr {
+                for popped in this.memory.write_with(run, tag) {
+                    for pointer in this.pointer_tags[popped].clone() {
+                        if let Some(edge) = this.ref_edge(pointer) {
+                            this.remove_edge(edge);
+                        }
+                    }
+                }
+                VisitAction::Stop
+            } else {
+                VisitAction::Continue
+            }
+        });
+    }
+
     pub fn can_read_through(&self, ptr: PlaceIndex, p: PlaceIndex) -> bool {
         assert_ne!(ptr, p);
 
@@ -1158,9 +3635,11 @@ impl PlaceTable {
             return false;
         };
         let mut can = true;
-        self.visit_transitive_subfields(p, |node| {
+        self.walk_subtree(p, |node| {
             if let Some(run) = self.places[node].run_ptr {
-                if !self.memory.can_read_with(run, tag) {
+                if !self.memory.can_read_with(run, tag)
+                    || self.memory.read_with_pops_protected(run, tag)
+                {
                     can = false;
                     return VisitAction::ShortCircuit;
                 }
@@ -1187,9 +3666,12 @@ impl PlaceTable {
             return false;
         };
         let mut can = true;
-        self.visit_transitive_subfields(p, |node| {
+        self.walk_subtree(p, |node| {
             if let Some(run) = self.places[node].run_ptr {
-                if !self.memory.can_write_with(run, tag) {
+                if !self.memory.can_write_with(run, tag)
+                    || self.memory.write_with_pops_protected(run, tag)
+                {
+                    log::trace!(target: "generate::mem", "{}", self.memory.explain(run, tag));
                     can = false;
                     return VisitAction::ShortCircuit;
                 }
@@ -1212,8 +3694,48 @@ impl PlaceTable {
             self.memory.fill(run_ptr, AbstractByte::Uninit);
         }
 
+        self.unlink_ref_edge(e);
+    }
+
+    /// Adds pointer -[Deref]-> pointee to the graph and the referencers of pointee
+    fn add_ref_edge(&mut self, pointer: PlaceIndex, pointee: PlaceIndex) {
+        self.places
+            .add_edge(pointer, pointee, ProjectionElem::Deref);
+        self.referencers.entry(pointee).or_default().push(pointer);
+    }
+
+    /// Removes a Deref edge from the graph and the referencers of its target, leaving the
+    /// pointer's tag and bytes alone
+    fn unlink_ref_edge(&mut self, e: ProjectionIndex) {
+        let (source, target) = self.places.edge_endpoints(e).expect("edge exists");
         let removed = self.places.remove_edge(e).expect("edge exists");
         assert!(removed.is_deref());
+        let pointers = self
+            .referencers
+            .get_mut(&target)
+            .expect("pointee has referencers");
+        pointers.retain(|p| *p != source);
+        if pointers.is_empty() {
+            self.referencers.remove(&target);
+        }
+    }
+
+    /// remove_edge, for a pointer whose value is overwritten. Once no pointer holds its tag,
+    /// the tag's borrows go too, unless they are protected until a call returns or exposed
+    /// to pointers cast from integers
+    fn remove_overwritten_edge(&mut self, e: ProjectionIndex) {
+        let (source, target) = self.places.edge_endpoints(e).expect("edge exists");
+        let tag = self.places[source].tag.expect("has tag");
+        self.remove_edge(e);
+        if self.pointer_tags[tag].is_empty()
+            && !self.memory.is_protected(tag)
+            && !self
+                .memory
+                .exposed_tags(self.places[target].alloc_id)
+                .any(|exposed| exposed == tag)
+        {
+            self.memory.remove_tag(tag);
+        }
     }
 }
 
@@ -1230,23 +3752,39 @@ impl PlacePath {
         self.source
     }
 
+    /// Names the place, indexing arrays with the first local holding each index.
     pub fn to_place(&self, pt: &PlaceTable) -> Place {
+        self.to_place_by(pt, |locals| locals.first().copied())
+            .expect("a local holds each index")
+    }
+
+    /// Names the place, indexing arrays with a random local holding each index.
+    /// Returns None if no local holds one of the indices anymore.
+    pub fn to_place_with(&self, pt: &PlaceTable, rng: &mut impl Rng) -> Option<Place> {
+        self.to_place_by(pt, |locals| locals.choose(rng).copied())
+    }
+
+    fn to_place_by(
+        &self,
+        pt: &PlaceTable,
+        mut pick: impl FnMut(&[Local]) -> Option<Local>,
+    ) -> Option<Place> {
         let projs: SmallVec<[ProjectionElem; 8]> = self
             .path
             .iter()
-            .map(|&proj| {
-                let mut proj = pt.places[proj];
-                if let ProjectionElem::ConstantIndex { offset } = proj {
-                    let local = pt.locals_with_val(offset as usize)[0]; // TODO: randomise this?
-                    proj = ProjectionElem::Index(local);
+            .map(|&proj| match pt.places[proj] {
+                ProjectionElem::ConstantIndex { offset } => {
+                    // Custom MIR can only index with a local
+                    pick(&pt.locals_with_val(offset as usize)).map(ProjectionElem::Index)
                 }
-                proj
+                proj => Some(proj),
             })
-            .collect();
-        Place::from_projected(
-            pt.current_frame().get_by_index(self.source).unwrap(),
-            &projs,
-        )
+            .collect::<Option<_>>()?;
+        let local = pt
+            .current_frame()
+            .get_by_index(self.source)
+            .expect("statics can't be named in a place yet");
+        Some(Place::from_projected(local, &projs))
     }
 
     pub fn projections<'pt>(
@@ -1264,11 +3802,35 @@ impl PlacePath {
         )
     }
 
+    /// Whether the path goes through a union field other than the one last written
+    pub fn through_inactive_union_field(&self, pt: &PlaceTable) -> bool {
+        self.nodes(pt)
+            .zip(self.projections(pt))
+            .any(|(node, proj)| match proj {
+                ProjectionElem::Field(fid) if pt.is_union(node) => {
+                    pt.places[node].active_field != Some(fid)
+                }
+                _ => false,
+            })
+    }
+
+    /// The pointer the path last dereferences, or its source if it has no Deref. The target
+    /// is accessed with that pointer's tag
+    pub fn access_source(&self, pt: &PlaceTable) -> PlaceIndex {
+        self.nodes(pt)
+            .zip(self.projections(pt))
+            .filter_map(|(node, proj)| proj.is_deref().then_some(node))
+            .last()
+            .unwrap_or(self.source)
+    }
+
+    /// Whether the path dereferences a pointer other than its source
+    pub fn has_nested_deref(&self, pt: &PlaceTable) -> bool {
+        self.projections(pt).skip(1).any(|proj| proj.is_deref())
+    }
+
     pub fn is_return_proj(&self, pt: &PlaceTable) -> bool {
-        pt.current_frame()
-            .get_by_index(self.source)
-            .expect("source exists")
-            == Local::RET
+        pt.current_frame().get_by_index(self.source) == Some(Local::RET)
     }
 
     pub fn target_index(&self) -> PlaceIndex {
@@ -1283,45 +3845,85 @@ pub struct ProjectionIter<'pt> {
     pt: &'pt PlaceTable,
     root: PlaceIndex,
     path: Path,
-    // Stack of nodes to visit and their depth (number of projections from root)
-    to_visit: Vec<(ProjectionIndex, usize)>,
+    // Stack of nodes to visit, their depth (number of projections from root) and the number of
+    // Derefs on the way
+    to_visit: Vec<(ProjectionIndex, usize, usize)>,
+    writes: bool,
+    // If set, only projections to places leading to these until the first Deref, see
+    // PlaceTable::leads_to
+    leading_to: Option<Rc<[PlaceIndex]>>,
 
     root_visited: bool,
 }
 
 impl<'pt> ProjectionIter<'pt> {
-    fn new(pt: &'pt PlaceTable, root: PlaceIndex) -> Self {
-        ProjectionIter {
+    fn new(
+        pt: &'pt PlaceTable,
+        root: PlaceIndex,
+        writes: bool,
+        leading_to: Option<Rc<[PlaceIndex]>>,
+    ) -> Self {
+        let mut iter = ProjectionIter {
             pt,
             root,
             path: smallvec![],
-            to_visit: pt
-                .places
-                .edges_directed(root, Direction::Outgoing)
-                .filter_map(|e| {
-                    // Only downcast to current variants
-                    if let ProjectionElem::DowncastField(vid, _, _) = e.weight()
-                        && pt.known_variant(e.source()) != Some(*vid)
-                    {
-                        return None;
-                    }
-                    // Only do indexing if we can find a local as index
-                    // TODO: move this to a function
-                    if let ProjectionElem::ConstantIndex { offset } = e.weight()
-                        && pt.locals_with_val(*offset as usize).is_empty()
-                    {
-                        return None;
-                    }
+            to_visit: vec![],
+            writes,
+            leading_to,
+            root_visited: false,
+        };
+        iter.push_edges(root, 0, 0);
+        iter
+    }
 
-                    if pt.ty(e.source()).is_raw_ptr(&pt.tcx) && pt.offseted(e.source()) {
-                        return None;
-                    }
+    /// Pushes the projections that can be followed out of node, which is depth projections
+    /// and derefs Derefs away from root
+    fn push_edges(&mut self, node: PlaceIndex, depth: usize, derefs: usize) {
+        let pt = self.pt;
+        let edges = pt
+            .places
+            .edges_directed(pt.projection_source(node), Direction::Outgoing);
+        self.to_visit.extend(edges.filter_map(|e| {
+            // Only downcast to current variants
+            if let ProjectionElem::DowncastField(vid, _, _) = e.weight()
+                && pt.known_variant(e.source()) != Some(*vid)
+            {
+                return None;
+            }
+            // Only do indexing if we can find a local as index
+            if let ProjectionElem::ConstantIndex { offset } = e.weight()
+                && pt.locals_with_val(*offset as usize).is_empty()
+            {
+                return None;
+            }
+            // Custom MIR has no syntax for subslices
+            if let ProjectionElem::Subslice { .. } = e.weight() {
+                return None;
+            }
 
-                    Some((e.id(), 1))
-                })
-                .collect(),
-            root_visited: false,
-        }
+            if !e.weight().is_deref() {
+                if derefs == 0 && !pt.leads_to(e.target(), self.leading_to.as_deref()) {
+                    return None;
+                }
+                return Some((e.id(), depth + 1, derefs));
+            }
+            if derefs >= pt.max_derefs_per_path || (depth > 0 && !pt.nested_derefs) {
+                return None;
+            }
+            if pt.ty(e.source()).is_raw_ptr(&pt.tcx)
+                && (pt.offseted(e.source()) || pt.reinterprets(e.source()))
+            {
+                return None;
+            }
+            if !pt.is_place_live(e.target()) {
+                return None;
+            }
+            // Never write through a shared reference
+            if self.writes && pt.deref_kind(e.id()).is_read_only() {
+                return None;
+            }
+            Some((e.id(), depth + 1, derefs + 1))
+        }));
     }
 }
 
@@ -1336,32 +3938,11 @@ impl<'pt> Iterator for ProjectionIter<'pt> {
                 target: self.root,
             });
         }
-        if let Some((edge, depth)) = self.to_visit.pop() {
+        if let Some((edge, depth, derefs)) = self.to_visit.pop() {
             let (_, target) = self.pt.places.edge_endpoints(edge).unwrap();
             self.path.truncate(depth - 1);
             self.path.push(edge);
-
-            let new_edges = self.pt.places.edges_directed(target, Direction::Outgoing);
-            self.to_visit.extend(new_edges.filter_map(|e| {
-                // Only downcast to current variants
-                if let ProjectionElem::DowncastField(vid, _, _) = e.weight()
-                    && self.pt.known_variant(e.source()) != Some(*vid)
-                {
-                    return None;
-                }
-
-                // Do not follow deref edges since we are not root
-                if e.weight().is_deref() {
-                    return None;
-                }
-
-                if let ProjectionElem::ConstantIndex { offset } = e.weight()
-                    && self.pt.locals_with_val(*offset as usize).is_empty()
-                {
-                    return None;
-                }
-                Some((e.id(), depth + 1))
-            }));
+            self.push_edges(target, depth, derefs);
 
             Some(PlacePath {
                 source: self.root,
@@ -1374,6 +3955,111 @@ impl<'pt> Iterator for ProjectionIter<'pt> {
     }
 }
 
+/// How many of the bytes are init. An empty run counts as init
+fn bytes_init_state(bytes: &[AbstractByte]) -> InitState {
+    match bytes.iter().filter(|b| b.is_init()).count() {
+        n if n == bytes.len() => InitState::Full,
+        0 => InitState::Uninit,
+        _ => InitState::Partial,
+    }
+}
+
+/// The bits a literal is stored as, if its bytes are tracked. Signed integers are stored
+/// in two's complement
+fn scalar_bits(lit: Literal) -> Option<u128> {
+    match lit {
+        Literal::Uint(i, _) => Some(i),
+        Literal::Int(i, _) => Some(i as u128),
+        Literal::Bool(b) => Some(b as u128),
+        Literal::Char(c) => Some(c as u128),
+        Literal::Float(..) => None,
+    }
+}
+
+/// The width in bits of an integer literal, and whether it is signed
+fn int_width(lit: &Literal) -> Option<(u32, bool)> {
+    let ptr_bits = Layout::PTR_SIZE.bits() as u32;
+    match *lit {
+        Literal::Uint(_, ty) => Some((
+            match ty {
+                UintTy::Usize => ptr_bits,
+                UintTy::U8 => 8,
+                UintTy::U16 => 16,
+                UintTy::U32 => 32,
+                UintTy::U64 => 64,
+                UintTy::U128 => 128,
+            },
+            false,
+        )),
+        Literal::Int(_, ty) => Some((
+            match ty {
+                IntTy::Isize => ptr_bits,
+                IntTy::I8 => 8,
+                IntTy::I16 => 16,
+                IntTy::I32 => 32,
+                IntTy::I64 => 64,
+                IntTy::I128 => 128,
+            },
+            true,
+        )),
+        _ => None,
+    }
+}
+
+/// The low bits of v
+fn truncate(v: u128, bits: u32) -> u128 {
+    if bits == 128 {
+        v
+    } else {
+        v & ((1 << bits) - 1)
+    }
+}
+
+/// The low bits of v as a signed integer of that width
+fn sign_extend(v: u128, bits: u32) -> i128 {
+    let shift = 128 - bits;
+    ((v << shift) as i128) >> shift
+}
+
+fn min_signed(bits: u32) -> i128 {
+    i128::MIN >> (128 - bits)
+}
+
+fn max_signed(bits: u32) -> i128 {
+    i128::MAX >> (128 - bits)
+}
+
+/// A literal of the same type as ty holding the low bits of val
+fn int_literal(ty: &Literal, val: u128) -> Literal {
+    let (bits, _) = int_width(ty).expect("literal is an integer");
+    match *ty {
+        Literal::Uint(_, ty) => Literal::Uint(truncate(val, bits), ty),
+        Literal::Int(_, ty) => Literal::Int(sign_extend(val, bits), ty),
+        _ => unreachable!("literal is an integer"),
+    }
+}
+
+/// Whether ord satisfies the comparison op
+pub fn compare(op: BinOp, ord: Ordering) -> bool {
+    match op {
+        BinOp::Eq => ord.is_eq(),
+        BinOp::Ne => ord.is_ne(),
+        BinOp::Lt => ord.is_lt(),
+        BinOp::Le => ord.is_le(),
+        BinOp::Ge => ord.is_ge(),
+        BinOp::Gt => ord.is_gt(),
+        _ => unreachable!("op is a comparison"),
+    }
+}
+
+/// The bits of an integer literal, which int_candidates are keyed by
+fn int_bits(lit: Literal) -> Option<u128> {
+    match lit {
+        Literal::Uint(..) | Literal::Int(..) => scalar_bits(lit),
+        _ => None,
+    }
+}
+
 pub trait HasComplexity {
     fn complexity(&self, pt: &PlaceTable) -> usize;
 }
@@ -1403,7 +4089,7 @@ impl HasComplexity for Rvalue {
                 l.complexity(pt) + r.complexity(pt)
             }
             Rvalue::Aggregate(_, elems) => elems.iter().map(|op| op.complexity(pt)).sum(),
-            Rvalue::Len(_) => 1,
+            Rvalue::Len(_) | Rvalue::PtrMetadata(_) => 1,
             Rvalue::Discriminant(place) => place.complexity(pt),
             Rvalue::AddressOf(_, place) => place.complexity(pt),
             Rvalue::Ref(_, place) => place.complexity(pt),
@@ -1422,22 +4108,40 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::rc::Rc;
+    extern crate test;
+    use std::{cmp::Ordering, collections::HashSet, rc::Rc};
 
+    use abi::size::Size;
+    use index_vec::IndexVec;
     use mir::{
         syntax::{
-            BinOp, FieldIdx, Literal, Local, Mutability, Operand, Place, ProjectionElem, Rvalue,
-            TyId, TyKind, UintTy,
+            Adt, BinOp, Body, FieldIdx, FloatTy, Function, IntTy, Literal, Local, Mutability,
+            Operand, Place, ProjectionElem, Rvalue, TyId, TyKind, UintTy, UnOp, VariantDef,
+            VariantIdx,
         },
-        tyctxt::TyCtxt,
+        tyctxt::{AdtMeta, TyCtxt},
     };
+    use petgraph::{visit::EdgeRef, Direction};
+    use rand::{rngs::SmallRng, SeedableRng};
+    use smallvec::smallvec;
+    use test::Bencher;
 
     use crate::{
-        mem::BasicMemory,
+        mem::{AbstractByte, BasicMemory, FreeError, Layout},
+        place_select::PlaceSelector,
         ptable::{HasComplexity, PlaceIndex, ToPlaceIndex},
     };
 
-    use super::PlaceTable;
+    use super::{DerefKind, Derivation, InitState, PlaceTable, PtrOffset, VisitAction};
+
+    /// A Copy, Rust-repr struct, for tests to adjust with struct update syntax
+    fn plain_meta() -> AdtMeta {
+        AdtMeta {
+            copy: true,
+            repr_c: false,
+            union: false,
+        }
+    }
 
     fn prepare_t() -> (PlaceTable, Local, Place, Place, Place, Place, Place) {
         /*
@@ -1473,20 +4177,185 @@ mod tests {
     }
 
     #[test]
-    fn nested_tuple() {
-        let (pt, local, _, b, ..) = prepare_t();
-
-        let visited: Vec<TyId> = pt
-            .reachable_from_node(local.to_place_index(&pt).unwrap())
-            .map(|ppath| pt.ty(ppath.target_index()))
-            .collect();
-        let root = pt.get_node(&Place::from_local(local)).unwrap();
-        let root_ty = pt.places[root].ty;
-        let b = pt.get_node(&b).unwrap();
-        let b_ty = pt.places[b].ty;
+    fn dot() {
+        let (mut pt, local, a, _, _, d, _) = prepare_t();
+        pt.mark_place_init(&a);
+        pt.assign_literal(&d, Some(Literal::Int(-2, IntTy::I16)));
+        pt.update_complexity(local, 3);
         assert_eq!(
-            &visited,
-            &[
+            pt.to_dot(),
+            r#"digraph {
+    0 [label="_1: (i8, (i16, i32), i64)\nuninit, complexity 3" style=bold]
+    1 [label="i8\ninit, complexity 3"]
+    2 [label="(i16, i32)\nuninit, complexity 3"]
+    3 [label="i16\nuninit, complexity 3, val (-2_i16)"]
+    4 [label="i32\nuninit, complexity 3"]
+    5 [label="i64\nuninit, complexity 3"]
+    0 -> 1 [label=".0"]
+    2 -> 3 [label=".0"]
+    2 -> 4 [label=".1"]
+    0 -> 2 [label=".1"]
+    0 -> 5 [label=".2"]
+}
+"#
+        );
+    }
+
+    #[test]
+    fn stack_limit() {
+        let mut tcx = TyCtxt::from_primitives();
+        let big = tcx.push(TyKind::Array(TyCtxt::U64, 8));
+        let tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::U8, TyCtxt::U64]));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx)).with_stack_limit(Size::from_bytes(100));
+        assert!(pt.can_allocate([big]));
+        pt.allocate_local(Local::new(1), big);
+        assert_eq!(pt.stack_usage(), Size::from_bytes(64));
+
+        // A call that needs another such frame is refused
+        assert!(!pt.can_allocate([TyCtxt::UNIT, big]));
+        assert!(pt.can_allocate([TyCtxt::U32, TyCtxt::U64]));
+
+        // Fields of a tuple are separate runs, so there is no padding to count
+        pt.allocate_local(Local::new(2), tuple);
+        assert_eq!(pt.stack_usage(), Size::from_bytes(73));
+        assert!(pt.can_allocate([tuple; 3]));
+        assert!(!pt.can_allocate([tuple; 4]));
+
+        pt.deallocate_local(Local::new(1)).unwrap();
+        assert!(pt.can_allocate([TyCtxt::UNIT, big]));
+    }
+
+    #[test]
+    fn dead_local_unreachable() {
+        let mut tcx = TyCtxt::from_primitives();
+        let tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::U8, TyCtxt::U32]));
+        let t_ref = tcx.push(TyKind::Ref(tuple, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let t = pt.allocate_local(Local::new(1), tuple);
+        pt.allocate_local(Local::new(2), t_ref);
+        let r = pt.allocate_local(Local::new(3), t_ref);
+        pt.mark_place_init(Local::new(1));
+        pt.mark_place_init(Local::new(2));
+        pt.set_ref(Local::new(2), Local::new(1), None);
+        pt.assign_literal(Local::new(3), None);
+        pt.copy_place(Local::new(3), Local::new(2));
+
+        let field = Place::from_projected(
+            Local::new(1),
+            &[ProjectionElem::TupleField(FieldIdx::new(0))],
+        );
+        let mut deref = Place::from_local(Local::new(2));
+        deref.project(ProjectionElem::Deref);
+        assert!(field.to_place_index(&pt).is_some());
+        assert_eq!(deref.to_place_index(&pt), Some(t));
+
+        pt.deallocate_local(Local::new(2)).unwrap();
+        assert!(!pt.is_place_live(Local::new(2)));
+        assert!(deref.to_place_index(&pt).is_none());
+        // The copy of the dead reference still borrows the tuple
+        assert_eq!(pt.pointee(r), Some(t));
+
+        pt.deallocate_local(Local::new(1)).unwrap();
+        assert!(pt
+            .reachable_nodes()
+            .all(|path| path.source() == r && path.target_index() == r));
+        assert!(field.to_place_index(&pt).is_none());
+        assert_eq!(pt.pointee(r), None);
+
+        // Storage can start again, as a fresh place
+        let t2 = pt.allocate_local(Local::new(1), tuple);
+        assert_ne!(t2, t);
+        assert_eq!(Local::new(1).to_place_index(&pt), Some(t2));
+        assert!(pt.reachable_nodes().any(|path| path.source() == t2));
+    }
+
+    #[test]
+    fn stats() {
+        let mut tcx = TyCtxt::from_primitives();
+        let tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::U8, TyCtxt::U32]));
+        let t_ref = tcx.push(TyKind::Ref(tuple, Mutability::Not));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        pt.allocate_local(Local::new(1), tuple);
+        pt.allocate_local(Local::new(2), t_ref);
+        let stats = pt.stats();
+        assert_eq!((stats.nodes, stats.edges), (4, 2));
+        assert_eq!(stats.memory.live_allocations, 2);
+        assert_eq!(stats.memory.runs, 3);
+
+        // The reference borrows both fields of the tuple
+        pt.mark_place_init(Local::new(1));
+        pt.mark_place_init(Local::new(2));
+        pt.set_ref(Local::new(2), Local::new(1), None);
+        let stats = pt.stats();
+        assert_eq!((stats.nodes, stats.edges), (4, 3));
+        assert_eq!(stats.memory.tags, 1);
+        assert_eq!(stats.memory.stack_entries, 5);
+
+        pt.deallocate_local(Local::new(1)).unwrap();
+        let stats = pt.stats();
+        assert_eq!(stats.memory.live_allocations, 1);
+        assert_eq!(stats.memory.dead_allocations, 1);
+    }
+
+    #[test]
+    fn zst_places() {
+        let mut tcx = TyCtxt::from_primitives();
+        let empty_array = tcx.push(TyKind::Array(TyCtxt::U32, 0));
+        let zsts = tcx.push(TyKind::Tuple(vec![TyCtxt::UNIT, empty_array]));
+        let ty = tcx.push(TyKind::RawPtr(zsts, Mutability::Mut));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let unit = Local::new(1);
+        let array = Local::new(2);
+        let tuple = Local::new(3);
+        pt.allocate_local(unit, TyCtxt::UNIT);
+        pt.allocate_local(array, empty_array);
+        pt.allocate_local(tuple, zsts);
+
+        // Never written to, but there is nothing to initialise
+        let tuple_0 = Place::from_projected(tuple, &[ProjectionElem::TupleField(FieldIdx::new(0))]);
+        for place in [
+            Place::from_local(unit),
+            Place::from_local(array),
+            Place::from_local(tuple),
+            tuple_0,
+        ] {
+            assert!(pt.is_place_init(&place));
+        }
+
+        let ptr = Local::new(4);
+        pt.allocate_local(ptr, ty);
+        pt.set_ref(ptr, tuple, None);
+        let ptr = ptr.to_place_index(&pt).unwrap();
+        let tuple = tuple.to_place_index(&pt).unwrap();
+        let tag = pt.places[ptr].tag.expect("ref to a ZST is tagged");
+        assert!(pt.can_read_through(ptr, tuple));
+        assert!(pt.can_write_through(ptr, tuple));
+
+        // Writing a ZST pops nothing
+        pt.place_written(tuple);
+        assert_eq!(pt.places[ptr].tag, Some(tag));
+        assert!(pt.can_write_through(ptr, tuple));
+    }
+
+    #[test]
+    fn nested_tuple() {
+        let (pt, local, _, b, ..) = prepare_t();
+
+        let visited: Vec<TyId> = pt
+            .reachable_from_node(local.to_place_index(&pt).unwrap(), false)
+            .map(|ppath| pt.ty(ppath.target_index()))
+            .collect();
+        let root = pt.get_node(&Place::from_local(local)).unwrap();
+        let root_ty = pt.places[root].ty;
+        let b = pt.get_node(&b).unwrap();
+        let b_ty = pt.places[b].ty;
+        assert_eq!(
+            &visited,
+            &[
                 root_ty,     // (i8, (i16, i32), i64)
                 TyCtxt::I8,  // i8
                 b_ty,        // (i16, 132)
@@ -1509,6 +4378,46 @@ mod tests {
         assert!(!pt.overlap(&a, &d))
     }
 
+    /// A tuple nested depth times, each level adding a field and an array next to the previous one
+    fn nested_tuple_ty(tcx: &mut TyCtxt, depth: usize) -> TyId {
+        let arr = tcx.push(TyKind::Array(TyCtxt::U16, 3));
+        let mut ty = TyCtxt::I32;
+        for _ in 0..depth {
+            ty = tcx.push(TyKind::Tuple(vec![ty, TyCtxt::U8, arr, TyCtxt::UNIT]));
+        }
+        ty
+    }
+
+    #[test]
+    fn overlap_matches_subfields() {
+        let mut tcx = TyCtxt::from_primitives();
+        let nested = nested_tuple_ty(&mut tcx, 3);
+        let arr = tcx.push(TyKind::Array(nested, 2));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        pt.allocate_local(Local::new(1), nested);
+        pt.allocate_local(Local::new(2), arr);
+
+        // Places overlap if they share a subfield, or if they share bytes
+        let expected = |a: PlaceIndex, b: PlaceIndex| {
+            let b_sub = pt.transitive_subfields(b);
+            pt.places[a].alloc_id == pt.places[b].alloc_id
+                && (pt
+                    .transitive_subfields(a)
+                    .iter()
+                    .any(|sub| b_sub.contains(sub))
+                    || pt.places[a]
+                        .run_ptr
+                        .zip(pt.places[b].run_ptr)
+                        .is_some_and(|(a, b)| a.overlap(&b)))
+        };
+        let nodes: Vec<PlaceIndex> = pt.places.node_indices().collect();
+        for &a in &nodes {
+            for &b in &nodes {
+                assert_eq!(pt.overlap(a, b), expected(a, b), "{a:?} and {b:?}");
+            }
+        }
+    }
+
     #[test]
     fn pointers() {
         let mut tcx = TyCtxt::from_primitives();
@@ -1518,6 +4427,8 @@ mod tests {
         let inner_ty = tcx.push(TyKind::Tuple(vec![ptr]));
         // *const (*const i32,)
         let ty = tcx.push(TyKind::RawPtr(inner_ty, Mutability::Not));
+        // *const *const i32
+        let ptr_ptr = tcx.push(TyKind::RawPtr(ptr, Mutability::Not));
 
         let mut pt = PlaceTable::new(Rc::new(tcx));
         let root = Local::new(1);
@@ -1539,11 +4450,11 @@ mod tests {
         pt.set_ref(root, tuple, None);
 
         let visited: Vec<PlaceIndex> = pt
-            .reachable_from_node(root.to_place_index(&pt).unwrap())
+            .reachable_from_node(root.to_place_index(&pt).unwrap(), false)
             .map(|ppath| ppath.target_index())
             .collect();
 
-        // int is not reachable because it is behind a Field projection
+        // int is not reachable from root, as it is behind a second Deref
         assert_eq!(
             &visited,
             &[
@@ -1552,190 +4463,1236 @@ mod tests {
                 tuple_0.to_place_index(&pt).unwrap(),
             ]
         );
+        // nor from tuple, unless a path may dereference the pointer in its field
+        assert!(!pt
+            .reachable_from_node(tuple.to_place_index(&pt).unwrap(), false)
+            .any(|ppath| Some(ppath.target_index()) == int.to_place_index(&pt)));
+        let nested = pt.clone().with_nested_derefs();
+        let visited: Vec<PlaceIndex> = nested
+            .reachable_from_node(tuple.to_place_index(&pt).unwrap(), false)
+            .map(|ppath| ppath.target_index())
+            .collect();
+        assert_eq!(
+            &visited,
+            &[
+                tuple.to_place_index(&pt).unwrap(),
+                tuple_0.to_place_index(&pt).unwrap(),
+                int.to_place_index(&pt).unwrap(),
+            ]
+        );
+        let deep = nested.with_max_derefs_per_path(2);
+        let (to_int, _) = deep
+            .reachable_from_node(root.to_place_index(&deep).unwrap(), false)
+            .map(|ppath| (ppath.to_place(&deep), ppath.target_index()))
+            .find(|(_, target)| Some(*target) == int.to_place_index(&deep))
+            .expect("int is two Derefs away from root");
+        assert_eq!(
+            to_int,
+            Place::from_projected(
+                root,
+                &[
+                    ProjectionElem::Deref,
+                    ProjectionElem::TupleField(FieldIdx::from_usize(0)),
+                    ProjectionElem::Deref,
+                ]
+            )
+        );
+
+        // The chain ends at the first place that isn't a pointer
+        let int = int.to_place_index(&pt).unwrap();
+        assert_eq!(
+            pt.resolve_pointee_chain(root),
+            Some((tuple.to_place_index(&pt).unwrap(), 1))
+        );
+        assert_eq!(pt.resolve_pointee_chain(&tuple_0), Some((int, 1)));
+        assert_eq!(pt.resolve_pointee_chain(int), Some((int, 0)));
+
+        // double -[Deref]-> tuple.0 -[Deref]-> int
+        let double = Local::new(4);
+        pt.allocate_local(double, ptr_ptr);
+        assert_eq!(pt.resolve_pointee_chain(double), None);
+        pt.set_ref(double, &tuple_0, None);
+        assert_eq!(pt.resolve_pointee_chain(double), Some((int, 2)));
+
+        // An offset pointer on the way doesn't point to its pointee
+        pt.offset_ptr(tuple_0.to_place_index(&pt).unwrap(), 1);
+        assert_eq!(pt.resolve_pointee_chain(double), None);
     }
 
     #[test]
-    fn tuple_projection() {
+    fn compare_pointers() {
         let mut tcx = TyCtxt::from_primitives();
-        let ty = tcx.push(TyKind::Tuple(vec![TyCtxt::I8, TyCtxt::I32]));
-
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
         let mut pt = PlaceTable::new(Rc::new(tcx));
-        let local = Local::new(1);
-        pt.allocate_local(local, ty);
-
-        let place = pt
-            .reachable_from_node(local.to_place_index(&pt).unwrap())
-            .filter(|ppath| !ppath.path.is_empty())
-            .map(|ppath| ppath.to_place(&pt))
-            .next()
-            .unwrap();
 
-        assert!(matches!(
-            place.projection()[0],
-            ProjectionElem::TupleField(..)
-        ));
+        let array = pt.allocate_local(Local::new(1), t_array);
+        let other = pt.allocate_local(Local::new(2), TyCtxt::I32);
+        let elem = |pt: &PlaceTable, i: u64| {
+            pt.project_from_node(array, ProjectionElem::ConstantIndex { offset: i })
+                .unwrap()
+        };
+        let (first, second) = (elem(&pt, 0), elem(&pt, 1));
+        let pointer = |pt: &mut PlaceTable, local: usize, pointee: PlaceIndex| {
+            let ptr = pt.allocate_local(Local::new(local), t_ptr);
+            pt.mark_place_init(ptr);
+            pt.set_ref(ptr, pointee, None);
+            ptr
+        };
+        let a = pointer(&mut pt, 3, first);
+        let b = pointer(&mut pt, 4, first);
+        let c = pointer(&mut pt, 5, second);
+        let d = pointer(&mut pt, 6, other);
+
+        // Same pointee, same offset
+        assert_eq!(pt.pointer_identity(a), pt.pointer_identity(b));
+        assert_eq!(pt.compare_pointers(a, b), Some(Ordering::Equal));
+
+        // Offset onto another element of the array, which b has no provenance over
+        pt.offset_ptr(b, 2);
+        assert_eq!(pt.pointer_identity(b), Some((first, 2)));
+        assert_eq!(pt.compare_pointers(a, b), Some(Ordering::Less));
+        assert_eq!(pt.compare_pointers(b, c), Some(Ordering::Greater));
+
+        // Out of the array
+        pt.offset_ptr(b, 3);
+        assert_eq!(pt.compare_pointers(a, b), None);
+
+        // Different allocations
+        assert_eq!(pt.compare_pointers(a, d), None);
     }
 
     #[test]
-    fn recursive_init() {
-        let (mut pt, local, a, b, c, d, e) = prepare_t();
-
-        pt.mark_place_init(&a);
-        assert!(pt.is_place_init(&a));
-        assert!(!pt.is_place_init(local));
-
-        pt.mark_place_init(&b);
-        assert!(pt.is_place_init(&d));
-        assert!(pt.is_place_init(&e));
-        assert!(!pt.is_place_init(local));
+    fn offset_units() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::U64, Mutability::Not));
+        let t_array = tcx.push(TyKind::Array(TyCtxt::U8, 4));
+        let t_slice = tcx.push(TyKind::Slice(TyCtxt::U8));
+        let t_slice_ptr = tcx.push(TyKind::RawPtr(t_slice, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
 
-        pt.mark_place_init(&c);
-        assert!(pt.is_place_init(local));
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::U64);
+        let p = pt.allocate_local(Local::new(2), t_ptr);
+        pt.mark_place_init(x);
+        pt.mark_place_init(p);
+        pt.set_ref(p, x, None);
+
+        // The Offset binop counts u64s
+        pt.offset_ptr(p, 3);
+        assert_eq!(pt.get_offset(p), Some(3));
+        assert_eq!(pt.get_offset_bytes(p), Some(24));
+        assert!(pt.offseted(p));
+        pt.offset_ptr(p, -3);
+        assert_eq!(pt.get_offset_bytes(p), Some(0));
+        assert!(pt.has_offset_roundtripped(p));
+        assert!(!pt.offseted(p));
+
+        // A slice has no size to count bytes by
+        let array = pt.allocate_local(Local::new(3), t_array);
+        let slice = pt.slice_view(array).unwrap();
+        let q = pt.allocate_local(Local::new(4), t_slice_ptr);
+        pt.mark_place_init(array);
+        pt.mark_place_init(q);
+        pt.set_ref(q, slice, None);
+        pt.offset_ptr(q, 1);
+        assert_eq!(pt.get_offset(q), Some(1));
+        assert_eq!(pt.get_offset_bytes(q), None);
     }
 
     #[test]
-    fn recursive_uninit() {
-        let (mut pt, local, a, b, c, d, e) = prepare_t();
-        pt.mark_place_init(local);
+    fn referencers() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::U32, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
 
-        pt.mark_place_uninit(&d);
-        assert!(!pt.is_place_init(&d));
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::U32);
+        let y = pt.allocate_local(Local::new(2), TyCtxt::U32);
+        let p = pt.allocate_local(Local::new(3), t_ref);
+        let q = pt.allocate_local(Local::new(4), t_ref);
+        pt.mark_place_init(x);
+        pt.mark_place_init(y);
+        pt.set_ref(p, x, None);
+        pt.set_ref(q, x, None);
+        assert_eq!(pt.referencers_of(x), [p, q]);
+        assert!(pt.referencers_of(y).is_empty());
+
+        // Retargeting p replaces its edge into x
+        pt.set_ref(p, y, None);
+        assert_eq!(pt.referencers_of(x), [q]);
+        assert_eq!(pt.referencers_of(y), [p]);
+
+        // A reference to an uninit place is gone
+        pt.mark_place_uninit(x);
+        assert!(pt.referencers_of(x).is_empty());
+        assert_eq!(pt.pointee(q), None);
+        assert_eq!(pt.referencers_of(y), [p]);
+    }
 
-        pt.mark_place_uninit(&e);
-        assert!(!pt.is_place_init(&b));
+    #[test]
+    fn sink_distances() {
+        let tcx = TyCtxt::from_primitives();
+        let mut pt = PlaceTable::new(Rc::new(tcx));
 
-        pt.mark_place_uninit(local);
-        assert!(!pt.is_place_init(&a));
-        assert!(!pt.is_place_init(&c));
+        pt.enter_fn0(&Body::new(&[], TyCtxt::U32, false));
+        let a = pt.allocate_local(Local::new(1), TyCtxt::U32);
+        let b = pt.allocate_local(Local::new(2), TyCtxt::U32);
+        let c = pt.allocate_local(Local::new(3), TyCtxt::U32);
+        pt.record_flow(b, &[a]);
+        pt.record_flow(Local::RET, &[b]);
+        assert_eq!(pt.distance_to_sink(Local::RET), Some(0));
+        assert_eq!(pt.distance_to_sink(b), Some(1));
+        assert_eq!(pt.distance_to_sink(a), Some(2));
+        assert_eq!(pt.distance_to_sink(c), None);
+
+        // The distances are recomputed once c flows somewhere observable
+        pt.record_flow(b, &[c]);
+        assert_eq!(pt.distance_to_sink(c), Some(2));
+        assert_eq!(pt.distance_to_sink(a), Some(2));
     }
 
     #[test]
-    fn complexity() {
-        let (mut pt, local, a, b, c, d, e) = prepare_t();
+    fn subtree_walk_order() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_inner = tcx.push(TyKind::Tuple(vec![TyCtxt::U16, TyCtxt::U32]));
+        let t_array = tcx.push(TyKind::Array(TyCtxt::U8, 3));
+        tcx.push(TyKind::Array(TyCtxt::U8, 2));
+        let t_outer = tcx.push(TyKind::Tuple(vec![TyCtxt::U8, t_inner, t_array]));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
 
-        pt.update_complexity(&a, Rvalue::Use(Operand::Constant(1.into())).complexity(&pt));
-        assert_eq!(a.complexity(&pt), 1);
-        assert_eq!(Place::from(local).complexity(&pt), 1);
-        pt.update_complexity(&c, Rvalue::Use(Operand::Constant(1.into())).complexity(&pt));
-        assert_eq!(c.complexity(&pt), 1);
-        assert_eq!(Place::from(local).complexity(&pt), 1);
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let a = pt.allocate_local(Local::new(1), t_outer);
+        let b = pt.allocate_local(Local::new(2), t_outer);
 
-        pt.update_complexity(
-            &d,
-            Rvalue::BinaryOp(
-                BinOp::Add,
-                Operand::Copy(a.clone()),
-                Operand::Copy(c.clone()),
-            )
-            .complexity(&pt),
-        );
-        assert_eq!(d.complexity(&pt), 2);
+        // The projections from the local, through the parents
+        let path = |pt: &PlaceTable, mut node: PlaceIndex| {
+            let mut projs = vec![];
+            while let Some((proj, parent)) = pt.parent(node) {
+                projs.push(proj);
+                node = parent;
+            }
+            projs.reverse();
+            projs
+        };
+        let walk = |pt: &PlaceTable, root: PlaceIndex| {
+            let mut paths = vec![];
+            pt.walk_subtree(root, |node| {
+                paths.push(path(pt, node));
+                VisitAction::Continue
+            });
+            paths
+        };
 
-        pt.update_complexity(&e, Rvalue::Use(Operand::Constant(1.into())).complexity(&pt));
-        assert_eq!(b.complexity(&pt), 2);
+        let field = |i| ProjectionElem::TupleField(FieldIdx::new(i));
+        let elem = |offset| ProjectionElem::ConstantIndex { offset };
+        let sub = |from, to| ProjectionElem::Subslice { from, to };
+        let expected = vec![
+            vec![],
+            vec![field(0)],
+            vec![field(1)],
+            vec![field(1), field(0)],
+            vec![field(1), field(1)],
+            vec![field(2)],
+            vec![field(2), elem(0)],
+            vec![field(2), elem(1)],
+            vec![field(2), elem(2)],
+            vec![field(2), sub(0, 2)],
+            vec![field(2), sub(1, 3)],
+        ];
+        assert_eq!(walk(&pt, a), expected);
+        assert_eq!(walk(&pt, b), expected);
+
+        let array = pt.project_from_node(a, field(2)).unwrap();
+        let projs: Vec<ProjectionElem> = pt.subfields(array).map(|(proj, _)| proj).collect();
+        assert_eq!(projs, [elem(0), elem(1), elem(2), sub(0, 2), sub(1, 3)]);
+        for (proj, sub) in pt.subfields(array) {
+            assert_eq!(pt.project_from_node(array, proj), Some(sub));
+        }
 
-        assert_eq!(Place::from(local).complexity(&pt), 2);
+        // The elements of a subslice are the array's
+        let tail = pt.project_from_node(array, sub(1, 3)).unwrap();
+        let (_, last) = pt.subfields(tail).last().unwrap();
+        assert_eq!(pt.parent(last), Some((elem(2), array)));
+        assert_eq!(pt.parent(a), None);
     }
 
     #[test]
-    fn prim_arrays() {
+    fn diamond_visited_once() {
         let mut tcx = TyCtxt::from_primitives();
-        let ty = tcx.push(TyKind::Array(TyCtxt::I32, 4));
-
+        let t_inner = tcx.push(TyKind::Tuple(vec![TyCtxt::I32]));
+        let t_outer = tcx.push(TyKind::Tuple(vec![t_inner, t_inner]));
         let mut pt = PlaceTable::new(Rc::new(tcx));
-        let local = Local::new(1);
-        let local_pidx = pt.allocate_local(local, ty);
 
-        let one = Local::new(2);
-        pt.allocate_local(one, TyCtxt::USIZE);
-        pt.assign_literal(one, Some(Literal::Uint(1, UintTy::Usize)));
-
-        let proj = ProjectionElem::Index(one);
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let a = pt.allocate_local(Local::new(1), t_outer);
+        let field = |pt: &PlaceTable, path: &[usize]| {
+            let projs: Vec<ProjectionElem> = path
+                .iter()
+                .map(|i| ProjectionElem::TupleField(FieldIdx::new(*i)))
+                .collect();
+            Place::from_projected(Local::new(1), &projs)
+                .to_place_index(pt)
+                .unwrap()
+        };
+        let (b, c, d) = (field(&pt, &[0]), field(&pt, &[1]), field(&pt, &[0, 0]));
+        // a.1 shares a.0.0 with a.0, so a.0.0 is reachable from a through both
+        pt.places
+            .add_edge(c, d, ProjectionElem::TupleField(FieldIdx::new(1)));
+
+        let mut visited = vec![];
+        pt.walk_subtree(a, |node| {
+            visited.push(node);
+            VisitAction::Continue
+        });
+        assert_eq!(visited.iter().filter(|node| **node == d).count(), 1);
+        assert_eq!(visited.len(), 5);
 
-        let local_one = pt.project_from_node(local_pidx, proj).unwrap();
+        let mut updated = vec![];
+        pt.update_transitive_subfields(a, |_, node| {
+            updated.push(node);
+            VisitAction::Continue
+        });
+        assert_eq!(updated.len(), 5);
 
-        assert_eq!(
-            pt.places[local_pidx].alloc_id,
-            pt.places[local_one].alloc_id
-        );
+        // Each superfield once, a only after both of its fields
+        let mut supers = vec![];
+        pt.update_transitive_superfields(d, |_, node| {
+            supers.push(node);
+            VisitAction::Continue
+        });
+        assert_eq!(supers.len(), 3);
+        assert_eq!(supers.last(), Some(&a));
 
-        assert_eq!(
-            pt.places[local_one].run_ptr.unwrap().run_and_offset,
-            pt.places[local_pidx]
-                .run_ptr
-                .unwrap()
-                .run_and_offset
-                .offset(
-                    BasicMemory::ty_size(TyCtxt::I32, &pt.tcx)
-                        .unwrap()
-                        .bytes_usize() as isize
-                )
-        )
+        pt.update_complexity(d, 5);
+        for node in [a, b, c, d] {
+            assert_eq!(pt.places[node].complexity, 5);
+        }
+        pt.update_complexity(d, 2);
+        assert_eq!(pt.places[a].complexity, 2);
     }
+
     #[test]
-    fn composite_arrays() {
+    fn raw_pointer_aliases() {
         let mut tcx = TyCtxt::from_primitives();
-        let elem_ty = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I64]));
-        let ty = tcx.push(TyKind::Array(elem_ty, 4));
+        let t_tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I32]));
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Mut));
         let mut pt = PlaceTable::new(Rc::new(tcx));
-        let local = Local::new(1);
-        let local_pidx = pt.allocate_local(local, ty);
 
-        let one = Local::new(2);
-        pt.allocate_local(one, TyCtxt::USIZE);
-        pt.assign_literal(one, Some(Literal::Uint(1, UintTy::Usize)));
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let tuple = pt.allocate_local(Local::new(1), t_tuple);
+        pt.mark_place_init(tuple);
+        let field = |pt: &PlaceTable, i: usize| {
+            pt.project_from_node(tuple, ProjectionElem::TupleField(FieldIdx::new(i)))
+                .unwrap()
+        };
+        let (field0, field1) = (field(&pt, 0), field(&pt, 1));
+        pt.assign_literal(field0, Some(Literal::Int(1, IntTy::I32)));
+        pt.assign_literal(field1, Some(Literal::Int(2, IntTy::I32)));
+        assert!(pt.composite_known_val(tuple).is_some());
+
+        // _2 = &raw mut _1.0; _3 = &raw mut _1.0
+        let p1 = pt.allocate_local(Local::new(2), t_ptr);
+        let p2 = pt.allocate_local(Local::new(3), t_ptr);
+        for p in [p1, p2] {
+            pt.mark_place_init(p);
+            pt.set_ref(p, field0, None);
+        }
+        let aliases = pt.aliases_of_pointee(p1);
+        assert_eq!(aliases, [tuple, field0, p1, p2]);
+
+        // (*_2) = unknown
+        pt.invalidate_knowledge(&aliases);
+        pt.place_written_through(p1, field0);
+        assert!(pt.known_val(field0).is_none());
+        assert!(pt.known_val(tuple).is_none());
+        assert!(pt.composite_known_val(tuple).is_none());
+        assert!(matches!(
+            pt.known_val(field1),
+            Some(Literal::Int(2, IntTy::I32))
+        ));
 
-        // local[one].0
-        let one_zero = Place::from_projected(
-            local,
-            &[
-                ProjectionElem::Index(one),
-                ProjectionElem::TupleField(FieldIdx::new(0)),
-            ],
-        );
-        let one_zero = pt.get_node(&one_zero).unwrap();
-        assert_eq!(pt.places[one_zero].ty, TyCtxt::I32);
-        assert_eq!(pt.places[local_pidx].alloc_id, pt.places[one_zero].alloc_id);
+        // An offset pointer aliases the element it is offset to
+        let array = pt.allocate_local(Local::new(4), t_array);
+        pt.mark_place_init(array);
+        let elem = |pt: &PlaceTable, i: u64| {
+            pt.project_from_node(array, ProjectionElem::ConstantIndex { offset: i })
+                .unwrap()
+        };
+        let p3 = pt.allocate_local(Local::new(5), t_ptr);
+        pt.mark_place_init(p3);
+        pt.set_ref(p3, elem(&pt, 0), None);
+        let elem_size = Some(Size::from_bytes(4));
+        pt.places[p3].offset = Some(PtrOffset {
+            elems: 2,
+            elem_size,
+        });
+        assert_eq!(pt.aliases_of_pointee(p3), [array, elem(&pt, 2)]);
+        pt.places[p3].offset = Some(PtrOffset {
+            elems: 4,
+            elem_size,
+        });
+        assert!(pt.aliases_of_pointee(p3).is_empty());
     }
 
     #[test]
-    fn shared_reference() {
+    fn deref_kinds() {
         let mut tcx = TyCtxt::from_primitives();
+        let tys = [
+            (
+                tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not)),
+                DerefKind::SharedRef,
+            ),
+            (
+                tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut)),
+                DerefKind::MutRef,
+            ),
+            (
+                tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not)),
+                DerefKind::ConstPtr,
+            ),
+            (
+                tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Mut)),
+                DerefKind::MutPtr,
+            ),
+        ];
+        let mut pt = PlaceTable::new(Rc::new(tcx));
 
-        let t_i16_i32 = tcx.push(TyKind::Tuple(vec![TyCtxt::I16, TyCtxt::I32]));
-        let t_ref = tcx.push(TyKind::Ref(t_i16_i32, Mutability::Not));
-        let t_ptr = tcx.push(TyKind::RawPtr(t_i16_i32, Mutability::Not));
+        let x = pt.allocate_local(Local::new(0), TyCtxt::I32);
+        pt.mark_place_init(x);
+        for (i, (ty, kind)) in tys.into_iter().enumerate() {
+            let pointer = pt.allocate_local(Local::new(i + 1), ty);
+            pt.mark_place_init(pointer);
+            pt.set_ref(pointer, x, None);
+            let edge = pt.ref_edge(pointer).unwrap();
+            assert_eq!(pt.deref_kind(edge), kind);
+            assert_eq!(kind.is_read_only(), kind == DerefKind::SharedRef);
+        }
+    }
 
+    #[test]
+    #[should_panic(expected = "_1.0 is i32 in the place table but i64 in the local decls")]
+    fn desynced_decl_ty() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_pair = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I32]));
+        let t_wide = tcx.push(TyKind::Tuple(vec![TyCtxt::I64, TyCtxt::I32]));
         let mut pt = PlaceTable::new(Rc::new(tcx));
 
-        let root = Local::new(1);
-        pt.allocate_local(root, t_i16_i32);
-        pt.mark_place_init(root);
+        let mut body = Body::new(&[t_pair], TyCtxt::UNIT, false);
+        pt.enter_fn0(&body);
+        let field = Place::from_projected(
+            Local::new(1),
+            &[ProjectionElem::TupleField(FieldIdx::new(0))],
+        );
+        pt.assert_ty_consistent(&field, &body.local_decls);
 
-        let root_0 = Place::from_projected(root, &[ProjectionElem::TupleField(FieldIdx::new(0))])
-            .to_place_index(&pt)
-            .unwrap();
-        let root_1 = Place::from_projected(root, &[ProjectionElem::TupleField(FieldIdx::new(1))])
-            .to_place_index(&pt)
-            .unwrap();
+        // The decl changes behind the table's back
+        body.local_decls[Local::new(1)].ty = t_wide;
+        pt.assert_ty_consistent(&field, &body.local_decls);
+    }
 
-        // root_ptr1 = addr_of!(root)
-        let root_ptr1 = Local::new(2);
-        pt.allocate_local(root_ptr1, t_ptr);
-        pt.set_ref(root_ptr1, root, None);
-        let root_ptr1_p = root_ptr1.to_place_index(&pt).unwrap();
+    #[test]
+    fn fn_targets() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_fn = tcx.push(TyKind::FnPtr(vec![TyCtxt::I32], TyCtxt::UNIT));
+        let t_pair = tcx.push(TyKind::Tuple(vec![t_fn, TyCtxt::I32]));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
 
-        // root_ref = &root
-        let root_ref = Local::new(3);
-        pt.allocate_local(root_ref, t_ref);
-        pt.set_ref(root_ref, root, None);
-        let root_ref_p = root_ref.to_place_index(&pt).unwrap();
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let f = pt.allocate_local(Local::new(1), t_fn);
+        let pair = pt.allocate_local(Local::new(2), t_pair);
+        let g = pt.allocate_local(Local::new(3), t_fn);
+        let first = pt
+            .project_from_node(pair, ProjectionElem::TupleField(FieldIdx::new(0)))
+            .unwrap();
 
-        // (*root_ref).0 can be read
-        assert!(pt.can_read_through(root_ref_p, root_0));
+        // _1 = fn1 as fn(i32)
+        pt.mark_place_init(f);
+        pt.assign_fn_target(f, Some(Function::new(1)));
+        assert_eq!(pt.known_fn_target(f), Some(Function::new(1)));
+
+        // _2 = (_1, 0); _3 = _2.0
+        pt.mark_place_init(pair);
+        pt.copy_place(first, f);
+        pt.mark_place_init(g);
+        pt.copy_place(g, first);
+        assert_eq!(pt.known_fn_target(first), Some(Function::new(1)));
+        assert_eq!(pt.known_fn_target(g), Some(Function::new(1)));
+
+        // Overwriting the pair with something unknown forgets its field's target
+        pt.assign_literal(pair, None);
+        assert_eq!(pt.known_fn_target(first), None);
+        assert_eq!(pt.known_fn_target(g), Some(Function::new(1)));
+        pt.mark_place_uninit(g);
+        assert_eq!(pt.known_fn_target(g), None);
+    }
 
-        // (*root_ref).0 cannot be written to
-        assert!(!pt.can_write_through(root_ref_p, root_0));
+    #[test]
+    fn subslices() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 8));
+        let t_sub = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let mid_range = ProjectionElem::Subslice { from: 2, to: 6 };
+        assert_eq!(t_array.projected_ty(&tcx, &[mid_range]), t_sub);
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let a = pt.allocate_local(Local::new(1), t_array);
+        let b = pt.allocate_local(Local::new(2), t_array);
+        let sub = |pt: &PlaceTable, array, from, to| {
+            pt.project_from_node(array, ProjectionElem::Subslice { from, to })
+                .unwrap()
+        };
+        let elem = |pt: &PlaceTable, array, offset| {
+            pt.project_from_node(array, ProjectionElem::ConstantIndex { offset })
+                .unwrap()
+        };
+
+        // Only [i32; 4] exists, so there are subslices for the five ranges of that length
+        let mid = sub(&pt, a, 2, 6);
+        assert_eq!(pt.ty(mid), t_sub);
+        assert_eq!(
+            pt.places
+                .edges_directed(a, Direction::Outgoing)
+                .filter(|e| matches!(e.weight(), ProjectionElem::Subslice { .. }))
+                .count(),
+            5
+        );
+        assert_eq!(
+            pt.project_from_node(mid, ProjectionElem::ConstantIndex { offset: 0 }),
+            Some(elem(&pt, a, 2))
+        );
+
+        assert!(pt.overlap(mid, a));
+        assert!(pt.overlap(a, mid));
+        assert!(pt.overlap(mid, elem(&pt, a, 5)));
+        assert!(!pt.overlap(mid, elem(&pt, a, 1)));
+        assert!(!pt.overlap(elem(&pt, a, 6), mid));
+        assert!(pt.overlap(mid, sub(&pt, a, 4, 8)));
+        assert!(!pt.overlap(sub(&pt, a, 0, 4), sub(&pt, a, 4, 8)));
+        assert!(!pt.overlap(mid, sub(&pt, b, 2, 6)));
+
+        // a[2..6] = b[4..8]
+        pt.mark_place_init(b);
+        for i in 0..8 {
+            pt.assign_literal(elem(&pt, b, i), Some(Literal::Int(i as i128, IntTy::I32)));
+        }
+        pt.copy_place(mid, sub(&pt, b, 4, 8));
+        for i in 0..4 {
+            assert!(matches!(
+                pt.known_val(elem(&pt, a, 2 + i)),
+                Some(&Literal::Int(v, IntTy::I32)) if v == 4 + i as i128
+            ));
+        }
+        assert!(pt.is_place_init(mid));
+        assert!(!pt.is_place_init(elem(&pt, a, 1)));
+        assert_eq!(pt.init_state(a), InitState::Partial);
+        assert_eq!(pt.init_state(sub(&pt, a, 3, 7)), InitState::Partial);
+    }
+
+    #[test]
+    fn cast_ptr_derivation() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_bytes = tcx.push(TyKind::Array(TyCtxt::U8, 4));
+        let t_int_ptr = tcx.push(TyKind::RawPtr(TyCtxt::U32, Mutability::Not));
+        let t_bytes_ptr = tcx.push(TyKind::RawPtr(t_bytes, Mutability::Not));
+        let t_signed_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
+        let t_wide_ptr = tcx.push(TyKind::RawPtr(TyCtxt::U64, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let int = pt.allocate_local(Local::new(1), TyCtxt::U32);
+        let p = pt.allocate_local(Local::new(2), t_int_ptr);
+        let q = pt.allocate_local(Local::new(3), t_bytes_ptr);
+        let r = pt.allocate_local(Local::new(4), t_signed_ptr);
+        let w = pt.allocate_local(Local::new(5), t_wide_ptr);
+
+        // _2 = &raw const _1; _3 = _2 as *const [u8; 4]
+        pt.mark_place_init(p);
+        pt.set_ref(p, int, None);
+        pt.mark_place_init(q);
+        pt.cast_ptr(q, p);
+        assert_eq!(pt.pointee(q), Some(int));
+        assert_eq!(pt.tag_of(q), pt.tag_of(p));
+        assert!(pt.reinterprets(q));
+        assert!(!pt.reinterprets(p));
+        assert_eq!(
+            pt.places[q].derivation,
+            Some(Derivation {
+                origin: p,
+                casts: smallvec![t_bytes_ptr],
+            })
+        );
+
+        // The u32 can't be projected to as a [u8; 4], only read as bytes
+        let mut deref = Place::from_local(Local::new(3));
+        deref.project(ProjectionElem::Deref);
+        assert!(deref.to_place_index(&pt).is_none());
+        assert!(!pt
+            .reachable_nodes()
+            .any(|path| path.source() == q && path.target_index() == int));
+        assert_eq!(pt.deref_init_state(q), Some(InitState::Uninit));
+        pt.mark_place_init(int);
+        pt.assign_literal(int, Some(Literal::Uint(0x04030201, UintTy::U32)));
+        assert_eq!(pt.deref_init_state(q), Some(InitState::Full));
+        let bytes = 0x04030201u32
+            .to_ne_bytes()
+            .map(|b| AbstractByte::Init(Some(b)));
+        assert_eq!(pt.deref_bytes(q), Some(&bytes[..]));
+
+        // Casting again extends the chain from the same origin
+        pt.mark_place_init(r);
+        pt.cast_ptr(r, q);
+        assert_eq!(
+            pt.places[r].derivation,
+            Some(Derivation {
+                origin: p,
+                casts: smallvec![t_bytes_ptr, t_signed_ptr],
+            })
+        );
+        assert!(matches!(
+            pt.read_through(r),
+            Some(Literal::Int(0x04030201, IntTy::I32))
+        ));
+        assert!(matches!(
+            pt.read_through(p),
+            Some(Literal::Uint(0x04030201, UintTy::U32))
+        ));
+
+        // A u64 doesn't fit in the u32
+        pt.mark_place_init(w);
+        pt.cast_ptr(w, p);
+        assert_eq!(pt.pointee(w), None);
+        assert_eq!(pt.deref_bytes(w), None);
+    }
+
+    #[test]
+    fn init_states() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_padded = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I64]));
+        let t_packed = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I32]));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        for (local, ty) in [(1, t_padded), (2, t_packed)] {
+            let pair = pt.allocate_local(Local::new(local), ty);
+            let field = |pt: &PlaceTable, i: usize| {
+                pt.project_from_node(pair, ProjectionElem::TupleField(FieldIdx::new(i)))
+                    .unwrap()
+            };
+            assert_eq!(pt.init_state(pair), InitState::Uninit);
+            pt.mark_place_init(field(&pt, 0));
+            assert_eq!(pt.init_state(pair), InitState::Partial);
+            assert!(!pt.is_place_init(pair));
+            pt.mark_place_init(field(&pt, 1));
+            assert_eq!(pt.init_state(pair), InitState::Full);
+            assert!(pt.is_place_init(pair));
+            pt.mark_place_uninit(field(&pt, 0));
+            assert_eq!(pt.init_state(pair), InitState::Partial);
+            assert_eq!(pt.init_state(field(&pt, 0)), InitState::Uninit);
+        }
+    }
+
+    #[test]
+    fn copy_partial_init() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let t_padded = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I64, t_ref]));
+        let t_packed = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I32]));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        pt.assign_literal(x, Some(Literal::Int(1, IntTy::I32)));
+        let field = |pt: &PlaceTable, local: usize, i: usize| {
+            Place::from_projected(
+                Local::new(local),
+                &[ProjectionElem::TupleField(FieldIdx::new(i))],
+            )
+            .to_place_index(pt)
+            .unwrap()
+        };
+        let inits = |pt: &PlaceTable, local: usize, n: usize| -> Vec<bool> {
+            (0..n)
+                .map(|i| pt.is_place_init(field(pt, local, i)))
+                .collect()
+        };
+
+        // _2 = (1, uninit, &_1)
+        pt.allocate_local(Local::new(2), t_padded);
+        pt.mark_place_init(field(&pt, 2, 0));
+        pt.assign_literal(field(&pt, 2, 0), Some(Literal::Int(2, IntTy::I32)));
+        pt.mark_place_init(field(&pt, 2, 2));
+        pt.set_ref(field(&pt, 2, 2), x, None);
+        pt.allocate_local(Local::new(3), t_padded);
+        pt.mark_place_init(Local::new(3));
+        pt.copy_place(Local::new(3), Local::new(2));
+        assert_eq!(inits(&pt, 3, 3), [true, false, true]);
+        assert!(!pt.is_place_init(Local::new(3)));
+        assert_eq!(pt.pointee(field(&pt, 3, 2)), Some(x));
+
+        // Without padding, the fields are copied along with the tuple's run
+        pt.allocate_local(Local::new(4), t_packed);
+        pt.mark_place_init(field(&pt, 4, 1));
+        pt.assign_literal(field(&pt, 4, 1), Some(Literal::Int(3, IntTy::I32)));
+        pt.allocate_local(Local::new(5), t_packed);
+        pt.copy_place(Local::new(5), Local::new(4));
+        assert_eq!(inits(&pt, 5, 2), [false, true]);
+
+        // Only init is mirrored: _3.0 and _3.2 go, _3.1 comes in with an unknown value
+        pt.allocate_local(Local::new(6), t_padded);
+        pt.mark_place_init(field(&pt, 6, 1));
+        pt.copy_init_only(Local::new(3), Local::new(6));
+        assert_eq!(inits(&pt, 3, 3), [false, true, false]);
+        assert_eq!(pt.pointee(field(&pt, 3, 2)), None);
+        assert!(pt.known_val(field(&pt, 3, 1)).is_none());
+        assert_eq!(inits(&pt, 6, 3), [false, true, false]);
+
+        pt.mark_place_init(Local::new(5));
+        pt.copy_init_only(Local::new(5), Local::new(4));
+        assert_eq!(inits(&pt, 5, 2), [false, true]);
+    }
+
+    #[test]
+    fn copy_nested_pointers() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ints = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let t_ptrs = tcx.push(TyKind::Array(t_ptr, 2));
+        let t_refs = tcx.push(TyKind::Array(t_ref, 2));
+        let t_ptrs_u8 = tcx.push(TyKind::Tuple(vec![t_ptrs, TyCtxt::U8]));
+        let t_refs_u8 = tcx.push(TyKind::Tuple(vec![t_refs, TyCtxt::U8]));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let ints = pt.allocate_local(Local::new(1), t_ints);
+        pt.mark_place_init(ints);
+        let int = |pt: &PlaceTable, i: u64| {
+            pt.project_from_node(ints, ProjectionElem::ConstantIndex { offset: i })
+                .unwrap()
+        };
+        // local.0[i]
+        let elem = |pt: &PlaceTable, local: usize, i: u64| {
+            Place::from_projected(
+                Local::new(local),
+                &[
+                    ProjectionElem::TupleField(FieldIdx::new(0)),
+                    ProjectionElem::ConstantIndex { offset: i },
+                ],
+            )
+            .to_place_index(pt)
+            .unwrap()
+        };
+
+        // src: ([*const i32; 2], u8) = ([&raw ints[0] offset out of bounds, &raw ints[1] + 2], _)
+        for local in 2..=5 {
+            let ty = if local <= 3 { t_ptrs_u8 } else { t_refs_u8 };
+            let place = pt.allocate_local(Local::new(local), ty);
+            pt.mark_place_init(place);
+        }
+        pt.set_ref(elem(&pt, 2, 0), int(&pt, 0), None);
+        pt.offset_ptr(elem(&pt, 2, 0), 7);
+        pt.set_ref(elem(&pt, 2, 1), int(&pt, 1), None);
+        pt.offset_ptr(elem(&pt, 2, 1), 2);
+        // dst already points somewhere else
+        pt.set_ref(elem(&pt, 3, 0), int(&pt, 2), None);
+
+        pt.copy_place(Local::new(3), Local::new(2));
+        for i in 0..2 {
+            let (src, dst) = (elem(&pt, 2, i), elem(&pt, 3, i));
+            assert_eq!(pt.pointer_identity(dst), pt.pointer_identity(src));
+            assert_eq!(pt.places[dst].tag, pt.places[src].tag);
+        }
+        assert_eq!(pt.pointer_identity(elem(&pt, 3, 0)), Some((int(&pt, 0), 7)));
+        assert_eq!(pt.pointer_identity(elem(&pt, 3, 1)), Some((int(&pt, 1), 2)));
+        assert!(pt.is_place_init(Local::new(3)));
+
+        // The same for references, whose bytes are uninit when they lose their pointee
+        pt.set_ref(elem(&pt, 4, 0), int(&pt, 0), None);
+        pt.set_ref(elem(&pt, 4, 1), int(&pt, 1), None);
+        pt.set_ref(elem(&pt, 5, 0), int(&pt, 2), None);
+        pt.set_ref(elem(&pt, 5, 1), int(&pt, 3), None);
+        pt.copy_place(Local::new(5), Local::new(4));
+        for i in 0..2 {
+            let (src, dst) = (elem(&pt, 4, i), elem(&pt, 5, i));
+            assert_eq!(pt.pointee(dst), Some(int(&pt, i)));
+            assert_eq!(pt.places[dst].tag, pt.places[src].tag);
+        }
+        assert!(pt.is_place_init(Local::new(5)));
+    }
+
+    #[test]
+    fn offset_within_array() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
+        let t_array_ptr = tcx.push(TyKind::RawPtr(t_array, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let array = pt.allocate_local(Local::new(1), t_array);
+        pt.mark_place_init(array);
+        let elem = |pt: &PlaceTable, i: u64| {
+            pt.project_from_node(array, ProjectionElem::ConstantIndex { offset: i })
+                .unwrap()
+        };
+        let (first, third) = (elem(&pt, 0), elem(&pt, 2));
+        pt.assign_literal(first, Some(Literal::Int(1, IntTy::I32)));
+        pt.assign_literal(third, Some(Literal::Int(3, IntTy::I32)));
+
+        // addr_of!(arr) as *const i32
+        let array_ptr = pt.allocate_local(Local::new(2), t_array_ptr);
+        pt.mark_place_init(array_ptr);
+        pt.set_ref(array_ptr, array, None);
+        let ptr = pt.allocate_local(Local::new(3), t_ptr);
+        pt.cast_ptr(ptr, array_ptr);
+        let mut deref = Place::from_local(Local::new(3));
+        deref.project(ProjectionElem::Deref);
+        let reachable = |pt: &PlaceTable| {
+            pt.reachable_nodes()
+                .any(|ppath| ppath.to_place(pt) == deref)
+        };
+
+        pt.offset_ptr(ptr, 2);
+        assert!(!pt.offseted(ptr));
+        assert_eq!(deref.to_place_index(&pt), Some(third));
+        assert!(pt.can_read_through(ptr, third));
+        assert!(reachable(&pt));
+        assert!(matches!(
+            pt.read_literal(&deref),
+            Some(Literal::Int(3, IntTy::I32))
+        ));
+
+        // Back onto the first element
+        pt.offset_ptr(ptr, -2);
+        assert_eq!(deref.to_place_index(&pt), Some(first));
+        assert!(matches!(
+            pt.read_literal(&deref),
+            Some(Literal::Int(1, IntTy::I32))
+        ));
+
+        // Out of bounds
+        pt.offset_ptr(ptr, 4);
+        assert!(pt.offseted(ptr));
+        assert_eq!(pt.pointer_identity(ptr), Some((first, 4)));
+        assert!(!reachable(&pt));
+        pt.offset_ptr(ptr, -5);
+        assert!(pt.offseted(ptr));
+        assert!(!reachable(&pt));
+
+        // &raw const arr[0] only has provenance over the first element
+        let elem_ptr = pt.allocate_local(Local::new(4), t_ptr);
+        pt.mark_place_init(elem_ptr);
+        pt.set_ref(elem_ptr, first, None);
+        pt.offset_ptr(elem_ptr, 1);
+        assert!(pt.offseted(elem_ptr));
+        assert_eq!(pt.pointer_identity(elem_ptr), Some((first, 1)));
+        assert!(!pt.can_read_through(elem_ptr, elem(&pt, 1)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let array = pt.allocate_local(Local::new(1), t_array);
+        pt.mark_place_init(array);
+        let x = pt.allocate_local(Local::new(2), TyCtxt::I32);
+        pt.mark_place_init(x);
+        pt.assign_literal(x, Some(Literal::Int(-4, IntTy::I32)));
+        let r = pt.allocate_local(Local::new(3), t_ref);
+        pt.mark_place_init(r);
+        pt.set_ref(r, x, None);
+        let p = pt.allocate_local(Local::new(4), t_ptr);
+        pt.mark_place_init(p);
+        let first = pt
+            .project_from_node(array, ProjectionElem::ConstantIndex { offset: 0 })
+            .unwrap();
+        pt.set_ref(p, first, None);
+        pt.offset_ptr(p, 2);
+        pt.allocate_local(Local::new(5), TyCtxt::UNIT);
+
+        // fn(x: i32), with x moved in
+        pt.enter_fn(
+            &Body::new(&[TyCtxt::I32], TyCtxt::UNIT, false),
+            &[Operand::Move(Place::from_local(Local::new(2)))],
+            &Place::from_local(Local::new(5)),
+        );
+
+        let json = serde_json::to_string(&pt).unwrap();
+        let mut restored: PlaceTable = serde_json::from_str(&json).unwrap();
+
+        let queries = |pt: &PlaceTable| {
+            let nodes: Vec<String> = pt
+                .places
+                .node_indices()
+                .map(|node| {
+                    let raw = pt.ty(node).is_raw_ptr(&pt.tcx);
+                    format!(
+                        "{:?} {} {} {} {:?} {:?} {:?} {:?}",
+                        pt.ty(node),
+                        pt.is_place_live(node),
+                        pt.is_place_init(node),
+                        pt.get_complexity(node),
+                        pt.known_val(node),
+                        raw.then(|| pt.get_offset(node)),
+                        raw.then(|| pt.pointer_identity(node)),
+                        pt.places[node].tag,
+                    )
+                })
+                .collect();
+            let places: Vec<Place> = pt
+                .reachable_nodes()
+                .map(|ppath| ppath.to_place(pt))
+                .collect();
+            (
+                nodes,
+                places,
+                pt.moved_in_args_stack().collect::<Vec<_>>(),
+                pt.return_dest_stack().collect::<Vec<_>>(),
+                pt.stats(),
+                pt.to_dot(),
+            )
+        };
+        assert_eq!(queries(&pt), queries(&restored));
+        assert!(restored.moved_in_args_stack().any(|arg| arg == x));
+
+        // The restored table carries on like the original
+        restored.mark_place_init(Local::RET);
+        restored.exit_fn();
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+        assert_eq!(queries(&pt), queries(&restored));
+        assert_eq!(restored.pointer_identity(p), pt.pointer_identity(p));
+    }
+
+    #[test]
+    fn tuple_projection() {
+        let mut tcx = TyCtxt::from_primitives();
+        let ty = tcx.push(TyKind::Tuple(vec![TyCtxt::I8, TyCtxt::I32]));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        pt.allocate_local(local, ty);
+
+        let place = pt
+            .reachable_from_node(local.to_place_index(&pt).unwrap(), false)
+            .filter(|ppath| !ppath.path.is_empty())
+            .map(|ppath| ppath.to_place(&pt))
+            .next()
+            .unwrap();
+
+        assert!(matches!(
+            place.projection()[0],
+            ProjectionElem::TupleField(..)
+        ));
+    }
+
+    #[test]
+    fn recursive_init() {
+        let (mut pt, local, a, b, c, d, e) = prepare_t();
+
+        pt.mark_place_init(&a);
+        assert!(pt.is_place_init(&a));
+        assert!(!pt.is_place_init(local));
+
+        pt.mark_place_init(&b);
+        assert!(pt.is_place_init(&d));
+        assert!(pt.is_place_init(&e));
+        assert!(!pt.is_place_init(local));
+
+        pt.mark_place_init(&c);
+        assert!(pt.is_place_init(local));
+    }
+
+    #[test]
+    fn recursive_uninit() {
+        let (mut pt, local, a, b, c, d, e) = prepare_t();
+        pt.mark_place_init(local);
+
+        pt.mark_place_uninit(&d);
+        assert!(!pt.is_place_init(&d));
+
+        pt.mark_place_uninit(&e);
+        assert!(!pt.is_place_init(&b));
+
+        pt.mark_place_uninit(local);
+        assert!(!pt.is_place_init(&a));
+        assert!(!pt.is_place_init(&c));
+    }
+
+    #[test]
+    fn complexity() {
+        let (mut pt, local, a, b, c, d, e) = prepare_t();
+
+        pt.update_complexity(&a, Rvalue::Use(Operand::Constant(1.into())).complexity(&pt));
+        assert_eq!(a.complexity(&pt), 1);
+        assert_eq!(Place::from(local).complexity(&pt), 1);
+        pt.update_complexity(&c, Rvalue::Use(Operand::Constant(1.into())).complexity(&pt));
+        assert_eq!(c.complexity(&pt), 1);
+        assert_eq!(Place::from(local).complexity(&pt), 1);
+
+        pt.update_complexity(
+            &d,
+            Rvalue::BinaryOp(
+                BinOp::Add,
+                Operand::Copy(a.clone()),
+                Operand::Copy(c.clone()),
+            )
+            .complexity(&pt),
+        );
+        assert_eq!(d.complexity(&pt), 2);
+
+        pt.update_complexity(&e, Rvalue::Use(Operand::Constant(1.into())).complexity(&pt));
+        assert_eq!(b.complexity(&pt), 2);
+
+        assert_eq!(Place::from(local).complexity(&pt), 2);
+
+        // Complexity saturates at the cap
+        pt.update_complexity(&a, 1000);
+        assert_eq!(a.complexity(&pt), 100);
+        assert_eq!(Place::from(local).complexity(&pt), 100);
+        let mut pt = pt.with_complexity_cap(4);
+        pt.update_complexity(&c, 1000);
+        assert_eq!(c.complexity(&pt), 4);
+    }
+
+    #[test]
+    fn complexity_decay() {
+        let (mut pt, local, a, b, c, d, e) = prepare_t();
+
+        pt.update_complexity(&a, 8);
+        pt.update_complexity(&d, 5);
+        for _ in 0..3 {
+            pt.decay_complexity(0.5);
+        }
+        assert_eq!(a.complexity(&pt), 1);
+        assert_eq!(d.complexity(&pt), 1);
+        // Places that were never written stay at zero
+        assert_eq!(e.complexity(&pt), 0);
+
+        // A recently written place outweighs the untouched ones
+        pt.update_complexity(&c, 3);
+        assert!(c.complexity(&pt) > a.complexity(&pt));
+        assert!(c.complexity(&pt) > d.complexity(&pt));
+        // Superfields are still the max of their subfields
+        assert_eq!(b.complexity(&pt), 1);
+        assert_eq!(Place::from(local).complexity(&pt), 3);
+    }
+
+    #[test]
+    fn prim_arrays() {
+        let mut tcx = TyCtxt::from_primitives();
+        let ty = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        let local_pidx = pt.allocate_local(local, ty);
+
+        let one = Local::new(2);
+        pt.allocate_local(one, TyCtxt::USIZE);
+        pt.mark_place_init(one);
+        pt.assign_literal(one, Some(Literal::Uint(1, UintTy::Usize)));
+
+        let proj = ProjectionElem::Index(one);
+
+        let local_one = pt.project_from_node(local_pidx, proj).unwrap();
+
+        assert_eq!(
+            pt.places[local_pidx].alloc_id,
+            pt.places[local_one].alloc_id
+        );
+
+        assert_eq!(
+            pt.places[local_one].run_ptr.unwrap().run_and_offset,
+            pt.places[local_pidx]
+                .run_ptr
+                .unwrap()
+                .run_and_offset
+                .offset(
+                    BasicMemory::ty_size(TyCtxt::I32, &pt.tcx)
+                        .unwrap()
+                        .bytes_usize() as isize
+                )
+        )
+    }
+    #[test]
+    fn composite_arrays() {
+        let mut tcx = TyCtxt::from_primitives();
+        let elem_ty = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I64]));
+        let ty = tcx.push(TyKind::Array(elem_ty, 4));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        let local_pidx = pt.allocate_local(local, ty);
+
+        let one = Local::new(2);
+        pt.allocate_local(one, TyCtxt::USIZE);
+        pt.mark_place_init(one);
+        pt.assign_literal(one, Some(Literal::Uint(1, UintTy::Usize)));
+
+        // local[one].0
+        let one_zero = Place::from_projected(
+            local,
+            &[
+                ProjectionElem::Index(one),
+                ProjectionElem::TupleField(FieldIdx::new(0)),
+            ],
+        );
+        let one_zero = pt.get_node(&one_zero).unwrap();
+        assert_eq!(pt.places[one_zero].ty, TyCtxt::I32);
+        assert_eq!(pt.places[local_pidx].alloc_id, pt.places[one_zero].alloc_id);
+    }
+
+    #[test]
+    fn slices() {
+        let mut tcx = TyCtxt::from_primitives();
+        let array_ty = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let array_ref_ty = tcx.push(TyKind::Ref(array_ty, Mutability::Not));
+        let slice_ty = tcx.push(TyKind::Slice(TyCtxt::I32));
+        let slice_ref_ty = tcx.push(TyKind::Ref(slice_ty, Mutability::Not));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let array = Local::new(1);
+        pt.allocate_local(array, array_ty);
+        pt.mark_place_init(array);
+
+        // array_ref = &array
+        let array_ref = Local::new(2);
+        pt.allocate_local(array_ref, array_ref_ty);
+        pt.set_ref(array_ref, array, None);
+
+        // slice_ref = array_ref as &[i32]
+        let slice_ref = Local::new(3);
+        pt.allocate_local(slice_ref, slice_ref_ty);
+        pt.unsize_ptr(slice_ref, array_ref);
+
+        let slice = Place::from_projected(slice_ref, &[ProjectionElem::Deref]);
+        assert_eq!(pt.ty(&slice), slice_ty);
+        assert_eq!(pt.slice_len(&slice), Some(4));
+        assert!(pt.is_place_init(&slice));
+        assert!(pt.overlap(&slice, array));
+
+        // The length is in the metadata of the fat pointer
+        let run_ptr = pt.places[slice_ref.to_place_index(&pt).unwrap()]
+            .run_ptr
+            .unwrap();
+        let (_, metadata) = BasicMemory::ptr_parts(run_ptr);
+        assert_eq!(pt.memory.read_scalar(metadata.unwrap()), Some(4));
+
+        // len = PtrMetadata(slice_ref)
+        let len = Local::new(6);
+        pt.allocate_local(len, TyCtxt::USIZE);
+        let len_val = pt
+            .pointee_len(slice_ref)
+            .map(|len| Literal::Uint(len as u128, UintTy::Usize));
+        pt.assign_literal(len, len_val);
+        assert_eq!(pt.ty(len), TyCtxt::USIZE);
+        assert!(matches!(
+            pt.known_val(len),
+            Some(Literal::Uint(4, UintTy::Usize))
+        ));
+
+        // (*slice_ref)[in_bounds] is the element of the array
+        let in_bounds = Local::new(4);
+        pt.allocate_local(in_bounds, TyCtxt::USIZE);
+        pt.mark_place_init(in_bounds);
+        pt.assign_literal(in_bounds, Some(Literal::Uint(3, UintTy::Usize)));
+        let elem = Place::from_projected(
+            slice_ref,
+            &[ProjectionElem::Deref, ProjectionElem::Index(in_bounds)],
+        );
+        assert_eq!(
+            pt.get_node(&elem),
+            pt.get_node(&Place::from_projected(
+                array,
+                &[ProjectionElem::Index(in_bounds)]
+            ))
+        );
+        assert_eq!(pt.ty(&elem), TyCtxt::I32);
+
+        // (*slice_ref)[out_of_bounds] doesn't exist
+        let out_of_bounds = Local::new(5);
+        pt.allocate_local(out_of_bounds, TyCtxt::USIZE);
+        pt.mark_place_init(out_of_bounds);
+        pt.assign_literal(out_of_bounds, Some(Literal::Uint(4, UintTy::Usize)));
+        let elem = Place::from_projected(
+            slice_ref,
+            &[ProjectionElem::Deref, ProjectionElem::Index(out_of_bounds)],
+        );
+        assert!(pt.get_node(&elem).is_none());
+    }
+
+    #[test]
+    fn composite_known_val() {
+        let mut tcx = TyCtxt::from_primitives();
+        let pair = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::U8]));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        pt.allocate_local(local, pair);
+        let field = |pt: &PlaceTable, i: usize| {
+            Place::from_projected(local, &[ProjectionElem::TupleField(FieldIdx::new(i))])
+                .to_place_index(pt)
+                .unwrap()
+        };
+
+        // local.0 = 1; local.1 = 2
+        pt.mark_place_init(local);
+        pt.assign_literal(field(&pt, 0), Some(Literal::Int(1, IntTy::I32)));
+        assert!(pt.composite_known_val(local).is_none());
+        pt.assign_literal(field(&pt, 1), Some(Literal::Uint(2, UintTy::U8)));
+        assert!(matches!(
+            pt.composite_known_val(local).as_deref(),
+            Some([Literal::Int(1, IntTy::I32), Literal::Uint(2, UintTy::U8)])
+        ));
+
+        // local.1 = unknown
+        pt.assign_literal(field(&pt, 1), None);
+        assert!(pt.composite_known_val(local).is_none());
+    }
+
+    #[test]
+    fn shared_reference() {
+        let mut tcx = TyCtxt::from_primitives();
+
+        let t_i16_i32 = tcx.push(TyKind::Tuple(vec![TyCtxt::I16, TyCtxt::I32]));
+        let t_ref = tcx.push(TyKind::Ref(t_i16_i32, Mutability::Not));
+        let t_ptr = tcx.push(TyKind::RawPtr(t_i16_i32, Mutability::Not));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let root = Local::new(1);
+        pt.allocate_local(root, t_i16_i32);
+        pt.mark_place_init(root);
+
+        let root_0 = Place::from_projected(root, &[ProjectionElem::TupleField(FieldIdx::new(0))])
+            .to_place_index(&pt)
+            .unwrap();
+        let root_1 = Place::from_projected(root, &[ProjectionElem::TupleField(FieldIdx::new(1))])
+            .to_place_index(&pt)
+            .unwrap();
+
+        // root_ptr1 = addr_of!(root)
+        let root_ptr1 = Local::new(2);
+        pt.allocate_local(root_ptr1, t_ptr);
+        pt.set_ref(root_ptr1, root, None);
+        let root_ptr1_p = root_ptr1.to_place_index(&pt).unwrap();
+
+        // root_ref = &root
+        let root_ref = Local::new(3);
+        pt.allocate_local(root_ref, t_ref);
+        pt.set_ref(root_ref, root, None);
+        let root_ref_p = root_ref.to_place_index(&pt).unwrap();
+
+        // (*root_ref).0 can be read
+        assert!(pt.can_read_through(root_ref_p, root_0));
+
+        // (*root_ref).0 cannot be written to
+        assert!(!pt.can_write_through(root_ref_p, root_0));
 
         // root_ptr2 = addr_of!(root)
         let root_ptr2 = Local::new(4);
@@ -1743,53 +5700,1986 @@ mod tests {
         pt.set_ref(root_ptr2, root, None);
         let root_ptr2_p = root_ptr2.to_place_index(&pt).unwrap();
 
-        // (*root_ptr2).0 cannot be written to
-        assert!(!pt.can_write_through(root_ptr2_p, root_0));
+        // (*root_ptr2).0 cannot be written to
+        assert!(!pt.can_write_through(root_ptr2_p, root_0));
+
+        // Writing through (*root_ptr1).0
+        pt.place_written(root_0);
+
+        // (*root_ref).0 is invalidated
+        assert!(!pt.can_read_through(root_ref_p, root_0));
+
+        // (*root_ptr2).0 is invalidated
+        assert!(!pt.can_read_through(root_ptr2_p, root_0));
+
+        // (*root_ref).1 is not invalidated
+        assert!(pt.can_read_through(root_ref_p, root_1));
+
+        // (*root_ptr2).1 is not invalidated
+        assert!(pt.can_read_through(root_ptr2_p, root_1));
+
+        // *root_ptr1 is not invalidated
+        assert!(pt.can_read_through(root_ptr1_p, root.to_place_index(&pt).unwrap(),));
+    }
+
+    #[test]
+    fn reference_tags() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        let y = pt.allocate_local(Local::new(2), TyCtxt::I32);
+        pt.mark_place_init(x);
+        pt.mark_place_init(y);
+        let r = pt.allocate_local(Local::new(3), t_ref);
+        pt.mark_place_init(r);
+        let x_run = pt.places[x].run_ptr.unwrap();
+        assert_eq!(pt.tag_of(r), None);
+
+        // r = &mut x
+        pt.set_ref(r, x, None);
+        let tag = pt.tag_of(r).expect("reference is tagged");
+        assert!(pt.memory.can_write_with(x_run, tag));
+
+        // r = &mut y, after which nothing holds the old tag
+        pt.set_ref(r, y, None);
+        assert_ne!(pt.tag_of(r), Some(tag));
+        assert!(!pt.memory.can_read_with(x_run, tag));
+
+        // A copy keeps the tag alive when the original is overwritten
+        let copy = pt.allocate_local(Local::new(4), t_ref);
+        pt.copy_place(copy, r);
+        let tag = pt.tag_of(r).unwrap();
+        let y_run = pt.places[y].run_ptr.unwrap();
+        pt.set_ref(r, x, None);
+        assert!(pt.memory.can_write_with(y_run, tag));
+        pt.mark_place_uninit(copy);
+        assert_eq!(pt.tag_of(copy), None);
+        assert!(!pt.memory.can_read_with(y_run, tag));
+    }
+
+    #[test]
+    fn reborrow() {
+        let mut tcx = TyCtxt::from_primitives();
+
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let int = Local::new(1);
+        pt.allocate_local(int, TyCtxt::I32);
+
+        // int_ref = &int
+        let int_ref = Local::new(2);
+        pt.allocate_local(int_ref, t_ref);
+        let int_ref_p = int_ref.to_place_index(&pt).unwrap();
+        pt.set_ref(int_ref, int, None);
+        assert_eq!(pt.pointer_tags.first().unwrap().len(), 1);
+
+        // int_ref = &*int_ref
+        pt.set_ref(int_ref, int, Some(int_ref_p));
+        assert_eq!(pt.pointer_tags.first().unwrap().len(), 1);
+
+        pt.place_written(int);
+        assert!(!pt
+            .places
+            .contains_edge(int_ref_p, int.to_place_index(&pt).unwrap()));
+    }
+
+    #[test]
+    fn dangling_tags() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let int = Local::new(1);
+        let int_p = pt.allocate_local(int, TyCtxt::I32);
+        pt.mark_place_init(int);
+
+        let ref1 = Local::new(2);
+        let ref1_p = pt.allocate_local(ref1, t_ref);
+        pt.set_ref(ref1, int, None);
+
+        let ref2 = Local::new(3);
+        let ref2_p = pt.allocate_local(ref2, t_ref);
+        pt.set_ref(ref2, int, None);
+
+        let alloc_id = pt.places[int_p].alloc_id;
+        pt.deallocate_local(int).unwrap();
+
+        let tags = pt.memory.dangling_tags(alloc_id);
+        assert_eq!(
+            tags,
+            vec![
+                pt.places[ref1_p].tag.unwrap(),
+                pt.places[ref2_p].tag.unwrap()
+            ]
+        );
+        assert!(!pt.places.contains_edge(ref1_p, int_p));
+        assert!(!pt.places.contains_edge(ref2_p, int_p));
+    }
+
+    #[test]
+    fn reassign_after_move() {
+        let tcx = Rc::new(TyCtxt::from_primitives());
+        let mut pt = PlaceTable::new(tcx.clone());
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        pt.mark_place_init(x);
+        pt.assign_literal(x, Some(Literal::Int(1, IntTy::I32)));
+        pt.allocate_local(Local::new(2), TyCtxt::UNIT);
+        let selectable = |pt: &PlaceTable| {
+            PlaceSelector::for_operand(tcx.clone())
+                .into_iter_place(pt)
+                .any(|place| place == Place::from_local(Local::new(1)))
+        };
+        assert!(selectable(&pt));
+
+        // fn(x: i32), with x moved in
+        pt.enter_fn(
+            &Body::new(&[TyCtxt::I32], TyCtxt::UNIT, false),
+            &[Operand::Move(Place::from_local(Local::new(1)))],
+            &Place::from_local(Local::new(2)),
+        );
+        assert!(pt.moved_in_args_stack().any(|arg| arg == x));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+
+        assert!(!pt.is_place_init(x));
+        assert!(pt.moved_in_args_stack().next().is_none());
+        assert!(!selectable(&pt));
+
+        pt.mark_place_init(x);
+        pt.assign_literal(x, Some(Literal::Int(2, IntTy::I32)));
+        assert!(pt.is_place_init(x));
+        assert!(selectable(&pt));
+    }
+
+    #[test]
+    fn move_field_into_call() {
+        let mut tcx = TyCtxt::from_primitives();
+        let pair = tcx.push(TyKind::Tuple(vec![TyCtxt::U8, TyCtxt::U32]));
+        let tcx = Rc::new(tcx);
+        let mut pt = PlaceTable::new(tcx.clone());
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), pair);
+        pt.mark_place_init(x);
+        pt.allocate_local(Local::new(2), TyCtxt::UNIT);
+        let field = |idx| {
+            Place::from_projected(
+                Local::new(1),
+                &[ProjectionElem::TupleField(FieldIdx::new(idx))],
+            )
+        };
+        let moved = field(0).to_place_index(&pt).unwrap();
+        let sibling = field(1).to_place_index(&pt).unwrap();
+        pt.assign_literal(sibling, Some(Literal::Uint(2, UintTy::U32)));
+
+        // fn(x: u8), with x.0 moved in
+        pt.enter_fn(
+            &Body::new(&[TyCtxt::U8], TyCtxt::UNIT, false),
+            &[Operand::Move(field(0))],
+            &Place::from_local(Local::new(2)),
+        );
+        assert!(pt.moved_in_args_stack().any(|arg| arg == moved));
+        assert_eq!(pt.init_state(x), InitState::Partial);
+        assert!(!pt.is_place_init(moved));
+        assert!(pt.is_place_init(sibling));
+        assert!(pt.is_place_live(x));
+        let run_ptr = pt.places[moved].run_ptr.unwrap();
+        assert!(pt
+            .memory
+            .bytes(run_ptr)
+            .iter()
+            .all(|b| *b == AbstractByte::Uninit));
+        let run_ptr = pt.places[sibling].run_ptr.unwrap();
+        assert!(pt
+            .memory
+            .bytes(run_ptr)
+            .iter()
+            .all(|b| *b != AbstractByte::Uninit));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+
+        // Only the moved field is gone, its sibling stays usable
+        assert_eq!(pt.init_state(x), InitState::Partial);
+        assert!(matches!(
+            pt.known_val(sibling),
+            Some(Literal::Uint(2, UintTy::U32))
+        ));
+        let selectable: Vec<Place> = PlaceSelector::for_operand(tcx.clone())
+            .into_iter_place(&pt)
+            .collect();
+        assert!(selectable.contains(&field(1)));
+        assert!(!selectable.contains(&field(0)));
+        assert!(!selectable.contains(&Place::from_local(Local::new(1))));
+    }
+
+    #[test]
+    fn dangling_after_return() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let dest = pt.allocate_local(Local::new(1), t_ptr);
+        let copy = pt.allocate_local(Local::new(2), t_ptr);
+
+        // fn() -> *const i32 { let x = 1; &raw const x }
+        pt.enter_fn(
+            &Body::new(&[], t_ptr, false),
+            &[],
+            &Place::from_local(Local::new(1)),
+        );
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        pt.mark_place_init(x);
+        pt.mark_place_init(Local::RET);
+        pt.set_ref(Local::RET, x, None);
+        pt.exit_fn();
+
+        let mut deref = Place::from_local(Local::new(1));
+        deref.project(ProjectionElem::Deref);
+        assert_eq!(pt.pointee(dest), None);
+        assert!(!pt
+            .reachable_nodes()
+            .any(|ppath| ppath.to_place(&pt) == deref));
+        let dangling = |pt: &PlaceTable| {
+            let mut pointers: Vec<PlaceIndex> = pt
+                .dangling_pointers()
+                .map(|ppath| ppath.target_index())
+                .collect();
+            pointers.sort();
+            pointers
+        };
+        assert_eq!(dangling(&pt), vec![dest]);
+
+        // Copies dangle too, until they are overwritten
+        pt.copy_place(copy, dest);
+        assert_eq!(dangling(&pt), vec![dest, copy]);
+        pt.mark_place_init(dest);
+        assert_eq!(dangling(&pt), vec![copy]);
+    }
+
+    #[test]
+    fn join() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let a = Local::new(1);
+        let b = Local::new(2);
+        let r = Local::new(3);
+        pt.allocate_local(a, TyCtxt::I32);
+        pt.allocate_local(b, TyCtxt::I32);
+        let r_p = pt.allocate_local(r, t_ref);
+        pt.mark_place_init(b);
+        pt.assign_literal(b, Some(Literal::Int(1, IntTy::I32)));
+
+        let mut left = pt.clone();
+        let mut right = pt.clone();
+        left.mark_place_init(a);
+        left.set_ref(r, b, None);
+        left.mark_place_init(r);
+        right.mark_place_init(r);
+
+        let joined = left.join(&right);
+        assert!(!joined.is_place_init(a));
+        assert!(joined.is_place_init(b));
+        assert!(matches!(
+            joined.known_val(b),
+            Some(Literal::Int(1, IntTy::I32))
+        ));
+        assert_eq!(joined.pointee(r_p), None);
+        // A reference without a pointee is uninit
+        assert!(!joined.is_place_init(r));
+
+        right.assign_literal(b, Some(Literal::Int(2, IntTy::I32)));
+        assert!(left.join(&right).known_val(b).is_none());
+    }
+
+    #[test]
+    fn join_keeps_tags_in_sync() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let a = Local::new(1);
+        let r = Local::new(2);
+        pt.allocate_local(a, TyCtxt::I32);
+        pt.allocate_local(r, t_ref);
+        pt.mark_place_init(a);
+
+        // Only the right path mints a tag
+        let left = pt.clone();
+        let mut right = pt.clone();
+        right.mark_place_init(r);
+        right.set_ref(r, a, None);
+
+        let mut joined = left.join(&right);
+        joined.mark_place_init(r);
+        joined.set_ref(r, a, None);
+        let tag = joined.tag_of(r).unwrap();
+        assert_ne!(Some(tag), right.tag_of(r));
+        assert!(joined.pointer_tags[tag].contains(&r.to_place_index(&joined).unwrap()));
+    }
+
+    #[test]
+    fn exit_fn_compacts() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_arr = tcx.push(TyKind::Array(TyCtxt::U64, 64));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let body = Body::new(&[TyCtxt::I64], TyCtxt::I64, false);
+        pt.enter_fn0(&body);
+        let retained = pt.memory.bytes_retained();
+
+        for _ in 0..300 {
+            pt.enter_fn(
+                &body,
+                &[Operand::Constant(Literal::Int(1, IntTy::I64))],
+                &Place::RETURN_SLOT,
+            );
+            pt.allocate_local(Local::new(2), t_arr);
+            pt.mark_place_init(Local::new(2));
+            pt.exit_fn();
+        }
+        assert_eq!(pt.memory.bytes_retained(), retained);
+    }
+
+    #[test]
+    fn checkpoint_rollback() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        // Everything a query could tell about the table
+        let queries = |pt: &PlaceTable| {
+            let nodes: Vec<_> = pt
+                .places
+                .node_indices()
+                .map(|p| {
+                    (
+                        pt.is_place_live(p),
+                        pt.is_place_init(p),
+                        pt.places[p].tag,
+                        pt.ty(p).is_any_ptr(&pt.tcx).then(|| pt.pointee(p)),
+                        pt.known_val(p).map(|val| format!("{val:?}")),
+                    )
+                })
+                .collect();
+            let reachable: Vec<Place> = pt.reachable_nodes().map(|p| p.to_place(pt)).collect();
+            (
+                pt.to_dot(),
+                nodes,
+                reachable,
+                pt.stats(),
+                pt.stack_usage(),
+                pt.frame_count(),
+                pt.locals_with_int_val(1, TyCtxt::I32),
+            )
+        };
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        pt.mark_place_init(x);
+        pt.assign_literal(x, Some(Literal::Int(1, IntTy::I32)));
+        let r = pt.allocate_local(Local::new(2), t_ref);
+        pt.mark_place_init(r);
+        pt.set_ref(r, x, None);
+        pt.allocate_local(Local::new(3), TyCtxt::UNIT);
+        let before = queries(&pt);
+        let mut untouched = pt.clone();
+
+        let checkpoint = pt.checkpoint();
+        pt.assign_literal(x, Some(Literal::Int(2, IntTy::I32)));
+        pt.place_written(x);
+        let y = pt.allocate_local(Local::new(4), TyCtxt::I32);
+        pt.mark_place_init(y);
+        pt.set_ref(r, y, None);
+        pt.mark_place_init(r);
+        pt.enter_fn(
+            &Body::new(&[t_ref], TyCtxt::UNIT, false),
+            &[Operand::Copy(Place::from_local(Local::new(2)))],
+            &Place::from_local(Local::new(3)),
+        );
+        pt.exit_fn();
+        pt.deallocate_local(Local::new(4)).unwrap();
+        pt.rollback(checkpoint);
+        assert_eq!(queries(&pt), before);
+
+        // Carrying on from the rollback is the same as never having left
+        for pt in [&mut pt, &mut untouched] {
+            let z = pt.allocate_local(Local::new(4), t_ref);
+            pt.mark_place_init(z);
+            pt.set_ref(z, x, None);
+        }
+        assert_eq!(queries(&pt), queries(&untouched));
+    }
+
+    #[test]
+    fn protected_deallocation() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let int = Local::new(1);
+        let r = Local::new(2);
+        let int_p = pt.allocate_local(int, TyCtxt::I32);
+        let r_p = pt.allocate_local(r, t_ref);
+        pt.mark_place_init(int);
+        pt.mark_place_init(r);
+        pt.set_ref(r, int, None);
+        pt.mark_ref_protected(r);
+        let tag = pt.places[r_p].tag.unwrap();
+
+        assert!(!pt.can_deallocate(int));
+        assert_eq!(pt.deallocate_local(int), Err(vec![tag]));
+        assert!(pt.is_place_live(int));
+        assert_eq!(pt.pointee(r_p), Some(int_p));
+
+        pt.memory.unprotect_tag(tag);
+        assert!(pt.can_deallocate(int));
+        assert_eq!(pt.deallocate_local(int), Ok(()));
+        assert!(!pt.is_place_live(int));
+    }
+
+    #[test]
+    fn argument_protectors() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::U32, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::U32);
+        pt.allocate_local(Local::new(2), t_ref);
+        let dest = Place::from_local(Local::new(3));
+        pt.allocate_local(Local::new(3), TyCtxt::UNIT);
+        pt.assign_literal(x, Some(Literal::Uint(1, UintTy::U32)));
+        pt.mark_place_init(Local::new(2));
+        pt.set_ref(Local::new(2), x, None);
+        assert!(!pt.write_pops_protected(x));
+
+        let callee = Body::new(&[t_ref], TyCtxt::UNIT, false);
+        pt.enter_fn(
+            &callee,
+            &[Operand::Copy(Place::from_local(Local::new(2)))],
+            &dest,
+        );
+        // Writing to x would invalidate the argument while it's in use
+        assert!(pt.write_pops_protected(x));
+
+        pt.exit_fn();
+        assert!(!pt.write_pops_protected(x));
+        pt.place_written(x);
+    }
+
+    #[test]
+    fn argument_retag() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        pt.assign_literal(x, Some(Literal::Int(1, IntTy::I32)));
+        let ptr = pt.allocate_local(Local::new(2), t_ptr);
+        pt.mark_place_init(ptr);
+        pt.set_ref(ptr, x, None);
+        let r = pt.allocate_local(Local::new(3), t_ref);
+        pt.mark_place_init(r);
+        pt.set_ref(r, x, None);
+        let dest = Place::from_local(Local::new(4));
+        pt.allocate_local(Local::new(4), TyCtxt::UNIT);
+        let parent = pt.tag_of(r).unwrap();
+
+        // fn(copy _3) keeps _3 usable after the call
+        let callee = Body::new(&[t_ref], TyCtxt::UNIT, false);
+        let args = [Operand::Copy(Place::from_local(Local::new(3)))];
+        pt.enter_fn(&callee, &args, &dest);
+        let arg = Local::new(1).to_place_index(&pt).unwrap();
+        assert_eq!(pt.pointee(arg), Some(x));
+        let child = pt.tag_of(arg).unwrap();
+        assert_ne!(child, parent);
+        assert_eq!(pt.memory.parent_tag(child), Some(parent));
+        assert!(pt.memory.is_protected(child));
+        assert!(!pt.memory.is_protected(parent));
+        assert!(pt.can_write_through(arg, x));
+        // The caller's tag survives, but using it or anything below it would pop the child
+        assert!(!pt.can_write_through(r, x));
+        assert!(!pt.can_read_through(ptr, x));
+
+        pt.exit_fn();
+        assert!(!pt.memory.is_protected(child));
+        assert_eq!(pt.tag_of(r), Some(parent));
+        assert!(pt.can_write_through(r, x));
+        assert!(pt.can_read_through(ptr, x));
+    }
+
+    #[test]
+    fn current_args() {
+        let tcx = TyCtxt::from_primitives();
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[TyCtxt::I32, TyCtxt::U8], TyCtxt::UNIT, false));
+        let a = Local::new(1).to_place_index(&pt).unwrap();
+        let b = Local::new(2).to_place_index(&pt).unwrap();
+        let fn0_args = vec![(Local::new(1), a), (Local::new(2), b)];
+        assert_eq!(pt.current_args().collect::<Vec<_>>(), fn0_args);
+        let x = pt.allocate_local(Local::new(3), TyCtxt::U8);
+        pt.mark_place_init(x);
+        assert_eq!(pt.arg_mutability(Local::new(3)), None);
+        let dest = Place::from_local(Local::new(4));
+        pt.allocate_local(Local::new(4), TyCtxt::UNIT);
+
+        // The callee's only argument is its _1, a copy of the caller's _3
+        let callee = Body::new(&[TyCtxt::U8], TyCtxt::UNIT, false);
+        let args = [Operand::Copy(Place::from_local(Local::new(3)))];
+        pt.enter_fn(&callee, &args, &dest);
+        let arg = Local::new(1).to_place_index(&pt).unwrap();
+        assert_ne!(arg, a);
+        assert_eq!(
+            pt.current_args().collect::<Vec<_>>(),
+            [(Local::new(1), arg)]
+        );
+        assert_eq!(pt.arg_mutability(Local::new(1)), Some(Mutability::Mut));
+        assert_eq!(pt.arg_mutability(Local::new(2)), None);
+
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+        assert_eq!(pt.current_args().collect::<Vec<_>>(), fn0_args);
+        assert_eq!(pt.arg_mutability(Local::new(2)), Some(Mutability::Mut));
+    }
+
+    #[test]
+    fn return_destination_protected() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::U32, TyCtxt::U32]));
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::U32, Mutability::Mut));
+        let tcx = Rc::new(tcx);
+        let mut pt = PlaceTable::new(tcx.clone());
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let dest = Place::from_local(Local::new(1));
+        pt.allocate_local(Local::new(1), t_tuple);
+        pt.mark_place_init(&dest);
+        let field = Place::from_projected(
+            Local::new(1),
+            &[ProjectionElem::TupleField(FieldIdx::new(0))],
+        );
+        let field = field.to_place_index(&pt).unwrap();
+        pt.allocate_local(Local::new(2), t_ref);
+        pt.mark_place_init(Local::new(2));
+        pt.set_ref(Local::new(2), field, None);
+
+        // dest = fn(&mut dest.0)
+        let callee = Body::new(&[t_ref], t_tuple, false);
+        let args = [Operand::Move(Place::from_local(Local::new(2)))];
+        assert!(pt.arguments_ok(&args, &dest));
+        pt.enter_fn(&callee, &args, &dest);
+        let arg = Local::new(1).to_place_index(&pt).unwrap();
+        assert_eq!(pt.pointee(arg), Some(field));
+        assert!(!pt.can_write_through(arg, field));
+        let mut deref = Place::from_local(Local::new(1));
+        deref.project(ProjectionElem::Deref);
+        assert!(!PlaceSelector::for_lhs(tcx.clone())
+            .into_iter_place(&pt)
+            .any(|place| place == deref));
+
+        pt.assign_literal(Local::RET, Some(Literal::Uint(1, UintTy::U32)));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+        assert!(!pt.memory.has_protectors(pt.places[field].alloc_id));
+    }
+
+    #[test]
+    fn call_destination_conflicts() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::U32, TyCtxt::U32]));
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::U32, Mutability::Mut));
+        let t_ref_ref = tcx.push(TyKind::Ref(t_ref, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let dest = Place::from_local(Local::new(1));
+        let other = Place::from_local(Local::new(3));
+        pt.allocate_local(Local::new(1), t_tuple);
+        pt.allocate_local(Local::new(2), t_ref);
+        pt.allocate_local(Local::new(3), TyCtxt::U32);
+        pt.allocate_local(Local::new(4), t_ref_ref);
+        pt.mark_place_init(&dest);
+        pt.mark_place_init(&other);
+        let field = Place::from_projected(
+            Local::new(1),
+            &[ProjectionElem::TupleField(FieldIdx::new(0))],
+        );
+        pt.mark_place_init(Local::new(2));
+        pt.set_ref(Local::new(2), &field, None);
+        pt.mark_place_init(Local::new(4));
+        pt.set_ref(Local::new(4), Local::new(2), None);
+
+        // _1 = fn(_1)
+        let args = [Operand::Copy(dest.clone())];
+        assert!(pt.call_destination_conflicts(&dest, &args));
+        assert!(!pt.call_destination_conflicts(&other, &args));
+
+        // _1 = fn(_1.0)
+        let args = [Operand::Copy(field.clone())];
+        assert!(pt.call_destination_conflicts(&dest, &args));
+        assert!(!pt.call_destination_conflicts(&other, &args));
+
+        // _1 = fn(_3, &mut _1.0)
+        let args = [
+            Operand::Copy(other.clone()),
+            Operand::Move(Place::from_local(Local::new(2))),
+        ];
+        assert!(pt.call_destination_conflicts(&dest, &args));
+        assert!(pt.call_destination_conflicts(&field, &args));
+        assert!(pt.call_destination_conflicts(&other, &args));
+
+        // _1 = fn(&&mut _1.0)
+        let args = [Operand::Copy(Place::from_local(Local::new(4)))];
+        assert!(pt.call_destination_conflicts(&dest, &args));
+        assert!(!pt.call_destination_conflicts(&other, &args));
+    }
+
+    #[test]
+    fn call_depth_limit() {
+        let tcx = TyCtxt::from_primitives();
+        let mut pt = PlaceTable::new(Rc::new(tcx)).with_max_call_depth(3);
+        let (fn1, fn2) = (Function::new(1), Function::new(2));
+        // fn() -> i32
+        let callee = Body::new(&[], TyCtxt::I32, false);
+        let dest = Place::from_local(Local::new(1));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        pt.allocate_local(Local::new(1), TyCtxt::I32);
+        assert_eq!(pt.call_depth(), 0);
+        // fn1 recurses twice, then calls fn2
+        for (depth, callee_fn) in [(1, fn1), (2, fn1), (3, fn2)] {
+            assert!(pt.can_call());
+            pt.try_enter_fn(callee_fn, &callee, &[], &dest).unwrap();
+            pt.allocate_local(Local::new(1), TyCtxt::I32);
+            assert_eq!(pt.call_depth(), depth);
+        }
+        assert_eq!(pt.active_frames(fn1), 2);
+        assert_eq!(pt.active_frames(fn2), 1);
+
+        let return_dests: Vec<PlaceIndex> = pt.return_dest_stack().collect();
+        assert_eq!(return_dests.len(), 3);
+        assert!(!pt.can_call());
+        let refused = pt.try_enter_fn(fn2, &callee, &[], &dest).unwrap_err();
+        assert_eq!(refused.depth, 3);
+        assert_eq!(pt.call_depth(), 3);
+        assert_eq!(pt.active_frames(fn2), 1);
+        assert_eq!(pt.return_dest_stack().collect::<Vec<_>>(), return_dests);
+
+        pt.assign_literal(Local::RET, Some(Literal::Int(0, IntTy::I32)));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+        assert_eq!(pt.active_frames(fn2), 0);
+        pt.try_enter_fn(fn2, &callee, &[], &dest).unwrap();
+        assert_eq!(pt.call_depth(), 3);
+        assert_eq!(pt.return_dest_stack().collect::<Vec<_>>(), return_dests);
+    }
+
+    #[test]
+    fn frame_place_budget() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I64, TyCtxt::U8]));
+        let mut pt = PlaceTable::new(Rc::new(tcx)).with_max_places_per_frame(10);
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        assert_eq!(pt.frame_count(), 1);
+        assert_eq!(pt.frame_place_count(), 1);
+        // Each tuple is 4 places
+        pt.try_allocate_local(Local::new(1), t_tuple).unwrap();
+        pt.mark_place_init(Local::new(1));
+        pt.try_allocate_local(Local::new(2), t_tuple).unwrap();
+        assert_eq!(pt.frame_place_count(), 9);
+        assert!(pt.frame_has_room());
+        pt.try_allocate_local(Local::new(3), TyCtxt::I32).unwrap();
+        assert!(!pt.frame_has_room());
+        let full = pt
+            .try_allocate_local(Local::new(4), TyCtxt::I32)
+            .unwrap_err();
+        assert_eq!(full.places, 10);
+        assert!(Local::new(4).to_place_index(&pt).is_none());
+
+        // The callee starts with a frame of its own
+        let callee = Body::new(&[t_tuple], TyCtxt::I32, false);
+        pt.enter_fn(
+            &callee,
+            &[Operand::Copy(Place::from_local(Local::new(1)))],
+            &Place::from_local(Local::new(3)),
+        );
+        assert_eq!(pt.frame_count(), 2);
+        assert_eq!(pt.frame_place_count(), 5);
+        assert!(pt.frame_has_room());
+        pt.try_allocate_local(Local::new(2), TyCtxt::I32).unwrap();
+        pt.assign_literal(Local::RET, Some(Literal::Int(0, IntTy::I32)));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+
+        assert_eq!(pt.frame_count(), 1);
+        assert_eq!(pt.frame_place_count(), 10);
+        assert!(!pt.frame_has_room());
+    }
+
+    #[test]
+    fn static_outlives_frames() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let usage = pt.stack_usage();
+        let s = pt.allocate_static(TyCtxt::I32);
+        assert_eq!(pt.stack_usage(), usage);
+        pt.mark_place_init(s);
+        pt.assign_literal(s, Some(Literal::Int(7, IntTy::I32)));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let r = Local::new(1);
+        pt.allocate_local(r, t_ref);
+        let dest = Place::from_local(Local::new(2));
+        pt.allocate_local(Local::new(2), t_ref);
+        pt.mark_place_init(r);
+        pt.set_ref(r, s, None);
+
+        let callee = Body::new(&[t_ref], t_ref, false);
+        let args = [Operand::Copy(Place::from_local(r))];
+        assert!(pt.arguments_ok(&args, &dest));
+        pt.enter_fn(&callee, &args, &dest);
+        // The static is reachable from the callee, and a reference to it may be returned
+        assert!(pt.reachable_nodes().any(|path| path.target_index() == s));
+        pt.mark_place_init(Local::RET);
+        pt.set_ref(Local::RET, s, None);
+        assert!(pt.can_return());
+        pt.exit_fn();
+
+        assert!(pt.is_static(s));
+        assert!(pt.is_place_init(s));
+        assert!(matches!(
+            pt.read_literal(s),
+            Some(Literal::Int(7, IntTy::I32))
+        ));
+        assert!(!pt.is_static(r));
+    }
+
+    #[test]
+    fn heap_outlives_frames() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Mut));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let p = Local::new(1);
+        let usage = pt.stack_usage();
+        let heap = pt.allocate_heap_local(p, t_ptr);
+        assert_eq!(
+            pt.stack_usage() - usage,
+            BasicMemory::ty_size(t_ptr, &pt.tcx).unwrap()
+        );
+        let dest = Place::from_local(Local::new(2));
+        pt.allocate_local(Local::new(2), TyCtxt::UNIT);
+        pt.mark_place_init(heap);
+        pt.assign_literal(heap, Some(Literal::Int(3, IntTy::I32)));
+
+        // The pointer is passed down and the callee returns without freeing
+        let callee = Body::new(&[t_ptr], TyCtxt::UNIT, false);
+        pt.enter_fn(&callee, &[Operand::Copy(Place::from_local(p))], &dest);
+        let mut arg = Place::from_local(Local::new(1));
+        arg.project(ProjectionElem::Deref);
+        assert_eq!(arg.to_place_index(&pt), Some(heap));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+        assert!(pt.is_place_live(heap));
+        assert!(matches!(
+            pt.read_literal(heap),
+            Some(Literal::Int(3, IntTy::I32))
+        ));
+
+        // Ownership moves into another callee, which frees the allocation
+        pt.enter_fn(&callee, &[Operand::Move(Place::from_local(p))], &dest);
+        let freed = arg.to_place_index(&pt).expect("arg points to the heap");
+        pt.free_heap(freed).unwrap();
+        assert_eq!(pt.free_heap(freed), Err(FreeError::DoubleFree));
+        assert!(arg.to_place_index(&pt).is_none());
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+        assert!(!pt.is_place_live(heap));
+        let mut deref_p = Place::from_local(p);
+        deref_p.project(ProjectionElem::Deref);
+        assert!(deref_p.to_place_index(&pt).is_none());
+    }
+
+    #[test]
+    fn return_caller_pointer() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        let r = pt.allocate_local(Local::new(2), t_ref);
+        let dest = pt.allocate_local(Local::new(3), t_ref);
+        pt.assign_literal(x, Some(Literal::Int(4, IntTy::I32)));
+        pt.mark_place_init(r);
+        pt.set_ref(r, x, None);
+        let tag = pt.places[r].tag;
+
+        // fn(r: &i32) -> &i32 { r }
+        let callee = Body::new(&[t_ref], t_ref, false);
+        pt.enter_fn(
+            &callee,
+            &[Operand::Copy(Place::from_local(Local::new(2)))],
+            &Place::from_local(Local::new(3)),
+        );
+        pt.copy_place(Local::RET, Local::new(1));
+        assert!(pt.can_return());
+        pt.exit_fn();
+
+        assert_eq!(pt.pointee(dest), Some(x));
+        // The argument was retagged on entry, so the returned reference holds the child tag
+        let returned = pt.places[dest].tag.unwrap();
+        assert_eq!(pt.memory.parent_tag(returned), tag);
+        assert!(pt.is_place_init(dest));
+        let mut deref = Place::from_local(Local::new(3));
+        deref.project(ProjectionElem::Deref);
+        assert_eq!(deref.to_place_index(&pt), Some(x));
+        assert!(matches!(
+            pt.read_literal(&deref),
+            Some(Literal::Int(4, IntTy::I32))
+        ));
+    }
+
+    #[test]
+    fn index_candidates_survive_calls() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let array = pt.allocate_local(Local::new(1), t_array);
+        pt.mark_place_init(array);
+        let index = Local::new(2);
+        pt.allocate_local(index, TyCtxt::USIZE);
+        pt.mark_place_init(index);
+        pt.assign_literal(index, Some(Literal::Uint(1, UintTy::Usize)));
+        pt.allocate_local(Local::new(3), TyCtxt::I32);
+
+        let indexed = |pt: &PlaceTable| {
+            pt.reachable_nodes()
+                .map(|ppath| ppath.to_place(pt))
+                .any(|place| place.projection() == [ProjectionElem::Index(index)])
+        };
+        assert!(indexed(&pt));
+
+        // fn() -> i32, from which the caller's array can't be reached
+        let callee = Body::new(&[], TyCtxt::I32, false);
+        pt.enter_fn(&callee, &[], &Place::from_local(Local::new(3)));
+        assert!(!indexed(&pt));
+        pt.assign_literal(Local::RET, Some(Literal::Int(0, IntTy::I32)));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+
+        assert!(indexed(&pt));
+    }
+
+    #[test]
+    fn index_local_choice() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let array = pt.allocate_local(Local::new(1), t_array);
+        pt.mark_place_init(array);
+        let indices = [Local::new(2), Local::new(3)];
+        for index in indices {
+            pt.allocate_local(index, TyCtxt::USIZE);
+            pt.mark_place_init(index);
+            pt.assign_literal(index, Some(Literal::Uint(1, UintTy::Usize)));
+        }
+
+        let elem = pt
+            .project_from_node(array, ProjectionElem::ConstantIndex { offset: 1 })
+            .unwrap();
+        let ppath = pt
+            .reachable_nodes()
+            .find(|ppath| ppath.target_index() == elem)
+            .expect("element is reachable");
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let chosen: HashSet<Local> = (0..32)
+            .map(|_| {
+                let place = ppath.to_place_with(&pt, &mut rng).unwrap();
+                assert_eq!(place.to_place_index(&pt), Some(elem));
+                let [ProjectionElem::Index(local)] = place.projection() else {
+                    panic!("indexed by a local")
+                };
+                *local
+            })
+            .collect();
+        assert_eq!(chosen, HashSet::from(indices));
+
+        // Neither local holds the index by the time the path is named
+        for index in indices {
+            pt.assign_literal(index, Some(Literal::Uint(2, UintTy::Usize)));
+        }
+        assert!(ppath.to_place_with(&pt, &mut rng).is_none());
+    }
+
+    #[test]
+    fn int_candidates() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let array = pt.allocate_local(Local::new(1), t_array);
+        pt.mark_place_init(array);
+        let (byte, index, neg) = (Local::new(2), Local::new(3), Local::new(4));
+        pt.allocate_local(byte, TyCtxt::U8);
+        pt.allocate_local(index, TyCtxt::USIZE);
+        pt.allocate_local(neg, TyCtxt::I8);
+        for (local, lit) in [
+            (byte, Literal::Uint(3, UintTy::U8)),
+            (index, Literal::Uint(3, UintTy::Usize)),
+            (neg, Literal::Int(-1, IntTy::I8)),
+        ] {
+            pt.mark_place_init(local);
+            pt.assign_literal(local, Some(lit));
+        }
+
+        assert_eq!(pt.locals_with_int_val(3, TyCtxt::U8), [byte]);
+        assert_eq!(pt.locals_with_int_val(3, TyCtxt::USIZE), [index]);
+        assert!(pt.locals_with_int_val(3, TyCtxt::I32).is_empty());
+        assert_eq!(pt.locals_with_int_val(-1i128 as u128, TyCtxt::I8), [neg]);
+
+        // Only the usize indexes
+        assert_eq!(pt.locals_with_val(3), [index]);
+        let elem = pt
+            .project_from_node(array, ProjectionElem::ConstantIndex { offset: 3 })
+            .unwrap();
+        let place = pt
+            .reachable_nodes()
+            .find(|ppath| ppath.target_index() == elem)
+            .expect("element is reachable")
+            .to_place(&pt);
+        assert_eq!(place.projection(), [ProjectionElem::Index(index)]);
+
+        pt.assign_literal(byte, Some(Literal::Uint(4, UintTy::U8)));
+        assert!(pt.locals_with_int_val(3, TyCtxt::U8).is_empty());
+        assert_eq!(pt.locals_with_int_val(4, TyCtxt::U8), [byte]);
+        pt.mark_place_uninit(index);
+        assert!(pt.locals_with_val(3).is_empty());
+    }
+
+    #[test]
+    fn composite_literal() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_pair = tcx.push(TyKind::Tuple(vec![TyCtxt::USIZE, TyCtxt::U8]));
+        let t_nested = tcx.push(TyKind::Tuple(vec![t_pair, TyCtxt::BOOL]));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let pair = pt.allocate_local(Local::new(1), t_pair);
+        let index = pt.allocate_local(Local::new(2), TyCtxt::USIZE);
+        let nested = pt.allocate_local(Local::new(3), t_nested);
+        let field = |pt: &PlaceTable, p, i| {
+            pt.project_from_node(p, ProjectionElem::TupleField(FieldIdx::new(i)))
+                .unwrap()
+        };
+
+        // _1 = (1usize, 2u8)
+        pt.mark_place_init(pair);
+        pt.assign_composite_literal(
+            pair,
+            &[
+                Literal::Uint(1, UintTy::Usize),
+                Literal::Uint(2, UintTy::U8),
+            ],
+        );
+        assert!(matches!(
+            pt.known_val(field(&pt, pair, 0)),
+            Some(Literal::Uint(1, UintTy::Usize))
+        ));
+        assert!(matches!(
+            pt.composite_known_val(pair).as_deref(),
+            Some([Literal::Uint(1, _), Literal::Uint(2, _)])
+        ));
+        let run_ptr = pt.places[field(&pt, pair, 1)].run_ptr.unwrap();
+        assert_eq!(pt.memory.read_scalar(run_ptr), Some(2));
+
+        // Only a local can index, which the field's value is copied to
+        assert!(pt.locals_with_val(1).is_empty());
+        pt.mark_place_init(index);
+        pt.copy_place(index, field(&pt, pair, 0));
+        assert_eq!(pt.locals_with_val(1), [Local::new(2)]);
+
+        // _3 = ((3usize, 4u8), true)
+        pt.mark_place_init(nested);
+        pt.assign_composite_literal(
+            nested,
+            &[
+                Literal::Uint(3, UintTy::Usize),
+                Literal::Uint(4, UintTy::U8),
+                Literal::Bool(true),
+            ],
+        );
+        let inner = field(&pt, nested, 0);
+        assert!(matches!(
+            pt.composite_known_val(inner).as_deref(),
+            Some([Literal::Uint(3, _), Literal::Uint(4, _)])
+        ));
+        assert!(matches!(
+            pt.known_val(field(&pt, nested, 1)),
+            Some(Literal::Bool(true))
+        ));
+    }
 
-        // Writing through (*root_ptr1).0
-        pt.place_written(root_0);
+    #[test]
+    fn nodes_of_ty() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_pair = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, TyCtxt::I32]));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Mut));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let of_ty = |pt: &PlaceTable, ty| {
+            let mut nodes: Vec<PlaceIndex> = pt.nodes_of_ty(ty).collect();
+            nodes.sort();
+            nodes
+        };
 
-        // (*root_ref).0 is invalidated
-        assert!(!pt.can_read_through(root_ref_p, root_0));
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let pair = pt.allocate_local(Local::new(1), t_pair);
+        let dest = pt.allocate_local(Local::new(2), TyCtxt::I32);
+        let fields: Vec<PlaceIndex> = pt.subfields(pair).map(|(_, field)| field).collect();
+        let stat = pt.allocate_static(TyCtxt::I32);
+        let heap = pt.allocate_heap_local(Local::new(3), t_ptr);
+        assert_eq!(of_ty(&pt, t_pair), [pair]);
+        assert_eq!(
+            of_ty(&pt, TyCtxt::I32),
+            [fields[0], fields[1], dest, stat, heap]
+        );
 
-        // (*root_ptr2).0 is invalidated
-        assert!(!pt.can_read_through(root_ptr2_p, root_0));
+        // The caller's locals are left out while in the callee
+        let callee = Body::new(&[TyCtxt::I32], TyCtxt::I32, false);
+        let args = [Operand::Constant(Literal::Int(1, IntTy::I32))];
+        pt.enter_fn(&callee, &args, &Place::from_local(Local::new(2)));
+        let ret = Local::RET.to_place_index(&pt).unwrap();
+        let arg = Local::new(1).to_place_index(&pt).unwrap();
+        assert_eq!(of_ty(&pt, TyCtxt::I32), [stat, heap, ret, arg]);
+        assert!(of_ty(&pt, t_pair).is_empty());
+        pt.assign_literal(Local::RET, Some(Literal::Int(2, IntTy::I32)));
+        pt.mark_place_init(Local::RET);
+        pt.exit_fn();
+
+        // and the callee's are gone after it returns
+        assert_eq!(
+            of_ty(&pt, TyCtxt::I32),
+            [fields[0], fields[1], dest, stat, heap]
+        );
+        pt.deallocate_local(Local::new(1)).unwrap();
+        assert_eq!(of_ty(&pt, TyCtxt::I32), [dest, stat, heap]);
+        assert!(of_ty(&pt, t_pair).is_empty());
+    }
 
-        // (*root_ref).1 is not invalidated
-        assert!(pt.can_read_through(root_ref_p, root_1));
+    fn assert_folds_to(folded: Option<Literal>, expected: Option<Literal>) {
+        match (folded, expected) {
+            (Some(a), Some(b)) => assert!(PlaceTable::same_literal(&a, &b), "{a:?} != {b:?}"),
+            (None, None) => {}
+            _ => panic!("folded to {folded:?}, expected {expected:?}"),
+        }
+    }
 
-        // (*root_ptr2).1 is not invalidated
-        assert!(pt.can_read_through(root_ptr2_p, root_1));
+    macro_rules! check_int_folds {
+        ($t:ty, $lit:expr, $signed:expr) => {{
+            use BinOp::*;
+            let lit = $lit;
+            let vals: [$t; 9] = [
+                <$t>::MIN,
+                <$t>::MIN.wrapping_add(1),
+                (0 as $t).wrapping_sub(1),
+                0,
+                1,
+                2,
+                <$t>::MAX / 2,
+                <$t>::MAX - 1,
+                <$t>::MAX,
+            ];
+            for x in vals {
+                for y in vals {
+                    let (a, b) = (lit(x), lit(y));
+                    let fold = |op| PlaceTable::fold_binop(op, &a, &b);
+                    assert_folds_to(fold(Add), Some(lit(x.wrapping_add(y))));
+                    assert_folds_to(fold(Sub), Some(lit(x.wrapping_sub(y))));
+                    assert_folds_to(fold(Mul), Some(lit(x.wrapping_mul(y))));
+                    assert_folds_to(fold(Div), x.checked_div(y).map(lit));
+                    assert_folds_to(fold(Rem), x.checked_rem(y).map(lit));
+                    assert_folds_to(fold(BitXor), Some(lit(x ^ y)));
+                    assert_folds_to(fold(BitAnd), Some(lit(x & y)));
+                    assert_folds_to(fold(BitOr), Some(lit(x | y)));
+                    assert_folds_to(fold(Eq), Some(Literal::Bool(x == y)));
+                    assert_folds_to(fold(Ne), Some(Literal::Bool(x != y)));
+                    assert_folds_to(fold(Lt), Some(Literal::Bool(x < y)));
+                    assert_folds_to(fold(Le), Some(Literal::Bool(x <= y)));
+                    assert_folds_to(fold(Ge), Some(Literal::Bool(x >= y)));
+                    assert_folds_to(fold(Gt), Some(Literal::Bool(x > y)));
+                    for (op, (val, overflowed)) in [
+                        (Add, x.overflowing_add(y)),
+                        (Sub, x.overflowing_sub(y)),
+                        (Mul, x.overflowing_mul(y)),
+                    ] {
+                        let (folded, folded_overflow) =
+                            PlaceTable::fold_checked_binop(op, &a, &b).unwrap();
+                        assert_folds_to(Some(folded), Some(lit(val)));
+                        assert_eq!(folded_overflow, overflowed, "{x} {y}");
+                    }
+                }
 
-        // *root_ptr1 is not invalidated
-        assert!(pt.can_read_through(root_ptr1_p, root.to_place_index(&pt).unwrap(),));
+                // Shift amounts are masked to the width, and negative ones wrap around
+                let bits = <$t>::BITS;
+                let amounts = [0, 1, bits - 1, bits, bits + 1, 1000];
+                for (amount, masked, overflowed) in amounts
+                    .map(|n| (Literal::Uint(n as u128, UintTy::U32), n % bits, n >= bits))
+                    .into_iter()
+                    .chain([-1, -(bits as i32), -1000].map(|n| {
+                        (
+                            Literal::Int(n as i128, IntTy::I32),
+                            n.rem_euclid(bits as i32) as u32,
+                            true,
+                        )
+                    }))
+                {
+                    let a = lit(x);
+                    for (op, expected) in [(Shl, x << masked), (Shr, x >> masked)] {
+                        assert_folds_to(
+                            PlaceTable::fold_binop(op, &a, &amount),
+                            Some(lit(expected)),
+                        );
+                        let (folded, folded_overflow) =
+                            PlaceTable::fold_checked_binop(op, &a, &amount).unwrap();
+                        assert_folds_to(Some(folded), Some(lit(expected)));
+                        assert_eq!(folded_overflow, overflowed);
+                    }
+                }
+
+                assert_folds_to(PlaceTable::fold_unop(UnOp::Not, &lit(x)), Some(lit(!x)));
+                let neg = $signed.then(|| lit((0 as $t).wrapping_sub(x)));
+                assert_folds_to(PlaceTable::fold_unop(UnOp::Neg, &lit(x)), neg);
+            }
+        }};
     }
 
     #[test]
-    fn reborrow() {
+    fn fold_ints() {
+        check_int_folds!(u8, |x| Literal::Uint(x as u128, UintTy::U8), false);
+        check_int_folds!(u16, |x| Literal::Uint(x as u128, UintTy::U16), false);
+        check_int_folds!(u32, |x| Literal::Uint(x as u128, UintTy::U32), false);
+        check_int_folds!(u64, |x| Literal::Uint(x as u128, UintTy::U64), false);
+        check_int_folds!(u128, |x| Literal::Uint(x, UintTy::U128), false);
+        check_int_folds!(usize, |x| Literal::Uint(x as u128, UintTy::Usize), false);
+        check_int_folds!(i8, |x| Literal::Int(x as i128, IntTy::I8), true);
+        check_int_folds!(i16, |x| Literal::Int(x as i128, IntTy::I16), true);
+        check_int_folds!(i32, |x| Literal::Int(x as i128, IntTy::I32), true);
+        check_int_folds!(i64, |x| Literal::Int(x as i128, IntTy::I64), true);
+        check_int_folds!(i128, |x| Literal::Int(x, IntTy::I128), true);
+        check_int_folds!(isize, |x| Literal::Int(x as i128, IntTy::Isize), true);
+    }
+
+    #[test]
+    fn fold_other_literals() {
+        use BinOp::*;
+        let fold = PlaceTable::fold_binop;
+        let (t, f) = (Literal::Bool(true), Literal::Bool(false));
+        assert_folds_to(fold(BitAnd, &t, &f), Some(f));
+        assert_folds_to(fold(BitOr, &t, &f), Some(t));
+        assert_folds_to(fold(BitXor, &t, &t), Some(f));
+        assert_folds_to(fold(Lt, &f, &t), Some(t));
+        assert_folds_to(fold(Add, &t, &t), None);
+        assert_folds_to(PlaceTable::fold_unop(UnOp::Not, &t), Some(f));
+        assert_folds_to(fold(Gt, &Literal::Char('b'), &Literal::Char('a')), Some(t));
+
+        // Operands must have the same type, except for shift amounts
+        let (one_u8, one_u16) = (Literal::Uint(1, UintTy::U8), Literal::Uint(1, UintTy::U16));
+        assert_folds_to(fold(Add, &one_u8, &one_u16), None);
+        assert_folds_to(fold(Eq, &one_u8, &one_u16), None);
+        assert_folds_to(
+            fold(Shl, &one_u8, &one_u16),
+            Some(Literal::Uint(2, UintTy::U8)),
+        );
+        assert_folds_to(fold(Offset, &one_u8, &one_u8), None);
+
+        // f32 rounds like f32
+        let f32 = |x: f32| Literal::Float(x as f64, FloatTy::F32);
+        let f64 = |x: f64| Literal::Float(x, FloatTy::F64);
+        assert_folds_to(fold(Add, &f32(0.1), &f32(0.2)), Some(f32(0.1 + 0.2)));
+        assert_folds_to(fold(Add, &f64(0.1), &f64(0.2)), Some(f64(0.1 + 0.2)));
+        assert_folds_to(
+            fold(Mul, &f32(f32::MAX), &f32(2.0)),
+            Some(f32(f32::INFINITY)),
+        );
+        assert_folds_to(fold(Rem, &f64(-7.5), &f64(2.0)), Some(f64(-1.5)));
+        assert_folds_to(fold(Div, &f64(1.0), &f64(0.0)), Some(f64(f64::INFINITY)));
+        assert_folds_to(fold(Div, &f64(0.0), &f64(0.0)), None);
+        assert_folds_to(fold(Sub, &f64(f64::INFINITY), &f64(f64::INFINITY)), None);
+        assert_folds_to(fold(Eq, &f64(f64::NAN), &f64(f64::NAN)), Some(f));
+        assert_folds_to(fold(Ne, &f64(f64::NAN), &f64(f64::NAN)), Some(t));
+        assert_folds_to(fold(Eq, &f64(0.0), &f64(-0.0)), Some(t));
+        assert_folds_to(fold(Add, &f32(1.0), &f64(1.0)), None);
+        assert_folds_to(PlaceTable::fold_unop(UnOp::Neg, &f64(0.0)), Some(f64(-0.0)));
+        assert_folds_to(PlaceTable::fold_unop(UnOp::Not, &f64(1.0)), None);
+        assert!(PlaceTable::fold_checked_binop(Add, &f64(1.0), &f64(1.0)).is_none());
+        assert!(PlaceTable::fold_checked_binop(Div, &one_u8, &one_u8).is_none());
+    }
+
+    #[test]
+    fn stale_index_place() {
         let mut tcx = TyCtxt::from_primitives();
+        let t_array = tcx.push(TyKind::Array(TyCtxt::I32, 4));
+        let tcx = Rc::new(tcx);
+        let mut pt = PlaceTable::new(tcx.clone());
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let array = pt.allocate_local(Local::new(1), t_array);
+        pt.mark_place_init(array);
+        let index = Local::new(2);
+        pt.allocate_local(index, TyCtxt::USIZE);
+        pt.mark_place_init(index);
+        pt.assign_literal(index, Some(Literal::Uint(1, UintTy::Usize)));
+
+        // _1[_2], built while _2 == 1
+        let indexed = Place::from_projected(Local::new(1), &[ProjectionElem::Index(index)]);
+        let elem = pt
+            .project_from_node(array, ProjectionElem::ConstantIndex { offset: 1 })
+            .unwrap();
+        assert_eq!(indexed.to_place_index(&pt), Some(elem));
+
+        // Past the end of the array
+        pt.assign_literal(index, Some(Literal::Uint(7, UintTy::Usize)));
+        assert_eq!(indexed.to_place_index(&pt), None);
+        // Unknown value
+        pt.assign_literal(index, None);
+        assert_eq!(indexed.to_place_index(&pt), None);
+        // Moved out
+        pt.assign_literal(index, Some(Literal::Uint(1, UintTy::Usize)));
+        pt.mark_place_moved(index);
+        assert_eq!(indexed.to_place_index(&pt), None);
+        assert!(PlaceSelector::for_lhs(tcx.clone())
+            .except(&indexed)
+            .into_iter_place(&pt)
+            .any(|place| place == Place::from_local(Local::new(1))));
+
+        // fn(_1: [i32; 4]), in which there is no _2
+        pt.assign_literal(index, Some(Literal::Uint(1, UintTy::Usize)));
+        pt.mark_place_init(index);
+        pt.allocate_local(Local::new(3), TyCtxt::UNIT);
+        let callee = Body::new(&[t_array], TyCtxt::UNIT, false);
+        pt.enter_fn(
+            &callee,
+            &[Operand::Copy(Place::from_local(Local::new(1)))],
+            &Place::from_local(Local::new(3)),
+        );
+        assert!(Local::new(1).to_place_index(&pt).is_some());
+        assert_eq!(indexed.to_place_index(&pt), None);
+    }
 
-        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Not));
+    #[test]
+    fn return_tuple() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::I32, Mutability::Not));
+        let t_tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::I32, t_ptr, t_ptr]));
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        pt.enter_fn0(&Body::new(&[], TyCtxt::UNIT, false));
+        let x = pt.allocate_local(Local::new(1), TyCtxt::I32);
+        let p = pt.allocate_local(Local::new(2), t_ptr);
+        pt.allocate_local(Local::new(3), t_tuple);
+        pt.assign_literal(x, Some(Literal::Int(4, IntTy::I32)));
+        pt.mark_place_init(p);
+        pt.set_ref(p, x, None);
+
+        let field = |local: usize, i: usize| {
+            Place::from_projected(
+                Local::new(local),
+                &[ProjectionElem::TupleField(FieldIdx::new(i))],
+            )
+        };
+
+        // fn(p: *const i32) -> (i32, *const i32, *const i32) {
+        //     let y = 5;
+        //     (y, p, &raw const y)
+        // }
+        let callee = Body::new(&[t_ptr], t_tuple, false);
+        pt.enter_fn(
+            &callee,
+            &[Operand::Copy(Place::from_local(Local::new(2)))],
+            &Place::from_local(Local::new(3)),
+        );
+        let y = pt.allocate_local(Local::new(2), TyCtxt::I32);
+        pt.assign_literal(y, Some(Literal::Int(5, IntTy::I32)));
+        pt.copy_place(field(0, 0), y);
+        pt.copy_place(field(0, 1), Local::new(1));
+        pt.mark_place_init(field(0, 2));
+        pt.set_ref(field(0, 2), y, None);
+        pt.exit_fn();
+
+        assert!(matches!(
+            pt.read_literal(field(3, 0)),
+            Some(Literal::Int(5, IntTy::I32))
+        ));
+        // The copy of the argument still points to the caller's local
+        let returned = field(3, 1).to_place_index(&pt).unwrap();
+        assert_eq!(pt.pointee(returned), Some(x));
+        assert_eq!(pt.places[returned].tag, pt.places[p].tag);
+        // The pointer to the callee's local dangles, so it is no longer followed
+        let dangling = field(3, 2).to_place_index(&pt).unwrap();
+        assert_eq!(pt.pointee(dangling), None);
+        assert!(!pt.is_place_live(y));
+        assert!(pt.is_place_live(x));
+    }
+
+    #[test]
+    fn provenance_through_bytes() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::U64, Mutability::Mut));
+        let t_bytes = tcx.push(TyKind::Array(TyCtxt::U8, 8));
 
         let mut pt = PlaceTable::new(Rc::new(tcx));
+        let int = Local::new(1);
+        let ptr = Local::new(2);
+        let bytes = Local::new(3);
+        let ptr2 = Local::new(4);
+        let int_p = pt.allocate_local(int, TyCtxt::U64);
+        let ptr_p = pt.allocate_local(ptr, t_ptr);
+        pt.allocate_local(bytes, t_bytes);
+        let ptr2_p = pt.allocate_local(ptr2, t_ptr);
+        pt.mark_place_init(int);
+        pt.mark_place_init(ptr);
+        pt.set_ref(ptr, int, None);
+
+        pt.transmute_place(bytes, ptr);
+        pt.transmute_place(ptr2, bytes);
+        assert_eq!(pt.pointee(ptr2_p), Some(int_p));
+        assert_eq!(pt.places[ptr2_p].tag, pt.places[ptr_p].tag);
+        assert!(pt.is_place_init(ptr2));
+
+        // Bytes without provenance can't be turned back into a dereferenceable pointer
+        pt.transmute_place(bytes, int);
+        pt.transmute_place(ptr2, bytes);
+        assert_eq!(pt.pointee(ptr2_p), None);
+        assert_eq!(pt.places[ptr2_p].tag, None);
+    }
+
+    #[test]
+    fn reborrow_ancestry() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
 
+        let mut pt = PlaceTable::new(Rc::new(tcx));
         let int = Local::new(1);
+        let r1 = Local::new(2);
+        let r2 = Local::new(3);
         pt.allocate_local(int, TyCtxt::I32);
+        let r1_p = pt.allocate_local(r1, t_ref);
+        let r2_p = pt.allocate_local(r2, t_ref);
+        pt.mark_place_init(int);
+        pt.mark_place_init(r1);
+        pt.set_ref(r1, int, None);
+
+        // r2 = &mut (*r1)
+        let reborrowed = Place::from_projected(r1, &[ProjectionElem::Deref]);
+        pt.mark_place_init(r2);
+        pt.set_reborrow(r2, &reborrowed, r1);
+
+        let r1_tag = pt.places[r1_p].tag.unwrap();
+        let r2_tag = pt.places[r2_p].tag.unwrap();
+        assert_ne!(r1_tag, r2_tag);
+        assert_eq!(pt.memory.parent_tag(r2_tag), Some(r1_tag));
+        assert_eq!(pt.memory.parent_tag(r1_tag), None);
+
+        // r1 = &mut (*r1)
+        pt.set_reborrow(r1, &reborrowed, r1);
+        let new_r1_tag = pt.places[r1_p].tag.unwrap();
+        assert_eq!(pt.memory.parent_tag(new_r1_tag), Some(r1_tag));
+    }
 
-        // int_ref = &int
-        let int_ref = Local::new(2);
-        pt.allocate_local(int_ref, t_ref);
-        let int_ref_p = int_ref.to_place_index(&pt).unwrap();
-        pt.set_ref(int_ref, int, None);
-        assert_eq!(pt.pointer_tags.first().unwrap().len(), 1);
+    #[test]
+    fn write_through_parent() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
 
-        // int_ref = &*int_ref
-        pt.set_ref(int_ref, int, Some(int_ref_p));
-        assert_eq!(pt.pointer_tags.first().unwrap().len(), 1);
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let int = Local::new(1);
+        let parent = Local::new(2);
+        let child = Local::new(3);
+        let int_p = pt.allocate_local(int, TyCtxt::I32);
+        pt.allocate_local(parent, t_ref);
+        let child_p = pt.allocate_local(child, t_ref);
+        pt.mark_place_init(int);
+        pt.mark_place_init(parent);
+        pt.set_ref(parent, int, None);
+
+        // child = &mut (*parent)
+        let through_parent = Place::from_projected(parent, &[ProjectionElem::Deref]);
+        pt.mark_place_init(child);
+        pt.set_reborrow(child, &through_parent, parent);
+        let through_child = Place::from_projected(child, &[ProjectionElem::Deref]);
+        assert!(pt.can_write_through(child_p, int_p));
+
+        // (*child) = 1 leaves both usable, (*parent) = 2 invalidates child
+        pt.place_written_through(child, &through_child);
+        assert!(pt.can_write_through(child_p, int_p));
+        pt.place_written_through(parent, &through_parent);
+        assert!(!pt.can_write_through(child_p, int_p));
+        assert_eq!(pt.pointee(child_p), None);
+        assert_eq!(pt.pointee(parent.to_place_index(&pt).unwrap()), Some(int_p));
+    }
+
+    #[test]
+    fn read_through_parent() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::I32, Mutability::Mut));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let int = Local::new(1);
+        let parent = Local::new(2);
+        let child = Local::new(3);
+        let int_p = pt.allocate_local(int, TyCtxt::I32);
+        pt.allocate_local(parent, t_ref);
+        let child_p = pt.allocate_local(child, t_ref);
+        pt.mark_place_init(int);
+        pt.mark_place_init(parent);
+        pt.set_ref(parent, int, None);
+
+        // child = &mut (*parent)
+        let through_parent = Place::from_projected(parent, &[ProjectionElem::Deref]);
+        pt.mark_place_init(child);
+        pt.set_reborrow(child, &through_parent, parent);
+        let through_child = Place::from_projected(child, &[ProjectionElem::Deref]);
+
+        // Reading (*child) leaves both usable, reading (*parent) invalidates child
+        pt.place_read_through(child, &through_child);
+        assert!(pt.can_read_through(child_p, int_p));
+        pt.place_read_through(parent, &through_parent);
+        assert!(!pt.can_read_through(child_p, int_p));
+        assert_eq!(pt.pointee(child_p), None);
+        assert_eq!(pt.pointee(parent.to_place_index(&pt).unwrap()), Some(int_p));
+    }
+
+    #[test]
+    fn bulk_copy() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_arr = tcx.push(TyKind::Array(TyCtxt::I32, 3));
+        let t_empty = tcx.push(TyKind::Array(TyCtxt::I32, 0));
+        let t_single = tcx.push(TyKind::Array(TyCtxt::I32, 1));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+
+        let src = Local::new(1);
+        let src_p = pt.allocate_local(src, t_arr);
+        let dst = Local::new(2);
+        let dst_p = pt.allocate_local(dst, t_arr);
+
+        let elem = |pt: &PlaceTable, pidx, i| {
+            pt.project_from_node(pidx, ProjectionElem::ConstantIndex { offset: i })
+                .unwrap()
+        };
+
+        // Partially init source is rejected
+        pt.mark_place_init(elem(&pt, src_p, 0));
+        assert!(!pt.bulk_copy(dst, src));
+        assert!(!pt.is_place_init(elem(&pt, dst_p, 0)));
+
+        // Overlapping places are rejected
+        pt.mark_place_init(src);
+        assert!(!pt.bulk_copy(src, src));
+
+        pt.assign_literal(elem(&pt, src_p, 2), Some(Literal::Int(5, IntTy::I32)));
+        assert!(pt.bulk_copy(dst, src));
+        assert!(pt.is_place_init(dst));
+        assert!(matches!(
+            pt.known_val(elem(&pt, dst_p, 2)),
+            Some(Literal::Int(5, IntTy::I32))
+        ));
+        assert!(pt.known_val(elem(&pt, dst_p, 0)).is_none());
+
+        // Length 0 and 1
+        let empty_src = Local::new(3);
+        pt.allocate_local(empty_src, t_empty);
+        let empty_dst = Local::new(4);
+        pt.allocate_local(empty_dst, t_empty);
+        assert!(pt.bulk_copy(empty_dst, empty_src));
+        assert!(pt.is_place_init(empty_dst));
+
+        let single_src = Local::new(5);
+        pt.allocate_local(single_src, t_single);
+        let single_dst = Local::new(6);
+        pt.allocate_local(single_dst, t_single);
+        assert!(!pt.bulk_copy(single_dst, single_src));
+        pt.mark_place_init(single_src);
+        assert!(pt.bulk_copy(single_dst, single_src));
+        assert!(pt.is_place_init(single_dst));
+
+        // Mismatched types are rejected
+        assert!(!pt.bulk_copy(single_dst, src));
+    }
+
+    #[test]
+    fn repr_c_struct() {
+        let mut tcx = TyCtxt::from_primitives();
+        let adt = Adt {
+            variants: IndexVec::from_iter([VariantDef {
+                fields: IndexVec::from_iter([TyCtxt::U8, TyCtxt::U32, TyCtxt::U8]),
+            }]),
+        };
+        let ty = tcx.push_adt(
+            adt,
+            AdtMeta {
+                repr_c: true,
+                ..plain_meta()
+            },
+        );
+
+        let layout = Layout::of(ty, &tcx).unwrap();
+        assert_eq!(
+            layout.field_offsets,
+            &[
+                Size::from_bytes(0),
+                Size::from_bytes(4),
+                Size::from_bytes(8)
+            ]
+        );
+        assert!(layout.has_padding);
+        assert_eq!(BasicMemory::ty_size(ty, &tcx), Some(Size::from_bytes(12)));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        let local_pidx = pt.allocate_local(local, ty);
+        let root = pt.places[local_pidx].run_ptr.unwrap();
+
+        let offsets: Vec<_> = (0..3)
+            .map(|i| {
+                let field = pt
+                    .project_from_node(local_pidx, ProjectionElem::Field(FieldIdx::new(i)))
+                    .unwrap();
+                let run_ptr = pt.places[field].run_ptr.unwrap();
+                assert!(run_ptr.run_and_offset.same_run(&root.run_and_offset));
+                (run_ptr.offset().bytes(), run_ptr.len().bytes())
+            })
+            .collect();
+        assert_eq!(offsets, [(0, 1), (4, 4), (8, 1)]);
+
+        // Padding isn't initialized along with the fields, and isn't required to be
+        pt.mark_place_init(local);
+        assert!(pt.is_place_init(local));
+        assert!(!pt.memory.bytes(root)[1].is_init());
+        assert!(!pt.memory.bytes(root)[9].is_init());
+
+        pt.mark_place_uninit(
+            pt.project_from_node(local_pidx, ProjectionElem::Field(FieldIdx::new(1)))
+                .unwrap(),
+        );
+        assert!(!pt.is_place_init(local));
+    }
+
+    #[test]
+    fn union_fields() {
+        let mut tcx = TyCtxt::from_primitives();
+        let meta = AdtMeta {
+            union: true,
+            ..plain_meta()
+        };
+        let union = |tcx: &mut TyCtxt, fields: [TyId; 2]| {
+            let adt = Adt {
+                variants: IndexVec::from_iter([VariantDef {
+                    fields: IndexVec::from_iter(fields),
+                }]),
+            };
+            tcx.push_adt(adt, meta)
+        };
+        let t_bytes4 = tcx.push(TyKind::Array(TyCtxt::U8, 4));
+        let t_bytes8 = tcx.push(TyKind::Array(TyCtxt::U8, 8));
+        let t_ptr = tcx.push(TyKind::RawPtr(TyCtxt::U64, Mutability::Mut));
+        let int_or_bytes = union(&mut tcx, [TyCtxt::U32, t_bytes4]);
+        let ptr_or_bytes = union(&mut tcx, [t_ptr, t_bytes8]);
+
+        let layout = Layout::of(int_or_bytes, &tcx).unwrap();
+        assert_eq!(layout.size, Size::from_bytes(4));
+        assert_eq!(layout.field_offsets, &[Size::ZERO, Size::ZERO]);
+        assert!(!layout.has_padding);
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        let local_pidx = pt.allocate_local(local, int_or_bytes);
+        let field = |pt: &PlaceTable, pidx, i| {
+            pt.project_from_node(pidx, ProjectionElem::Field(FieldIdx::new(i)))
+                .unwrap()
+        };
+        let (int, bytes) = (field(&pt, local_pidx, 0), field(&pt, local_pidx, 1));
+        assert_eq!(pt.places[int].run_ptr, pt.places[bytes].run_ptr);
+        assert!(pt.overlap(int, bytes));
+
+        // Writing one field initializes the bytes read by the other
+        assert!(!pt.is_place_init(bytes));
+        pt.mark_place_init(int);
+        assert!(pt.is_place_init(bytes));
+        pt.mark_place_uninit(bytes);
+        assert!(!pt.is_place_init(int));
+
+        // A value written through one field isn't known to be in the other
+        pt.mark_place_init(int);
+        pt.assign_literal(int, Some(Literal::Uint(1, UintTy::U32)));
+        let byte = pt
+            .project_from_node(bytes, ProjectionElem::ConstantIndex { offset: 0 })
+            .unwrap();
+        pt.assign_literal(byte, Some(Literal::Uint(0, UintTy::U8)));
+        assert!(pt.known_val(int).is_none());
+
+        // Pointer bytes written through one field carry over to the other
+        let target = Local::new(2);
+        let other = Local::new(3);
+        let ptr_local = Local::new(4);
+        let target_pidx = pt.allocate_local(target, TyCtxt::U64);
+        let other_pidx = pt.allocate_local(other, ptr_or_bytes);
+        let ptr_pidx = pt.allocate_local(ptr_local, t_ptr);
+        let (ptr, bytes) = (field(&pt, other_pidx, 0), field(&pt, other_pidx, 1));
+        pt.mark_place_init(target);
+        pt.mark_place_init(ptr);
+        pt.set_ref(ptr, target, None);
+        assert!(pt.is_place_init(bytes));
+        let tag = pt.places[ptr].tag;
+        assert!(tag.is_some());
+        assert_eq!(pt.memory.provenance(pt.places[bytes].run_ptr.unwrap()), tag);
+
+        pt.transmute_place(ptr_local, bytes);
+        assert_eq!(pt.pointee(ptr_pidx), Some(target_pidx));
+    }
+
+    #[test]
+    fn union_overlapping_fields() {
+        let mut tcx = TyCtxt::from_primitives();
+        let struct_of = |tcx: &mut TyCtxt, fields: &[TyId], union: bool| {
+            let adt = Adt {
+                variants: IndexVec::from_iter([VariantDef {
+                    fields: IndexVec::from_iter(fields.iter().copied()),
+                }]),
+            };
+            let meta = AdtMeta {
+                repr_c: !union,
+                union,
+                ..plain_meta()
+            };
+            tcx.push_adt(adt, meta)
+        };
+        let t_pair = struct_of(&mut tcx, &[TyCtxt::U32, TyCtxt::U32], false);
+        let t_union = struct_of(&mut tcx, &[TyCtxt::U64, t_pair], true);
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let (u, v) = (Local::new(1), Local::new(2));
+        let u_pidx = pt.allocate_local(u, t_union);
+        let v_pidx = pt.allocate_local(v, t_union);
+        let field = |pt: &PlaceTable, pidx, i| {
+            pt.project_from_node(pidx, ProjectionElem::Field(FieldIdx::new(i)))
+                .unwrap()
+        };
+        let (int, pair) = (field(&pt, u_pidx, 0), field(&pt, u_pidx, 1));
+        let (lo, hi) = (field(&pt, pair, 0), field(&pt, pair, 1));
+        assert!(pt.overlap(int, pair) && pt.overlap(int, hi));
+        assert!(!pt.overlap(lo, hi));
+
+        // Writing the u64 initializes both halves of the pair
+        pt.mark_place_init(int);
+        pt.assign_literal(int, Some(Literal::Uint(u64::MAX as u128, UintTy::U64)));
+        assert!(pt.is_place_init(pair) && pt.is_place_init(lo) && pt.is_place_init(hi));
+        assert_eq!(pt.active_union_field(u), Some(int));
+        let through_inactive = |pt: &PlaceTable, target| {
+            pt.reachable_nodes()
+                .find(|path| path.target_index() == target)
+                .unwrap()
+                .through_inactive_union_field(pt)
+        };
+        assert!(!through_inactive(&pt, int));
+        assert!(through_inactive(&pt, hi));
+
+        // An uninit tail makes the u64 uninit, but not the other half
+        pt.mark_place_uninit(hi);
+        assert!(!pt.is_place_init(int) && !pt.is_place_init(pair));
+        pt.mark_place_init(lo);
+        assert_eq!(pt.active_union_field(u), Some(pair));
+        assert!(pt.is_place_init(lo) && !pt.is_place_init(hi));
+        assert!(!through_inactive(&pt, lo));
+
+        // Copies keep the bytes exactly, including the uninit tail
+        pt.mark_place_init(v);
+        pt.copy_place(v_pidx, u_pidx);
+        let (v_int, v_pair) = (field(&pt, v_pidx, 0), field(&pt, v_pidx, 1));
+        assert!(pt.is_place_init(field(&pt, v_pair, 0)));
+        assert!(!pt.is_place_init(field(&pt, v_pair, 1)));
+        assert!(!pt.is_place_init(v_int));
+        assert_eq!(pt.active_union_field(v), Some(v_pair));
+    }
+
+    #[test]
+    fn union_siblings_forgotten_on_write() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_ref = tcx.push(TyKind::Ref(TyCtxt::U32, Mutability::Not));
+        let adt = Adt {
+            variants: IndexVec::from_iter([VariantDef {
+                fields: IndexVec::from_iter([TyCtxt::USIZE, t_ref]),
+            }]),
+        };
+        let meta = AdtMeta {
+            union: true,
+            ..plain_meta()
+        };
+        let t_union = tcx.push_adt(adt, meta);
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let (x, u) = (Local::new(1), Local::new(2));
+        pt.allocate_local(x, TyCtxt::U32);
+        pt.mark_place_init(x);
+        let u_pidx = pt.allocate_local(u, t_union);
+        let field = |pt: &PlaceTable, i| {
+            pt.project_from_node(u_pidx, ProjectionElem::Field(FieldIdx::new(i)))
+                .unwrap()
+        };
+        let (int, ptr) = (field(&pt, 0), field(&pt, 1));
 
+        pt.mark_place_init(int);
+        pt.assign_literal(int, Some(Literal::Uint(5, UintTy::Usize)));
         pt.place_written(int);
-        assert!(!pt
+        assert!(pt.known_val(int).is_some());
+
+        // Storing a reference over the integer goes through set_ref rather than
+        // assign_literal, and the integer's bytes are still overwritten
+        pt.mark_place_init(ptr);
+        pt.set_ref(ptr, x, None);
+        pt.place_written(ptr);
+        assert!(pt.known_val(int).is_none());
+        assert!(pt.pointee(ptr).is_some());
+    }
+
+    #[test]
+    fn fieldless_enums() {
+        let mut tcx = TyCtxt::from_primitives();
+        let meta = plain_meta();
+        let mut fieldless = |variant_count| {
+            let adt = Adt {
+                variants: IndexVec::from_iter((0..variant_count).map(|_| VariantDef {
+                    fields: IndexVec::new(),
+                })),
+            };
+            tcx.push_adt(adt, meta)
+        };
+        let one = fieldless(1);
+        let two = fieldless(2);
+        let many = fieldless(300);
+
+        assert_eq!(BasicMemory::ty_size(one, &tcx), Some(Size::ZERO));
+        assert_eq!(BasicMemory::ty_size(two, &tcx), Some(Size::from_bytes(1)));
+        assert_eq!(BasicMemory::ty_size(many, &tcx), Some(Size::from_bytes(2)));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        let local_pidx = pt.allocate_local(local, many);
+        assert_eq!(
+            pt.places[local_pidx].run_ptr.unwrap().len(),
+            Size::from_bytes(2)
+        );
+        pt.mark_place_init(local);
+        assert!(pt.is_place_init(local));
+    }
+
+    #[test]
+    fn enum_variants() {
+        let mut tcx = TyCtxt::from_primitives();
+        let adt = Adt {
+            variants: IndexVec::from_iter([
+                VariantDef {
+                    fields: IndexVec::new(),
+                },
+                VariantDef {
+                    fields: IndexVec::from_iter([TyCtxt::I32]),
+                },
+            ]),
+        };
+        let meta = plain_meta();
+        let t_option = tcx.push_adt(adt, meta);
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        let pidx = pt.allocate_local(local, t_option);
+        let payload = pt
             .places
-            .contains_edge(int_ref_p, int.to_place_index(&pt).unwrap()));
+            .edges_directed(pidx, Direction::Outgoing)
+            .find(|e| {
+                *e.weight()
+                    == ProjectionElem::DowncastField(
+                        VariantIdx::new(1),
+                        FieldIdx::new(0),
+                        TyCtxt::I32,
+                    )
+            })
+            .expect("payload exists")
+            .target();
+        let discriminant = pt.places[pidx].discriminant.expect("enum has fields");
+        assert_eq!(discriminant.size, Size::from_bytes(1));
+        let reachable = |pt: &PlaceTable| {
+            pt.reachable_nodes()
+                .any(|path| path.target_index() == payload)
+        };
+        assert!(!reachable(&pt));
+
+        pt.assign_discriminant(local, Some(VariantIdx::new(1)));
+        assert!(reachable(&pt));
+        assert_eq!(pt.memory.read_scalar(discriminant), Some(1));
+
+        pt.assign_discriminant(local, Some(VariantIdx::new(0)));
+        assert!(!reachable(&pt));
+        assert_eq!(pt.memory.read_scalar(discriminant), Some(0));
+
+        pt.assign_discriminant(local, None);
+        assert!(!pt.memory.bytes(discriminant)[0].is_init());
+    }
+
+    #[test]
+    fn discriminant_flips() {
+        let mut tcx = TyCtxt::from_primitives();
+        let adt = Adt {
+            variants: IndexVec::from_iter([
+                VariantDef {
+                    fields: IndexVec::new(),
+                },
+                VariantDef {
+                    fields: IndexVec::from_iter([TyCtxt::I32]),
+                },
+            ]),
+        };
+        let meta = plain_meta();
+        let t_option = tcx.push_adt(adt, meta);
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let local = Local::new(1);
+        pt.allocate_local(local, t_option);
+        let mut payload = Place::from_local(local);
+        payload.project(ProjectionElem::DowncastField(
+            VariantIdx::new(1),
+            FieldIdx::new(0),
+            TyCtxt::I32,
+        ));
+        assert!(pt.known_discriminant(local).is_none());
+
+        pt.assign_discriminant(local, Some(VariantIdx::new(1)));
+        pt.mark_place_init(&payload);
+        pt.assign_literal(&payload, Some(Literal::Int(5, IntTy::I32)));
+        assert!(pt.is_place_init(local));
+        assert!(matches!(
+            pt.known_discriminant(local),
+            Some(Literal::Int(1, IntTy::Isize))
+        ));
+
+        // Flipping the variant leaves the old payload uninit, even once it's active again
+        pt.assign_discriminant(local, Some(VariantIdx::new(0)));
+        assert!(matches!(
+            pt.known_discriminant(local),
+            Some(Literal::Int(0, IntTy::Isize))
+        ));
+        assert!(!pt.is_place_init(&payload));
+        assert!(pt.known_val(&payload).is_none());
+        pt.assign_discriminant(local, Some(VariantIdx::new(1)));
+        assert!(!pt.is_place_init(&payload));
+        assert!(!pt.is_place_init(local));
+
+        pt.mark_place_uninit(local);
+        assert!(pt.known_discriminant(local).is_none());
+    }
+
+    #[test]
+    fn literal_through_bytes() {
+        let mut tcx = TyCtxt::from_primitives();
+        let t_bytes = tcx.push(TyKind::Array(TyCtxt::U8, 2));
+
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let int = Local::new(1);
+        let bytes = Local::new(2);
+        let int2 = Local::new(3);
+        let signed = Local::new(4);
+        pt.allocate_local(int, TyCtxt::U16);
+        pt.allocate_local(bytes, t_bytes);
+        pt.allocate_local(int2, TyCtxt::U16);
+        pt.allocate_local(signed, TyCtxt::I16);
+        pt.mark_place_init(int);
+        pt.assign_literal(int, Some(Literal::Uint(0x0102, UintTy::U16)));
+
+        let run_ptr = pt.places[int.to_place_index(&pt).unwrap()].run_ptr.unwrap();
+        let expected = 0x0102u16
+            .to_ne_bytes()
+            .map(|byte| AbstractByte::Init(Some(byte)));
+        assert_eq!(pt.memory.bytes(run_ptr), expected);
+
+        // The place-level value is lost through the array, but not the bytes
+        pt.transmute_place(bytes, int);
+        for (i, byte) in 0x0102u16.to_ne_bytes().into_iter().enumerate() {
+            let elem =
+                Place::from_projected(bytes, &[ProjectionElem::ConstantIndex { offset: i as u64 }]);
+            assert!(matches!(
+                pt.read_literal(&elem),
+                Some(Literal::Uint(val, UintTy::U8)) if val == byte as u128
+            ));
+        }
+        pt.transmute_place(int2, bytes);
+        assert!(pt.known_val(int2).is_none());
+        assert!(matches!(
+            pt.read_literal(int2),
+            Some(Literal::Uint(0x0102, UintTy::U16))
+        ));
+
+        pt.mark_place_init(signed);
+        pt.assign_literal(signed, Some(Literal::Int(-2, IntTy::I16)));
+        pt.transmute_place(int2, signed);
+        assert!(matches!(
+            pt.read_literal(int2),
+            Some(Literal::Uint(0xfffe, UintTy::U16))
+        ));
+        pt.transmute_place(signed, int2);
+        assert!(matches!(
+            pt.read_literal(signed),
+            Some(Literal::Int(-2, IntTy::I16))
+        ));
+
+        // Forgetting a value forgets the bytes with it, so they don't give it away
+        pt.assign_literal(signed, None);
+        assert!(pt.is_place_init(signed));
+        assert!(pt.read_literal(signed).is_none());
+        let int2_pidx = int2.to_place_index(&pt).unwrap();
+        pt.invalidate_knowledge(&[int2_pidx]);
+        assert!(pt.is_place_init(int2));
+        assert!(pt.read_literal(int2).is_none());
+
+        // A write of an unknown value forgets the bytes
+        pt.mark_place_init(int2);
+        assert!(pt.read_literal(int2).is_none());
+    }
+
+    #[bench]
+    fn bench_overlap(b: &mut Bencher) {
+        let mut tcx = TyCtxt::from_primitives();
+        let nested = nested_tuple_ty(&mut tcx, 32);
+        let mut pt = PlaceTable::new(Rc::new(tcx));
+        let root = pt.allocate_local(Local::new(1), nested);
+        let nodes: Vec<PlaceIndex> = pt.transitive_subfields(root);
+        let innermost = *nodes
+            .iter()
+            .find(|&&node| pt.ty(node) == TyCtxt::I32)
+            .unwrap();
+        let last = *nodes.iter().max().unwrap();
+
+        b.iter(|| {
+            assert!(pt.overlap(root, innermost));
+            assert!(!pt.overlap(innermost, last));
+        })
     }
 }
@@ -21,6 +21,8 @@ pub const ARRAY_MAX_LEN: usize = 8;
 const STRUCT_MAX_FIELDS: usize = 8;
 /// Max. number of variants in an enum
 const ADT_MAX_VARIANTS: usize = 4;
+/// Chance of a struct being #[repr(C)]
+const REPR_C_CHANCE: f64 = 0.5;
 /// Number of composite structural types
 const COMPOSITE_COUNT: usize = 64;
 /// Number of ADTs
@@ -58,7 +60,8 @@ impl TySelect {
 
         for (idx, ty) in tcx.iter_enumerated() {
             let p = match ty {
-                TyKind::Unit => Some(0.),
+                // Unsized types can't be locals, only be pointed to
+                TyKind::Unit | TyKind::Slice(..) | TyKind::Str => Some(0.),
                 TyKind::Bool => Some(p_bool),
                 TyKind::Char => Some(p_char),
                 TyKind::Int(IntTy::Isize) => Some(p_isize),
@@ -182,11 +185,59 @@ fn new_adt(tcx: &mut TyCtxt, rng: &mut impl Rng) {
         false
     };
 
-    let meta = AdtMeta { copy };
+    // repr(C) structs have a known layout, so they are a single run
+    let repr_c = !adt.is_enum() && rng.gen_bool(REPR_C_CHANCE);
+
+    let meta = AdtMeta {
+        copy,
+        repr_c,
+        union: false,
+    };
 
     tcx.push_adt(adt, meta);
 }
 
+/// Adds a shared reference to each array type, and to the slice type of its elements, so that
+/// references to arrays can be unsized
+fn new_slices(tcx: &mut TyCtxt) {
+    let arrays: Vec<(TyId, TyId)> = tcx
+        .iter_enumerated()
+        .filter_map(|(ty, kind)| match kind {
+            TyKind::Array(elem, _) => Some((ty, *elem)),
+            _ => None,
+        })
+        .collect();
+    for (array, elem) in arrays {
+        let existing = tcx
+            .iter_enumerated()
+            .find_map(|(ty, kind)| (*kind == TyKind::Slice(elem)).then_some(ty));
+        let slice = existing.unwrap_or_else(|| tcx.push(TyKind::Slice(elem)));
+        for pointee in [array, slice] {
+            let new_ty = TyKind::Ref(pointee, Mutability::Not);
+            if !tcx.iter().any(|ty| *ty == new_ty) {
+                tcx.push(new_ty);
+            }
+        }
+    }
+}
+
+/// Adds a raw pointer of each mutability to each array type, so that arrays can be copied
+/// through copy_nonoverlapping
+fn new_array_ptrs(tcx: &mut TyCtxt) {
+    let arrays: Vec<TyId> = tcx
+        .iter_enumerated()
+        .filter_map(|(ty, kind)| matches!(kind, TyKind::Array(..)).then_some(ty))
+        .collect();
+    for array in arrays {
+        for mutability in [Mutability::Not, Mutability::Mut] {
+            let new_ty = TyKind::RawPtr(array, mutability);
+            if !tcx.iter().any(|ty| *ty == new_ty) {
+                tcx.push(new_ty);
+            }
+        }
+    }
+}
+
 pub fn seed_tys<R: Rng>(rng: &mut R) -> TyCtxt {
     // Seed with primitives
     let mut tcx: TyCtxt = TyCtxt::from_primitives();
@@ -208,6 +259,8 @@ pub fn seed_tys<R: Rng>(rng: &mut R) -> TyCtxt {
             Kind::Structural => new_composite(&mut tcx, rng),
         }
     }
+    new_slices(&mut tcx);
+    new_array_ptrs(&mut tcx);
     tcx
 }
 
@@ -33,6 +33,9 @@ impl Serialize for TyId {
             TyKind::Ref(ty, mutability) => {
                 format!("&'static {}{}", mutability.prefix_str(), ty.serialize(tcx))
             }
+            TyKind::FnPtr(args, ret) => {
+                format!("fn({}) -> {}", args.as_slice().serialize(tcx), ret.serialize(tcx))
+            }
             // Sequence types
             TyKind::Tuple(elems) => {
                 if elems.len() == 1 {
@@ -44,6 +47,8 @@ impl Serialize for TyId {
             TyKind::Array(ty, len) => {
                 format!("[{}; {len}]", ty.serialize(tcx))
             }
+            TyKind::Slice(ty) => format!("[{}]", ty.serialize(tcx)),
+            TyKind::Str => "str".to_owned(),
             // User-defined type
             TyKind::Adt(_) => self.type_name(),
         }
@@ -74,6 +79,7 @@ impl Place {
                 fid.index()
             ),
             ProjectionElem::ConstantIndex { offset } => format!("{acc}[{offset}]"),
+            ProjectionElem::Subslice { from, to } => format!("{acc}[{from}..{to}]"),
         })
     }
 
@@ -92,6 +98,7 @@ impl Place {
                 fid.index()
             ),
             ProjectionElem::ConstantIndex { offset } => format!("{acc}[{offset}]"),
+            ProjectionElem::Subslice { from, to } => format!("{acc}[{from}..{to}]"),
         })
     }
 }
@@ -153,6 +160,7 @@ impl Serialize for Rvalue {
 
             Rvalue::Cast(a, target) => format!("{} as {}", a.serialize(tcx), target.serialize(tcx)),
             Rvalue::Len(place) => format!("Len({})", place.serialize_value(tcx)),
+            Rvalue::PtrMetadata(a) => format!("PtrMetadata({})", a.serialize(tcx)),
             Rvalue::Discriminant(place) => format!("Discriminant({})", place.serialize_value(tcx)),
             Rvalue::AddressOf(Mutability::Not, place) => {
                 format!("core::ptr::addr_of!({})", place.serialize_place(tcx))
@@ -381,7 +389,13 @@ impl Serialize for Program {
 
 #[cfg(test)]
 mod tests {
-    use crate::{syntax::*, tyctxt::TyCtxt};
+    use index_vec::IndexVec;
+
+    use crate::{
+        syntax::*,
+        tyctxt::{AdtMeta, TyCtxt},
+        VarDumper,
+    };
 
     use super::Serialize;
 
@@ -398,6 +412,44 @@ mod tests {
         });
     }
 
+    #[test]
+    fn serialize_deterministic() {
+        let build = || {
+            let mut tcx = TyCtxt::from_primitives();
+            let tuple = tcx.push(TyKind::Tuple(vec![TyCtxt::I8, TyCtxt::F64]));
+            for (i, copy) in [true, false, true].into_iter().enumerate() {
+                let fields =
+                    IndexVec::from_iter([tuple, TyCtxt::U16, TyCtxt::CHAR].into_iter().take(i + 1));
+                let variants = (0..=i).map(|_| VariantDef {
+                    fields: fields.clone(),
+                });
+                tcx.push_adt(
+                    Adt {
+                        variants: IndexVec::from_iter(variants),
+                    },
+                    AdtMeta {
+                        copy,
+                        repr_c: i == 0,
+                        union: false,
+                    },
+                );
+            }
+            tcx
+        };
+        let (a, b) = (build(), build());
+        for dumper in [
+            VarDumper::HashDumper,
+            VarDumper::StdVarDumper,
+            VarDumper::PrintfVarDumper,
+        ] {
+            assert_eq!(a.serialize(dumper), a.serialize(dumper));
+            assert_eq!(a.serialize(dumper), b.serialize(dumper));
+        }
+
+        let lit = Literal::Float(-0.1, FloatTy::F64);
+        assert_eq!(lit.serialize(&a), "(-0.1_f64)");
+    }
+
     #[test]
     fn serialize_literal() {
         let tcx = TyCtxt::from_primitives();
@@ -127,6 +127,7 @@ impl From<Local> for Place {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProjectionElem {
     Deref,
     /// This should be the same as Field, but to allow for context free serialization
@@ -139,7 +140,11 @@ pub enum ProjectionElem {
     ConstantIndex {
         offset: u64,
     },
-    // TODO: Subslice
+    /// Elements from..to of an array, as an array of their own
+    Subslice {
+        from: u64,
+        to: u64,
+    },
 }
 
 impl ProjectionElem {
@@ -209,6 +214,8 @@ pub enum Rvalue {
     CheckedBinaryOp(BinOp, Operand, Operand),
     // define!("mir_len", fn Len<T>(place: T) -> usize);
     Len(Place),
+    // define!("mir_ptr_metadata", fn PtrMetadata<P: ?Sized>(place: *const P) -> <P as ::core::ptr::Pointee>::Metadata);
+    PtrMetadata(Operand),
     // define!("mir_discriminant",fn Discriminant<T>(place: T) -> <T as ::core::marker::DiscriminantKind>::Discriminant);
     Discriminant(Place),
     AddressOf(Mutability, Place),
@@ -226,6 +233,7 @@ pub enum AggregateKind {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
     Uint(u128, UintTy),
     Int(i128, IntTy),
@@ -279,6 +287,7 @@ pub enum Statement {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mutability {
     // N.B. Order is deliberate, so that Not < Mut
     Not,
@@ -286,6 +295,7 @@ pub enum Mutability {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IntTy {
     Isize,
     I8,
@@ -296,6 +306,7 @@ pub enum IntTy {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UintTy {
     Usize,
     U8,
@@ -306,6 +317,7 @@ pub enum UintTy {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FloatTy {
     F32,
     F64,
@@ -363,10 +375,19 @@ impl TyId {
                     }
                     ProjectionElem::Index(_) | ProjectionElem::ConstantIndex { .. } => {
                         match self.kind(tcx) {
-                            TyKind::Array(ty, ..) => *ty,
+                            TyKind::Array(ty, ..) | TyKind::Slice(ty) => *ty,
                             _ => panic!("not an array"),
                         }
                     }
+                    ProjectionElem::Subslice { from, to } => match self.kind(tcx) {
+                        TyKind::Array(ty, ..) => {
+                            let kind = TyKind::Array(*ty, (to - from) as usize);
+                            tcx.iter_enumerated()
+                                .find_map(|(sub, sub_kind)| (*sub_kind == kind).then_some(sub))
+                                .expect("subslice type exists")
+                        }
+                        _ => panic!("not an array"),
+                    },
                     ProjectionElem::Field(fid) => match self.kind(tcx) {
                         TyKind::Adt(adt) => {
                             let fields = &adt.variants.first().expect("adt is a struct").fields;
@@ -394,7 +415,7 @@ impl TyId {
         match self.kind(tcx) {
             TyKind::Tuple(elems) => elems.iter().any(|ty| ty.contains(tcx, predicate)),
             TyKind::RawPtr(pointee, _) => pointee.contains(tcx, predicate),
-            TyKind::Array(ty, ..) => ty.contains(tcx, predicate),
+            TyKind::Array(ty, ..) | TyKind::Slice(ty) => ty.contains(tcx, predicate),
             TyKind::Adt(adt) => adt
                 .variants
                 .iter()
@@ -415,6 +436,15 @@ impl TyId {
         matches!(self.kind(tcx), TyKind::RawPtr(..) | TyKind::Ref(..))
     }
 
+    pub fn is_fn_ptr(self, tcx: &TyCtxt) -> bool {
+        matches!(self.kind(tcx), TyKind::FnPtr(..))
+    }
+
+    /// Whether the type has no size known at compile time, so pointers to it are fat
+    pub fn is_unsized(self, tcx: &TyCtxt) -> bool {
+        matches!(self.kind(tcx), TyKind::Slice(..) | TyKind::Str)
+    }
+
     pub fn pointee_ty(self, tcx: &TyCtxt) -> Option<Self> {
         match self.kind(tcx) {
             TyKind::RawPtr(ty, ..) | TyKind::Ref(ty, ..) => Some(*ty),
@@ -424,7 +454,7 @@ impl TyId {
 
     // If doesn't contain printer
     pub fn determ_printable(self, tcx: &TyCtxt) -> bool {
-        !self.contains(tcx, |tcx, ty| ty.is_any_ptr(tcx))
+        !self.contains(tcx, |tcx, ty| ty.is_any_ptr(tcx) || ty.is_fn_ptr(tcx))
     }
 
     pub fn hashable(self, tcx: &TyCtxt) -> bool {
@@ -447,14 +477,17 @@ impl TyId {
             | TyKind::Uint(_)
             | TyKind::Float(_) => true,
             TyKind::RawPtr(_, _) | TyKind::Ref(_, _) => false,
+            TyKind::FnPtr(..) => true,
             TyKind::Tuple(tys) => tys.iter().all(|ty| ty.is_copy(tcx)),
             TyKind::Array(ty, _) => ty.is_copy(tcx),
+            TyKind::Slice(_) | TyKind::Str => false,
             TyKind::Adt(_) => tcx.meta(self).copy,
         }
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TyKind {
     // Scalars
     Unit,
@@ -466,10 +499,15 @@ pub enum TyKind {
     // Composite
     RawPtr(TyId, Mutability),
     Ref(TyId, Mutability),
+    // Pointer to a function taking the args and returning the last
+    FnPtr(Vec<TyId>, TyId),
     Tuple(Vec<TyId>),
     // User-defined
     Adt(Adt),
     Array(TyId, usize),
+    // Unsized, only usable behind a pointer
+    Slice(TyId),
+    Str,
     // TODO: more types
 }
 
@@ -544,8 +582,10 @@ impl PartialEq for TyKind {
             (Self::Float(l0), Self::Float(r0)) => l0 == r0,
             (Self::RawPtr(l0, l1), Self::RawPtr(r0, r1)) => l0 == r0 && l1 == r1,
             (Self::Ref(l0, l1), Self::Ref(r0, r1)) => l0 == r0 && l1 == r1,
+            (Self::FnPtr(l0, l1), Self::FnPtr(r0, r1)) => l0 == r0 && l1 == r1,
             (Self::Tuple(l0), Self::Tuple(r0)) => l0 == r0,
             (Self::Array(l0, l1), Self::Array(r0, r1)) => l0 == r0 && l1 == r1,
+            (Self::Slice(l0), Self::Slice(r0)) => l0 == r0,
             (Self::Adt(..), Self::Adt(..)) => false,
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
@@ -553,12 +593,14 @@ impl PartialEq for TyKind {
 }
 
 #[derive(PartialEq, Eq, Clone, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariantDef {
     /// Fields of this variant.
     pub fields: IndexVec<FieldIdx, TyId>,
 }
 
 #[derive(Clone, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Adt {
     pub variants: IndexVec<VariantIdx, VariantDef>,
 }
@@ -572,6 +614,11 @@ impl Adt {
     pub fn is_enum(&self) -> bool {
         self.variants.len() > 1
     }
+
+    /// Whether no variant has any field, like a C-like enum
+    pub fn is_fieldless(&self) -> bool {
+        self.variants.iter().all(|variant| variant.fields.is_empty())
+    }
 }
 
 #[derive(Clone, Copy)]
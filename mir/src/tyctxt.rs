@@ -1,4 +1,4 @@
-use std::{collections::HashMap, slice};
+use std::{collections::BTreeMap, slice};
 
 use index_vec::IndexVec;
 
@@ -7,13 +7,19 @@ use crate::{
 };
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AdtMeta {
     pub copy: bool,
+    /// Lay out the struct as #[repr(C)]. Ignored for enums
+    pub repr_c: bool,
+    /// Declare the struct as a #[repr(C)] union, with all fields at offset 0. Ignored for enums
+    pub union: bool,
 }
 
 impl AdtMeta {
     fn derive_attrs(&self) -> String {
-        let mut attrs = vec!["Debug"];
+        // Unions can't derive Debug
+        let mut attrs = if self.union { vec![] } else { vec!["Debug"] };
         if self.copy {
             attrs.push("Copy");
             attrs.push("Clone");
@@ -28,9 +34,21 @@ impl AdtMeta {
     }
 }
 
+/// Layout of a struct under the default repr, as reported by the target compiler
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProbedLayout {
+    pub size: u64,
+    pub align: u64,
+    pub field_offsets: Vec<u64>,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TyCtxt {
     tys: IndexVec<TyId, TyKind>,
-    adt_meta: HashMap<TyId, AdtMeta>,
+    // Ordered, so that nothing iterating over it can make emission nondeterministic
+    adt_meta: BTreeMap<TyId, AdtMeta>,
+    probed_layouts: BTreeMap<TyId, ProbedLayout>,
 }
 
 impl TyCtxt {
@@ -75,7 +93,8 @@ impl TyCtxt {
         let tys = IndexVec::from_iter(primitives);
         Self {
             tys,
-            adt_meta: HashMap::new(),
+            adt_meta: BTreeMap::new(),
+            probed_layouts: BTreeMap::new(),
         }
     }
 
@@ -94,6 +113,15 @@ impl TyCtxt {
         self.adt_meta[&ty]
     }
 
+    pub fn set_probed_layout(&mut self, ty: TyId, layout: ProbedLayout) {
+        assert!(self.tys[ty].is_adt());
+        self.probed_layouts.insert(ty, layout);
+    }
+
+    pub fn probed_layout(&self, ty: TyId) -> Option<&ProbedLayout> {
+        self.probed_layouts.get(&ty)
+    }
+
     pub fn kind(&self, ty: TyId) -> &TyKind {
         &self.tys[ty]
     }
@@ -137,8 +165,13 @@ impl TyCtxt {
                 str += &format!("pub enum {} {{\n{variants}}}\n", id.type_name())
             } else {
                 let def = adt.variants.first().expect("has only one variant");
+                let meta = self.adt_meta[&id];
+                if meta.repr_c || meta.union {
+                    str += "#[repr(C)]\n";
+                }
+                let keyword = if meta.union { "union" } else { "struct" };
                 str += &format!(
-                    "pub struct {} {{\n{}}}\n",
+                    "pub {keyword} {} {{\n{}}}\n",
                     id.type_name(),
                     def.serialize(self)
                 )
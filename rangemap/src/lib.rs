@@ -13,6 +13,7 @@ use std::ops;
 use abi::size::Size;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Elem<T> {
     /// The range covered by this element; never empty.
     range: ops::Range<u64>,
@@ -20,6 +21,7 @@ struct Elem<T> {
     data: T,
 }
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RangeMap<T> {
     v: Vec<Elem<T>>,
 }
@@ -87,7 +89,7 @@ impl<T> RangeMap<T> {
         // The first offset that is not included any more.
         let end = offset + len;
         assert!(
-            end <= self.v.last().unwrap().range.end,
+            end <= self.v.last().map_or(0, |elem| elem.range.end),
             "iterating beyond the bounds of this RangeMap"
         );
         slice
@@ -243,6 +245,14 @@ mod tests {
             .collect()
     }
 
+    #[test]
+    fn empty_map() {
+        let mut map = RangeMap::<i32>::new(Size::ZERO, -1);
+        assert_eq!(map.iter(Size::ZERO, Size::ZERO).count(), 0);
+        assert_eq!(map.iter_mut(Size::ZERO, Size::ZERO).count(), 0);
+        assert_eq!(map.iter_all().count(), 0);
+    }
+
     #[test]
     fn basic_insert() {
         let mut map = RangeMap::<i32>::new(Size::from_bytes(20), -1);